@@ -1,4 +1,5 @@
-use rusqlite::{Connection, Result};
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection, Result};
 use std::fs;
 use std::path::PathBuf;
 use std::collections::HashMap;
@@ -86,25 +87,32 @@ fn load_chrome_profiles() -> Vec<HashMap<String, String>> {
     result
 }
 
-fn where_clauses(table_title: &str, terms: &[&str]) -> String {
-    terms.iter().map(|&term| format!("({}.title LIKE '%{}%' OR {}.url LIKE '%{}%')", table_title, term, table_title, term)).collect::<Vec<_>>().join(" AND ")
+// One condition per term, with the term bound as a LIKE pattern whose wildcards are escaped
+fn where_clauses(table_title: &str, terms: &[&str]) -> (String, Vec<Value>) {
+    let clauses = (1..=terms.len()).map(|number| format!("({0}.title LIKE ?{1} ESCAPE '\\' OR {0}.url LIKE ?{1} ESCAPE '\\')", table_title, number)).collect::<Vec<_>>().join(" AND ");
+    let patterns = terms.iter().map(|term| Value::Text(format!("%{}%", term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")))).collect();
+    (clauses, patterns)
 }
 
-fn get_history_query(table: &str, terms: &[&str], limit: i64, offset: i64) -> String {
-    let where_clauses_string = where_clauses(table, terms);
-    format!(
+// The query and its parameters: the LIKE patterns of the terms, then the limit and the offset
+fn get_history_query(table: &str, terms: &[&str], limit: i64, offset: i64) -> (String, Vec<Value>) {
+    let (where_clauses_string, mut params) = where_clauses(table, terms);
+    let query = format!(
         "SELECT id, url, title, datetime(last_visit_time / 1000000 + (strftime('%s', '1601-01-01')), 'unixepoch', 'localtime') as last_visited \
-        FROM {} {} ORDER BY last_visit_time DESC LIMIT {} OFFSET {};",
+        FROM {} {} ORDER BY last_visit_time DESC LIMIT ?{} OFFSET ?{};",
         table,
         if where_clauses_string.is_empty() { "".to_string() } else { format!("WHERE {}", where_clauses_string) },
-        limit,
-        offset
-    )
+        params.len() + 1,
+        params.len() + 2
+    );
+    params.push(Value::Integer(limit));
+    params.push(Value::Integer(offset));
+    (query, params)
 }
 
 fn search_history(profile: &str, query: Option<&str>, limit: i64, offset: i64) -> HistoryResult {
     let terms: Vec<&str> = query.unwrap_or("").trim().split_whitespace().collect();
-    let query = get_history_query("urls", &terms, limit, offset);
+    let (query, params) = get_history_query("urls", &terms, limit, offset);
     let db_path = get_history_db_path(Some(profile));
 
     println!("db_path: {:?}", db_path);
@@ -145,7 +153,7 @@ fn search_history(profile: &str, query: Option<&str>, limit: i64, offset: i64) -
         }
     };
 
-    let history_iter = match stmt.query_map([], |row| {
+    let history_iter = match stmt.query_map(params_from_iter(params), |row| {
       Ok((
           row.get(0)?,
           row.get(1)?,
@@ -216,6 +224,7 @@ pub fn search_chrome(profile: String, user_query: String, limit: i64, page: i64)
       frecency_rank: 0.0,
       is_pinned: false,
       comment: None,
+      is_encrypted: false,
     }
   }).collect();
 
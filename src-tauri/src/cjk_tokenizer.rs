@@ -0,0 +1,183 @@
+// Tokenizer for text that mixes CJK (Chinese, Japanese, Korean) and space-separated languages
+// The default tantivy tokenizer splits on whitespace and punctuation, so an unspaced CJK paragraph becomes one giant token
+// Here every run of CJK characters is broken into overlapping bigrams (東京都 -> 東京, 京都) and everything else
+// is split exactly like the SimpleTokenizer. A two character word is then a single term and longer words become phrase queries.
+
+use tantivy::tokenizer::{Token, TokenStream, Tokenizer};
+
+pub const CJK_TOKENIZER_NAME: &str = "buzee_cjk";
+
+pub fn is_cjk_char(c: char) -> bool {
+  matches!(c,
+    '\u{1100}'..='\u{11FF}'     // Hangul Jamo
+    | '\u{3040}'..='\u{309F}'   // Hiragana
+    | '\u{30A0}'..='\u{30FF}'   // Katakana
+    | '\u{3130}'..='\u{318F}'   // Hangul Compatibility Jamo
+    | '\u{31F0}'..='\u{31FF}'   // Katakana Phonetic Extensions
+    | '\u{3400}'..='\u{4DBF}'   // CJK Unified Ideographs Extension A
+    | '\u{4E00}'..='\u{9FFF}'   // CJK Unified Ideographs
+    | '\u{AC00}'..='\u{D7AF}'   // Hangul Syllables
+    | '\u{F900}'..='\u{FAFF}'   // CJK Compatibility Ideographs
+    | '\u{FF66}'..='\u{FF9F}'   // Halfwidth Katakana
+    | '\u{20000}'..='\u{2FA1F}' // CJK Unified Ideographs Extension B onwards
+  )
+}
+
+pub fn contains_cjk(text: &str) -> bool {
+  text.chars().any(is_cjk_char)
+}
+
+#[derive(Clone, Default)]
+pub struct CjkBigramTokenizer;
+
+pub struct CjkBigramTokenStream {
+  tokens: Vec<Token>,
+  current: usize,
+}
+
+impl Tokenizer for CjkBigramTokenizer {
+  type TokenStream<'a> = CjkBigramTokenStream;
+  fn token_stream<'a>(&'a mut self, text: &'a str) -> CjkBigramTokenStream {
+    CjkBigramTokenStream {
+      tokens: tokenize(text),
+      current: 0,
+    }
+  }
+}
+
+impl TokenStream for CjkBigramTokenStream {
+  fn advance(&mut self) -> bool {
+    if self.current < self.tokens.len() {
+      self.current += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn token(&self) -> &Token {
+    &self.tokens[self.current - 1]
+  }
+
+  fn token_mut(&mut self) -> &mut Token {
+    &mut self.tokens[self.current - 1]
+  }
+}
+
+fn push_token(tokens: &mut Vec<Token>, text: &str, offset_from: usize, offset_to: usize) {
+  tokens.push(Token {
+    offset_from,
+    offset_to,
+    position: tokens.len(),
+    text: text[offset_from..offset_to].to_string(),
+    position_length: 1,
+  });
+}
+
+// A single CJK character on its own is kept as a unigram so that it is still searchable
+fn push_cjk_run(tokens: &mut Vec<Token>, text: &str, run: &Vec<(usize, char)>) {
+  if run.len() == 1 {
+    let (offset, c) = run[0];
+    push_token(tokens, text, offset, offset + c.len_utf8());
+    return;
+  }
+  for pair in run.windows(2) {
+    let (offset_from, _) = pair[0];
+    let (second_offset, second_char) = pair[1];
+    push_token(tokens, text, offset_from, second_offset + second_char.len_utf8());
+  }
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+  let mut tokens: Vec<Token> = vec![];
+  let mut cjk_run: Vec<(usize, char)> = vec![];
+  let mut word_start: Option<usize> = None;
+
+  for (offset, c) in text.char_indices() {
+    if is_cjk_char(c) {
+      if let Some(start) = word_start.take() {
+        push_token(&mut tokens, text, start, offset);
+      }
+      cjk_run.push((offset, c));
+    } else {
+      if !cjk_run.is_empty() {
+        push_cjk_run(&mut tokens, text, &cjk_run);
+        cjk_run.clear();
+      }
+      if c.is_alphanumeric() {
+        if word_start.is_none() {
+          word_start = Some(offset);
+        }
+      } else if let Some(start) = word_start.take() {
+        push_token(&mut tokens, text, start, offset);
+      }
+    }
+  }
+  if !cjk_run.is_empty() {
+    push_cjk_run(&mut tokens, text, &cjk_run);
+  }
+  if let Some(start) = word_start {
+    push_token(&mut tokens, text, start, text.len());
+  }
+  tokens
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  // (text, byte offsets, position) of every token
+  fn tokens(text: &str) -> Vec<(String, usize, usize, usize)> {
+    let mut tokenizer = CjkBigramTokenizer;
+    let mut stream = tokenizer.token_stream(text);
+    let mut tokens = vec![];
+    while stream.advance() {
+      let token = stream.token();
+      tokens.push((token.text.clone(), token.offset_from, token.offset_to, token.position));
+    }
+    tokens
+  }
+
+  fn texts(text: &str) -> Vec<String> {
+    tokens(text).into_iter().map(|(text, _, _, _)| text).collect()
+  }
+
+  #[test]
+  fn cjk_runs_become_bigrams() {
+    assert_eq!(texts("東京都"), vec!["東京", "京都"]);
+    assert_eq!(texts("会議"), vec!["会議"]);
+    // a single character stays searchable on its own
+    assert_eq!(texts("本"), vec!["本"]);
+    assert_eq!(texts("한국어 문서"), vec!["한국", "국어", "문서"]);
+  }
+
+  #[test]
+  fn mixed_cjk_and_latin_text() {
+    assert_eq!(texts("Q3予算report"), vec!["Q3", "予算", "report"]);
+    assert_eq!(texts("東京タワーのticket 2024年"), vec!["東京", "京タ", "タワ", "ワー", "ーの", "ticket", "2024", "年"]);
+    assert_eq!(texts("budget, 予算。final-draft"), vec!["budget", "予算", "final", "draft"]);
+    // full-width punctuation breaks a run like a space does
+    assert_eq!(texts("東京、大阪"), vec!["東京", "大阪"]);
+  }
+
+  #[test]
+  fn offsets_and_positions() {
+    assert_eq!(
+      tokens("Tokyo東京都 trip"),
+      vec![
+        ("Tokyo".to_string(), 0, 5, 0),
+        ("東京".to_string(), 5, 11, 1),
+        ("京都".to_string(), 8, 14, 2),
+        ("trip".to_string(), 15, 19, 3),
+      ]
+    );
+  }
+
+  #[test]
+  fn text_without_cjk_splits_like_the_simple_tokenizer() {
+    assert_eq!(texts("Café déjà-vu, naïve_2"), vec!["Café", "déjà", "vu", "naïve", "2"]);
+    assert!(texts("  ... ").is_empty());
+    assert!(!contains_cjk("plain text"));
+    assert!(contains_cjk("plain 漢字"));
+  }
+}
@@ -34,7 +34,15 @@ pub struct TantivyDocumentItem {
     pub body: String,
     pub file_type: String,
     pub last_modified: i64,
+    pub created_at: i64,
+    pub size: Option<f64>,
     pub comment: String,
+    pub language: String,
+    // (field name, value) pairs extracted from the file, e.g. ("author", "Priya")
+    pub fields: Vec<(String, String)>,
+    // part of the file this chunk comes from, e.g. "slide 17" and 17 (empty and 0 if the file has no sections)
+    pub section: String,
+    pub section_number: i64,
 }
 
 // Struct for TantivyDocumentSearchResult
@@ -42,6 +50,18 @@ pub struct TantivyDocumentItem {
 pub struct TantivyDocumentSearchResult {
   pub id: i64,
   pub last_modified: i64,
+  pub section: Option<String>,
+  pub section_number: Option<i64>,
+  pub snippets: Vec<SearchSnippet>,
+}
+
+// A passage of a field that matched the query, e.g. of `body` or `subject`
+// `highlights` are the (start, end) of the matching words in `text`, counted in UTF-16 code units like a JavaScript string
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchSnippet {
+  pub field: String,
+  pub text: String,
+  pub highlights: Vec<(usize, usize)>,
 }
 
 // Struct for TantivyBrowserHistorySearchResult
@@ -103,23 +123,29 @@ pub struct TantivyEmailSearchResult {
   pub frecency_last_accessed: Option<i64>,
 }
 
-// DateLimit struct
+// The date range picked in the UI, as Unix timestamps from `start` to `end`, both included
+// The UI sends them as numbers or as strings of digits, a bound that can't be read is left open
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DateLimit {
-  pub start: String,
-  pub end: String,
+  #[serde(default, deserialize_with = "deserialize_timestamp")]
+  pub start: Option<i64>,
+  #[serde(default, deserialize_with = "deserialize_timestamp")]
+  pub end: Option<i64>,
+  #[serde(default)]
   pub text: String
 }
 
-// Query Segments struct
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct QuerySegments {
-  #[serde(rename = "quotedSegments")]
-  pub quoted_segments: Vec<String>,
-  #[serde(rename = "greedySegments")]
-  pub greedy_segments: Vec<String>,
-  #[serde(rename = "notSegments")]
-  pub not_segments: Vec<String>,
+fn deserialize_timestamp<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
+where
+  D: serde::Deserializer<'de>,
+{
+  let timestamp = match serde_json::Value::deserialize(deserializer)? {
+    serde_json::Value::Number(number) => number.as_i64().or_else(|| number.as_f64().map(|number| number as i64)),
+    serde_json::Value::String(text) => text.trim().parse::<f64>().ok().map(|number| number as i64),
+    _ => None,
+  };
+  // 0 is what the UI sends when no date was picked
+  Ok(timestamp.filter(|timestamp| *timestamp > 0))
 }
 
 // Payload for IPC events
@@ -179,6 +205,21 @@ impl TantivyReaderState {
   }
 }
 
+use std::sync::mpsc::Sender;
+use crate::index_writer::IndexCommand;
+// Struct for the channel to the index writer thread
+pub(crate) struct IndexWriterState {
+  pub sender: Sender<IndexCommand>
+}
+
+impl IndexWriterState {
+  pub fn new(given_sender: Sender<IndexCommand>) -> Self {
+    Self {
+      sender: given_sender
+    }
+  }
+}
+
 use diesel::r2d2::{Pool, ConnectionManager};
 use diesel::SqliteConnection;
 
@@ -210,6 +251,20 @@ impl Default for SyncRunningState {
   }
 }
 
+// Key of the password store, read from the keyring when an indexing run first meets an encrypted file and forgotten
+// when the run ends
+pub(crate) struct PasswordStoreState {
+  pub key: Option<Vec<u8>>
+}
+
+impl Default for PasswordStoreState {
+  fn default() -> Self {
+    Self {
+      key: None
+    }
+  }
+}
+
 // Struct for Global Shortcut String
 #[derive(Serialize, Clone)]
 pub(crate) struct GlobalShortcutState {
@@ -241,6 +296,9 @@ pub(crate) struct UserPreferencesState {
   pub roadmap_survey_answered: bool,
   pub parse_pdfs: bool,
   pub manual_setup: bool,
+  pub search_languages: String,
+  pub transcribe_audio: bool,
+  pub ranking_weights: String,
 }
 
 impl Default for UserPreferencesState {
@@ -257,11 +315,50 @@ impl Default for UserPreferencesState {
           detailed_scan: true,
           roadmap_survey_answered: false,
           parse_pdfs: false,
-          manual_setup: false
+          manual_setup: false,
+          search_languages: "en".to_string(),
+          transcribe_audio: false,
+          ranking_weights: "".to_string(),
         }
     }
 }
 
+// How much each signal counts when the results of a search are ranked, see database/ranking.rs
+// `filename` and `content` weigh the file name and the contents matches, 1.0 being the top hit of a source
+// `pinned`, `frecency` and `recency` are added on top for pinned, frequently used and recently modified or opened files
+// Stored as JSON in user_preferences, missing values keep their default
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct RankingWeights {
+  pub filename: f32,
+  pub content: f32,
+  pub pinned: f32,
+  pub frecency: f32,
+  pub recency: f32,
+}
+
+impl Default for RankingWeights {
+    fn default() -> Self {
+        Self {
+          filename: 1.2,
+          content: 1.0,
+          pinned: 0.5,
+          frecency: 0.5,
+          recency: 0.2,
+        }
+    }
+}
+
+// The number of files that match a search under a facet, e.g. 42 under /pdf of the file_type facet
+// `value` is the facet path that is sent back to drill down, `label` is what the UI shows
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FacetCount {
+  pub facet: String,
+  pub value: String,
+  pub label: String,
+  pub count: usize,
+}
+
 use tauri::Wry;
 use tauri::menu::Menu;
 
@@ -12,12 +12,23 @@ use queries::{
   METADATA_TABLE_CREATE_STATEMENT, 
   METADATA_FTS_VIRTUAL_TABLE_CREATE_STATEMENT,
   BODY_TABLE_CREATE_STATEMENT,
+  PROPERTY_TABLE_CREATE_STATEMENT,
+  BIB_ENTRY_TABLE_CREATE_STATEMENT,
+  CALENDAR_EVENT_TABLE_CREATE_STATEMENT,
+  CONTACT_TABLE_CREATE_STATEMENT,
+  HIGHLIGHT_TABLE_CREATE_STATEMENT,
   TRIGGER_INSERT_DOCUMENT_METADATA, TRIGGER_UPDATE_DOCUMENT_METADATA,
+  TRIGGER_INSERT_BIB_ENTRY_METADATA,
+  TRIGGER_INSERT_CALENDAR_EVENT_METADATA,
+  TRIGGER_INSERT_CONTACT_METADATA,
+  TRIGGER_INSERT_HIGHLIGHT_METADATA,
+  METADATA_SOURCE_FOREIGN_KEY_CHECK, METADATA_SOURCE_FOREIGN_KEY_MIGRATION,
   USER_PREFS_TABLE_CREATE_STATEMENT,
   APP_DATA_TABLE_CREATE_STATEMENT,
   IGNORE_LIST_TABLE_CREATE_STATEMENT,
   ALLOW_LIST_TABLE_CREATE_STATEMENT,
-  FILE_TYPES_TABLE_CREATE_STATEMENT
+  FILE_TYPES_TABLE_CREATE_STATEMENT,
+  ADDED_COLUMNS
 };
 
 const DB_NAME: &str = r#"buzee.db"#;
@@ -26,6 +37,7 @@ const DB_NAME: &str = r#"buzee.db"#;
 pub mod schema;
 pub mod models;
 pub mod search;
+pub mod ranking;
 mod queries;
 // mod response_models;
 
@@ -57,7 +69,7 @@ pub fn get_connection_pool() -> Pool<ConnectionManager<SqliteConnection>> {
       .expect("Could not build connection pool")
 }
 
-pub fn establish_connection(app: &tauri::AppHandle) -> PooledConnection<ConnectionManager<SqliteConnection>> {
+pub fn establish_connection<R: tauri::Runtime>(app: &tauri::AppHandle<R>) -> PooledConnection<ConnectionManager<SqliteConnection>> {
   let state_mutex = app.state::<Mutex<DBConnPoolState>>();
   let state = state_mutex.lock().unwrap();
   let pool = &state.conn_pool;
@@ -106,30 +118,76 @@ pub fn create_tables_if_not_exists(conn: &mut SqliteConnection) -> Result<usize,
   diesel::sql_query(BODY_TABLE_CREATE_STATEMENT.to_string()).execute(conn)?;
   diesel::sql_query(METADATA_TABLE_CREATE_STATEMENT.to_string()).execute(conn)?;
   diesel::sql_query(METADATA_FTS_VIRTUAL_TABLE_CREATE_STATEMENT.to_string()).execute(conn)?;
+  diesel::sql_query(PROPERTY_TABLE_CREATE_STATEMENT.to_string()).execute(conn)?;
+  diesel::sql_query(BIB_ENTRY_TABLE_CREATE_STATEMENT.to_string()).execute(conn)?;
+  diesel::sql_query(CALENDAR_EVENT_TABLE_CREATE_STATEMENT.to_string()).execute(conn)?;
+  diesel::sql_query(CONTACT_TABLE_CREATE_STATEMENT.to_string()).execute(conn)?;
+  diesel::sql_query(HIGHLIGHT_TABLE_CREATE_STATEMENT.to_string()).execute(conn)?;
   // diesel::sql_query(BODY_FTS_VIRTUAL_TABLE_CREATE_STATEMENT.to_string()).execute(conn)?;
 
   // Triggers
   diesel::sql_query(TRIGGER_INSERT_DOCUMENT_METADATA.to_string()).execute(conn)?;
   diesel::sql_query(TRIGGER_UPDATE_DOCUMENT_METADATA.to_string()).execute(conn)?;
+  diesel::sql_query(TRIGGER_INSERT_BIB_ENTRY_METADATA.to_string()).execute(conn)?;
+  diesel::sql_query(TRIGGER_INSERT_CALENDAR_EVENT_METADATA.to_string()).execute(conn)?;
+  diesel::sql_query(TRIGGER_INSERT_CONTACT_METADATA.to_string()).execute(conn)?;
+  diesel::sql_query(TRIGGER_INSERT_HIGHLIGHT_METADATA.to_string()).execute(conn)?;
   // diesel::sql_query(TRIGGER_INSERT_BODY_FTS.to_string()).execute(conn)?;
   // diesel::sql_query(TRIGGER_UPDATE_BODY_FTS.to_string()).execute(conn)?;
+
+  // Columns added in later versions
+  for (table_name, column_name, column_definition) in ADDED_COLUMNS.iter() {
+    add_column_if_not_exists(conn, table_name, column_name, column_definition)?;
+  }
+  // Constraints changed in later versions
+  drop_metadata_source_foreign_key(conn)?;
   Ok(1)
 }
 
-// use diesel::sql_query;
-// #[derive(QueryableByName, Debug)]
-// struct TableInfo {
-//     #[sql_type = "diesel::sql_types::Text"]
-//     name: String
-// }
-
-// fn get_table_columns(conn: &mut SqliteConnection, table_name: &str) -> Result<Vec<String>, diesel::result::Error> {
-//     let query = format!("SELECT name FROM pragma_table_info('{}')", table_name);
-//     let results = sql_query(query).load::<TableInfo>(conn)?;
-
-//     let mut columns = Vec::new();
-//     for result in results {
-//         columns.push(result.name);
-//     }
-//     Ok(columns)
-// }
\ No newline at end of file
+// Remove the foreign key from metadata.source_id to document.id for users upgrading from an older version
+fn drop_metadata_source_foreign_key(conn: &mut SqliteConnection) -> Result<usize, diesel::result::Error> {
+  let tables_with_foreign_key = diesel::sql_query(METADATA_SOURCE_FOREIGN_KEY_CHECK.to_string()).load::<TableInfo>(conn)?;
+  if tables_with_foreign_key.is_empty() {
+    return Ok(0);
+  }
+  println!("Removing the document foreign key from the metadata table");
+  diesel::sql_query("PRAGMA foreign_keys = OFF;").execute(conn)?;
+  diesel::sql_query("PRAGMA legacy_alter_table = ON;").execute(conn)?;
+  let migration = conn.transaction::<_, diesel::result::Error, _>(|connection| {
+    for statement in METADATA_SOURCE_FOREIGN_KEY_MIGRATION.iter() {
+      diesel::sql_query(statement.to_string()).execute(connection)?;
+    }
+    Ok(1)
+  });
+  diesel::sql_query("PRAGMA legacy_alter_table = OFF;").execute(conn)?;
+  diesel::sql_query("PRAGMA foreign_keys = ON;").execute(conn)?;
+  migration
+}
+
+#[derive(QueryableByName, Debug)]
+struct TableInfo {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    name: String
+}
+
+fn get_table_columns(conn: &mut SqliteConnection, table_name: &str) -> Result<Vec<String>, diesel::result::Error> {
+    let results = diesel::sql_query("SELECT name FROM pragma_table_info(?)")
+      .bind::<diesel::sql_types::Text, _>(table_name)
+      .load::<TableInfo>(conn)?;
+
+    let mut columns = Vec::new();
+    for result in results {
+        columns.push(result.name);
+    }
+    Ok(columns)
+}
+
+// Add a column to an existing table for users upgrading from an older version
+fn add_column_if_not_exists(conn: &mut SqliteConnection, table_name: &str, column_name: &str, column_definition: &str) -> Result<usize, diesel::result::Error> {
+  let columns = get_table_columns(conn, table_name)?;
+  if columns.iter().any(|column| column == column_name) {
+    return Ok(0);
+  }
+  println!("Adding column {} to table {}", column_name, table_name);
+  diesel::sql_query(format!("ALTER TABLE {} ADD COLUMN {} {};", table_name, column_name, column_definition)).execute(conn)
+}
\ No newline at end of file
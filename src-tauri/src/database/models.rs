@@ -3,9 +3,10 @@
 
 use diesel::prelude::*;
 use diesel::Insertable;
-use super::schema::{document, metadata, metadata_fts, body, user_preferences, app_data, ignore_list, allow_list, file_types};
+use super::schema::{document, metadata, metadata_fts, body, property, bib_entry, calendar_event, contact, highlight, user_preferences, app_data, ignore_list, allow_list, file_types};
 use serde::Deserialize;
 use serde::Serialize;
+use crate::custom_types::{FacetCount, SearchSnippet};
 
 // This struct is for CRUD on the user_prefs table
 #[derive(Serialize, Deserialize, Insertable, Queryable, QueryableByName, PartialEq, Debug, Clone)]
@@ -23,6 +24,9 @@ pub struct UserPrefs {
     pub roadmap_survey_answered: bool,
     pub parse_pdfs: bool,
     pub manual_setup: bool,
+    pub search_languages: String,
+    pub transcribe_audio: bool,
+    pub ranking_weights: String,
 }
 
 // This struct is for CRUD on the app_data table
@@ -111,6 +115,84 @@ pub struct BodyItem {
     pub source_id: i32,
     pub text: String,
     pub last_parsed: i64,
+    pub section: String,
+    pub section_number: i64,
+}
+
+// This struct is for INSERTING into the property table
+#[derive(Serialize, Deserialize, Insertable, Queryable, QueryableByName, PartialEq, Debug, Clone)]
+#[diesel(table_name = property)]
+pub struct PropertyItem {
+    pub metadata_id: i32,
+    pub source_id: i32,
+    pub name: String,
+    pub value: String,
+}
+
+// This struct is for INSERTING into the bib_entry table
+// The metadata row of each entry is added by a trigger
+#[derive(Serialize, Deserialize, Insertable, Queryable, QueryableByName, PartialEq, Debug, Clone)]
+#[diesel(table_name = bib_entry)]
+pub struct BibEntryItem {
+    pub document_id: i32,
+    pub entry_number: i64,
+    pub citekey: String,
+    pub entry_type: String,
+    pub title: String,
+    pub authors: String,
+    pub year: String,
+    pub journal: String,
+    pub path: String,
+    pub last_modified: i64,
+}
+
+// This struct is for INSERTING into the calendar_event table
+// The metadata row of each event is added by a trigger
+#[derive(Serialize, Deserialize, Insertable, Queryable, QueryableByName, PartialEq, Debug, Clone)]
+#[diesel(table_name = calendar_event)]
+pub struct CalendarEventItem {
+    pub document_id: i32,
+    pub event_number: i64,
+    pub summary: String,
+    pub location: String,
+    pub attendees: String,
+    pub starts_at: i64,
+    pub ends_at: i64,
+    pub path: String,
+    pub last_modified: i64,
+}
+
+// This struct is for INSERTING into the contact table
+// The metadata row of each contact is added by a trigger
+#[derive(Serialize, Deserialize, Insertable, Queryable, QueryableByName, PartialEq, Debug, Clone)]
+#[diesel(table_name = contact)]
+pub struct ContactItem {
+    pub document_id: i32,
+    pub contact_number: i64,
+    pub name: String,
+    pub emails: String,
+    pub phones: String,
+    pub organization: String,
+    pub path: String,
+    pub last_modified: i64,
+}
+
+// This struct is for INSERTING into the highlight table
+// The metadata row of each highlight is added by a trigger
+#[derive(Serialize, Deserialize, Insertable, Queryable, QueryableByName, PartialEq, Debug, Clone)]
+#[diesel(table_name = highlight)]
+pub struct HighlightItem {
+    pub document_id: i32,
+    pub highlight_number: i64,
+    pub book_title: String,
+    pub author: String,
+    pub location: String,
+    pub kind: String,
+    pub text: String,
+    pub note: String,
+    pub highlighted_at: i64,
+    pub path: String,
+    pub last_modified: i64,
 }
 
 // This struct is for SELECTING from the metadata table without any JOINs
@@ -145,7 +227,7 @@ pub struct MetadataFTSSearchResult {
 // }
 
 // This struct is for SELECTING from the document table without any JOINs
-#[derive(Serialize, Deserialize, Queryable, QueryableByName, PartialEq, Debug, Clone)]
+#[derive(Serialize, Deserialize, Queryable, QueryableByName, Selectable, PartialEq, Debug, Clone)]
 #[diesel(table_name = document)]
 pub struct DocumentSearchResult {
     pub id: i32,
@@ -163,6 +245,73 @@ pub struct DocumentSearchResult {
     pub frecency_rank: f32,
     pub frecency_last_accessed: i64,
     pub comment: Option<String>,
+    pub is_encrypted: bool,
+}
+
+// This struct is for RETURNING search results to the frontend
+// The document fields are flattened so that the frontend receives the same object as DocumentSearchResult
+// `section` and `section_number` say where the query matched inside the document (e.g. "slide 17" and 17), if known
+// Items stored inside a file (e.g. the entries of a .bib file, the events of an .ics file) are returned with their file as `document`,
+// their own `source_table` and `title`, and their position in the file as the section
+// `snippets` are the highlighted passages that matched, only hits from the tantivy index have them
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DocumentSearchHit {
+    #[serde(flatten)]
+    pub document: DocumentSearchResult,
+    pub section: Option<String>,
+    pub section_number: Option<i64>,
+    pub source_table: String,
+    pub title: Option<String>,
+    #[serde(default)]
+    pub snippets: Vec<SearchSnippet>,
+}
+
+impl From<DocumentSearchResult> for DocumentSearchHit {
+    fn from(document: DocumentSearchResult) -> Self {
+        DocumentSearchHit {
+            document,
+            section: None,
+            section_number: None,
+            source_table: "document".to_string(),
+            title: None,
+            snippets: vec![],
+        }
+    }
+}
+
+// The hits of a search with the facet counts of everything it matched, the counts are only sent with the first page
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SearchResults {
+    pub hits: Vec<DocumentSearchHit>,
+    pub facets: Vec<FacetCount>,
+}
+
+// This struct is for SELECTING items stored inside a file (bib_entry, calendar_event, contact, highlight) together with their file
+#[derive(QueryableByName, Debug, Clone)]
+pub struct ItemSearchResult {
+    #[diesel(embed)]
+    pub document: DocumentSearchResult,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub source_table: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub title: String,
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    pub section: String,
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub section_number: i64,
+}
+
+impl From<ItemSearchResult> for DocumentSearchHit {
+    fn from(item: ItemSearchResult) -> Self {
+        DocumentSearchHit {
+            document: item.document,
+            section: Some(item.section),
+            section_number: Some(item.section_number),
+            source_table: item.source_table,
+            title: Some(item.title),
+            snippets: vec![],
+        }
+    }
 }
 
 // This struct is for SELECTING from the document table via the metadata table when searching the body_fts table
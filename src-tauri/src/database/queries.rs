@@ -23,9 +23,12 @@
   name, path, size, file_type, last_modified, last_opened : metadata of the document
   last_synced = timestamp when the item was last synced with the local database
   is_pinned = boolean to indicate if the document is pinned by the user in the app
-  frecency_rank = float to indicate the frecency of the document
+  frecency_rank = float to indicate the frecency of the document, as of frecency_last_accessed (see frecency.rs)
   frecency_last_accessed = timestamp when the document was last accessed using the app
   comment = user comment added in the app
+  language = ISO 639-1 code of the language detected in the parsed text (empty if unknown or not parsed)
+  is_encrypted = boolean to indicate that the document is password-protected and none of the saved passwords open it
+  frecency_excluded = boolean to indicate that the user doesn't want the document's use tracked
   
   Note: cannot add metadata_id here because data is added to the `document` table first and then
   the metadata table gets automatically populated using triggers
@@ -47,7 +50,10 @@ pub const DOCUMENT_TABLE_CREATE_STATEMENT : &str = r#"
     "is_pinned" BOOLEAN NOT NULL DEFAULT 0,
     "frecency_rank" REAL NOT NULL DEFAULT 0,
     "frecency_last_accessed" BIGINT,
-    "comment" TEXT
+    "comment" TEXT,
+    "language" TEXT NOT NULL DEFAULT '',
+    "is_encrypted" BOOLEAN NOT NULL DEFAULT 0,
+    "frecency_excluded" BOOLEAN NOT NULL DEFAULT 0
   );
 "#;
 
@@ -56,6 +62,8 @@ pub const DOCUMENT_TABLE_CREATE_STATEMENT : &str = r#"
   metadata_id = id from the metadata table
   source_id = id from the source table (document, email, article, website etc.)
   text = body content of the document, email, article, website etc.
+  section = readable label of the part of the item this chunk comes from (e.g. "slide 17"), empty if the item has no sections
+  section_number = number of that part (e.g. 17), 0 if the item has no sections
 */
 pub const BODY_TABLE_CREATE_STATEMENT : &str = r#"
   CREATE TABLE IF NOT EXISTS "body" 
@@ -65,15 +73,39 @@ pub const BODY_TABLE_CREATE_STATEMENT : &str = r#"
     source_id INTEGER NOT NULL,
     text TEXT NOT NULL,
     last_parsed BIGINT NOT NULL DEFAULT 0,
+    section TEXT NOT NULL DEFAULT '',
+    section_number BIGINT NOT NULL DEFAULT 0,
+    FOREIGN KEY (metadata_id) REFERENCES metadata(id)
+  );
+"#;
+
+/*
+  PROPERTY TABLE (for all sources)
+  Searchable fields extracted from the content of an item (e.g. author, subject and keywords of a docx file)
+  metadata_id = id from the metadata table
+  source_id = id from the source table (document, email, article, website etc.)
+  name = name of the field, same as the field name in the tantivy index (e.g. "author")
+  value = value of the field
+  Kept in SQLite so that the tantivy index can be rebuilt without parsing the files again
+*/
+pub const PROPERTY_TABLE_CREATE_STATEMENT : &str = r#"
+  CREATE TABLE IF NOT EXISTS "property" 
+  (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    metadata_id INTEGER NOT NULL,
+    source_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    value TEXT NOT NULL,
     FOREIGN KEY (metadata_id) REFERENCES metadata(id)
   );
 "#;
 
 /*
   METADATA TABLE
-  source_table = "document", "email", "bookmark", "website" etc.
+  source_table = "document", "bib_entry", "calendar_event", "contact", "highlight", "email", "bookmark", "website" etc.
   source_domain = "local", "google_drive", "dropbox", "gmail", "outlook", "pocket", "instapaper" etc.
   source_id = id from the source table (document, email, article, website etc.)
+    There is no foreign key on source_id because it points into the table named in source_table
   title = title of the document, email, article, website etc.
   url = url or path
   created_at = timestamp when the item was created on the source
@@ -96,8 +128,159 @@ pub const METADATA_TABLE_CREATE_STATEMENT : &str = r#"
     frecency_rank REAL NOT NULL DEFAULT 0,
     frecency_last_accessed BIGINT,
     comment TEXT,
-    extra_tag TEXT NOT NULL,
-    FOREIGN KEY (source_id) REFERENCES document(id)
+    extra_tag TEXT NOT NULL
+  );
+"#;
+
+/*
+  METADATA MIGRATION
+  Older versions created the metadata table with `FOREIGN KEY (source_id) REFERENCES document(id)`,
+  which rejects rows from any other source table. SQLite cannot drop a constraint, so the table is copied
+  into one without it. ids are kept, so body, property and metadata_fts rows still point at the same rows.
+  Run with foreign keys turned off (PRAGMA foreign_keys cannot be changed inside a transaction) and with
+  legacy_alter_table on, otherwise the rename fails because the document triggers mention the dropped table.
+*/
+pub const METADATA_SOURCE_FOREIGN_KEY_CHECK : &str = r#"
+  SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'metadata' AND sql LIKE '%REFERENCES document%';
+"#;
+pub const METADATA_SOURCE_FOREIGN_KEY_MIGRATION : [&str; 4] = [
+  r#"
+  CREATE TABLE metadata_without_source_foreign_key (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source_table TEXT NOT NULL,
+    source_domain TEXT NOT NULL,
+    source_id INTEGER NOT NULL,
+    title TEXT NOT NULL,
+    url TEXT NOT NULL,
+    created_at BIGINT NOT NULL,
+    last_modified BIGINT NOT NULL,
+    frecency_rank REAL NOT NULL DEFAULT 0,
+    frecency_last_accessed BIGINT,
+    comment TEXT,
+    extra_tag TEXT NOT NULL
+  );
+  "#,
+  r#"
+  INSERT INTO metadata_without_source_foreign_key
+  SELECT id, source_table, source_domain, source_id, title, url, created_at, last_modified, frecency_rank, frecency_last_accessed, comment, extra_tag
+  FROM metadata;
+  "#,
+  r#"DROP TABLE metadata;"#,
+  r#"ALTER TABLE metadata_without_source_foreign_key RENAME TO metadata;"#,
+];
+
+/*
+  BIB_ENTRY TABLE
+  One row per entry of a BibTeX (.bib) file, so that every entry is its own searchable item
+  document_id = id of the .bib file in the document table
+  entry_number = position of the entry in the file (same as section_number of its chunks)
+  citekey, entry_type = the key and type of the entry, e.g. "vaswani2017" and "inproceedings"
+  title, authors, year, journal = fields of the entry, authors are separated by "; "
+  path = path of the .bib file
+  last_modified = last_modified of the .bib file
+  The entries of a file are deleted and inserted again whenever the file is parsed
+*/
+pub const BIB_ENTRY_TABLE_CREATE_STATEMENT : &str = r#"
+  CREATE TABLE IF NOT EXISTS "bib_entry" 
+  (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    document_id INTEGER NOT NULL,
+    entry_number BIGINT NOT NULL,
+    citekey TEXT NOT NULL,
+    entry_type TEXT NOT NULL,
+    title TEXT NOT NULL DEFAULT '',
+    authors TEXT NOT NULL DEFAULT '',
+    year TEXT NOT NULL DEFAULT '',
+    journal TEXT NOT NULL DEFAULT '',
+    path TEXT NOT NULL,
+    last_modified BIGINT NOT NULL,
+    FOREIGN KEY (document_id) REFERENCES document(id)
+  );
+"#;
+
+/*
+  CALENDAR_EVENT TABLE
+  One row per event (VEVENT) of an iCalendar (.ics) file
+  document_id = id of the .ics file in the document table
+  event_number = position of the event in the file (same as section_number of its chunks)
+  summary, location = fields of the event
+  attendees = organizer and attendees, separated by "; "
+  starts_at, ends_at = UNIX timestamps of the start and end of the event (0 if missing)
+  path, last_modified = of the .ics file
+  The events of a file are deleted and inserted again whenever the file is parsed
+*/
+pub const CALENDAR_EVENT_TABLE_CREATE_STATEMENT : &str = r#"
+  CREATE TABLE IF NOT EXISTS "calendar_event" 
+  (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    document_id INTEGER NOT NULL,
+    event_number BIGINT NOT NULL,
+    summary TEXT NOT NULL DEFAULT '',
+    location TEXT NOT NULL DEFAULT '',
+    attendees TEXT NOT NULL DEFAULT '',
+    starts_at BIGINT NOT NULL DEFAULT 0,
+    ends_at BIGINT NOT NULL DEFAULT 0,
+    path TEXT NOT NULL,
+    last_modified BIGINT NOT NULL,
+    FOREIGN KEY (document_id) REFERENCES document(id)
+  );
+"#;
+
+/*
+  CONTACT TABLE
+  One row per contact (VCARD) of a vCard (.vcf) file
+  document_id = id of the .vcf file in the document table
+  contact_number = position of the contact in the file (same as section_number of its chunks)
+  name, organization = fields of the contact
+  emails, phones = separated by "; "
+  path, last_modified = of the .vcf file
+  The contacts of a file are deleted and inserted again whenever the file is parsed
+*/
+pub const CONTACT_TABLE_CREATE_STATEMENT : &str = r#"
+  CREATE TABLE IF NOT EXISTS "contact" 
+  (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    document_id INTEGER NOT NULL,
+    contact_number BIGINT NOT NULL,
+    name TEXT NOT NULL,
+    emails TEXT NOT NULL DEFAULT '',
+    phones TEXT NOT NULL DEFAULT '',
+    organization TEXT NOT NULL DEFAULT '',
+    path TEXT NOT NULL,
+    last_modified BIGINT NOT NULL,
+    FOREIGN KEY (document_id) REFERENCES document(id)
+  );
+"#;
+
+/*
+  HIGHLIGHT TABLE
+  One row per highlight or note of an e-reader file: Kindle's "My Clippings.txt" or a KOReader metadata.*.lua
+  document_id = id of the clippings or metadata file in the document table
+  highlight_number = position of the highlight in the file (same as section_number of its chunks)
+  book_title, author = of the highlighted book, authors are separated by "; "
+  location = where the highlight is in the book, e.g. "page 12, Location 123-125"
+  kind = "highlight" or "note"
+  text = highlighted text (empty for a note on its own), note = note added to it
+  highlighted_at = UNIX timestamp of when the highlight was made (0 if unknown)
+  path, last_modified = of the clippings or metadata file
+  The highlights of a file are deleted and inserted again whenever the file is parsed
+*/
+pub const HIGHLIGHT_TABLE_CREATE_STATEMENT : &str = r#"
+  CREATE TABLE IF NOT EXISTS "highlight" 
+  (
+    id INTEGER PRIMARY KEY AUTOINCREMENT NOT NULL,
+    document_id INTEGER NOT NULL,
+    highlight_number BIGINT NOT NULL,
+    book_title TEXT NOT NULL DEFAULT '',
+    author TEXT NOT NULL DEFAULT '',
+    location TEXT NOT NULL DEFAULT '',
+    kind TEXT NOT NULL DEFAULT 'highlight',
+    text TEXT NOT NULL DEFAULT '',
+    note TEXT NOT NULL DEFAULT '',
+    highlighted_at BIGINT NOT NULL DEFAULT 0,
+    path TEXT NOT NULL,
+    last_modified BIGINT NOT NULL,
+    FOREIGN KEY (document_id) REFERENCES document(id)
   );
 "#;
 
@@ -190,6 +373,55 @@ pub const TRIGGER_UPDATE_DOCUMENT_METADATA : &str = r#"
       WHERE source_table = 'document' AND source_id = OLD.id;
   END;
 "#;
+// The citekey goes into extra_tag so that it can be found by name, e.g. `vaswani2017`
+// An entry without a title is listed under its citekey
+// Entries are deleted and inserted again on every parse, so the metadata_fts rowid is set to the metadata id.
+// Otherwise fts5 reuses the deleted rowids and its rows point at the wrong (or missing) metadata rows.
+pub const TRIGGER_INSERT_BIB_ENTRY_METADATA : &str = r#"
+  CREATE TRIGGER IF NOT EXISTS insert_bib_entry_metadata
+  AFTER INSERT ON bib_entry
+  BEGIN
+      INSERT INTO metadata (source_table, source_domain, source_id, title, url, created_at, last_modified, frecency_rank, frecency_last_accessed, comment, extra_tag)
+      VALUES ('bib_entry', 'local', NEW.id, CASE WHEN NEW.title = '' THEN NEW.citekey ELSE NEW.title END, NEW.path, NEW.last_modified, NEW.last_modified, 0, 0, NULL, NEW.citekey);
+      INSERT INTO metadata_fts (rowid, source_table, source_domain, source_id, title, url, created_at, last_modified, frecency_rank, frecency_last_accessed, comment, extra_tag)
+      VALUES ((SELECT id FROM metadata WHERE source_table = 'bib_entry' AND source_id = NEW.id), 'bib_entry', 'local', NEW.id, CASE WHEN NEW.title = '' THEN NEW.citekey ELSE NEW.title END, NEW.path, NEW.last_modified, NEW.last_modified, 0, 0, NULL, NEW.citekey);
+  END;
+"#;
+// The location and attendees of an event go into extra_tag so that events can be found by place or person
+// An event is "created" when it starts, so that the item can be sorted by it
+pub const TRIGGER_INSERT_CALENDAR_EVENT_METADATA : &str = r#"
+  CREATE TRIGGER IF NOT EXISTS insert_calendar_event_metadata
+  AFTER INSERT ON calendar_event
+  BEGIN
+      INSERT INTO metadata (source_table, source_domain, source_id, title, url, created_at, last_modified, frecency_rank, frecency_last_accessed, comment, extra_tag)
+      VALUES ('calendar_event', 'local', NEW.id, CASE WHEN NEW.summary = '' THEN 'Untitled event' ELSE NEW.summary END, NEW.path, CASE WHEN NEW.starts_at = 0 THEN NEW.last_modified ELSE NEW.starts_at END, NEW.last_modified, 0, 0, NULL, TRIM(NEW.location || ' ' || NEW.attendees));
+      INSERT INTO metadata_fts (rowid, source_table, source_domain, source_id, title, url, created_at, last_modified, frecency_rank, frecency_last_accessed, comment, extra_tag)
+      VALUES ((SELECT id FROM metadata WHERE source_table = 'calendar_event' AND source_id = NEW.id), 'calendar_event', 'local', NEW.id, CASE WHEN NEW.summary = '' THEN 'Untitled event' ELSE NEW.summary END, NEW.path, CASE WHEN NEW.starts_at = 0 THEN NEW.last_modified ELSE NEW.starts_at END, NEW.last_modified, 0, 0, NULL, TRIM(NEW.location || ' ' || NEW.attendees));
+  END;
+"#;
+// The emails, phones and organization of a contact go into extra_tag so that a contact can be found by any of them
+pub const TRIGGER_INSERT_CONTACT_METADATA : &str = r#"
+  CREATE TRIGGER IF NOT EXISTS insert_contact_metadata
+  AFTER INSERT ON contact
+  BEGIN
+      INSERT INTO metadata (source_table, source_domain, source_id, title, url, created_at, last_modified, frecency_rank, frecency_last_accessed, comment, extra_tag)
+      VALUES ('contact', 'local', NEW.id, NEW.name, NEW.path, NEW.last_modified, NEW.last_modified, 0, 0, NULL, TRIM(NEW.emails || ' ' || NEW.phones || ' ' || NEW.organization));
+      INSERT INTO metadata_fts (rowid, source_table, source_domain, source_id, title, url, created_at, last_modified, frecency_rank, frecency_last_accessed, comment, extra_tag)
+      VALUES ((SELECT id FROM metadata WHERE source_table = 'contact' AND source_id = NEW.id), 'contact', 'local', NEW.id, NEW.name, NEW.path, NEW.last_modified, NEW.last_modified, 0, 0, NULL, TRIM(NEW.emails || ' ' || NEW.phones || ' ' || NEW.organization));
+  END;
+"#;
+// A highlight is titled with its book. The highlighted text and the author go into extra_tag and the note into comment,
+// so that a highlight can be found by any of them. It is "created" when it was highlighted
+pub const TRIGGER_INSERT_HIGHLIGHT_METADATA : &str = r#"
+  CREATE TRIGGER IF NOT EXISTS insert_highlight_metadata
+  AFTER INSERT ON highlight
+  BEGIN
+      INSERT INTO metadata (source_table, source_domain, source_id, title, url, created_at, last_modified, frecency_rank, frecency_last_accessed, comment, extra_tag)
+      VALUES ('highlight', 'local', NEW.id, CASE WHEN NEW.book_title = '' THEN 'Untitled book' ELSE NEW.book_title END, NEW.path, CASE WHEN NEW.highlighted_at = 0 THEN NEW.last_modified ELSE NEW.highlighted_at END, NEW.last_modified, 0, 0, NULLIF(NEW.note, ''), TRIM(NEW.author || ' ' || NEW.text));
+      INSERT INTO metadata_fts (rowid, source_table, source_domain, source_id, title, url, created_at, last_modified, frecency_rank, frecency_last_accessed, comment, extra_tag)
+      VALUES ((SELECT id FROM metadata WHERE source_table = 'highlight' AND source_id = NEW.id), 'highlight', 'local', NEW.id, CASE WHEN NEW.book_title = '' THEN 'Untitled book' ELSE NEW.book_title END, NEW.path, CASE WHEN NEW.highlighted_at = 0 THEN NEW.last_modified ELSE NEW.highlighted_at END, NEW.last_modified, 0, 0, NULLIF(NEW.note, ''), TRIM(NEW.author || ' ' || NEW.text));
+  END;
+"#;
 // pub const TRIGGER_DELETE_DOCUMENT_METADATA : &str = r#"
 //   CREATE TRIGGER IF NOT EXISTS delete_document_metadata
 //   BEFORE DELETE ON document
@@ -256,7 +488,10 @@ pub const USER_PREFS_TABLE_CREATE_STATEMENT : &str = r#"
     detailed_scan BOOLEAN NOT NULL DEFAULT 1,
     roadmap_survey_answered BOOLEAN NOT NULL DEFAULT 0,
     parse_pdfs BOOLEAN NOT NULL DEFAULT 0,
-    manual_setup BOOLEAN NOT NULL DEFAULT 0
+    manual_setup BOOLEAN NOT NULL DEFAULT 0,
+    search_languages TEXT NOT NULL DEFAULT "en",
+    transcribe_audio BOOLEAN NOT NULL DEFAULT 0,
+    ranking_weights TEXT NOT NULL DEFAULT ''
   );
 "#;
 
@@ -265,6 +500,23 @@ pub const _USER_PREFS_TABLE_ALTER_STATEMENT_V_0_2_0 : &str = r#"
   ADD COLUMN roadmap_survey_answered BOOLEAN NOT NULL DEFAULT 0;
 "#;
 
+/*
+  ADDED COLUMNS
+  Columns added to existing tables after release. `CREATE TABLE IF NOT EXISTS` leaves old tables untouched,
+  so each of these is added with ALTER TABLE on startup if the column is missing.
+  (table name, column name, column definition)
+*/
+pub const ADDED_COLUMNS : &[(&str, &str, &str)] = &[
+  ("document", "language", "TEXT NOT NULL DEFAULT ''"),
+  ("user_preferences", "search_languages", r#"TEXT NOT NULL DEFAULT "en""#),
+  ("body", "section", "TEXT NOT NULL DEFAULT ''"),
+  ("body", "section_number", "BIGINT NOT NULL DEFAULT 0"),
+  ("user_preferences", "transcribe_audio", "BOOLEAN NOT NULL DEFAULT 0"),
+  ("document", "is_encrypted", "BOOLEAN NOT NULL DEFAULT 0"),
+  ("user_preferences", "ranking_weights", "TEXT NOT NULL DEFAULT ''"),
+  ("document", "frecency_excluded", "BOOLEAN NOT NULL DEFAULT 0"),
+];
+
 // APP_DATA stores basic app data and file type data
 pub const APP_DATA_TABLE_CREATE_STATEMENT : &str = r#"
   CREATE TABLE IF NOT EXISTS "app_data" 
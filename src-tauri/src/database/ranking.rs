@@ -0,0 +1,95 @@
+// Ranks the hits of one search across its sources
+// Every source (file names in metadata_fts, the contents in tantivy, ...) returns its hits best first with its own scores,
+// which can't be compared with each other. So they are fused with reciprocal rank fusion: a hit scores
+// weight * (K + 1) / (K + rank) in each source that returned it, 1.0 for the top hit of a source of weight 1
+// Whether the file is pinned, its frecency and how recently it was modified or opened are added on top, in the same units
+// The weights are set by the user, see `RankingWeights`
+
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::custom_types::RankingWeights;
+use crate::database::models::DocumentSearchHit;
+use crate::frecency::current_frecency;
+
+// The larger K, the less the top ranks of a source stand out from the ones below them
+const RRF_K: f32 = 60.0;
+// A file modified or opened this many days ago gets half of the recency weight
+const RECENCY_HALF_LIFE_DAYS: f32 = 30.0;
+pub(crate) const MAX_SNIPPETS_PER_HIT: usize = 3;
+
+// The ranked lists of a search, each with the weight of its source
+pub(crate) struct RankedList {
+  pub hits: Vec<DocumentSearchHit>,
+  pub weight: f32,
+}
+
+// Fuses the lists into one, best first, with every file or item in it once
+// Every list has the best hits of its source down to the end of the requested page, the page is cut from the fused list
+// A file found by its name and by its contents keeps the section and snippets of the contents hit
+pub(crate) fn fuse_ranked_lists(lists: Vec<RankedList>, weights: &RankingWeights) -> Vec<DocumentSearchHit> {
+  let mut fused: Vec<(DocumentSearchHit, f32)> = Vec::new();
+  let mut positions: HashMap<(i32, String, Option<i64>), usize> = HashMap::new();
+  for list in lists {
+    for (rank, hit) in list.hits.into_iter().enumerate() {
+      let rank_score = list.weight * (RRF_K + 1.0) / (RRF_K + 1.0 + rank as f32);
+      match positions.get(&hit_key(&hit)) {
+        Some(position) => {
+          let (existing, score) = &mut fused[*position];
+          merge_hit(existing, hit);
+          *score += rank_score;
+        }
+        None => {
+          positions.insert(hit_key(&hit), fused.len());
+          fused.push((hit, rank_score));
+        }
+      }
+    }
+  }
+
+  let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs() as i64).unwrap_or(0);
+  // the stored frecency is decayed to now, the hits are returned with it
+  for (hit, _) in fused.iter_mut() {
+    hit.document.frecency_rank = current_frecency(hit.document.frecency_rank, hit.document.frecency_last_accessed, now);
+  }
+  // frecency has no fixed scale, so it counts relative to the most frequently used file among the hits
+  let max_frecency = fused.iter().map(|(hit, _)| hit.document.frecency_rank).fold(0.0, f32::max);
+  for (hit, score) in fused.iter_mut() {
+    if hit.document.is_pinned {
+      *score += weights.pinned;
+    }
+    if max_frecency > 0.0 {
+      *score += weights.frecency * hit.document.frecency_rank / max_frecency;
+    }
+    *score += weights.recency * recency(hit, now);
+  }
+
+  fused.sort_by(|(a, a_score), (b, b_score)| {
+    b_score.total_cmp(a_score).then(b.document.last_modified.cmp(&a.document.last_modified))
+  });
+  fused.into_iter().map(|(hit, _)| hit).collect()
+}
+
+// A file is one hit, items inside a file (e.g. the entries of a .bib file) are one hit each
+fn hit_key(hit: &DocumentSearchHit) -> (i32, String, Option<i64>) {
+  let section_number = if hit.source_table == "document" { None } else { hit.section_number };
+  (hit.document.id, hit.source_table.clone(), section_number)
+}
+
+fn merge_hit(existing: &mut DocumentSearchHit, other: DocumentSearchHit) {
+  if existing.section.is_none() {
+    existing.section = other.section;
+    existing.section_number = other.section_number;
+  }
+  if existing.title.is_none() {
+    existing.title = other.title;
+  }
+  let room = MAX_SNIPPETS_PER_HIT.saturating_sub(existing.snippets.len());
+  existing.snippets.extend(other.snippets.into_iter().take(room));
+}
+
+// 1.0 for a file modified or opened just now, halving every RECENCY_HALF_LIFE_DAYS
+fn recency(hit: &DocumentSearchHit, now: i64) -> f32 {
+  let last_used = hit.document.last_modified.max(hit.document.last_opened);
+  let age_days = (now - last_used).max(0) as f32 / 86_400.0;
+  0.5_f32.powf(age_days / RECENCY_HALF_LIFE_DAYS)
+}
@@ -17,6 +17,9 @@ table! {
       frecency_rank -> Float,
       frecency_last_accessed -> BigInt,
       comment -> Nullable<Text>,
+      language -> Text,
+      is_encrypted -> Bool,
+      frecency_excluded -> Bool,
   }
 }
 
@@ -61,6 +64,8 @@ table! {
       source_id -> Integer,
       text -> Text,
       last_parsed -> BigInt,
+      section -> Text,
+      section_number -> BigInt,
   }
 }
 
@@ -74,13 +79,95 @@ table! {
   }
 }
 
+table! {
+  property (id) {
+      id -> Integer,
+      metadata_id -> Integer,
+      source_id -> Integer,
+      name -> Text,
+      value -> Text,
+  }
+}
+
+table! {
+  bib_entry (id) {
+      id -> Integer,
+      document_id -> Integer,
+      entry_number -> BigInt,
+      citekey -> Text,
+      entry_type -> Text,
+      title -> Text,
+      authors -> Text,
+      year -> Text,
+      journal -> Text,
+      path -> Text,
+      last_modified -> BigInt,
+  }
+}
+
+table! {
+  calendar_event (id) {
+      id -> Integer,
+      document_id -> Integer,
+      event_number -> BigInt,
+      summary -> Text,
+      location -> Text,
+      attendees -> Text,
+      starts_at -> BigInt,
+      ends_at -> BigInt,
+      path -> Text,
+      last_modified -> BigInt,
+  }
+}
+
+table! {
+  contact (id) {
+      id -> Integer,
+      document_id -> Integer,
+      contact_number -> BigInt,
+      name -> Text,
+      emails -> Text,
+      phones -> Text,
+      organization -> Text,
+      path -> Text,
+      last_modified -> BigInt,
+  }
+}
+
+table! {
+  highlight (id) {
+      id -> Integer,
+      document_id -> Integer,
+      highlight_number -> BigInt,
+      book_title -> Text,
+      author -> Text,
+      location -> Text,
+      kind -> Text,
+      text -> Text,
+      note -> Text,
+      highlighted_at -> BigInt,
+      path -> Text,
+      last_modified -> BigInt,
+  }
+}
+
 joinable!(body -> metadata (metadata_id));
+joinable!(property -> metadata (metadata_id));
 joinable!(document -> metadata (id));
+joinable!(bib_entry -> document (document_id));
+joinable!(calendar_event -> document (document_id));
+joinable!(contact -> document (document_id));
+joinable!(highlight -> document (document_id));
 
 allow_tables_to_appear_in_same_query!(
   document,
   metadata,
-  body
+  body,
+  property,
+  bib_entry,
+  calendar_event,
+  contact,
+  highlight
 );
 
 table! {
@@ -97,7 +184,10 @@ table! {
     detailed_scan -> Bool,
     roadmap_survey_answered -> Bool,
     parse_pdfs -> Bool,
-    manual_setup -> Bool
+    manual_setup -> Bool,
+    search_languages -> Text,
+    transcribe_audio -> Bool,
+    ranking_weights -> Text,
   }
 }
 
@@ -1,325 +1,340 @@
 use crate::arc_read::search_arc;
 use crate::chrome_read::search_chrome;
 use crate::firefox_read::search_firefox;
-use crate::custom_types::{Error, DBStat, DateLimit, QuerySegments};
+use crate::custom_types::{Error, DBStat, DateLimit, FacetCount};
 use crate::database::establish_connection;
-use crate::database::models::{DocumentSearchResult, MetadataFTSSearchResult};
+use crate::database::models::{DocumentSearchHit, DocumentSearchResult, ItemSearchResult, MetadataFTSSearchResult, SearchResults};
+use crate::database::ranking::{fuse_ranked_lists, RankedList, MAX_SNIPPETS_PER_HIT};
+use crate::facets::{facet_filters_query, parse_facet_filters, FacetCountCollector, FacetFilter};
 use crate::indexing::all_allowed_filetypes;
-use crate::tantivy_index::{acquire_searcher_from_reader, create_tantivy_schema, get_tantivy_index, parse_query_and_get_top_docs, return_document_search_results};
-use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection};
+use crate::query_language::{fts_string, parse_query, with_date_limit, with_file_types, with_filter, OpenedDocuments, QueryNode, SqlPredicate, SqlValue, ValueRange};
+use crate::user_prefs::{get_ranking_weights, get_search_languages};
+use crate::tantivy_index::{acquire_searcher_from_reader, create_tantivy_schema, get_tantivy_index, get_top_docs, return_document_search_results};
+use crate::cjk_tokenizer::contains_cjk;
+use diesel::{ExpressionMethods, JoinOnDsl, QueryDsl, RunQueryDsl, SelectableHelper, SqliteConnection};
+use diesel::query_builder::{BoxedSqlQuery, SqlQuery};
 use diesel::r2d2::{PooledConnection, ConnectionManager};
-use serde_json;
-use super::schema::{body, document};
+use diesel::sql_types::{BigInt, Text};
+use diesel::sqlite::Sqlite;
+use super::schema::{bib_entry, body, calendar_event, contact, document, highlight, metadata};
 use tantivy::{Searcher, Index};
 
-fn parse_stringified_query_segments(json_string: &str) -> QuerySegments {
-    let parsed_json = serde_json::from_str(json_string);
-    // convert to QuerySegments
-    let query_segments: QuerySegments = match parsed_json {
-        Ok(value) => value,
-        Err(_) => QuerySegments {
-            quoted_segments: Vec::new(),
-            greedy_segments: Vec::new(),
-            not_segments: Vec::new(),
-        },
-    };
-    query_segments
-}
-
-fn create_match_statement(query_segments: &QuerySegments) -> String {
-    let mut match_string: String = String::new();
-
-    // If there are quoted segments, join them with double quotes
-    if query_segments.quoted_segments.len() > 0 {
-        match_string = format!("{}",
-        // query_segments.quoted_segments.join("\" \"")
-        query_segments.quoted_segments
-            .iter()
-            .map(|segment| {
-                // if segment contains ^^, it contains a punctuation mark (using code from frontend because regex caused problems in rust)
-                if segment.contains("^^") {
-                    // Remove ^^ from the segment and
-                    // Add double quotes around the segment
-                    format!("\"{}\"", segment.replace("^^", ""))
-                } else {
-                    // Add double quotes around the segment
-                    format!("\"{}\"", segment)
-                }
-            })
-            .collect::<Vec<String>>()
-            .join(" ")
-        );
-    }
-    // If there are greedy segments, join them with an asterisk space
-    if query_segments.greedy_segments.len() > 0 {
-        match_string = format!(
-            "{} {}*",
-            match_string,
-            query_segments.greedy_segments.join("* ")
-        );
-    }
-    // If there are NOT segments, join them with `NOT` and OR
-    if query_segments.not_segments.len() > 0 {
-        match_string = format!(
-            "{} NOT ({})",
-            match_string,
-            // join with `* OR` and remove the trailing `OR`
-            query_segments.not_segments
-              .iter()
-              .map(|segment| {
-                  // if segment starts with ^^, it contains a punctuation mark
-                  // or if it has a space in it, it is a phrase
-                  if segment.starts_with("^^") || segment.contains(" ") {
-                      // Remove ^^ from the segment and
-                      // Add double quotes around the segment
-                      format!("\"{}\"", segment.replace("^^", ""))
-                  } else {
-                      // Add * to the end of the segment
-                      format!("{}*", segment)
-                  }
-              })
-              .collect::<Vec<String>>()
-              .join(" OR ")
-        );
-    }
-    // remove the trailing `OR )`
-    match_string = match_string.trim().to_string();
-    match_string
-}
-
-fn create_tantivy_query_statement(query_segments: &QuerySegments, file_type_string: String) -> String {
-    let mut tantivy_query_string: String = String::new();
-
-    // If there are quoted segments, join them with double quotes
-    if query_segments.quoted_segments.len() > 0 {
-      tantivy_query_string = format!("{}",
-        query_segments.quoted_segments
-            .iter()
-            .map(|segment| {
-                // if segment contains ^^, it contains a punctuation mark (using code from frontend because regex caused problems in rust)
-                if segment.contains("^^") {
-                    // Remove ^^ from the segment and
-                    // Add double quotes around the segment
-                    format!("\"{}\"", segment.replace("^^", ""))
-                } else {
-                    // Add double quotes around the segment
-                    format!("\"{}\"", segment)
-                }
-            })
-            .collect::<Vec<String>>()
-            .join(" ")
-        );
-    }
-    // If there are greedy segments, join them with an asterisk space
-    if query_segments.greedy_segments.len() > 0 {
-      tantivy_query_string = format!(
-            "{} {}*",
-            tantivy_query_string,
-            query_segments.greedy_segments.join("* ")
-        );
-    }
-    // If there are NOT segments, place a - in front of each segment
-    if query_segments.not_segments.len() > 0 {
-      tantivy_query_string = format!(
-          "{} -{}",
-          tantivy_query_string,
-          query_segments.not_segments.iter().map(|segment| {
-            // if segment contains a space, wrap it in double quotes
-            if segment.contains(" ") {
-              format!("\"{}\"", segment)
-            } else if segment.contains("^^") {
-              // Remove ^^ from the segment and
-              // Add double quotes around the segment
-              format!("\"{}\"", segment.replace("^^", ""))
-            } else {
-              segment.to_string()
-            }
-          })
-          .collect::<Vec<String>>()
-          .join(" -")
-        );
-    }
-    // remove the trailing `OR )`
-    tantivy_query_string = tantivy_query_string.trim().to_string();
-
-    if file_type_string.len() == 0 {
-        return tantivy_query_string;
-    } 
-    if tantivy_query_string.len() != 0 {
-      // add file_type to query
-      if !file_type_string.contains(",") {
-        tantivy_query_string = format!("{} AND file_type:{}", tantivy_query_string, file_type_string);
-      } else {
-        let file_type_query_string = file_type_string.replace(",", " OR file_type:");
-        tantivy_query_string = format!("{} AND (file_type:{})", tantivy_query_string, file_type_query_string);
-      }
-    }
-
-    tantivy_query_string
-}
-
 // Return documents from the metadata_fts index that match the given search query (name and type)
 // bm25(document_fts, 10) is the ranking function which gives 10x weight to the file name (first column)
+// `query` is the query as typed by the user, see query_language.rs for what it can contain
 pub fn search_fts_index(
     query: String,
     page: i32,
     limit: i32,
     file_type: Option<String>,
     date_limit: Option<DateLimit>,
+    fuzzy: bool,
+    facet_filters: Vec<String>,
     mut conn: PooledConnection<ConnectionManager<SqliteConnection>>,
     app: &tauri::AppHandle
-) -> Result<Vec<DocumentSearchResult>, diesel::result::Error> {
+) -> Result<SearchResults, diesel::result::Error> {
     println!(
-        "search_fts_index: query: {}, page: {}, limit: {}, file_type: {:?}, date_limit: {:?}",
-        query, page, limit, file_type, date_limit
+        "search_fts_index: query: {}, page: {}, limit: {}, file_type: {:?}, date_limit: {:?}, fuzzy: {}, facet_filters: {:?}",
+        query, page, limit, file_type, date_limit, fuzzy, facet_filters
     );
-
-    let query_segments: QuerySegments = parse_stringified_query_segments(&query);
-    println!("query_segments: {:?}", query_segments);
-
-    let file_type_clone = file_type.clone();
-    let file_type_clone_two = file_type.clone();
-    // Add file type(s)
-    let where_file_type = if let Some(file_type) = file_type {
-      if !file_type.contains(",") {
-        format!(r#"file_type IN ('{}')"#, file_type)
-      } else {
-        format!(
-            r#"file_type IN ('{}')"#,
-            file_type.replace(",", "','").replace("' ", "'")
-        )
-      }
-    } else {
-      "".to_string()
-    };
-    // Add date limit(s)
-    let date_limit_clone = date_limit.clone();
-    let where_date_limit: String = if let Some(date_limit) = date_limit {
-      format!(
-        r#"{} last_modified >= '{}' AND last_modified <= '{}'"#,
-        if !where_file_type.is_empty() {
-          "AND"
-        } else {
-          ""
-        },
-        date_limit.start,
-        date_limit.end
-      )
-    } else {
-        "".to_string()
-    };
-
-    let mut search_results: Vec<DocumentSearchResult> = Vec::new();
-    // if there is only a NOT query, pass it to `handle_special_case` function
-    if query_segments.quoted_segments.is_empty() && query_segments.greedy_segments.is_empty() && !query_segments.not_segments.is_empty() {
-      search_results = handle_special_case(query, page, limit, file_type_clone, conn).unwrap();
+    let facet_filters = parse_facet_filters(&facet_filters);
+
+    // the file types and the date range picked in the UI are more filters of the query
+    // every back end applies the filters before it cuts a page of results, so pages are never emptied afterwards
+    let file_types: Vec<String> = file_type.clone().map(|file_type| file_type.split(",").map(|file_type| file_type.to_string()).collect()).unwrap_or_default();
+    let search_query = with_date_limit(with_file_types(parse_query(&query), &file_types), &date_limit);
+    // the tantivy index applies the picked facets itself, SQLite gets them as filters
+    // the filters and the words are bound as parameters of the SQL queries, never spliced into them
+    let filter_query = with_filter(search_query.clone(), facet_filters_query(&facet_filters));
+    let where_filters = filter_query.to_sql_predicate();
+
+    let ranking_weights = get_ranking_weights(&app);
+    // every source returns its best hits down to the end of the page, which are fused once before the page is cut
+    // so a file found by several sources counts once and no page repeats or skips the hits of another one
+    let window = (page + 1) * limit;
+    let mut ranked_lists: Vec<RankedList> = Vec::new();
+    let mut facets: Vec<FacetCount> = Vec::new();
+    // if the query only excludes words, pass it to `handle_special_case` function
+    // its results don't match anything, so they are only ranked by pinned status, frecency and recency
+    if search_query.has_text() && !search_query.has_included_text() {
+      let hits = handle_special_case(&filter_query, window, &mut conn)?
+        .into_iter()
+        .map(DocumentSearchHit::from)
+        .collect();
+      ranked_lists.push(RankedList { hits, weight: 0.0 });
     }
     // otherwise run the body and metadata fts queries as usual
     else {
-      let match_string = create_match_statement(&query_segments);
-      println!("match_string: {}", match_string);
-      let tantivy_string = create_tantivy_query_statement(&query_segments, file_type_clone_two.unwrap_or("".to_string()));
-      println!("tantivy_string: {}", tantivy_string);
-
-      let tantivy_index = get_tantivy_index(create_tantivy_schema()).unwrap();
-      let searcher = acquire_searcher_from_reader(&app).unwrap();
-      let new_conn = establish_connection(&app);
-      let tantivy_search_results = get_search_results_from_tantivy_index(&tantivy_string, limit, page, &searcher, &tantivy_index, new_conn).unwrap_or(Vec::new());
-
-      // let tantivy_search_results = Vec::new();
-      println!("got {} results from tantivy index", tantivy_search_results.len());
-
-      let metadata_fts_query = create_metadata_fts_query(&where_file_type, &where_date_limit, &match_string, limit, page);
-      let metadata_search_results: Vec<DocumentSearchResult> = diesel::sql_query(metadata_fts_query).load::<DocumentSearchResult>(&mut conn).unwrap_or(Vec::new());
-      println!("got {} results from metadata_fts", metadata_search_results.len());
-      // combine the results from body_fts and metadata_fts
-      // let metadata_search_results = Vec::new();
-      for result in metadata_search_results.iter().chain(tantivy_search_results.iter()) {
-        search_results.push(result.clone());
+      let match_string = search_query.to_fts_match();
+
+      let mut tantivy_search_results = Vec::new();
+      if !search_query.is_empty() {
+        let tantivy_index = get_tantivy_index(create_tantivy_schema()).unwrap();
+        let searcher = acquire_searcher_from_reader(&app).unwrap();
+        let new_conn = establish_connection(&app);
+        let search_languages = get_search_languages(&app);
+        (tantivy_search_results, facets) = get_search_results_from_tantivy_index(&search_query, &search_languages, fuzzy, &facet_filters, window, page, &searcher, &tantivy_index, new_conn).unwrap_or((Vec::new(), Vec::new()));
+        println!("got {} results from tantivy index", tantivy_search_results.len());
       }
-    }
-    // and order them by last_modified
-    // TODO: change this to frecency rank
-    search_results.sort_by(|a, b| b.last_modified.cmp(&a.last_modified));
-    // remove duplicates by checking if the id is the same
-    search_results.dedup_by(|a, b| a.id == b.id);
-    if search_results.len() > 0 && date_limit_clone.is_some() {
-      let start_date = date_limit_clone.clone().unwrap().start.parse::<i64>().unwrap_or(0);
-      let end_date = date_limit_clone.clone().unwrap().end.parse::<i64>().unwrap_or(0);
-      if start_date > 0 || end_date > 0 {
-        // remove results that don't match the date limit
-        search_results.retain(|result| {
-          let last_modified = result.last_modified;
-          let date_limit = date_limit_clone.clone().unwrap();
-          last_modified >= date_limit.start.parse::<i64>().unwrap() && last_modified <= date_limit.end.parse::<i64>().unwrap()
-        });
+
+      // no file name can match a query that only searches fields of the tantivy index
+      if let Some(match_string) = match_string {
+        let (metadata_fts_query, values) = create_metadata_fts_query(&where_filters, &match_string, window);
+        let metadata_search_results: Vec<DocumentSearchResult> = bind_values(metadata_fts_query, values).load::<DocumentSearchResult>(&mut conn).unwrap_or(Vec::new());
+        println!("got {} results from metadata_fts", metadata_search_results.len());
+        ranked_lists.push(RankedList { hits: metadata_search_results.into_iter().map(DocumentSearchHit::from).collect(), weight: ranking_weights.filename });
+        // items inside files (BibTeX entries, calendar events, contacts, highlights) whose title or tags match
+        if !match_string.is_empty() {
+          let (item_search_query, values) = create_item_fts_query(&where_filters, &match_string, window);
+          let item_search_results = bind_values(item_search_query, values).load::<ItemSearchResult>(&mut conn).unwrap_or(Vec::new());
+          println!("got {} results from items in files", item_search_results.len());
+          ranked_lists.push(RankedList { hits: item_search_results.into_iter().map(DocumentSearchHit::from).collect(), weight: ranking_weights.filename });
+        }
+      }
+      // metadata_fts keeps an unspaced CJK file name as one token, so CJK words inside a file name are matched separately
+      let cjk_search_results = get_cjk_file_name_matches(&search_query, &where_filters, window, &mut conn).unwrap_or(Vec::new());
+      if cjk_search_results.len() > 0 {
+        println!("got {} results from CJK file name matching", cjk_search_results.len());
+        ranked_lists.push(RankedList { hits: cjk_search_results.into_iter().map(DocumentSearchHit::from).collect(), weight: ranking_weights.filename });
       }
+      ranked_lists.push(RankedList { hits: tantivy_search_results, weight: ranking_weights.content });
     }
-
-    Ok(search_results)
+    // a file found by several sources is returned once, ranked by all of them
+    let search_results = fuse_ranked_lists(ranked_lists, &ranking_weights)
+      .into_iter()
+      .skip((page * limit) as usize)
+      .take(limit as usize)
+      .collect();
+
+    Ok(SearchResults { hits: search_results, facets })
 }
 
-fn get_search_results_from_tantivy_index(query: &String, limit: i32, page: i32, searcher: &Searcher, tantivy_index: &Index, mut conn:  PooledConnection<ConnectionManager<SqliteConnection>>,) -> Result<Vec<DocumentSearchResult>, Error> {
-  let top_docs = parse_query_and_get_top_docs(&tantivy_index, &searcher, query.to_string(), limit, page*limit).unwrap_or(Vec::new());
+// The best `window` hits of the query in the tantivy index, with the facet counts of all its matches on the first page
+fn get_search_results_from_tantivy_index(query: &QueryNode, languages: &Vec<String>, fuzzy: bool, facet_filters: &[FacetFilter], window: i32, page: i32, searcher: &Searcher, tantivy_index: &Index, mut conn:  PooledConnection<ConnectionManager<SqliteConnection>>,) -> Result<(Vec<DocumentSearchHit>, Vec<FacetCount>), Error> {
+  let mut opened_documents = OpenedDocuments::new();
+  for range in query.opened_ranges() {
+    let ids = get_opened_document_ids(&range, &mut conn).unwrap_or(Vec::new());
+    opened_documents.insert(range, ids);
+  }
+  let tantivy_query = query.to_tantivy_query(&tantivy_index, languages, &opened_documents);
+  let (top_docs, parsed_query) = match get_top_docs(&tantivy_index, &searcher, tantivy_query, fuzzy, facet_filters, window, 0) {
+    Ok(top_docs_and_query) => top_docs_and_query,
+    Err(_) => return Ok((Vec::new(), Vec::new())),
+  };
+  let facets = if page == 0 {
+    searcher.search(parsed_query.as_ref(), &FacetCountCollector::new(facet_filters)).unwrap_or(Vec::new())
+  } else {
+    Vec::new()
+  };
   if top_docs.len() > 0 {
-    let search_results = return_document_search_results(&tantivy_index, &searcher, top_docs).unwrap_or(vec![]);
+    let search_results = return_document_search_results(&tantivy_index, &searcher, parsed_query.as_ref(), languages, top_docs, &mut conn).unwrap_or(vec![]);
     let document_ids: Vec<i32> = search_results.iter().map(|result| result.id as i32).collect();
 
-    let search_results_to_return = document::table
+    let documents = document::table
       .filter(document::id.eq_any(document_ids))
+      .select(DocumentSearchResult::as_select())
       .load::<DocumentSearchResult>(&mut conn)
       .unwrap_or(Vec::new());
 
-    Ok(search_results_to_return)
+    // chunks are returned best first, so the first chunk of a document decides its section
+    // the sections of .bib, .ics, .vcf and e-reader files are their entries, events, contacts and highlights, so every matching one is returned as its own item
+    // the snippets of the other matching chunks of a document are added to its hit
+    let mut search_results_to_return: Vec<DocumentSearchHit> = Vec::new();
+    for chunk in search_results.iter() {
+      let document = match documents.iter().find(|document| i64::from(document.id) == chunk.id) {
+        Some(document) => document,
+        None => continue,
+      };
+      let item_table = item_table_of_file_type(&document.file_type).filter(|_| chunk.section_number.is_some());
+      let already_returned = search_results_to_return.iter_mut().find(|hit| {
+        hit.document.id == document.id && (item_table.is_none() || hit.section_number == chunk.section_number)
+      });
+      if let Some(hit) = already_returned {
+        let room = MAX_SNIPPETS_PER_HIT.saturating_sub(hit.snippets.len());
+        hit.snippets.extend(chunk.snippets.iter().take(room).cloned());
+        continue;
+      }
+      search_results_to_return.push(DocumentSearchHit {
+        document: document.clone(),
+        section: chunk.section.clone(),
+        section_number: chunk.section_number,
+        source_table: item_table.unwrap_or("document").to_string(),
+        title: None,
+        snippets: chunk.snippets.iter().take(MAX_SNIPPETS_PER_HIT).cloned().collect(),
+      });
+    }
+    add_item_titles(&mut search_results_to_return, &mut conn);
+
+    Ok((search_results_to_return, facets))
   } else {
-    Ok(Vec::new())
+    Ok((Vec::new(), facets))
+  }
+}
+
+// The ids of the files last opened in the range, for the `opened:` filters in the tantivy index
+fn get_opened_document_ids(range: &ValueRange, conn: &mut SqliteConnection) -> Result<Vec<i64>, diesel::result::Error> {
+  let mut opened_query = document::table
+    .filter(document::last_opened.gt(0))
+    .select(document::id)
+    .into_boxed();
+  if let Some(start) = range.start {
+    opened_query = opened_query.filter(document::last_opened.ge(start));
+  }
+  if let Some(end) = range.end {
+    opened_query = opened_query.filter(document::last_opened.lt(end));
+  }
+  let ids = opened_query.load::<i32>(conn)?;
+  Ok(ids.into_iter().map(i64::from).collect())
+}
+
+// The table that holds the items inside files of this type, if they have any
+fn item_table_of_file_type(file_type: &str) -> Option<&'static str> {
+  match file_type {
+    "bib" => Some("bib_entry"),
+    "ics" => Some("calendar_event"),
+    "vcf" => Some("contact"),
+    "clippings" | "koreader" => Some("highlight"),
+    _ => None,
+  }
+}
+
+// Items found in the body of a file get the title of their row: the title of an entry, the summary of an event
+// or the name of a contact. Highlights are titled with their book
+fn add_item_titles(search_results: &mut Vec<DocumentSearchHit>, conn: &mut SqliteConnection) {
+  let document_ids: Vec<i32> = search_results
+    .iter()
+    .filter(|hit| hit.source_table != "document")
+    .map(|hit| hit.document.id)
+    .collect();
+  if document_ids.is_empty() {
+    return;
+  }
+  let mut items: Vec<(&str, i32, i64, String)> = vec![];
+  let entries = bib_entry::table
+    .filter(bib_entry::document_id.eq_any(&document_ids))
+    .select((bib_entry::document_id, bib_entry::entry_number, bib_entry::title))
+    .load::<(i32, i64, String)>(conn)
+    .unwrap_or(Vec::new());
+  items.extend(entries.into_iter().map(|(document_id, number, title)| ("bib_entry", document_id, number, title)));
+  let events = calendar_event::table
+    .filter(calendar_event::document_id.eq_any(&document_ids))
+    .select((calendar_event::document_id, calendar_event::event_number, calendar_event::summary))
+    .load::<(i32, i64, String)>(conn)
+    .unwrap_or(Vec::new());
+  items.extend(events.into_iter().map(|(document_id, number, title)| ("calendar_event", document_id, number, title)));
+  let contacts = contact::table
+    .filter(contact::document_id.eq_any(&document_ids))
+    .select((contact::document_id, contact::contact_number, contact::name))
+    .load::<(i32, i64, String)>(conn)
+    .unwrap_or(Vec::new());
+  items.extend(contacts.into_iter().map(|(document_id, number, title)| ("contact", document_id, number, title)));
+  let highlights = highlight::table
+    .filter(highlight::document_id.eq_any(&document_ids))
+    .select((highlight::document_id, highlight::highlight_number, highlight::book_title))
+    .load::<(i32, i64, String)>(conn)
+    .unwrap_or(Vec::new());
+  items.extend(highlights.into_iter().map(|(document_id, number, title)| ("highlight", document_id, number, title)));
+
+  for hit in search_results.iter_mut().filter(|hit| hit.source_table != "document") {
+    hit.title = items
+      .iter()
+      .find(|(source_table, document_id, number, _)| *source_table == hit.source_table && *document_id == hit.document.id && Some(*number) == hit.section_number)
+      .map(|(_, _, _, title)| title.clone())
+      .filter(|title| !title.is_empty());
   }
 }
 
+// The columns of DocumentSearchResult, for raw queries on the document table
+const DOCUMENT_COLUMNS: &str = "document.id, document.source_domain, document.created_at, document.name, document.path, document.size, \
+  document.file_type, document.last_modified, document.last_opened, document.last_synced, document.last_parsed, document.is_pinned, \
+  document.frecency_rank, document.frecency_last_accessed, document.comment, document.is_encrypted";
+
+// A raw query with its values bound to its ?s in order
+fn bind_values(query: String, values: Vec<SqlValue>) -> BoxedSqlQuery<'static, Sqlite, SqlQuery> {
+    let mut query = diesel::sql_query(query).into_boxed();
+    for value in values {
+        query = match value {
+            SqlValue::Text(text) => query.bind::<Text, _>(text),
+            SqlValue::Integer(number) => query.bind::<BigInt, _>(number),
+        };
+    }
+    query
+}
+
+// Match CJK words anywhere in file names with LIKE
+// Every required word or phrase with CJK characters must be present in the file name
+// `where_filters` is the SQL predicate of the filters of the query and the date limit
+fn get_cjk_file_name_matches(
+    search_query: &QueryNode,
+    where_filters: &SqlPredicate,
+    window: i32,
+    conn: &mut SqliteConnection,
+) -> Result<Vec<DocumentSearchResult>, diesel::result::Error> {
+    let cjk_segments: Vec<String> = search_query.required_words()
+        .into_iter()
+        .filter(|segment| contains_cjk(segment))
+        .collect();
+    if cjk_segments.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut conditions = vec!["metadata.source_table = 'document'".to_string()];
+    let mut values = Vec::new();
+    for segment in cjk_segments {
+        // escape LIKE wildcards in the segment
+        conditions.push("metadata.title LIKE ? ESCAPE '\\'".to_string());
+        values.push(SqlValue::Text(format!("%{}%", segment.replace("\\", "\\\\").replace("%", "\\%").replace("_", "\\_"))));
+    }
+    if !where_filters.is_empty() {
+        conditions.push(where_filters.sql.clone());
+        values.extend(where_filters.values.iter().cloned());
+    }
+    let cjk_query = format!(
+        r#"
+          SELECT {columns}
+          FROM document
+          JOIN metadata ON document.id = metadata.source_id
+          WHERE {conditions}
+          ORDER BY document.last_modified DESC
+          LIMIT {window}
+        "#,
+        columns = DOCUMENT_COLUMNS,
+        conditions = conditions.join(" AND "),
+        window = window
+    );
+    bind_values(cjk_query, values).load::<DocumentSearchResult>(conn)
+}
+
 fn _create_body_fts_query(
-    where_file_type: &String,
-    where_date_limit: &String,
+    where_filters: &SqlPredicate,
     match_string: &String,
     limit: i32,
     page: i32,
-) -> String {
+) -> (String, Vec<SqlValue>) {
     // Give 100x weight to the title/name column (4th) in metadata_fts and 2x weight to the url/path column (5th)
     let inner_query = format!(
         r#" 
             SELECT d.id, d.source_domain, d.created_at,
                 d.name, d.path, d.size, d.file_type,
                 d.last_modified, d.last_opened, d.last_synced, d.last_parsed,
-                d.is_pinned, d.frecency_rank, d.frecency_last_accessed, d.comment
+                d.is_pinned, d.frecency_rank, d.frecency_last_accessed, d.comment, d.is_encrypted
             FROM (
                 SELECT DISTINCT metadata_id FROM body_fts
                 {match_clause}
             ) b
             JOIN metadata m ON b.metadata_id = m.id
             JOIN document d ON m.source_id = d.id
-            {inner_where} {where_file_type} {where_date_limit}
+            {where_clause}
             LIMIT {limit} OFFSET {offset}
         "#,
         match_clause = if !match_string.is_empty() {
-            format!(
-                "WHERE body_fts MATCH '{}' ORDER BY bm25(body_fts, 1,1,100,2)",
-                match_string
-            )
+            "WHERE body_fts MATCH ? ORDER BY bm25(body_fts, 1,1,100,2)"
         } else {
-            "".to_string()
+            ""
         },
-        inner_where = if !where_file_type.is_empty() || !where_date_limit.is_empty() {
-            "WHERE".to_string()
-        } else {
-            "".to_string()
-        },
-        where_file_type = if !where_file_type.is_empty() {
-            where_file_type.clone()
-        } else {
-            "".to_string()
-        },
-        where_date_limit = if !where_date_limit.is_empty() {
-            // replace `last_modified` with `document.last_modified` in where_date_limit
-            where_date_limit.clone().replace("last_modified", "d.last_modified")
+        where_clause = if !where_filters.is_empty() {
+            // the document table is joined as `d` here
+            format!("WHERE {}", where_filters.sql.replace("document.", "d."))
         } else {
             "".to_string()
         },
@@ -327,59 +342,96 @@ fn _create_body_fts_query(
         offset = page * limit*2
     );
 
-    println!("body_inner_query: {}", inner_query);
-    inner_query
+    // the values in the order of their ?s
+    let mut values = Vec::new();
+    if !match_string.is_empty() {
+        values.push(SqlValue::Text(match_string.clone()));
+    }
+    values.extend(where_filters.values.iter().cloned());
+    (inner_query, values)
 }
 
 fn create_metadata_fts_query(
-    where_file_type: &String,
-    where_date_limit: &String,
+    where_filters: &SqlPredicate,
     match_string: &String,
-    limit: i32,
-    page: i32,
-) -> String {
+    window: i32,
+) -> (String, Vec<SqlValue>) {
     // Give 5x weight to the title column (4th) in metadata_fts
     let inner_query = format!(
         r#"
-          SELECT m.source_domain, m.source_id as id, m.title as name, m.url as path, m.created_at, m.frecency_rank, m.frecency_last_accessed, d.file_type, d.size, d.is_pinned, d.comment, d.last_opened, d.last_synced, d.last_modified, d.last_parsed
+          SELECT m.source_domain, m.source_id as id, m.title as name, m.url as path, m.created_at, m.frecency_rank, m.frecency_last_accessed, d.file_type, d.size, d.is_pinned, d.comment, d.last_opened, d.last_synced, d.last_modified, d.last_parsed, d.is_encrypted
           FROM metadata_fts m
           JOIN (
-              SELECT id, file_type, size, is_pinned, comment, last_opened, last_synced, last_modified, last_parsed
+              SELECT id, file_type, size, is_pinned, comment, last_opened, last_synced, last_modified, last_parsed, is_encrypted
               FROM document
-              {inner_where} {where_file_type} {where_date_limit}
-          ) d ON m.source_id = d.id
+              {where_clause}
+          ) d ON m.source_id = d.id AND m.source_table = 'document'
           {match_clause}
-          LIMIT {limit} OFFSET {offset}
+          LIMIT {window}
         "#,
-        inner_where = if !where_file_type.is_empty() || !where_date_limit.is_empty() {
-            "WHERE".to_string()
+        where_clause = if !where_filters.is_empty() {
+            format!("WHERE {}", where_filters.sql)
         } else {
             "".to_string()
         },
         match_clause = if !match_string.is_empty() {
-            format!(
-                "WHERE metadata_fts MATCH '{}' ORDER BY bm25(metadata_fts, 1,1,1,1,100,2)",
-                match_string
-            )
-        } else {
-            "".to_string()
-        },
-        where_file_type = if !where_file_type.is_empty() {
-            where_file_type.clone()
+            "WHERE metadata_fts MATCH ? ORDER BY bm25(metadata_fts, 1,1,1,1,100,2)"
         } else {
-            "".to_string()
+            ""
         },
-        where_date_limit = if !where_date_limit.is_empty() {
-            where_date_limit.clone()
+        window = window
+    );
+
+    // the values in the order of their ?s
+    let mut values = where_filters.values.clone();
+    if !match_string.is_empty() {
+        values.push(SqlValue::Text(match_string.clone()));
+    }
+    (inner_query, values)
+}
+
+// Items inside files (BibTeX entries, calendar events, contacts, highlights) that match the given search query, with the file they are in
+// The title and the extra_tag of each item (citekey; location and attendees; emails, phones and organization;
+// author and highlighted text) are searched in metadata_fts, the file type and date filters apply to the file
+// The section of an entry is its citekey, of an event its start, of a contact their organization and of a highlight its location
+fn create_item_fts_query(
+    where_filters: &SqlPredicate,
+    match_string: &String,
+    window: i32,
+) -> (String, Vec<SqlValue>) {
+    let inner_query = format!(
+        r#"
+          SELECT d.id, d.source_domain, d.created_at, d.name, d.path, d.size, d.file_type, d.last_modified, d.last_opened, d.last_synced, d.last_parsed, d.is_pinned, d.frecency_rank, d.frecency_last_accessed, d.comment, d.is_encrypted,
+            m.source_table, m.title,
+            COALESCE(b.citekey, CASE WHEN e.starts_at > 0 THEN strftime('%Y-%m-%d %H:%M', e.starts_at, 'unixepoch', 'localtime') END, c.organization, h.location, '') as section,
+            COALESCE(b.entry_number, e.event_number, c.contact_number, h.highlight_number) as section_number
+          FROM metadata_fts m
+          LEFT JOIN bib_entry b ON m.source_id = b.id AND m.source_table = 'bib_entry'
+          LEFT JOIN calendar_event e ON m.source_id = e.id AND m.source_table = 'calendar_event'
+          LEFT JOIN contact c ON m.source_id = c.id AND m.source_table = 'contact'
+          LEFT JOIN highlight h ON m.source_id = h.id AND m.source_table = 'highlight'
+          JOIN (
+              SELECT *
+              FROM document
+              {where_clause}
+          ) d ON COALESCE(b.document_id, e.document_id, c.document_id, h.document_id) = d.id
+          WHERE metadata_fts MATCH ?
+            AND m.source_table IN ('bib_entry', 'calendar_event', 'contact', 'highlight')
+          ORDER BY bm25(metadata_fts, 1,1,1,1,100,2)
+          LIMIT {window}
+        "#,
+        where_clause = if !where_filters.is_empty() {
+            format!("WHERE {}", where_filters.sql)
         } else {
             "".to_string()
         },
-        limit = limit*2,
-        offset = page * limit*2
+        window = window
     );
 
-    println!("metadata_inner_query: {}", inner_query);
-    inner_query
+    // the values in the order of their ?s
+    let mut values = where_filters.values.clone();
+    values.push(SqlValue::Text(match_string.clone()));
+    (inner_query, values)
 }
 
 // Get recently opened documents
@@ -389,46 +441,21 @@ pub fn get_recently_opened_docs(
     file_type: Option<String>,
     mut conn: PooledConnection<ConnectionManager<SqliteConnection>>,
 ) -> Result<Vec<DocumentSearchResult>, diesel::result::Error> {
+    let mut recent_query = document::table
+        .inner_join(metadata::table.on(document::id.eq(metadata::source_id)))
+        .filter(metadata::source_table.eq("document"))
+        .select(DocumentSearchResult::as_select())
+        .into_boxed();
     // Add file type(s)
-    let where_file_type = if let Some(file_type) = file_type {
-        if !file_type.contains(",") {
-            format!(r#" WHERE file_type IN ('{}')"#, file_type)
-        } else {
-            format!(
-                r#" WHERE file_type IN ('{}')"#,
-                file_type.replace(",", "','").replace("' ", "'")
-            )
-        }
-    } else {
-        "".to_string()
-    };
-
-    let inner_query = format!(
-        r#"
-        SELECT m.source_domain, m.source_id as id, m.title as name, m.url as path, m.created_at, m.last_modified, m.frecency_rank, m.frecency_last_accessed, d.file_type, d.size, d.is_pinned, d.comment, d.last_opened, d.last_synced, d.last_parsed
-        FROM metadata m
-        JOIN (
-            SELECT id, file_type, size, is_pinned, comment, last_opened, last_synced, last_parsed
-            FROM document
-            {where_file_type}
-        ) d ON m.source_id = d.id
-        ORDER BY last_modified DESC
-        LIMIT {limit} OFFSET {offset}
-    "#,
-        where_file_type = if !where_file_type.is_empty() {
-            where_file_type
-        } else {
-            "".to_string()
-        },
-        limit = limit,
-        offset = page * limit
-    );
-    println!("inner_query: {}", inner_query);
-    let search_results = diesel::sql_query(inner_query).load::<DocumentSearchResult>(&mut conn)?;
-
-    if search_results.len() > 0 {
-        println!("search_results: {:?}", search_results[0]);
+    if let Some(file_type) = file_type {
+        let file_types: Vec<String> = file_type.split(",").map(|file_type| file_type.trim().to_string()).collect();
+        recent_query = recent_query.filter(document::file_type.eq_any(file_types));
     }
+    let search_results = recent_query
+        .order(metadata::last_modified.desc())
+        .limit(i64::from(limit))
+        .offset(i64::from(page * limit))
+        .load::<DocumentSearchResult>(&mut conn)?;
     Ok(search_results)
 }
 
@@ -464,152 +491,28 @@ pub fn get_file_parsed_count(mut conn: PooledConnection<ConnectionManager<Sqlite
 }
 
 // Handle special case with NEGATIVE query only
-// Get recently opened docs (which is what the user was seeing when they typed the query)
-// Then filter out the results that match the negative query/queries
+// Get the most recently modified docs that pass the filters (which is what the user was seeing when they typed the query)
+// and don't have the excluded words in their name. The words are excluded in SQL, before a page is cut
 fn handle_special_case(
-    query: String,
-    page: i32,
-    limit: i32,
-    file_type: Option<String>,
-    conn: PooledConnection<ConnectionManager<SqliteConnection>>,
-) -> Result<Vec<DocumentSearchResult>, diesel::result::Error> {
-    let query_segments: QuerySegments = parse_stringified_query_segments(&query);
-    println!("query_segments: {:?}", query_segments);
-    let outer_search_results = get_recently_opened_docs(page, limit*2, file_type, conn).unwrap();
-    let mut search_results: Vec<DocumentSearchResult> = Vec::new();
-    // iterate over outer_search_results and remove any item where item.name or item.path contains any of query_segments.not_segments
-    for result in outer_search_results {
-        let mut found = false;
-        for not_segment in &query_segments.not_segments {
-            if result.name.contains(not_segment) || result.path.contains(not_segment) || result.file_type.contains(not_segment) {
-                found = true;
-                break;
-            }
-        }
-        if !found {
-            search_results.push(result);
-        }
-    }
-    Ok(search_results)
-}
-
-// Handle special case with NEGATIVE query only
-// We don't bother looking into body_fts here because it's not needed for eliminating results
-fn _handle_special_case_old(
-    query: String,
-    page: i32,
-    limit: i32,
-    where_date_limit: String,
-    where_file_type: String,
-    mut conn: PooledConnection<ConnectionManager<SqliteConnection>>,
+    search_query: &QueryNode,
+    window: i32,
+    conn: &mut SqliteConnection,
 ) -> Result<Vec<DocumentSearchResult>, diesel::result::Error> {
-    let query_segments: QuerySegments = parse_stringified_query_segments(&query);
-    println!("query_segments: {:?}", query_segments);
-
-    let where_date_limit_clone = where_date_limit.clone();
-    let where_file_type_clone = where_file_type.clone();
-
-    let mut match_string = format!("(\"{}\")", query_segments.not_segments.join("\" OR \""));
-    match_string = match_string.trim().to_string();
-    println!("match_string: {}", match_string);
-
-    // Run a modified query to get all documents that MATCH the query
-    // Not running OFFSET here because only the top matches are needed
-    // getting 5x limit results to catch OR cases that might be missed in first set
-    let inner_query = format!(
-        r#"
-          SELECT m.source_domain, m.source_id as id, m.title as name, m.url as path, m.created_at, m.frecency_rank, m.frecency_last_accessed, d.file_type, d.size, d.is_pinned, d.comment, d.last_opened, d.last_synced, d.last_modified, d.last_parsed
-          FROM metadata_fts m
-          JOIN (
-              SELECT id, file_type, size, is_pinned, comment, last_opened, last_synced, last_modified, last_parsed
-              FROM document
-              {inner_where} {where_file_type} {where_date_limit}
-          ) d ON m.source_id = d.id
-          {match_clause}
-          LIMIT {limit}
-        "#,
-        inner_where = if !where_file_type.is_empty() || !where_date_limit.is_empty() {
-            "WHERE".to_string()
-        } else {
-            "".to_string()
-        },
-        match_clause = if !match_string.is_empty() {
-            format!(
-                "WHERE metadata_fts MATCH '{}' ORDER BY bm25(metadata_fts, 1,1,1,1,50)",
-                match_string
-            )
-        } else {
-            "".to_string()
-        },
-        where_file_type = if !where_file_type.is_empty() {
-            where_file_type
-        } else {
-            "".to_string()
-        },
-        where_date_limit = if !where_date_limit.is_empty() {
-            where_date_limit
-        } else {
-            "".to_string()
-        },
-        limit = limit * 5
-    );
-
-    println!("inner_query: {}", inner_query);
-    let inner_search_results: Vec<DocumentSearchResult> =
-        diesel::sql_query(inner_query).load::<DocumentSearchResult>(&mut conn)?;
-
-    // Run another query to get all documents for the given date_limit and file_type
-    let outer_query = format!(
+    let predicate = search_query.to_sql_predicate_with_words();
+    let recent_query = format!(
         r#"
-          SELECT m.source_domain, m.source_id as id, m.title as name, m.url as path, m.created_at, m.frecency_rank, m.frecency_last_accessed, d.file_type, d.size, d.is_pinned, d.comment, d.last_opened, d.last_synced, d.last_modified, d.last_parsed
-          FROM metadata_fts m
-          JOIN (
-              SELECT id, file_type, size, is_pinned, comment, last_opened, last_synced, last_modified, last_parsed
-              FROM document
-              {inner_where} {where_file_type_clone} {where_date_limit_clone}
-              ORDER BY last_modified DESC
-          ) d ON m.source_id = d.id
-          LIMIT {limit} OFFSET {offset}
+          SELECT {columns}
+          FROM document
+          JOIN metadata ON document.id = metadata.source_id
+          WHERE metadata.source_table = 'document' AND {predicate}
+          ORDER BY document.last_modified DESC
+          LIMIT {window}
         "#,
-        inner_where = if !where_date_limit_clone.is_empty() || !where_file_type_clone.is_empty() {
-            "WHERE".to_string()
-        } else {
-            "".to_string()
-        },
-        where_date_limit_clone = if !where_date_limit_clone.is_empty() {
-            where_date_limit_clone
-        } else {
-            "".to_string()
-        },
-        where_file_type_clone = if !where_file_type_clone.is_empty() {
-            where_file_type_clone
-        } else {
-            "".to_string()
-        },
-        limit = limit,
-        offset = page * limit,
+        columns = DOCUMENT_COLUMNS,
+        predicate = predicate.sql,
+        window = window
     );
-
-    println!("outer_query: {}", outer_query);
-    let outer_search_results: Vec<DocumentSearchResult> =
-        diesel::sql_query(outer_query).load::<DocumentSearchResult>(&mut conn)?;
-
-    // Get the difference between the two sets of results
-    let mut search_results: Vec<DocumentSearchResult> = Vec::new();
-    for outer_result in outer_search_results {
-        let mut found = false;
-        for inner_result in &inner_search_results {
-            if inner_result.id == outer_result.id {
-                found = true;
-                break;
-            }
-        }
-        if !found {
-            search_results.push(outer_result);
-        }
-    }
-
-    Ok(search_results)
+    bind_values(recent_query, predicate.values).load::<DocumentSearchResult>(conn)
 }
 
 // Get search suggestions
@@ -618,13 +521,16 @@ pub fn get_metadata_title_matches(
     conn: &mut SqliteConnection,
 ) -> Result<Vec<String>, diesel::result::Error> {
     println!("getting suggestions for: {}!", query);
-    let inner_query = format!(
-        r#"
-            SELECT snippet(metadata_fts, 4, '', '', '', 2) as title from metadata_fts WHERE metadata_fts MATCH '{}*' ORDER BY rank LIMIT 10;
-        "#,
-        query
-    );
-    let keyword_suggestions: Vec<MetadataFTSSearchResult> = diesel::sql_query(inner_query).load::<MetadataFTSSearchResult>(conn)?;
+    if !query.chars().any(|c| c.is_alphanumeric()) {
+        return Ok(Vec::new());
+    }
+    let inner_query = r#"
+            SELECT snippet(metadata_fts, 4, '', '', '', 2) as title from metadata_fts WHERE metadata_fts MATCH ? ORDER BY rank LIMIT 10;
+        "#;
+    // the query is an FTS5 string, so it is matched as typed
+    let keyword_suggestions: Vec<MetadataFTSSearchResult> = diesel::sql_query(inner_query)
+        .bind::<Text, _>(format!("{}*", fts_string(&query)))
+        .load::<MetadataFTSSearchResult>(conn)?;
     let mut suggestions: Vec<String> = keyword_suggestions.iter().map(|suggestion| suggestion.title.clone()).collect();
     
     // convert keywords to lowercase
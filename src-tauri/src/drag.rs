@@ -10,6 +10,8 @@ use tauri::{
     AppHandle, Runtime, WebviewWindow,
 };
 
+use crate::frecency::record_file_access;
+
 type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug, thiserror::Error)]
@@ -92,6 +94,12 @@ pub async fn start_drag<R: Runtime>(
     on_event_fn: Option<CallbackFn>,
 ) -> Result<()> {
     let (tx, rx) = channel();
+    // files dropped somewhere else count as used
+    let dragged_files: Vec<String> = match &item {
+        DragItem::Files(files) => files.iter().map(|file| file.to_string_lossy().to_string()).collect(),
+        DragItem::Data { .. } => vec![],
+    };
+    let app_handle = app.clone();
 
     let image = match image {
         Image::Raw(r) => r,
@@ -119,6 +127,9 @@ pub async fn start_drag<R: Runtime>(
                 },
                 image,
                 move |result, cursor_pos| {
+                    if matches!(result, drag::DragResult::Dropped) {
+                        record_file_access(&app_handle, &dragged_files);
+                    }
                     if let Some(on_event_fn) = on_event_fn {
                         let callback_result = CallbackResult { result, cursor_pos };
                         let js = format_callback(
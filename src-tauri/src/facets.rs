@@ -0,0 +1,312 @@
+// Facets: counts of the files that match a search by file type, folder, modification month, size and source
+// Every chunk in the tantivy index carries the facets of its file, e.g. /pdf, /Documents/Projects, /2024/03, /1-10 MB and
+// /local. A search counts the files (not the chunks) under each facet, and a facet the user picked is applied as a
+// filter, after which the counts are one level deeper: picking /2024 shows the counts of its months
+// Folders are relative to the home folder, files outside of it get their full path
+
+use chrono::{Local, TimeZone};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path, PathBuf, MAIN_SEPARATOR};
+use tantivy::collector::{Collector, SegmentCollector};
+use tantivy::columnar::Column;
+use tantivy::fastfield::FacetReader;
+use tantivy::query::{BooleanQuery, Occur, Query, TermQuery};
+use tantivy::schema::{Facet, IndexRecordOption, Term};
+use tantivy::{DocId, Index, Score, SegmentOrdinal, SegmentReader};
+use crate::custom_types::FacetCount;
+use crate::query_language::{parse_filter, QueryFilter, QueryNode, ValueRange};
+
+// (facet name, field in the tantivy index)
+pub const FACET_FIELDS: [(&str, &str); 5] = [
+  ("file_type", "file_type_facet"),
+  ("folder", "folder_facet"),
+  ("modified", "modified_facet"),
+  ("size", "size_facet"),
+  ("source", "source_facet"),
+];
+
+// (upper limit in bytes, label) of the size buckets, the last one has no upper limit
+const SIZE_BUCKETS: [(f64, &str); 5] = [
+  (100_000.0, "under 100 KB"),
+  (1_000_000.0, "100 KB-1 MB"),
+  (10_000_000.0, "1-10 MB"),
+  (100_000_000.0, "10-100 MB"),
+  (f64::INFINITY, "over 100 MB"),
+];
+
+// A facet picked by the user, sent as e.g. "modified:/2024"
+#[derive(Debug, Clone)]
+pub struct FacetFilter {
+  pub name: String,
+  pub facet: Facet,
+}
+
+pub fn parse_facet_filters(filters: &[String]) -> Vec<FacetFilter> {
+  filters
+    .iter()
+    .filter_map(|filter| {
+      let (name, path) = filter.split_once(':')?;
+      if !FACET_FIELDS.iter().any(|(facet_name, _)| *facet_name == name) {
+        return None;
+      }
+      let facet = Facet::from_text(path).ok()?;
+      Some(FacetFilter { name: name.to_string(), facet })
+    })
+    .collect()
+}
+
+// The facets of a file, by facet name
+pub fn document_facets(path: &str, file_type: &str, last_modified: i64, size: Option<f64>, source_domain: &str) -> Vec<(&'static str, Facet)> {
+  let mut facets = vec![];
+  if !file_type.is_empty() {
+    facets.push(("file_type", Facet::from_path([file_type.to_lowercase()])));
+  }
+  let folders = folder_path(path);
+  if !folders.is_empty() {
+    facets.push(("folder", Facet::from_path(folders)));
+  }
+  if let Some(modified) = Local.timestamp_opt(last_modified, 0).single().filter(|_| last_modified > 0) {
+    facets.push(("modified", Facet::from_path([modified.format("%Y").to_string(), modified.format("%m").to_string()])));
+  }
+  if let Some(size) = size {
+    let (_, label) = SIZE_BUCKETS.iter().find(|(limit, _)| size < *limit).unwrap_or(&SIZE_BUCKETS[SIZE_BUCKETS.len() - 1]);
+    facets.push(("size", Facet::from_path([*label])));
+  }
+  if !source_domain.is_empty() {
+    facets.push(("source", Facet::from_path([source_domain])));
+  }
+  facets
+}
+
+fn folder_path(path: &str) -> Vec<String> {
+  match Path::new(path).parent() {
+    Some(folder) => folder_facet_path(folder),
+    None => vec![],
+  }
+}
+
+// The folders of the folder facet of a folder, e.g. ["Documents", "Projects"] for ~/Documents/Projects
+pub fn folder_facet_path(folder: &Path) -> Vec<String> {
+  let folder = dirs::home_dir()
+    .and_then(|home| folder.strip_prefix(home).ok().map(|relative| relative.to_path_buf()))
+    .unwrap_or_else(|| folder.to_path_buf());
+  folder
+    .components()
+    .filter_map(|component| match component {
+      Component::Normal(name) => Some(name.to_string_lossy().to_string()),
+      _ => None,
+    })
+    .collect()
+}
+
+// The facets picked by the user as filters of the query language, for the file name search in SQLite: one of the
+// picked values of every facet that was picked
+pub fn facet_filters_query(filters: &[FacetFilter]) -> Option<QueryNode> {
+  let facets: Vec<QueryNode> = FACET_FIELDS
+    .iter()
+    .filter_map(|(name, _)| {
+      let alternatives: Vec<QueryNode> = filters.iter().filter(|filter| filter.name == *name).filter_map(facet_filter_query).collect();
+      match alternatives.len() {
+        0 => None,
+        1 => alternatives.into_iter().next(),
+        _ => Some(QueryNode::Or(alternatives)),
+      }
+    })
+    .collect();
+  match facets.len() {
+    0 => None,
+    1 => facets.into_iter().next(),
+    _ => Some(QueryNode::And(facets)),
+  }
+}
+
+fn facet_filter_query(filter: &FacetFilter) -> Option<QueryNode> {
+  let path = filter.facet.to_path();
+  match filter.name.as_str() {
+    "file_type" => parse_filter("type", path.first()?),
+    // the folders of files outside of the home folder are full paths, so the facet may stand for either
+    "folder" => {
+      let relative: PathBuf = path.iter().collect();
+      let mut folders = vec![QueryNode::Filter(QueryFilter::Folder(Path::new(&MAIN_SEPARATOR.to_string()).join(&relative)))];
+      if let Some(home) = dirs::home_dir() {
+        folders.insert(0, QueryNode::Filter(QueryFilter::Folder(home.join(&relative))));
+      }
+      Some(QueryNode::Or(folders))
+    }
+    "modified" => parse_filter("modified", &path.join("-")),
+    "size" => {
+      let position = SIZE_BUCKETS.iter().position(|(_, label)| path.first() == Some(label))?;
+      let start = position.checked_sub(1).map(|previous| SIZE_BUCKETS[previous].0 as i64);
+      let end = Some(SIZE_BUCKETS[position].0).filter(|limit| limit.is_finite()).map(|limit| limit as i64);
+      Some(QueryNode::Filter(QueryFilter::Size(ValueRange { start, end })))
+    }
+    "source" => Some(QueryNode::Filter(QueryFilter::Source(path.first()?.to_string()))),
+    _ => None,
+  }
+}
+
+// The query restricted to the picked facets, values of the same facet are alternatives
+pub fn with_facet_filters(index: &Index, query: Box<dyn Query>, filters: &[FacetFilter]) -> Box<dyn Query> {
+  if filters.is_empty() {
+    return query;
+  }
+  let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![(Occur::Must, query)];
+  for (name, field_name) in FACET_FIELDS {
+    let field = match index.schema().get_field(field_name) {
+      Ok(field) => field,
+      Err(_) => continue,
+    };
+    let alternatives: Vec<(Occur, Box<dyn Query>)> = filters
+      .iter()
+      .filter(|filter| filter.name == name)
+      .map(|filter| (Occur::Should, Box::new(TermQuery::new(Term::from_facet(field, &filter.facet), IndexRecordOption::Basic)) as Box<dyn Query>))
+      .collect();
+    if !alternatives.is_empty() {
+      clauses.push((Occur::Must, Box::new(BooleanQuery::new(alternatives))));
+    }
+  }
+  Box::new(BooleanQuery::new(clauses))
+}
+
+// Counts the files that match a query under each facet: below the picked value of a facet, or at its top level if
+// none was picked. A file is counted once however many of its chunks match
+pub struct FacetCountCollector {
+  parents: Vec<Facet>,
+}
+
+impl FacetCountCollector {
+  pub fn new(filters: &[FacetFilter]) -> FacetCountCollector {
+    let parents = FACET_FIELDS
+      .iter()
+      .map(|(name, _)| {
+        // with several values picked for a facet, there is no single level to go down to
+        let picked: Vec<&FacetFilter> = filters.iter().filter(|filter| filter.name == *name).collect();
+        match picked.as_slice() {
+          [filter] => filter.facet.clone(),
+          _ => Facet::root(),
+        }
+      })
+      .collect();
+    FacetCountCollector { parents }
+  }
+}
+
+pub struct FacetCountSegmentCollector {
+  ids: Column<i64>,
+  readers: Vec<Option<FacetReader>>,
+  parents: Vec<Facet>,
+  // the child facet of each facet ordinal of this segment, None if it isn't below the parent
+  children: HashMap<(usize, u64), Option<Facet>>,
+  seen_ids: HashSet<i64>,
+  documents: Vec<(i64, Vec<(usize, Facet)>)>,
+}
+
+impl Collector for FacetCountCollector {
+  type Fruit = Vec<FacetCount>;
+  type Child = FacetCountSegmentCollector;
+
+  fn for_segment(&self, _segment_local_id: SegmentOrdinal, segment_reader: &SegmentReader) -> tantivy::Result<FacetCountSegmentCollector> {
+    // facet fields that are missing in an older index are left out until it has been rebuilt
+    let readers = FACET_FIELDS.iter().map(|(_, field_name)| segment_reader.facet_reader(field_name).ok()).collect();
+    Ok(FacetCountSegmentCollector {
+      ids: segment_reader.fast_fields().i64("id")?,
+      readers,
+      parents: self.parents.clone(),
+      children: HashMap::new(),
+      seen_ids: HashSet::new(),
+      documents: vec![],
+    })
+  }
+
+  fn requires_scoring(&self) -> bool {
+    false
+  }
+
+  fn merge_fruits(&self, segment_fruits: Vec<Vec<(i64, Vec<(usize, Facet)>)>>) -> tantivy::Result<Vec<FacetCount>> {
+    // the chunks of a file may be in several segments
+    let mut seen_ids: HashSet<i64> = HashSet::new();
+    let mut counts: HashMap<(usize, Facet), usize> = HashMap::new();
+    for (id, facets) in segment_fruits.into_iter().flatten() {
+      if !seen_ids.insert(id) {
+        continue;
+      }
+      for facet in facets {
+        *counts.entry(facet).or_insert(0) += 1;
+      }
+    }
+
+    let mut facet_counts: Vec<FacetCount> = counts
+      .into_iter()
+      .map(|((field_index, facet), count)| {
+        let name = FACET_FIELDS[field_index].0;
+        FacetCount {
+          facet: name.to_string(),
+          value: facet.to_path_string(),
+          label: facet_label(name, &facet),
+          count,
+        }
+      })
+      .collect();
+    facet_counts.sort_by(|a, b| a.facet.cmp(&b.facet).then(b.count.cmp(&a.count)).then(a.value.cmp(&b.value)));
+    Ok(facet_counts)
+  }
+}
+
+impl SegmentCollector for FacetCountSegmentCollector {
+  type Fruit = Vec<(i64, Vec<(usize, Facet)>)>;
+
+  fn collect(&mut self, doc: DocId, _score: Score) {
+    let id = match self.ids.first(doc) {
+      Some(id) => id,
+      None => return,
+    };
+    if !self.seen_ids.insert(id) {
+      return;
+    }
+    let mut facets = vec![];
+    for (field_index, reader) in self.readers.iter().enumerate() {
+      let reader = match reader {
+        Some(reader) => reader,
+        None => continue,
+      };
+      for ord in reader.facet_ords(doc) {
+        let parent = &self.parents[field_index];
+        let child = self.children.entry((field_index, ord)).or_insert_with(|| {
+          let mut facet = Facet::root();
+          reader.facet_from_ord(ord, &mut facet).ok()?;
+          child_facet(parent, &facet)
+        });
+        if let Some(child) = child {
+          facets.push((field_index, child.clone()));
+        }
+      }
+    }
+    self.documents.push((id, facets));
+  }
+
+  fn harvest(self) -> Self::Fruit {
+    self.documents
+  }
+}
+
+// The facet one level below `parent` on the way to `facet`, e.g. /2024/03 for /2024 and /2024/03
+fn child_facet(parent: &Facet, facet: &Facet) -> Option<Facet> {
+  if !parent.is_prefix_of(facet) {
+    return None;
+  }
+  let depth = if parent.is_root() { 0 } else { parent.to_path().len() };
+  let path = facet.to_path();
+  if path.len() <= depth {
+    return None;
+  }
+  Some(Facet::from_path(&path[..=depth]))
+}
+
+// What the UI shows: the last folder of a folder facet, a month as 2024-03
+fn facet_label(name: &str, facet: &Facet) -> String {
+  let path = facet.to_path();
+  if name == "modified" {
+    return path.join("-");
+  }
+  path.last().map(|segment| segment.to_string()).unwrap_or_default()
+}
@@ -1,4 +1,5 @@
-use rusqlite::{Connection, Result, OpenFlags};
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection, Result, OpenFlags};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::thread;
@@ -52,28 +53,36 @@ fn _get_bookmarks_directory_path() -> Option<PathBuf> {
     })
 }
 
-fn where_clauses(terms: &[&str]) -> String {
-    terms.iter().map(|&term| format!("(moz_places.title LIKE '%{}%' OR moz_places.url LIKE '%{}%')", term, term)).collect::<Vec<_>>().join(" AND ")
+// One condition per term, with the term bound as a LIKE pattern whose wildcards are escaped
+fn where_clauses(terms: &[&str]) -> (String, Vec<Value>) {
+    let clauses = (1..=terms.len()).map(|number| format!("(moz_places.title LIKE ?{0} ESCAPE '\\' OR moz_places.url LIKE ?{0} ESCAPE '\\')", number)).collect::<Vec<_>>().join(" AND ");
+    let patterns = terms.iter().map(|term| Value::Text(format!("%{}%", term.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")))).collect();
+    (clauses, patterns)
 }
 
-fn get_history_query(query: Option<&str>, limit: i64, offset: i64) -> String {
+// The query and its parameters: the LIKE patterns of the terms, then the limit and the offset
+fn get_history_query(query: Option<&str>, limit: i64, offset: i64) -> (String, Vec<Value>) {
     let terms: Vec<&str> = query.map_or(vec![], |q| q.trim().split_whitespace().collect());
+    let (where_clauses_string, mut params) = where_clauses(&terms);
     let where_clause = if terms.is_empty() {
         "".to_string()
     } else {
-        format!("WHERE {}", where_clauses(&terms))
+        format!("WHERE {}", where_clauses_string)
     };
-    format!(
+    let query = format!(
         "
         SELECT
             id, url, title,
             datetime(last_visit_date/1000000, 'unixepoch') as last_visited
         FROM moz_places
         {}
-        ORDER BY last_visit_date DESC LIMIT {} OFFSET {};
+        ORDER BY last_visit_date DESC LIMIT ?{} OFFSET ?{};
         ",
-        where_clause, limit, offset
-    )
+        where_clause, params.len() + 1, params.len() + 2
+    );
+    params.push(Value::Integer(limit));
+    params.push(Value::Integer(offset));
+    (query, params)
 }
 
 fn open_connection_with_retries(db_path: &Path, retries: usize, delay: Duration) -> Result<Connection> {
@@ -127,7 +136,7 @@ fn use_history_search(query: Option<&str>, limit: i64, offset: i64) -> HistoryRe
         }
     };
 
-    let in_query = get_history_query(query, limit, offset);
+    let (in_query, params) = get_history_query(query, limit, offset);
     let mut stmt = match conn.prepare(&in_query) {
         Ok(stmt) => stmt,
         Err(err) => {
@@ -139,7 +148,7 @@ fn use_history_search(query: Option<&str>, limit: i64, offset: i64) -> HistoryRe
         }
     };
 
-    let history_iter = match stmt.query_map([], |row| {
+    let history_iter = match stmt.query_map(params_from_iter(params), |row| {
         Ok((
             row.get(0)?,
             row.get(1)?,
@@ -205,6 +214,7 @@ pub fn search_firefox(user_query: String, limit: i64, page: i64) -> Result<Vec<D
       frecency_rank: 0.0,
       is_pinned: false,
       comment: None,
+      is_encrypted: false,
     }
   }).collect();
 
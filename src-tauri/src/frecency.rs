@@ -0,0 +1,99 @@
+// Frecency: how frequently and how recently a file was used from the app
+// Opening a file, previewing it with QuickLook or dragging it out of the app adds 1 to its score. The score halves every
+// FRECENCY_HALF_LIFE_DAYS, so `frecency_rank` holds the score at `frecency_last_accessed` and is decayed to the current
+// time when it's read. It ranks search results (see database/ranking.rs) and the "frequently used" list
+// Files excluded by the user keep a score of 0 and their accesses are not recorded
+
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl, SelectableHelper, SqliteConnection};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::Runtime;
+use crate::database::establish_connection;
+use crate::database::models::DocumentSearchResult;
+use crate::database::schema::document;
+
+const FRECENCY_HALF_LIFE_DAYS: f32 = 14.0;
+
+fn now() -> i64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs() as i64).unwrap_or(0)
+}
+
+// The score of a file at `at`, given its score at the time it was last accessed
+pub fn current_frecency(frecency_rank: f32, frecency_last_accessed: i64, at: i64) -> f32 {
+  if frecency_rank <= 0.0 || frecency_last_accessed <= 0 {
+    return 0.0;
+  }
+  let age_days = (at - frecency_last_accessed).max(0) as f32 / 86_400.0;
+  frecency_rank * 0.5_f32.powf(age_days / FRECENCY_HALF_LIFE_DAYS)
+}
+
+// Records that the files at these paths were used. Paths that aren't indexed files (e.g. folders) are left out
+pub fn record_file_access<R: Runtime>(app: &tauri::AppHandle<R>, paths: &[String]) {
+  let mut conn = establish_connection(app);
+  let accessed_at = now();
+  for path in paths {
+    if let Err(e) = record_access_in_db(path, accessed_at, &mut conn) {
+      println!("Error recording access to {}: {:?}", path, e);
+    }
+  }
+}
+
+fn record_access_in_db(path: &String, accessed_at: i64, conn: &mut SqliteConnection) -> Result<(), diesel::result::Error> {
+  let documents = document::table
+    .filter(document::path.eq(path))
+    .filter(document::frecency_excluded.eq(false))
+    .select((document::id, document::frecency_rank, document::frecency_last_accessed))
+    .load::<(i32, f32, i64)>(conn)?;
+  for (id, frecency_rank, frecency_last_accessed) in documents {
+    // the TRIGGER copies the new score to the metadata tables
+    diesel::update(document::table.filter(document::id.eq(id)))
+      .set((
+        document::frecency_rank.eq(current_frecency(frecency_rank, frecency_last_accessed, accessed_at) + 1.0),
+        document::frecency_last_accessed.eq(accessed_at),
+      ))
+      .execute(conn)?;
+  }
+  Ok(())
+}
+
+// The most frequently used files, best first, with their current scores
+pub fn get_frequently_used_docs(page: i32, limit: i32, file_type: Option<String>, conn: &mut SqliteConnection) -> Result<Vec<DocumentSearchResult>, diesel::result::Error> {
+  let mut used_documents = document::table
+    .filter(document::frecency_rank.gt(0.0))
+    .filter(document::frecency_excluded.eq(false))
+    .into_boxed();
+  if let Some(file_type) = file_type {
+    let file_types: Vec<String> = file_type.split(",").map(|file_type| file_type.trim().to_string()).collect();
+    used_documents = used_documents.filter(document::file_type.eq_any(file_types));
+  }
+  let mut documents = used_documents
+    .select(DocumentSearchResult::as_select())
+    .load::<DocumentSearchResult>(conn)?;
+
+  let at = now();
+  for document in documents.iter_mut() {
+    document.frecency_rank = current_frecency(document.frecency_rank, document.frecency_last_accessed, at);
+  }
+  documents.sort_by(|a, b| b.frecency_rank.total_cmp(&a.frecency_rank));
+  Ok(documents.into_iter().skip((page * limit).max(0) as usize).take(limit.max(0) as usize).collect())
+}
+
+// Forgets how a file was used, or every file if no id is given
+pub fn reset_frecency(document_id: Option<i32>, conn: &mut SqliteConnection) -> Result<usize, diesel::result::Error> {
+  let mut used_documents = diesel::update(document::table).filter(document::frecency_rank.ne(0.0)).into_boxed();
+  if let Some(document_id) = document_id {
+    used_documents = used_documents.filter(document::id.eq(document_id));
+  }
+  used_documents
+    .set((document::frecency_rank.eq(0.0), document::frecency_last_accessed.eq(0)))
+    .execute(conn)
+}
+
+// Excluded files are left out of the "frequently used" list and don't get a frecency boost in search results
+pub fn set_frecency_excluded(document_id: i32, excluded: bool, conn: &mut SqliteConnection) -> Result<usize, diesel::result::Error> {
+  if excluded {
+    reset_frecency(Some(document_id), conn)?;
+  }
+  diesel::update(document::table.filter(document::id.eq(document_id)))
+    .set(document::frecency_excluded.eq(excluded))
+    .execute(conn)
+}
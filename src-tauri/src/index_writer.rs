@@ -0,0 +1,198 @@
+// The one IndexWriter of the app. tantivy allows a single writer per index, so every add, delete and commit is sent to
+// this thread over a channel instead of opening a writer per call
+// Commits are batched: they run once COMMIT_BATCH_SIZE changes are pending or COMMIT_INTERVAL after the first pending
+// change, and the reader is reloaded right after so searches see them. Segments are merged in the background by the
+// merge policy of the writer
+// A rebuild builds the new index on a thread of its own. The writer keeps changing the live index in the meantime and
+// keeps a copy of the changes, which it applies to the new index before swapping it in
+use std::collections::HashSet;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+use tantivy::merge_policy::LogMergePolicy;
+use tantivy::{Index, IndexWriter, Term};
+use crate::custom_types::TantivyDocumentItem;
+use crate::tantivy_index::{add_items_to_writer, build_index, create_tantivy_schema, get_tantivy_index, index_directory_name, make_index_live, open_index_in, reload_reader, remove_unused_indexes, send_to_index_writer};
+
+const INDEX_WRITER_MEMORY: usize = 150_000_000;
+const COMMIT_BATCH_SIZE: usize = 1000;
+const COMMIT_INTERVAL: Duration = Duration::from_secs(5);
+
+pub enum IndexCommand {
+  Add(Vec<TantivyDocumentItem>),
+  // deletes every chunk of these documents
+  Delete(Vec<i32>),
+  DeleteAll,
+  // commits right away and sends back the result
+  Commit(Sender<tantivy::Result<()>>),
+  // builds a new index from the body table and swaps it in
+  Rebuild,
+  // sent by the rebuild thread: the directory of the new index, or why it couldn't be built
+  Rebuilt(Result<String, String>),
+}
+
+pub fn start_index_writer(app: &tauri::AppHandle) -> tantivy::Result<Sender<IndexCommand>> {
+  let index = get_tantivy_index(create_tantivy_schema())?;
+  let writer = open_writer(&index)?;
+  let (sender, receiver) = channel();
+  let app = app.clone();
+  std::thread::spawn(move || run_index_writer(app, index, writer, receiver));
+  Ok(sender)
+}
+
+fn open_writer(index: &Index) -> tantivy::Result<IndexWriter> {
+  let writer: IndexWriter = index.writer(INDEX_WRITER_MEMORY)?;
+  // a changed file is deleted and added again, so segments full of deleted chunks are merged without waiting for their level
+  let mut merge_policy = LogMergePolicy::default();
+  merge_policy.set_del_docs_ratio_before_merge(0.3);
+  writer.set_merge_policy(Box::new(merge_policy));
+  Ok(writer)
+}
+
+fn run_index_writer(app: tauri::AppHandle, mut index: Index, mut writer: IndexWriter, receiver: Receiver<IndexCommand>) {
+  let mut pending_changes = 0;
+  let mut first_pending_change: Option<Instant> = None;
+  // the changes since a rebuild started, None when no rebuild is running
+  let mut rebuild_changes: Option<Vec<IndexCommand>> = None;
+  loop {
+    // with nothing to commit there is nothing to time, so the thread sleeps until the next command
+    let command = match first_pending_change {
+      Some(since) => receiver.recv_timeout(COMMIT_INTERVAL.saturating_sub(since.elapsed())),
+      None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+    };
+    match command {
+      Ok(IndexCommand::Add(items)) => {
+        if let Err(e) = add_items_to_writer(&index, &mut writer, &items) {
+          println!("Error adding files to Tantivy Index: {:?}", e);
+        }
+        pending_changes += items.len();
+        if let Some(changes) = rebuild_changes.as_mut() {
+          changes.push(IndexCommand::Add(items));
+        }
+      }
+      Ok(IndexCommand::Delete(ids)) => {
+        match index.schema().get_field("id") {
+          Ok(id_field) => {
+            for id in &ids {
+              writer.delete_term(Term::from_field_i64(id_field, i64::from(*id)));
+            }
+          }
+          Err(e) => println!("Error deleting files from Tantivy Index: {:?}", e),
+        }
+        pending_changes += ids.len();
+        if let Some(changes) = rebuild_changes.as_mut() {
+          changes.push(IndexCommand::Delete(ids));
+        }
+      }
+      Ok(IndexCommand::DeleteAll) => {
+        if let Err(e) = writer.delete_all_documents() {
+          println!("Error deleting all documents from Tantivy Index: {:?}", e);
+        }
+        pending_changes += 1;
+        if let Some(changes) = rebuild_changes.as_mut() {
+          changes.push(IndexCommand::DeleteAll);
+        }
+      }
+      Ok(IndexCommand::Commit(reply)) => {
+        let _ = reply.send(commit(&app, &mut writer));
+        pending_changes = 0;
+        first_pending_change = None;
+        continue;
+      }
+      Ok(IndexCommand::Rebuild) => {
+        if rebuild_changes.is_none() {
+          rebuild_changes = Some(Vec::new());
+          let app = app.clone();
+          std::thread::spawn(move || {
+            let built = build_index(&app).map_err(|e| e.to_string());
+            if send_to_index_writer(&app, IndexCommand::Rebuilt(built)).is_err() {
+              println!("The index writer stopped before the Tantivy index was rebuilt");
+            }
+          });
+        }
+        continue;
+      }
+      Ok(IndexCommand::Rebuilt(built)) => {
+        let changes = rebuild_changes.take().unwrap_or_default();
+        match built.map_err(Box::<dyn std::error::Error>::from).and_then(|directory_name| swap_in_index(&app, &directory_name, changes)) {
+          Ok((new_index, new_writer)) => {
+            // replacing the writer drops the old one, which lets go of the old directory. Its pending changes are
+            // already committed to the new index
+            writer = new_writer;
+            index = new_index;
+            pending_changes = 0;
+            first_pending_change = None;
+            println!("Swapped in the rebuilt Tantivy index");
+          }
+          Err(e) => println!("Error rebuilding the Tantivy index: {}", e),
+        }
+        remove_unused_indexes(&index_directory_name());
+        continue;
+      }
+      Err(RecvTimeoutError::Timeout) => {}
+      Err(RecvTimeoutError::Disconnected) => {
+        if pending_changes > 0 {
+          let _ = commit(&app, &mut writer);
+        }
+        break;
+      }
+    }
+
+    if pending_changes > 0 && first_pending_change.is_none() {
+      first_pending_change = Some(Instant::now());
+    }
+    let interval_passed = first_pending_change.map(|since| since.elapsed() >= COMMIT_INTERVAL).unwrap_or(false);
+    if pending_changes >= COMMIT_BATCH_SIZE || interval_passed {
+      if let Err(e) = commit(&app, &mut writer) {
+        println!("Error committing changes to Tantivy Index: {:?}", e);
+      }
+      pending_changes = 0;
+      first_pending_change = None;
+    }
+  }
+}
+
+// Opens the rebuilt index, applies the changes made while it was built and makes it the live index
+fn swap_in_index(app: &tauri::AppHandle, directory_name: &str, changes: Vec<IndexCommand>) -> Result<(Index, IndexWriter), Box<dyn std::error::Error>> {
+  let index = open_index_in(directory_name, create_tantivy_schema())?;
+  let mut writer = open_writer(&index)?;
+  replay_changes(&index, &mut writer, changes)?;
+  writer.commit()?;
+  make_index_live(app, directory_name, &index)?;
+  Ok((index, writer))
+}
+
+// The rebuild may already have read the chunks of a file that was added again while it ran, so the first time a file
+// comes up its chunks are deleted before they are added
+fn replay_changes(index: &Index, writer: &mut IndexWriter, changes: Vec<IndexCommand>) -> tantivy::Result<()> {
+  let id_field = index.schema().get_field("id")?;
+  let mut replaced: HashSet<i64> = HashSet::new();
+  for change in changes {
+    match change {
+      IndexCommand::Add(items) => {
+        for item in &items {
+          if replaced.insert(item.source_id) {
+            writer.delete_term(Term::from_field_i64(id_field, item.source_id));
+          }
+        }
+        add_items_to_writer(index, writer, &items)?;
+      }
+      IndexCommand::Delete(ids) => {
+        for id in ids {
+          replaced.insert(i64::from(id));
+          writer.delete_term(Term::from_field_i64(id_field, i64::from(id)));
+        }
+      }
+      IndexCommand::DeleteAll => {
+        writer.delete_all_documents()?;
+      }
+      IndexCommand::Commit(_) | IndexCommand::Rebuild | IndexCommand::Rebuilt(_) => {}
+    }
+  }
+  Ok(())
+}
+
+// The reader is reloaded right away instead of waiting for its reload policy
+fn commit(app: &tauri::AppHandle, writer: &mut IndexWriter) -> tantivy::Result<()> {
+  writer.commit()?;
+  reload_reader(app)
+}
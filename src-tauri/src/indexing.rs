@@ -1,18 +1,23 @@
 use crate::custom_types::{Error, TantivyDocumentItem};
-use crate::database::schema::{document, metadata, metadata_fts, body, ignore_list, allow_list, file_types};
-use crate::database::models::{AllowList, BodyItem, DocumentItem, FileTypes, IgnoreList};
+use crate::database::schema::{document, metadata, metadata_fts, body, property, bib_entry, calendar_event, contact, highlight, ignore_list, allow_list, file_types};
+use crate::database::models::{AllowList, BibEntryItem, BodyItem, CalendarEventItem, ContactItem, DocumentItem, FileTypes, HighlightItem, IgnoreList, PropertyItem};
 use crate::db_sync::sync_status;
 use crate::housekeeping::get_home_directory;
 use crate::ipc::send_message_to_frontend;
+use crate::language::{detect_language, is_supported_language};
+use crate::password_store::forget_store_key;
+use crate::query_language::folder_like_pattern;
 use crate::user_prefs::return_user_prefs_state;
 use crate::utils::{self, get_metadata};
-use crate::text_extraction::Extractor;
+use crate::text_extraction::{highlights, subtitles, transcription, ContentSection, ExtractedContent, Extractor};
+use crate::text_extraction::encryption::is_encrypted_error;
 use crate::tantivy_index;
 use diesel::connection::Connection;
-use diesel::{ExpressionMethods, QueryDsl, JoinOnDsl, RunQueryDsl, SqliteConnection};
+use diesel::expression_methods::EscapeExpressionMethods;
+use diesel::{ExpressionMethods, QueryDsl, JoinOnDsl, RunQueryDsl, SqliteConnection, TextExpressionMethods};
 use jwalk::{WalkDir, WalkDirGeneric};
 // use log::{info, error};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 pub fn all_allowed_filetypes(connection: &mut SqliteConnection, only_allowed: bool) -> Vec<FileTypes> {
@@ -87,7 +92,8 @@ pub fn create_document_item(file_path: PathBuf, allowed_extensions: &Vec<String>
   }
 
   let filename = file_path.file_name().and_then(|s| s.to_str()).unwrap_or("");
-  let mut extension = file_path.extension().and_then(|s| s.to_str());
+  // e-reader highlights (Kindle's My Clippings.txt, KOReader's book.sdr/metadata.epub.lua) get their own file type
+  let mut extension = highlights::highlights_file_type(&file_path).or(file_path.extension().and_then(|s| s.to_str()));
 
   // if extension is not in allowed filetypes, continue
   if extension.is_none() || !allowed_extensions.contains(&extension.unwrap().to_string()) {
@@ -257,7 +263,7 @@ pub fn walk_directory(conn: &mut SqliteConnection, window: &tauri::WebviewWindow
     }
 
     // remove files from the database that do not exist in the filesystem
-    remove_nonexistent_and_ignored_files(conn);
+    remove_nonexistent_and_ignored_files(conn, &app);
     // add folders to the database
     add_folders_to_db(conn);
     // return number of files_added
@@ -276,9 +282,10 @@ pub fn add_file_metadata_to_database(files_array: &Vec<DocumentItem>, connection
             document::last_modified,
             document::last_opened,
             document::size,
+            document::file_type,
         ))
         .filter(document::path.eq_any(file_paths))
-        .load::<(String, i64, i64, Option<f64>)>(connection)
+        .load::<(String, i64, i64, Option<f64>, String)>(connection)
         .unwrap();
 
     // filter files that do not exist in the database
@@ -287,22 +294,23 @@ pub fn add_file_metadata_to_database(files_array: &Vec<DocumentItem>, connection
         .filter(|file| {
             !existing_files
                 .iter()
-                .any(|(path, _, _, _)| path == &file.path)
+                .any(|(path, _, _, _, _)| path == &file.path)
         })
         .collect();
 
     // filter files that already exist in the database
-    // and whose last_modified, last_opened or size has changed
+    // and whose last_modified, last_opened, size or file_type has changed
     let files_to_update: Vec<_> = files_array_clone
         .into_iter()
         .filter(|file| {
             existing_files
                 .iter()
-                .any(|(path, last_modified, last_opened, size)| {
+                .any(|(path, last_modified, last_opened, size, file_type)| {
                     path == &file.path
                         && (last_modified != &file.last_modified
                             || last_opened != &file.last_opened
-                            || size != &file.size)
+                            || size != &file.size
+                            || file_type != &file.file_type)
                 })
         })
         .collect();
@@ -333,6 +341,16 @@ pub fn add_file_metadata_to_database(files_array: &Vec<DocumentItem>, connection
                 ))
                 .execute(connection)
                 .unwrap();
+            // a file whose type changed (e.g. a My Clippings.txt indexed as txt by an older version) is parsed again
+            if existing_files.iter().any(|(path, _, _, _, file_type)| path == &file.path && file_type != &file.file_type) {
+                let _ = diesel::update(document::table.filter(document::path.eq(&file.path)))
+                    .set((
+                        document::file_type.eq(&file.file_type),
+                        document::last_parsed.eq(0),
+                    ))
+                    .execute(connection)
+                    .unwrap();
+            }
         }
     }
 }
@@ -340,7 +358,7 @@ pub fn add_file_metadata_to_database(files_array: &Vec<DocumentItem>, connection
 pub async fn parse_content_from_files(conn: &mut SqliteConnection, app: tauri::AppHandle) -> usize {
   let mut files_parsed = 0;
 
-  let document_filetypes = ["docx", "md", "pptx", "txt", "epub"];
+  let document_filetypes = ["docx", "md", "pptx", "txt", "epub", "mobi", "azw3", "rs", "py", "ts", "ipynb", "tex", "bib", "ics", "vcf", "clippings", "koreader", "mp4", "mkv", "avi", "mov", "wmv"];
   let image_filetypes = ["png", "jpeg", "jpg"];
   let image_cutoff_size: f64 = 50_000.0;
 
@@ -361,10 +379,11 @@ pub async fn parse_content_from_files(conn: &mut SqliteConnection, app: tauri::A
   // For all files that have the filetype in the array above
   let not_pdf_files_data = document::table
     .inner_join(metadata::table.on(document::id.eq(metadata::source_id)))
+    .filter(metadata::source_table.eq("document"))
     .filter(document::file_type.eq_any(document_filetypes))
-    .select((metadata::id, document::id, document::source_domain, document::name, document::path, document::file_type, document::last_modified, document::last_parsed, document::comment, document::size))
+    .select((metadata::id, document::id, document::source_domain, document::name, document::path, document::file_type, document::last_modified, document::last_parsed, document::comment, document::size, document::created_at))
     .order_by(document::size.asc())
-    .load::<(i32, i32, String, String, String, String, i64, i64, Option<String>, Option<f64>)>(conn)
+    .load::<(i32, i32, String, String, String, String, i64, i64, Option<String>, Option<f64>, i64)>(conn)
     .unwrap();
   
   println!("Not PDF files: {}", not_pdf_files_data.len());
@@ -375,19 +394,21 @@ pub async fn parse_content_from_files(conn: &mut SqliteConnection, app: tauri::A
     // Get the same for all PDF files
     let pdf_files_data = document::table
       .inner_join(metadata::table.on(document::id.eq(metadata::source_id)))
+      .filter(metadata::source_table.eq("document"))
       .filter(document::file_type.eq_any(["pdf"]))
-      .select((metadata::id, document::id, document::source_domain, document::name, document::path, document::file_type, document::last_modified, document::last_parsed, document::comment, document::size))
+      .select((metadata::id, document::id, document::source_domain, document::name, document::path, document::file_type, document::last_modified, document::last_parsed, document::comment, document::size, document::created_at))
       .order_by(document::size.asc())
-      .load::<(i32, i32, String, String, String, String, i64, i64, Option<String>, Option<f64>)>(conn)
+      .load::<(i32, i32, String, String, String, String, i64, i64, Option<String>, Option<f64>, i64)>(conn)
       .unwrap();
     // Get the same for all Image files (only files > 50KB)
     let image_files_data = document::table
       .inner_join(metadata::table.on(document::id.eq(metadata::source_id)))
+      .filter(metadata::source_table.eq("document"))
       .filter(document::file_type.eq_any(image_filetypes))
       .filter(document::size.gt(image_cutoff_size))
-      .select((metadata::id, document::id, document::source_domain, document::name, document::path, document::file_type, document::last_modified, document::last_parsed, document::comment, document::size))
+      .select((metadata::id, document::id, document::source_domain, document::name, document::path, document::file_type, document::last_modified, document::last_parsed, document::comment, document::size, document::created_at))
       .order_by(document::size.asc())
-      .load::<(i32, i32, String, String, String, String, i64, i64, Option<String>, Option<f64>)>(conn)
+      .load::<(i32, i32, String, String, String, String, i64, i64, Option<String>, Option<f64>, i64)>(conn)
       .unwrap();
     
     println!("PDF files: {}", pdf_files_data.len());
@@ -399,44 +420,85 @@ pub async fn parse_content_from_files(conn: &mut SqliteConnection, app: tauri::A
     all_files_data = all_files_data.into_iter().chain(image_files_data.into_iter()).collect();
   }
 
-  // Filter the files based on the ignore_list and allow_list, and last_parsed/last_modified
-  let all_files_data: Vec<(i32, i32, String, String, String, String, i64, i64, Option<String>, Option<f64>)> = all_files_data.into_iter().filter(|item| {
-    let path = item.4.clone();
-    // Check if the file is in the allowed_files list
-    if allowed_files.iter().any(|allowed_file| path.contains(&allowed_file.path)) {
-      return true;
-    }
-    // Check if the file path starts with a path in the allowed_folders list
-    if allowed_folders.iter().any(|allowed_folder| path.starts_with(&allowed_folder.path)) {
-      return true;
-    }
-    // Check if the file is in the ignored_files list
-    if ignored_files.iter().any(|ignored_file| path.contains(&ignored_file.path)) {
-      return false;
-    }
-    // Check if the file path starts with a path in the ignored_folders list
-    if ignored_folders.iter().any(|ignored_folder| path.starts_with(&ignored_folder.path)) {
-      return false;
-    }
-    // Check if last_parsed is 0 (default) OR last_modified > last_parsed
-    if item.7 != 0 && item.6 < item.7 {
-      return false;
-    }
-    true
-  }).collect();
+  // Videos take the modification time of their newest subtitle file, so that new or edited subtitles are parsed
+  // Then the files are filtered based on the ignore_list and allow_list, and last_parsed/last_modified
+  let mut sidecar_folders = subtitles::SidecarFolders::default();
+  let mut files_to_parse = |files: Vec<(i32, i32, String, String, String, String, i64, i64, Option<String>, Option<f64>, i64)>| -> Vec<(i32, i32, String, String, String, String, i64, i64, Option<String>, Option<f64>, i64)> {
+    files.into_iter().map(|mut item| {
+      if subtitles::VIDEO_FILETYPES.contains(&item.5.as_str()) {
+        item.6 = item.6.max(sidecar_folders.last_modified(Path::new(&item.4)));
+      }
+      item
+    }).filter(|item| {
+      let path = item.4.clone();
+      // Check if the file is in the allowed_files list
+      if allowed_files.iter().any(|allowed_file| path.contains(&allowed_file.path)) {
+        return true;
+      }
+      // Check if the file path starts with a path in the allowed_folders list
+      if allowed_folders.iter().any(|allowed_folder| path.starts_with(&allowed_folder.path)) {
+        return true;
+      }
+      // Check if the file is in the ignored_files list
+      if ignored_files.iter().any(|ignored_file| path.contains(&ignored_file.path)) {
+        return false;
+      }
+      // Check if the file path starts with a path in the ignored_folders list
+      if ignored_folders.iter().any(|ignored_folder| path.starts_with(&ignored_folder.path)) {
+        return false;
+      }
+      // Check if last_parsed is 0 (default) OR last_modified > last_parsed
+      if item.7 != 0 && item.6 < item.7 {
+        return false;
+      }
+      true
+    }).collect()
+  };
+
+  files_parsed += parse_files(conn, &app, files_to_parse(all_files_data), false).await;
+
+  // Recordings are transcribed in their own queue once everything else is searchable, whisper takes about as long
+  // as the recording itself
+  if user_preferences.transcribe_audio && transcription::is_available() && sync_status(&app).0 == "true" {
+    let recordings_data = document::table
+      .inner_join(metadata::table.on(document::id.eq(metadata::source_id)))
+      .filter(metadata::source_table.eq("document"))
+      .filter(document::file_type.eq_any(transcription::TRANSCRIPTION_FILETYPES))
+      .select((metadata::id, document::id, document::source_domain, document::name, document::path, document::file_type, document::last_modified, document::last_parsed, document::comment, document::size, document::created_at))
+      .order_by(document::size.asc())
+      .load::<(i32, i32, String, String, String, String, i64, i64, Option<String>, Option<f64>, i64)>(conn)
+      .unwrap();
+    println!("Recordings: {}", recordings_data.len());
+    files_parsed += parse_files(conn, &app, files_to_parse(recordings_data), true).await;
+  }
+
+  // the next run asks the keyring again, so a key removed from it in between isn't kept in memory
+  forget_store_key(&app);
+
+  files_parsed
+}
 
-  // Get sync_running status
-  let mut sync_running = sync_status(&app).0;
+// Extracts the text of the files and adds it to the index and the database in batches
+// With transcribe, the files are recordings and get a whisper transcript. A recording that can't be transcribed keeps
+// its last_parsed, so it is tried again in the next run
+async fn parse_files(conn: &mut SqliteConnection, app: &tauri::AppHandle, files: Vec<(i32, i32, String, String, String, String, i64, i64, Option<String>, Option<f64>, i64)>, transcribe: bool) -> usize {
+  let mut files_parsed = 0;
+  // videos without subtitles are left to the transcription queue
+  let transcription_queued = !transcribe && return_user_prefs_state(app).transcribe_audio && transcription::is_available();
 
   // Set up body_tantivy_items and body_items
   let mut body_items: Vec<BodyItem> = vec![];
   let mut body_tantivy_items: Vec<TantivyDocumentItem> = vec![];
   let mut body_tantivy_source_ids: Vec<i32> = vec![];
+  let mut body_languages: Vec<(i32, String)> = vec![];
+  let mut body_encryption: Vec<(i32, bool)> = vec![];
+  let mut property_items: Vec<PropertyItem> = vec![];
+  let mut file_items = FileItems::default();
   let mut body_file_chunk_cutoff = 500;
   let mut average_body_file_size = 0.0;
 
-  // Iterate over all_files_data and extract text from each file
-  for file_item in all_files_data {
+  // Iterate over the files and extract text from each file
+  for file_item in files {
     let metadata_id = file_item.0;
     let source_id = file_item.1;
     let source_domain = file_item.2;
@@ -447,9 +509,10 @@ pub async fn parse_content_from_files(conn: &mut SqliteConnection, app: tauri::A
     let last_parsed = file_item.7;
     let comment = file_item.8;
     let file_size  = file_item.9;
+    let created_at = file_item.10;
 
     // 1. BEFORE EXTRACTING TEXT: Break the loop if sync_running is false
-    if sync_running == "false" {
+    if sync_status(app).0 == "false" {
       break;
     }
 
@@ -457,13 +520,41 @@ pub async fn parse_content_from_files(conn: &mut SqliteConnection, app: tauri::A
     if last_parsed == 0 || last_modified > last_parsed {
       // Extract text from the file
       // info!("Extracting text from: {}", path.clone());
-      let text = extract_text_from_path(path.clone(), file_type.clone(), &app).await;
+      // Encrypted files that no saved password opens come back empty and flagged, so they aren't re-tried until they
+      // change or a password is added for them
+      let (content, is_encrypted) = if transcribe {
+        match transcription::extract(&path, app).await {
+          Ok(content) => (content, false),
+          Err(e) => {
+            println!("Error transcribing {}: {}", path, e);
+            continue;
+          }
+        }
+      } else {
+        extract_content_from_path(path.clone(), file_type.clone(), app).await
+      };
+      if transcription_queued && content.sections.is_empty() && subtitles::VIDEO_FILETYPES.contains(&file_type.as_str()) && transcription::TRANSCRIPTION_FILETYPES.contains(&file_type.as_str()) {
+        continue;
+      }
+      // Use the language the file declares, or detect it once for the whole file, so that all chunks go to the same language fields
+      let language = content.language.clone().filter(|language| is_supported_language(language)).unwrap_or_else(|| detect_language(&content.text));
       // If there is no text, still add this file so that next time its last_parsed is compared
       // Chunk the text into 2000 character chunks
-      let chunks = chunk_text(text);
+      // Files with sections (slides, chapters etc.) are chunked per section so that every chunk knows where it is
+      let chunks: Vec<(String, Option<&ContentSection>)> = if content.sections.is_empty() {
+        chunk_text(content.text).into_iter().map(|chunk| (chunk, None)).collect()
+      } else {
+        content.sections.iter()
+          .flat_map(|section| chunk_text(section.text.clone()).into_iter().map(move |chunk| (chunk, Some(section))))
+          .collect()
+      };
 
       // For each chunk, create a TantivyDocumentItem, with the body key as the chunk
-      for chunk in chunks {
+      for (chunk, section) in chunks {
+        let (section_label, section_number, section_fields) = match section {
+          Some(section) => (section.label.clone(), section.number, section.fields.clone()),
+          None => ("".to_string(), 0, vec![]),
+        };
         body_tantivy_items.push(
           TantivyDocumentItem {
             source_id: i64::from(source_id),
@@ -474,7 +565,13 @@ pub async fn parse_content_from_files(conn: &mut SqliteConnection, app: tauri::A
             body: chunk.clone(),
             file_type: file_type.clone(),
             last_modified: i64::from(last_modified),
+            created_at: created_at,
+            size: file_size,
             comment: comment.clone().unwrap_or_else(|| {return "".to_string(); }),
+            language: language.clone(),
+            fields: content.fields.iter().cloned().chain(section_fields).collect(),
+            section: section_label.clone(),
+            section_number: section_number,
           }
         );
         // Also createa a BodyItem for the chunk
@@ -484,11 +581,26 @@ pub async fn parse_content_from_files(conn: &mut SqliteConnection, app: tauri::A
             source_id: source_id,
             text: chunk,
             last_parsed: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64,
+            section: section_label,
+            section_number: section_number,
           }
         );
       }
 
       body_tantivy_source_ids.push(source_id);
+      body_languages.push((source_id, language));
+      body_encryption.push((source_id, is_encrypted));
+      file_items.add(source_id, &file_type, &path, last_modified, &content.sections);
+      for (name, value) in content.fields {
+        property_items.push(
+          PropertyItem {
+            metadata_id: metadata_id,
+            source_id: source_id,
+            name: name,
+            value: value,
+          }
+        );
+      }
       average_body_file_size += file_size.unwrap();
       average_body_file_size = average_body_file_size / body_tantivy_items.len() as f64;
       files_parsed += 1;
@@ -514,14 +626,14 @@ pub async fn parse_content_from_files(conn: &mut SqliteConnection, app: tauri::A
       if body_tantivy_items.len() >= body_file_chunk_cutoff {
         println!("Adding {} items to Tantivy Index", body_tantivy_items.len());
         // Delete all items from the Tantivy Index using source_ids
-        let indexing_commit_response = tantivy_index::delete_docs_from_index_with_ids(&body_tantivy_source_ids);
+        let indexing_commit_response = tantivy_index::delete_docs_from_index_with_ids(app, &body_tantivy_source_ids);
         if indexing_commit_response.is_err() {
           println!("Error deleting files from Tantivy Index: {:?}", indexing_commit_response);
         } else {
           println!("Successfully deleted files from Tantivy index");
         }
         // Add all body_tantivy_items to the Tantivy Index
-        let indexing_commit_response = tantivy_index::add_docs_to_index(&body_tantivy_items);
+        let indexing_commit_response = tantivy_index::add_docs_to_index(app, &body_tantivy_items);
         if indexing_commit_response.is_err() {
           println!("Error adding files to Tantivy Index: {:?}", indexing_commit_response);
         } else {
@@ -529,40 +641,58 @@ pub async fn parse_content_from_files(conn: &mut SqliteConnection, app: tauri::A
         }
         // Add all body_items to the Body table
         add_body_to_database(&body_items, conn);
-        // Update last_parsed in document table for these files
+        // Replace the properties of these files in the Property table
+        replace_properties_in_database(&property_items, &body_tantivy_source_ids, conn);
+        // Replace the entries, events and contacts of these files in their tables
+        replace_file_items_in_database(&file_items, &body_tantivy_source_ids, conn);
+        // Update last_parsed and language in document table for these files
         update_last_parsed_in_document_table(conn, body_tantivy_source_ids.clone());
+        update_language_in_document_table(conn, &body_languages);
+        update_encryption_in_document_table(conn, &body_encryption);
         body_tantivy_items.clear();
         body_tantivy_source_ids.clear();
+        body_languages.clear();
+        body_encryption.clear();
+        property_items.clear();
+        file_items.clear();
         average_body_file_size = 0.0;
       }
     }
-
-    // 2. AFTER ADDING TO DB: Break the loop if sync_running is false
-    if sync_running == "false" {
-      break;
-    }
-    // Update sync_running status
-    sync_running = sync_status(&app).0;
   }
 
   // 1.5 process leftover files from the last iteration
   if body_tantivy_items.len() > 0 {
     // Delete all items from the Tantivy Index using source_ids
-    let indexing_commit_response = tantivy_index::delete_docs_from_index_with_ids(&body_tantivy_source_ids);
+    let indexing_commit_response = tantivy_index::delete_docs_from_index_with_ids(app, &body_tantivy_source_ids);
     if indexing_commit_response.is_err() {
       println!("Error deleting files from Tantivy Index: {:?}", indexing_commit_response);
     }
     // Add all body_tantivy_items to the Tantivy Index
-    let indexing_commit_response = tantivy_index::add_docs_to_index(&body_tantivy_items);
+    let indexing_commit_response = tantivy_index::add_docs_to_index(app, &body_tantivy_items);
     if indexing_commit_response.is_err() {
       println!("Error adding files to Tantivy Index: {:?}", indexing_commit_response);
     }
     // Add all body_items to the Body table
     add_body_to_database(&body_items, conn);
-    // Update last_parsed in document table for these files
+    // Replace the properties of these files in the Property table
+    replace_properties_in_database(&property_items, &body_tantivy_source_ids, conn);
+    // Replace the entries, events and contacts of these files in their tables
+    replace_file_items_in_database(&file_items, &body_tantivy_source_ids, conn);
+    // Update last_parsed and language in document table for these files
     update_last_parsed_in_document_table(conn, body_tantivy_source_ids.clone());
+    update_language_in_document_table(conn, &body_languages);
+    update_encryption_in_document_table(conn, &body_encryption);
     body_tantivy_items.clear();
     body_tantivy_source_ids.clear();
+    body_languages.clear();
+    body_encryption.clear();
+    property_items.clear();
+    file_items.clear();
+  }
+  // make the files of this run searchable now instead of at the next batched commit
+  let indexing_commit_response = tantivy_index::commit_index(app);
+  if indexing_commit_response.is_err() {
+    println!("Error committing changes to Tantivy Index: {:?}", indexing_commit_response);
   }
 
   files_parsed
@@ -621,6 +751,228 @@ pub fn update_last_parsed_in_document_table(conn: &mut SqliteConnection, body_ta
   }).unwrap();
 }
 
+pub fn update_language_in_document_table(conn: &mut SqliteConnection, languages: &Vec<(i32, String)>) {
+  // update the detected language in document table for each file using a SQLite Transaction
+  conn.transaction::<_, diesel::result::Error, _>(|connection| {
+    for (source_id, language) in languages {
+      diesel::update(document::table.filter(document::id.eq(source_id)))
+        .set(document::language.eq(language))
+        .execute(connection)?;
+    }
+    Ok(())
+  }).unwrap();
+}
+
+pub fn update_encryption_in_document_table(conn: &mut SqliteConnection, encryption: &Vec<(i32, bool)>) {
+  // update whether each file could not be decrypted in document table using a SQLite Transaction
+  conn.transaction::<_, diesel::result::Error, _>(|connection| {
+    for (source_id, is_encrypted) in encryption {
+      diesel::update(document::table.filter(document::id.eq(source_id)))
+        .set(document::is_encrypted.eq(is_encrypted))
+        .execute(connection)?;
+    }
+    Ok(())
+  }).unwrap();
+}
+
+// Documents flagged as encrypted under a path are parsed again on the next sync, e.g. after a password was saved for it
+pub fn clear_last_parsed_of_encrypted_documents(path: &String, is_folder: bool, conn: &mut SqliteConnection) -> Result<usize, diesel::result::Error> {
+  let encrypted_documents = document::table.filter(document::is_encrypted.eq(true)).into_boxed();
+  let encrypted_documents = if is_folder {
+    encrypted_documents.filter(document::path.like(folder_like_pattern(path)).escape('\\'))
+  } else {
+    encrypted_documents.filter(document::path.eq(path))
+  };
+  let document_ids = encrypted_documents.select(document::id).load::<i32>(conn)?;
+  diesel::update(document::table.filter(document::id.eq_any(document_ids)))
+    .set(document::last_parsed.eq(0))
+    .execute(conn)
+}
+
+// Properties of a re-parsed file replace the old ones, so removed authors or keywords don't linger
+fn replace_properties_in_database(property_items: &Vec<PropertyItem>, source_ids: &Vec<i32>, connection: &mut SqliteConnection) {
+  connection.transaction::<_, diesel::result::Error, _>(|connection| {
+    diesel::delete(property::table.filter(property::source_id.eq_any(source_ids)))
+      .execute(connection)?;
+    if property_items.len() > 0 {
+      diesel::insert_into(property::table)
+        .values(property_items)
+        .execute(connection)?;
+    }
+    Ok(())
+  }).unwrap();
+}
+
+// Items stored inside files, one row each in their own table: the entries of .bib files, the events of .ics files,
+// the contacts of .vcf files and the highlights of e-readers. They are made from the sections that text_extraction
+// returns for these files
+#[derive(Default)]
+struct FileItems {
+  bib_entries: Vec<BibEntryItem>,
+  calendar_events: Vec<CalendarEventItem>,
+  contacts: Vec<ContactItem>,
+  highlights: Vec<HighlightItem>,
+}
+
+impl FileItems {
+  fn add(&mut self, document_id: i32, file_type: &str, path: &String, last_modified: i64, sections: &Vec<ContentSection>) {
+    for section in sections {
+      let values = |name: &str| {
+        section.fields.iter().filter(|(field_name, _)| field_name == name).map(|(_, value)| value.clone()).collect::<Vec<String>>()
+      };
+      match file_type {
+        "bib" => self.bib_entries.push(BibEntryItem {
+          document_id: document_id,
+          entry_number: section.number,
+          citekey: section.label.clone(),
+          entry_type: values("entry_type").join(""),
+          title: values("section_title").join(""),
+          authors: values("author").join("; "),
+          year: values("year").join(""),
+          journal: values("journal").join(""),
+          path: path.clone(),
+          last_modified: last_modified,
+        }),
+        "ics" => self.calendar_events.push(CalendarEventItem {
+          document_id: document_id,
+          event_number: section.number,
+          summary: values("section_title").join(""),
+          location: values("location").join(""),
+          attendees: values("attendees").join("; "),
+          starts_at: values("starts_at").join("").parse::<i64>().unwrap_or(0),
+          ends_at: values("ends_at").join("").parse::<i64>().unwrap_or(0),
+          path: path.clone(),
+          last_modified: last_modified,
+        }),
+        "vcf" => self.contacts.push(ContactItem {
+          document_id: document_id,
+          contact_number: section.number,
+          name: section.label.clone(),
+          emails: values("emails").join("; "),
+          phones: values("phones").join("; "),
+          organization: values("organization").join(""),
+          path: path.clone(),
+          last_modified: last_modified,
+        }),
+        highlights::KINDLE_CLIPPINGS_FILE_TYPE | highlights::KOREADER_FILE_TYPE => self.highlights.push(HighlightItem {
+          document_id: document_id,
+          highlight_number: section.number,
+          book_title: values("section_title").join(""),
+          author: values("author").join(""),
+          location: values("highlight_location").join(""),
+          kind: values("highlight_kind").join(""),
+          text: values("highlight_text").join(""),
+          note: values("notes").join(""),
+          highlighted_at: values("highlighted_at").join("").parse::<i64>().unwrap_or(0),
+          path: path.clone(),
+          last_modified: last_modified,
+        }),
+        _ => return,
+      }
+    }
+  }
+
+  fn clear(&mut self) {
+    self.bib_entries.clear();
+    self.calendar_events.clear();
+    self.contacts.clear();
+    self.highlights.clear();
+  }
+}
+
+// Items of re-parsed files replace the old ones. Their metadata rows are added by triggers
+fn replace_file_items_in_database(file_items: &FileItems, document_ids: &Vec<i32>, connection: &mut SqliteConnection) {
+  connection.transaction::<_, diesel::result::Error, _>(|connection| {
+    delete_file_items_of_documents(document_ids, connection)?;
+    if file_items.bib_entries.len() > 0 {
+      diesel::insert_into(bib_entry::table)
+        .values(&file_items.bib_entries)
+        .execute(connection)?;
+    }
+    if file_items.calendar_events.len() > 0 {
+      diesel::insert_into(calendar_event::table)
+        .values(&file_items.calendar_events)
+        .execute(connection)?;
+    }
+    if file_items.contacts.len() > 0 {
+      diesel::insert_into(contact::table)
+        .values(&file_items.contacts)
+        .execute(connection)?;
+    }
+    if file_items.highlights.len() > 0 {
+      diesel::insert_into(highlight::table)
+        .values(&file_items.highlights)
+        .execute(connection)?;
+    }
+    Ok(())
+  }).unwrap();
+}
+
+// Delete the entries, events, contacts and highlights of files together with their rows in the Metadata and Metadata_fts tables
+fn delete_file_items_of_documents(document_ids: &Vec<i32>, connection: &mut SqliteConnection) -> Result<usize, diesel::result::Error> {
+  let entry_ids = bib_entry::table
+    .filter(bib_entry::document_id.eq_any(document_ids))
+    .select(bib_entry::id)
+    .load::<i32>(connection)?;
+  delete_item_metadata("bib_entry", &entry_ids, connection)?;
+  let mut deleted = diesel::delete(bib_entry::table.filter(bib_entry::id.eq_any(&entry_ids)))
+    .execute(connection)?;
+
+  let event_ids = calendar_event::table
+    .filter(calendar_event::document_id.eq_any(document_ids))
+    .select(calendar_event::id)
+    .load::<i32>(connection)?;
+  delete_item_metadata("calendar_event", &event_ids, connection)?;
+  deleted += diesel::delete(calendar_event::table.filter(calendar_event::id.eq_any(&event_ids)))
+    .execute(connection)?;
+
+  let contact_ids = contact::table
+    .filter(contact::document_id.eq_any(document_ids))
+    .select(contact::id)
+    .load::<i32>(connection)?;
+  delete_item_metadata("contact", &contact_ids, connection)?;
+  deleted += diesel::delete(contact::table.filter(contact::id.eq_any(&contact_ids)))
+    .execute(connection)?;
+
+  let highlight_ids = highlight::table
+    .filter(highlight::document_id.eq_any(document_ids))
+    .select(highlight::id)
+    .load::<i32>(connection)?;
+  delete_item_metadata("highlight", &highlight_ids, connection)?;
+  deleted += diesel::delete(highlight::table.filter(highlight::id.eq_any(&highlight_ids)))
+    .execute(connection)?;
+  Ok(deleted)
+}
+
+fn delete_item_metadata(source_table: &str, source_ids: &Vec<i32>, connection: &mut SqliteConnection) -> Result<usize, diesel::result::Error> {
+  if source_ids.is_empty() {
+    return Ok(0);
+  }
+  let metadata_ids = metadata::table
+    .filter(metadata::source_table.eq(source_table))
+    .filter(metadata::source_id.eq_any(source_ids))
+    .select(metadata::id)
+    .load::<i32>(connection)?;
+  // metadata_fts reads the values to delete from metadata, so it goes first
+  diesel::delete(metadata_fts::table.filter(metadata_fts::id.eq_any(&metadata_ids)))
+    .execute(connection)?;
+  diesel::delete(metadata::table.filter(metadata::id.eq_any(&metadata_ids)))
+    .execute(connection)
+}
+
+// The content of the file and whether it's encrypted with a password we don't have
+pub async fn extract_content_from_path(path: String, file_type: String, app: &tauri::AppHandle) -> (ExtractedContent, bool) {
+  let extractor: Extractor = Extractor::new();
+  let extracted_content = extractor.extract_content_from_file(path, file_type, app).await;
+  match extracted_content {
+    Ok(content) => (content, false),
+    Err(e) => {
+      eprintln!("Error extracting text: {}", e);
+      (ExtractedContent::default(), is_encrypted_error(e.as_ref()))
+    }
+  }
+}
+
 pub async fn extract_text_from_path(path: String, file_type: String, app: &tauri::AppHandle) -> String {
   let extractor: Extractor = Extractor::new();
   let extracted_text = extractor.extract_text_from_file(path, file_type, app).await;
@@ -650,7 +1002,7 @@ fn chunk_text(text: String) -> Vec<String> {
   chunks
 }
 
-pub fn remove_nonexistent_and_ignored_files(conn: &mut SqliteConnection) {
+pub fn remove_nonexistent_and_ignored_files(conn: &mut SqliteConnection, app: &tauri::AppHandle) {
   let all_file_paths = document::table
     .select(document::path)
     .load::<String>(conn)
@@ -719,7 +1071,7 @@ pub fn remove_nonexistent_and_ignored_files(conn: &mut SqliteConnection) {
     // remove files from the database
     for chunks_of_files_to_remove in chunked_files_to_remove {
       println!("Removing {} files from chunk", chunks_of_files_to_remove.len());
-      remove_vector_of_file_paths_from_db(&chunks_of_files_to_remove, conn, false);
+      remove_vector_of_file_paths_from_db(&chunks_of_files_to_remove, conn, false, app);
     }
   }
 
@@ -738,25 +1090,36 @@ pub fn remove_nonexistent_and_ignored_files(conn: &mut SqliteConnection) {
     // remove files from the database
     for chunks_of_files_to_remove in chunked_files_to_remove {
       println!("Removing {} files from chunk", chunks_of_files_to_remove.len());
-      remove_vector_of_file_paths_from_db(&chunks_of_files_to_remove, conn, true);
+      remove_vector_of_file_paths_from_db(&chunks_of_files_to_remove, conn, true, app);
     }
   }
 }
 
-fn remove_vector_of_file_paths_from_db(file_paths: &Vec<String>, conn: &mut SqliteConnection, remove_from_index_only: bool) {
+fn remove_vector_of_file_paths_from_db(file_paths: &Vec<String>, conn: &mut SqliteConnection, remove_from_index_only: bool, app: &tauri::AppHandle) {
   let file_paths_clone_two = file_paths.clone();
   // get metadata_id for all file_paths
   let metadata_ids = document::table
     .inner_join(metadata::table.on(document::id.eq(metadata::source_id)))
+    .filter(metadata::source_table.eq("document"))
     .filter(document::path.eq_any(file_paths_clone_two))
     .select(metadata::id)
     .load::<i32>(conn)
     .unwrap();
 
-  // first delete from Body table using metadata_ids because depends on metadata_id as foreign key
+  let document_ids_with_content = document::table
+    .filter(document::path.eq_any(file_paths))
+    .select(document::id)
+    .load::<i32>(conn)
+    .unwrap();
+
+  // first delete from Body and Property tables using metadata_ids because they depend on metadata_id as foreign key
+  // the entries, events and contacts of files are content too, and they depend on document_id as foreign key
   conn.transaction::<_, diesel::result::Error, _>(|connection| {
     diesel::delete(body::table.filter(body::metadata_id.eq_any(metadata_ids.clone())))
-      .execute(connection)
+      .execute(connection)?;
+    diesel::delete(property::table.filter(property::metadata_id.eq_any(metadata_ids.clone())))
+      .execute(connection)?;
+    delete_file_items_of_documents(&document_ids_with_content, connection)
   }).unwrap();
 
   if !remove_from_index_only {
@@ -786,7 +1149,7 @@ fn remove_vector_of_file_paths_from_db(file_paths: &Vec<String>, conn: &mut Sqli
   .select(document::id)
   .load::<i32>(conn)
   .unwrap();
-  let indexing_commit_response = tantivy_index::delete_docs_from_index_with_ids(&document_ids);
+  let indexing_commit_response = tantivy_index::delete_docs_from_index_with_ids(app, &document_ids);
   if indexing_commit_response.is_err() {
     println!("Error deleting files from Tantivy Index: {:?}", indexing_commit_response);
   }  
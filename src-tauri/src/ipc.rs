@@ -3,18 +3,20 @@
 
 use crate::arc_read::get_arc_profiles;
 use crate::chrome_read::get_chrome_profiles;
-use crate::custom_types::{ContextMenuState, DBConnPoolState, DBStat, DateLimit, Error, Payload, SyncRunningState, TantivyBookmarkSearchResult, TantivyDocumentSearchResult, TantivyReaderState, UserPreferencesState};
+use crate::custom_types::{ContextMenuState, DBConnPoolState, DBStat, DateLimit, Error, IndexWriterState, PasswordStoreState, Payload, RankingWeights, SyncRunningState, TantivyBookmarkSearchResult, TantivyDocumentSearchResult, TantivyReaderState, UserPreferencesState};
 use crate::database::{establish_connection, get_connection_pool};
-use crate::database::models::{DocumentSearchResult, IgnoreList};
+use crate::database::models::{DocumentSearchResult, IgnoreList, SearchResults};
 use crate::database::search::{
     get_counts_for_all_filetypes, get_file_parsed_count, get_metadata_title_matches, get_parsed_text_for_file, get_recently_opened_docs, search_browser_history, search_fts_index
 };
 use crate::db_sync::{run_sync_operation, sync_status, add_specific_folders};
 use crate::housekeeping::get_app_directory;
-use crate::indexing::{add_path_to_ignore_list, all_allowed_filetypes, clear_last_parsed_dates_from_db, get_all_ignored_paths, remove_nonexistent_and_ignored_files, remove_paths_from_ignore_list};
-use crate::user_prefs::{fix_global_shortcut_string, get_global_shortcut, get_modifiers_and_code_from_global_shortcut, is_global_shortcut_enabled, return_user_prefs_state, set_automatic_background_sync_flag_in_db, set_default_user_prefs, set_detailed_scan_flag_in_db, set_global_shortcut_flag_in_db, set_launch_at_startup_flag_in_db, set_manual_setup_flag_in_db, set_new_global_shortcut_in_db, set_onboarding_done_flag_in_db, set_roadmap_survey_answered_flag_in_db, set_show_search_suggestions_flag_in_db, set_parse_pdfs_flag_in_db, set_user_preferences_state_from_db_value};
+use crate::language::parse_languages_string;
+use crate::indexing::{add_path_to_ignore_list, all_allowed_filetypes, clear_last_parsed_dates_from_db, clear_last_parsed_of_encrypted_documents, get_all_ignored_paths, remove_nonexistent_and_ignored_files, remove_paths_from_ignore_list};
+use crate::user_prefs::{fix_global_shortcut_string, get_global_shortcut, get_modifiers_and_code_from_global_shortcut, is_global_shortcut_enabled, return_user_prefs_state, set_automatic_background_sync_flag_in_db, set_default_user_prefs, set_detailed_scan_flag_in_db, set_global_shortcut_flag_in_db, set_launch_at_startup_flag_in_db, set_manual_setup_flag_in_db, set_new_global_shortcut_in_db, set_onboarding_done_flag_in_db, set_roadmap_survey_answered_flag_in_db, set_show_search_suggestions_flag_in_db, set_parse_pdfs_flag_in_db, set_transcribe_audio_flag_in_db, set_user_preferences_state_from_db_value, get_search_languages, set_search_languages_in_db, get_ranking_weights, set_ranking_weights_in_db};
 use crate::utils::{extract_text_from_pdf, graceful_restart, read_image_to_base64, read_text_from_file, save_text_to_file};
 use crate::window::hide_or_show_window;
+use crate::password_store::{add_password, protected_paths, remove_passwords, ProtectedPath};
 use serde_json;
 use tauri::Manager;
 use tauri_plugin_shell;
@@ -24,8 +26,10 @@ use crate::context_menu::{contextmenu_receiver, searchresult_context_menu_folder
 // use log::info;
 use std::sync::Mutex;
 use std::process::Command;
-use crate::tantivy_index::{acquire_searcher_from_reader, create_tantivy_schema, delete_all_docs_from_index, get_reader_for_index, get_tantivy_index, parse_query_and_get_top_docs, return_bookmark_search_results, return_document_search_results};
+use crate::frecency::{get_frequently_used_docs, record_file_access, reset_frecency, set_frecency_excluded};
+use crate::tantivy_index::{acquire_searcher_from_reader, create_tantivy_schema, delete_all_docs_from_index, get_reader_for_index, get_tantivy_index, parse_query_and_get_top_docs, rebuild_index_if_outdated, return_bookmark_search_results, return_document_search_results};
 use crate::tantivy_index::internal_test_create_csv_dump_from_index;
+use crate::index_writer::start_index_writer;
 use tauri::Emitter;
 
 #[cfg(target_os = "windows")]
@@ -97,11 +101,82 @@ fn open_file_or_folder(file_path: String, window: tauri::Window) -> Result<Strin
         window.label(),
         file_path
     );
+    record_file_access(window.app_handle(), &[file_path.clone()]);
     let _ = open::that(file_path);
     // If it worked
     Ok("Opened the file or folder!".into())
 }
 
+// Open a file at a section reported by the search results, e.g. slide 17 of a presentation
+// `file_type` picks the app that can go to the section: PowerPoint (macOS) goes to the slide of a pptx, QuickTime (macOS)
+// or VLC to the time of a subtitle or transcript of a recording. Every other file is opened normally
+#[tauri::command]
+fn open_file_at_section(file_path: String, file_type: String, section_number: i64, app: tauri::AppHandle) -> Result<String, Error> {
+    println!("Opening {} at section {}", file_path, section_number);
+    record_file_access(&app, &[file_path.clone()]);
+
+    #[cfg(target_os = "macos")]
+    if file_type == "pptx" && section_number > 0 {
+      let script = format!(
+        r#"tell application "Microsoft PowerPoint"
+          open POSIX file "{}"
+          -- the deck is open by now, so a slide it can't go to doesn't open it a second time below
+          try
+            go to slide (slide view of document window 1) number {}
+          end try
+          activate
+        end tell"#,
+        file_path.replace("\\", "\\\\").replace("\"", "\\\""),
+        section_number
+      );
+      let opened_at_section = Command::new("osascript")
+        .args(["-e", &script])
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+      if opened_at_section {
+        return Ok("Opened the file at the section!".into());
+      }
+    }
+
+    // Recordings are opened at the start of the matching subtitles or transcript (section_number is in seconds)
+    #[cfg(target_os = "macos")]
+    if ["mp4", "mov", "mp3", "m4a", "wav"].contains(&file_type.as_str()) && section_number > 0 {
+      let script = format!(
+        r#"tell application "QuickTime Player"
+          open POSIX file "{}"
+          set current time of document 1 to {}
+          play document 1
+          activate
+        end tell"#,
+        file_path.replace("\\", "\\\\").replace("\"", "\\\""),
+        section_number
+      );
+      let opened_at_section = Command::new("osascript")
+        .args(["-e", &script])
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+      if opened_at_section {
+        return Ok("Opened the file at the section!".into());
+      }
+    }
+
+    // VLC, if it is installed, can start any of the audio and video types at a given time
+    if ["mp4", "mkv", "avi", "mov", "wmv", "mp3", "m4a", "wav"].contains(&file_type.as_str()) && section_number > 0 {
+      let opened_at_section = Command::new("vlc")
+        .args([format!("--start-time={}", section_number), file_path.clone()])
+        .spawn()
+        .is_ok();
+      if opened_at_section {
+        return Ok("Opened the file at the section!".into());
+      }
+    }
+
+    let _ = open::that(file_path);
+    Ok("Opened the file or folder!".into())
+}
+
 // Open the folder containing the file from the filepath
 #[tauri::command]
 fn open_folder_containing_file(file_path: String) -> Result<String, Error> {
@@ -165,7 +240,7 @@ async fn run_file_sync(switch_off: bool, app: tauri::AppHandle, window: tauri::W
 async fn ignore_file_or_folder(app: tauri::AppHandle, path: String, is_directory: bool, should_ignore_indexing: bool) {
   let mut conn = establish_connection(&app);
   add_path_to_ignore_list(path, is_directory, should_ignore_indexing, &mut conn).unwrap();
-  remove_nonexistent_and_ignored_files(&mut conn);
+  remove_nonexistent_and_ignored_files(&mut conn, &app);
 }
 
 // Remove list of paths from Ignore List
@@ -182,6 +257,28 @@ async fn show_ignored_paths(app: tauri::AppHandle) -> Result<Vec<IgnoreList>, Er
   Ok(get_all_ignored_paths(&mut conn))
 }
 
+// Save a password for an encrypted file, or for every encrypted file in a folder
+// Files it may open are parsed again on the next sync
+#[tauri::command]
+async fn add_document_password(app: tauri::AppHandle, path: String, is_directory: bool, password: String) -> Result<(), Error> {
+  add_password(path.clone(), is_directory, password).map_err(|e| Error::new(&e.to_string()))?;
+  let mut conn = establish_connection(&app);
+  let _ = clear_last_parsed_of_encrypted_documents(&path, is_directory, &mut conn);
+  Ok(())
+}
+
+// Remove the saved passwords of a list of paths
+#[tauri::command]
+async fn remove_document_passwords(paths: Vec<String>) -> Result<(), Error> {
+  remove_passwords(paths).map_err(|e| Error::new(&e.to_string()))
+}
+
+// Paths that have a saved password, without the passwords
+#[tauri::command]
+async fn show_password_protected_paths() -> Result<Vec<ProtectedPath>, Error> {
+  Ok(protected_paths())
+}
+
 // Get sync status
 #[tauri::command]
 fn get_sync_status(app: tauri::AppHandle) -> Result<String, Error> {
@@ -199,13 +296,14 @@ fn get_search_suggestions(query: String, app: tauri::AppHandle) -> Result<Vec<St
 
 // Run search
 #[tauri::command]
-fn run_search(query: String, page: i32, limit: i32, file_type: Option<String>, date_limit: Option<DateLimit>, app: tauri::AppHandle) -> Result<Vec<DocumentSearchResult>, Error> {
+fn run_search(query: String, page: i32, limit: i32, file_type: Option<String>, date_limit: Option<DateLimit>, fuzzy: Option<bool>, facet_filters: Option<Vec<String>>, app: tauri::AppHandle) -> Result<SearchResults, Error> {
     println!(
-        "run_search: query: {}, page: {}, limit: {}, file_type: {:?}, date_limit: {:?}",
-        query, page, limit, file_type, date_limit
+        "run_search: query: {}, page: {}, limit: {}, file_type: {:?}, date_limit: {:?}, fuzzy: {:?}, facet_filters: {:?}",
+        query, page, limit, file_type, date_limit, fuzzy, facet_filters
     );
     let conn = establish_connection(&app);
-    let search_results = search_fts_index(query, page, limit, file_type, date_limit, conn, &app).unwrap_or(vec![]);
+    let search_results = search_fts_index(query, page, limit, file_type, date_limit, fuzzy.unwrap_or(false), facet_filters.unwrap_or_default(), conn, &app)
+        .unwrap_or(SearchResults { hits: vec![], facets: vec![] });
     Ok(search_results)
 }
 
@@ -222,6 +320,35 @@ fn get_recent_docs(
     Ok(search_results)
 }
 
+// Get the most frequently used documents
+#[tauri::command]
+fn get_frequently_used(
+    page: i32,
+    limit: i32,
+    file_type: Option<String>,
+    app: tauri::AppHandle
+) -> Result<Vec<DocumentSearchResult>, Error> {
+    let mut conn = establish_connection(&app);
+    let frequently_used = get_frequently_used_docs(page, limit, file_type, &mut conn).unwrap_or(vec![]);
+    Ok(frequently_used)
+}
+
+// Forget how a document was used, or how every document was used if no id is given
+#[tauri::command]
+fn reset_document_frecency(document_id: Option<i32>, app: tauri::AppHandle) -> Result<usize, Error> {
+    let mut conn = establish_connection(&app);
+    let documents_reset = reset_frecency(document_id, &mut conn).unwrap_or(0);
+    Ok(documents_reset)
+}
+
+// Stop (or start again) tracking how a document is used
+#[tauri::command]
+fn exclude_document_from_frecency(document_id: i32, excluded: bool, app: tauri::AppHandle) -> Result<usize, Error> {
+    let mut conn = establish_connection(&app);
+    let documents_updated = set_frecency_excluded(document_id, excluded, &mut conn).unwrap_or(0);
+    Ok(documents_updated)
+}
+
 // Get DB Stats
 #[tauri::command]
 fn get_db_stats(app: tauri::AppHandle) -> Result<Vec<DBStat>, Error> {
@@ -269,8 +396,9 @@ async fn read_text_from_txt_file(file_path: String) -> Result<String, Error>  {
 
 // Open QuickLook (MacOS) or Peek (Windows)
 #[tauri::command]
-fn open_quicklook(file_path: String) -> Result<String, Error> {
+fn open_quicklook(file_path: String, app: tauri::AppHandle) -> Result<String, Error> {
     println!("Opening QuickLook for {}", file_path);
+    record_file_access(&app, &[file_path.clone()]);
 
     #[cfg(target_os = "macos")]
     std::thread::spawn(move || {
@@ -305,6 +433,18 @@ fn open_context_menu(app_handle: tauri::AppHandle, window: tauri::Window, option
           "pptx" => &state.docs,
           "txt" => &state.docs,
           "epub" => &state.docs,
+          "mobi" => &state.docs,
+          "azw3" => &state.docs,
+          "rs" => &state.docs,
+          "py" => &state.docs,
+          "ts" => &state.docs,
+          "ipynb" => &state.docs,
+          "tex" => &state.docs,
+          "bib" => &state.docs,
+          "ics" => &state.docs,
+          "vcf" => &state.docs,
+          "clippings" => &state.docs,
+          "koreader" => &state.docs,
           "pdf" => &state.docs,
           _ => &state.other,
         };
@@ -378,6 +518,10 @@ async fn set_user_preference(window: tauri::WebviewWindow, app_handle: tauri::Ap
       set_parse_pdfs_flag_in_db(value, &app_handle);
       set_user_preferences_state_from_db_value(&app_handle);
     }
+    "transcribe_audio" => {
+      set_transcribe_audio_flag_in_db(value, &app_handle);
+      set_user_preferences_state_from_db_value(&app_handle);
+    }
     "manual_setup" => {
       set_manual_setup_flag_in_db(value, &app_handle);
       set_user_preferences_state_from_db_value(&app_handle);
@@ -407,6 +551,31 @@ async fn set_new_global_shortcut(app_handle: tauri::AppHandle, new_shortcut_stri
   // app_handle.restart();
 }
 
+// Set the languages used to analyse search queries (ISO 639-1 codes, e.g. ["en", "de"])
+#[tauri::command]
+async fn set_search_languages(app_handle: tauri::AppHandle, languages: Vec<String>) -> Result<Vec<String>, Error> {
+  println!("Setting search languages: {:?}", languages);
+  let languages = parse_languages_string(&languages.join(","));
+  set_search_languages_in_db(languages.join(","), &app_handle);
+  set_user_preferences_state_from_db_value(&app_handle);
+  Ok(languages)
+}
+
+// Get the weights of the signals that rank search results
+#[tauri::command]
+fn get_search_ranking_weights(app_handle: tauri::AppHandle) -> Result<RankingWeights, Error> {
+  Ok(get_ranking_weights(&app_handle))
+}
+
+// Set the weights of the signals that rank search results, values that are left out go back to their default
+#[tauri::command]
+async fn set_search_ranking_weights(app_handle: tauri::AppHandle, weights: RankingWeights) -> Result<RankingWeights, Error> {
+  println!("Setting search ranking weights: {:?}", weights);
+  set_ranking_weights_in_db(&weights, &app_handle);
+  set_user_preferences_state_from_db_value(&app_handle);
+  Ok(weights)
+}
+
 // #[tauri::command]
 // async fn run_sidecar(app: tauri::AppHandle) {
 //   use tauri_plugin_shell::{ShellExt, process::CommandEvent};
@@ -428,8 +597,10 @@ fn search_tantivy_files_index(app_handle: tauri::AppHandle, user_query: String,
   let tantivy_index = get_tantivy_index(create_tantivy_schema()).unwrap();
   let searcher = acquire_searcher_from_reader(&app_handle).unwrap();
 
-  let top_docs = parse_query_and_get_top_docs(&tantivy_index, &searcher, user_query, limit, page*limit).unwrap();
-  let search_results = return_document_search_results(&tantivy_index, &searcher, top_docs).unwrap_or(vec![]);
+  let search_languages = get_search_languages(&app_handle);
+  let (top_docs, parsed_query) = parse_query_and_get_top_docs(&tantivy_index, &searcher, user_query, &search_languages, false, &[], limit, page*limit).unwrap();
+  let mut conn = establish_connection(&app_handle);
+  let search_results = return_document_search_results(&tantivy_index, &searcher, parsed_query.as_ref(), &search_languages, top_docs, &mut conn).unwrap_or(vec![]);
 
   Ok(search_results)
 }
@@ -440,7 +611,8 @@ fn search_tantivy_bookmarks_index(app_handle: tauri::AppHandle, user_query: Stri
   let tantivy_index = get_tantivy_index(create_tantivy_schema()).unwrap();
   let searcher = acquire_searcher_from_reader(&app_handle).unwrap();
 
-  let top_docs = parse_query_and_get_top_docs(&tantivy_index, &searcher, user_query, limit, page*limit).unwrap();
+  let search_languages = get_search_languages(&app_handle);
+  let (top_docs, _) = parse_query_and_get_top_docs(&tantivy_index, &searcher, user_query, &search_languages, false, &[], limit, page*limit).unwrap();
   let search_results = return_bookmark_search_results(&tantivy_index, &searcher, top_docs).unwrap_or(vec![]);
   Ok(search_results)
 }
@@ -455,7 +627,7 @@ fn create_csv_dump(app_handle: tauri::AppHandle) {
 fn clear_index(app_handle: tauri::AppHandle) {
   let mut conn = establish_connection(&app_handle);
   // delete the tantivy index
-  let _ = delete_all_docs_from_index();
+  let _ = delete_all_docs_from_index(&app_handle);
   // clear last_parsed timestamps from the database
   clear_last_parsed_dates_from_db(&mut conn);
 }
@@ -486,6 +658,7 @@ pub fn initialize() {
       get_allowed_filetypes,
       get_os,
       open_file_or_folder,
+      open_file_at_section,
       open_folder_containing_file,
       run_file_indexing,
       run_file_sync,
@@ -493,6 +666,9 @@ pub fn initialize() {
       get_search_suggestions,
       run_search,
       get_recent_docs,
+      get_frequently_used,
+      reset_document_frecency,
+      exclude_document_from_frecency,
       get_db_stats,
       get_count_of_files_parsed,
       get_text_for_file,
@@ -503,12 +679,18 @@ pub fn initialize() {
       open_context_menu,
       set_user_preference,
       set_new_global_shortcut,
+      set_search_languages,
+      get_search_ranking_weights,
+      set_search_ranking_weights,
       crate::drag::start_drag,
       get_user_preferences_state,
       reset_user_preferences,
       ignore_file_or_folder,
       show_ignored_paths,
       remove_from_ignore_list,
+      add_document_password,
+      remove_document_passwords,
+      show_password_protected_paths,
       get_image_base64,
       search_tantivy_files_index,
       search_tantivy_bookmarks_index,
@@ -532,11 +714,18 @@ pub fn initialize() {
           let tantivy_index = get_tantivy_index(create_tantivy_schema()).unwrap();
           let given_reader = get_reader_for_index(&tantivy_index).unwrap();
           handle.manage(Mutex::new(TantivyReaderState::new(given_reader)));
+          // tantivy index writer, the only one of the app
+          let index_writer_sender = start_index_writer(handle).unwrap();
+          handle.manage(Mutex::new(IndexWriterState::new(index_writer_sender)));
+          // an index from an older schema is rebuilt from the body table in the background
+          rebuild_index_if_outdated(handle);
           // user preferences state
           handle.manage(Mutex::new(UserPreferencesState::default()));
           set_user_preferences_state_from_db_value(app.handle());
           // sync running state
           handle.manage(Mutex::new(SyncRunningState::default()));
+          // password store key, cached during an indexing run
+          handle.manage(Mutex::new(PasswordStoreState::default()));
           // context menu
           let main_window = handle.get_webview_window("main").unwrap();
           let folder_context_menu = searchresult_context_menu_folder(&main_window);
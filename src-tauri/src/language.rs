@@ -0,0 +1,50 @@
+// Language detection for parsed text
+// The detected language decides which language-specific tantivy fields (stemming, stopwords) a document is indexed into
+// Languages are stored as ISO 639-1 codes (e.g. "en", "de") and an empty string means the language is unknown
+
+use std::collections::HashSet;
+use whatlang::{detect, Lang};
+
+// Languages that have their own analyzer in the tantivy index
+pub const SUPPORTED_LANGUAGES: [&str; 4] = ["en", "de", "fr", "es"];
+// Detection does not get more accurate beyond a few thousand characters so only a sample is checked
+const DETECTION_SAMPLE_SIZE: usize = 5000;
+
+pub fn detect_language(text: &str) -> String {
+  let sample: String = text.chars().take(DETECTION_SAMPLE_SIZE).collect();
+  match detect(&sample) {
+    Some(info) if info.is_reliable() => lang_to_code(info.lang()).to_string(),
+    _ => "".to_string(),
+  }
+}
+
+fn lang_to_code(lang: Lang) -> &'static str {
+  match lang {
+    Lang::Eng => "en",
+    Lang::Deu => "de",
+    Lang::Fra => "fr",
+    Lang::Spa => "es",
+    _ => "",
+  }
+}
+
+pub fn is_supported_language(language: &str) -> bool {
+  SUPPORTED_LANGUAGES.contains(&language)
+}
+
+// Parse the comma separated languages string stored in user preferences
+// Falls back to English so that there is always at least one analyzer to search with
+pub fn parse_languages_string(languages: &str) -> Vec<String> {
+  // a language listed twice keeps its first position
+  let mut seen: HashSet<String> = HashSet::new();
+  let mut parsed: Vec<String> = languages
+    .split(",")
+    .map(|language| language.trim().to_lowercase())
+    .filter(|language| is_supported_language(language))
+    .filter(|language| seen.insert(language.clone()))
+    .collect();
+  if parsed.is_empty() {
+    parsed.push("en".to_string());
+  }
+  parsed
+}
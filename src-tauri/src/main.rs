@@ -20,6 +20,8 @@ extern crate zip;
 extern crate csv;
 extern crate rusqlite;
 extern crate dirs;
+extern crate whatlang;
+extern crate toml;
 
 mod utils;
 mod custom_types;
@@ -33,10 +35,17 @@ mod text_extraction;
 mod context_menu;
 mod user_prefs;
 mod tantivy_index;
+mod index_writer;
+mod language;
+mod cjk_tokenizer;
 mod window;
 mod firefox_read;
 mod chrome_read;
 mod arc_read;
+mod password_store;
+mod frecency;
+mod facets;
+mod query_language;
 
 fn main() {
   housekeeping::initialize();
@@ -0,0 +1,210 @@
+// Passwords the user saved for encrypted documents, per file or per folder
+// They are kept in passwords.enc in the app directory, encrypted with AES-256-CBC and authenticated with HMAC-SHA256
+// The key of the store lives in the OS keyring (Keychain, Credential Manager, Secret Service). When there is no
+// keyring it's kept in passwords.key next to the store, readable only by the user. Indexing looks the key up once per
+// run, the first time a file turns out to be encrypted
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use aes::Aes256;
+use cbc::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tauri::Manager;
+use crate::custom_types::PasswordStoreState;
+use crate::housekeeping::get_app_directory;
+use crate::utils::norm;
+
+const KEYRING_SERVICE: &str = "buzee";
+const KEYRING_USER: &str = "document-passwords";
+const STORE_FILE: &str = "passwords.enc";
+const KEY_FILE: &str = "passwords.key";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct StoredPassword {
+  path: String,
+  is_folder: bool,
+  password: String,
+}
+
+// What the settings page lists, the passwords themselves never leave the backend
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ProtectedPath {
+  pub path: String,
+  pub is_folder: bool,
+}
+
+fn store_path() -> String {
+  norm(&format!("{}/{}", get_app_directory(), STORE_FILE))
+}
+
+fn key_file_path() -> String {
+  norm(&format!("{}/{}", get_app_directory(), KEY_FILE))
+}
+
+// 64 bytes: the AES key followed by the HMAC key
+const KEY_LENGTH: usize = 64;
+
+fn store_key() -> Result<Vec<u8>, Box<dyn Error>> {
+  use base64::prelude::*;
+  if let Ok(entry) = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER) {
+    if let Ok(encoded_key) = entry.get_password() {
+      let key = BASE64_STANDARD.decode(encoded_key)?;
+      if key.len() != KEY_LENGTH {
+        return Err("The password store key in the keyring has the wrong length".into());
+      }
+      return Ok(key);
+    }
+  }
+  if let Ok(key) = fs::read(key_file_path()) {
+    if key.len() == KEY_LENGTH {
+      return Ok(key);
+    }
+  }
+  new_store_key()
+}
+
+// Replaces the key in the keyring, or in passwords.key when there is no keyring
+fn new_store_key() -> Result<Vec<u8>, Box<dyn Error>> {
+  use base64::prelude::*;
+  let mut key = vec![0u8; KEY_LENGTH];
+  rand::thread_rng().fill_bytes(&mut key);
+  let saved_to_keyring = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+    .map(|entry| entry.set_password(&BASE64_STANDARD.encode(&key)).is_ok())
+    .unwrap_or(false);
+  if !saved_to_keyring {
+    println!("No keyring available, keeping the password store key in the app directory");
+    write_private_file(&key_file_path(), &key)?;
+  }
+  Ok(key)
+}
+
+// A store that its key doesn't open can't be recovered: the key was removed from the keyring, or one of them is
+// damaged. It's deleted and a new key is made, so that saving passwords works again instead of failing every time
+fn reset_store(reason: Box<dyn Error>) -> Result<(), Box<dyn Error>> {
+  println!("Resetting the password store, the saved passwords have to be added again: {}", reason);
+  if Path::new(&store_path()).exists() {
+    fs::remove_file(store_path())?;
+  }
+  new_store_key()?;
+  Ok(())
+}
+
+// The key kept in PasswordStoreState, so the keyring isn't asked for every encrypted file of an indexing run
+fn cached_store_key(app: &tauri::AppHandle) -> Result<Vec<u8>, Box<dyn Error>> {
+  let state_mutex = app.state::<Mutex<PasswordStoreState>>();
+  let mut state = state_mutex.lock().unwrap();
+  if let Some(key) = &state.key {
+    return Ok(key.clone());
+  }
+  let key = store_key()?;
+  state.key = Some(key.clone());
+  Ok(key)
+}
+
+// Called when an indexing run ends
+pub fn forget_store_key(app: &tauri::AppHandle) {
+  let state_mutex = app.state::<Mutex<PasswordStoreState>>();
+  state_mutex.lock().unwrap().key = None;
+}
+
+fn write_private_file(path: &str, contents: &[u8]) -> Result<(), Box<dyn Error>> {
+  fs::write(path, contents)?;
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+  }
+  Ok(())
+}
+
+fn read_store() -> Result<Vec<StoredPassword>, Box<dyn Error>> {
+  match decrypt_store(store_key) {
+    Ok(passwords) => Ok(passwords),
+    Err(e) => {
+      reset_store(e)?;
+      Ok(vec![])
+    }
+  }
+}
+
+// The key is only looked up when there is a store to decrypt
+fn decrypt_store(load_key: impl FnOnce() -> Result<Vec<u8>, Box<dyn Error>>) -> Result<Vec<StoredPassword>, Box<dyn Error>> {
+  let data = match fs::read(store_path()) {
+    Ok(data) => data,
+    Err(_) => return Ok(vec![]),
+  };
+  if data.len() < 48 {
+    return Err("The password store is damaged".into());
+  }
+  let key = load_key()?;
+  if key.len() != KEY_LENGTH {
+    return Err("The password store key has the wrong length".into());
+  }
+  let (encrypted, tag) = data.split_at(data.len() - 32);
+  let mut mac = Hmac::<Sha256>::new_from_slice(&key[32..])?;
+  mac.update(encrypted);
+  mac.verify_slice(tag).map_err(|_| "The password store doesn't match its key")?;
+  let json = cbc::Decryptor::<Aes256>::new_from_slices(&key[..32], &encrypted[..16])?
+    .decrypt_padded_vec_mut::<Pkcs7>(&encrypted[16..])
+    .map_err(|_| "The password store is damaged")?;
+  Ok(serde_json::from_slice(&json)?)
+}
+
+// IV, then the encrypted JSON, then the HMAC of both
+// Only called after read_store, which resets a store that its key doesn't open, so a broken key has nothing to lose
+fn write_store(passwords: &Vec<StoredPassword>) -> Result<(), Box<dyn Error>> {
+  let key = store_key().or_else(|_| new_store_key())?;
+  let mut iv = [0u8; 16];
+  rand::thread_rng().fill_bytes(&mut iv);
+  let json = serde_json::to_vec(passwords)?;
+  let mut data = iv.to_vec();
+  data.extend(cbc::Encryptor::<Aes256>::new_from_slices(&key[..32], &iv)?.encrypt_padded_vec_mut::<Pkcs7>(&json));
+  let mut mac = Hmac::<Sha256>::new_from_slice(&key[32..])?;
+  mac.update(&data);
+  data.extend(mac.finalize().into_bytes());
+  write_private_file(&store_path(), &data)
+}
+
+pub fn add_password(path: String, is_folder: bool, password: String) -> Result<(), Box<dyn Error>> {
+  let mut passwords = read_store()?;
+  passwords.retain(|stored| stored.path != path);
+  passwords.push(StoredPassword { path, is_folder, password });
+  write_store(&passwords)
+}
+
+pub fn remove_passwords(paths: Vec<String>) -> Result<(), Box<dyn Error>> {
+  let mut passwords = read_store()?;
+  passwords.retain(|stored| !paths.contains(&stored.path));
+  write_store(&passwords)
+}
+
+pub fn protected_paths() -> Vec<ProtectedPath> {
+  read_store()
+    .unwrap_or_default()
+    .into_iter()
+    .map(|stored| ProtectedPath { path: stored.path, is_folder: stored.is_folder })
+    .collect()
+}
+
+// Passwords to try for a file: its own first, then those of the folders it's in from the closest one up
+pub fn passwords_for_path(file: &String, app: &tauri::AppHandle) -> Vec<String> {
+  let mut passwords = match decrypt_store(|| cached_store_key(app)) {
+    Ok(passwords) => passwords,
+    Err(e) => {
+      // the next lookup reads the new key
+      forget_store_key(app);
+      if let Err(e) = reset_store(e) {
+        println!("Error resetting the password store: {}", e);
+      }
+      return vec![];
+    }
+  };
+  let file_path = Path::new(file);
+  passwords.retain(|stored| if stored.is_folder { file_path.starts_with(&stored.path) } else { stored.path == *file });
+  passwords.sort_by_key(|stored| (stored.is_folder, std::cmp::Reverse(stored.path.len())));
+  passwords.into_iter().map(|stored| stored.password).collect()
+}
@@ -0,0 +1,1094 @@
+// The search query language
+// A query is parsed into a tree of `QueryNode`s, which is compiled for each back end: to an FTS5 MATCH expression and an
+// SQL predicate on the document table for the file name search in SQLite, and to a query for the tantivy index
+//   budget report          words, file names match them as the start of a word
+//   "annual report"        a phrase
+//   -draft, NOT draft      without the word
+//   a OR b, (a OR b) c     either of them, parentheses group
+//   title:budget           the word in one field: title, author and the other text fields of the tantivy index
+//   type:pdf, type:pdf,docx
+//   in:~/Projects          files in the folder or below it, a folder without ~ or / is in the home folder
+//   modified:>2024-03      modified after March 2024, also >=, <, <=, modified:2024 for the year and 2024-01..2024-03
+//   modified:last-2-weeks  also today, yesterday, this-week, this-month, this-year and last/past [N] days, weeks,
+//                          months or years, written with - or in quotes ("last 2 weeks"), in the user's time zone
+//   created:, opened:      like modified:, a file that was never opened has no opened date
+//   size:>10MB             larger than 10 MB, also B, KB, GB and TB. size:10MB is size:>=10MB
+// The parser never fails: operators it doesn't know are searched as text, filters with a value it can't read are left
+// out, unbalanced quotes and parentheses are closed and stray OR/NOT are ignored
+// SQLite can't express everything: fields other than title and comment only exist in the tantivy index. OR and NOT
+// groups that mix words and filters can't be split between MATCH and the predicate, so the predicate looks up their words
+// The filters are applied by both back ends before a page of results is cut, as SQL predicates and as range and term
+// queries on the fast fields of the tantivy index. The tantivy index has no opened dates, as they change all the time, so
+// the files opened in a range are looked up in SQLite first, see `opened_ranges`
+
+use chrono::{Datelike, Days, Local, Months, NaiveDate, TimeZone};
+use std::collections::HashMap;
+use std::ops::Bound;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use tantivy::query::{AllQuery, BooleanQuery, Occur, Query, RangeQuery, TermQuery, TermSetQuery};
+use tantivy::schema::{Facet, IndexRecordOption, Schema, Term};
+use tantivy::Index;
+use crate::custom_types::DateLimit;
+use crate::facets::folder_facet_path;
+use crate::tantivy_index::{create_query_parser, DEFAULT_SEARCH_FIELDS};
+
+// `name:value` operators that filter files
+const FILTER_OPERATORS: [&str; 6] = ["type", "in", "modified", "created", "opened", "size"];
+// `name:value` operators that search one field of the tantivy index
+const FIELD_OPERATORS: [&str; 31] = [
+  "title", "author", "body", "comment", "tags", "sender", "recipient", "subject", "attachments", "keywords", "publisher",
+  "isbn", "headings", "links", "aliases", "code_language", "code_comments", "identifiers", "outputs", "abstract", "journal", "year",
+  "citekey", "location", "attendees", "organization", "emails", "phones", "annotations", "annotation_author", "notes",
+];
+// fields that are also columns of metadata_fts
+const FTS_COLUMNS: [&str; 2] = ["title", "comment"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryNode {
+  Word(String),
+  Phrase(String),
+  // a word or phrase searched in one field, e.g. title:budget
+  Field(String, Box<QueryNode>),
+  Filter(QueryFilter),
+  Not(Box<QueryNode>),
+  And(Vec<QueryNode>),
+  Or(Vec<QueryNode>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryFilter {
+  FileType(String),
+  // an absolute path
+  Folder(PathBuf),
+  Modified(ValueRange),
+  Created(ValueRange),
+  Opened(ValueRange),
+  Size(ValueRange),
+  // the source of a file, e.g. local, only picked as a facet
+  Source(String),
+}
+
+// Unix timestamps or bytes from `start` up to, but not including, `end`. None is open ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ValueRange {
+  pub start: Option<i64>,
+  pub end: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Open,
+  Close,
+  Or,
+  Not,
+  // `name:(`, the group that follows is searched in this field or filtered by it
+  OperatorOpen(String),
+  Text { operator: Option<String>, value: String, quoted: bool },
+}
+
+fn is_operator(name: &str) -> bool {
+  let name = name.to_lowercase();
+  FILTER_OPERATORS.contains(&name.as_str()) || FIELD_OPERATORS.contains(&name.as_str())
+}
+
+fn tokenize(query: &str) -> Vec<Token> {
+  // some keyboards type curly quotes
+  let chars: Vec<char> = query.chars().map(|c| if matches!(c, '“' | '”' | '„') { '"' } else { c }).collect();
+  let mut tokens = vec![];
+  let mut position = 0;
+  while position < chars.len() {
+    match chars[position] {
+      c if c.is_whitespace() => position += 1,
+      '(' => {
+        tokens.push(Token::Open);
+        position += 1;
+      }
+      ')' => {
+        tokens.push(Token::Close);
+        position += 1;
+      }
+      '"' => {
+        let (value, next) = read_quoted(&chars, position + 1);
+        tokens.push(Token::Text { operator: None, value, quoted: true });
+        position = next;
+      }
+      '-' if chars.get(position + 1).map_or(false, |next| !next.is_whitespace()) => {
+        tokens.push(Token::Not);
+        position += 1;
+      }
+      _ => {
+        let start = position;
+        while position < chars.len() && !chars[position].is_whitespace() && !matches!(chars[position], '(' | ')' | '"') {
+          position += 1;
+        }
+        let word: String = chars[start..position].iter().collect();
+        // an operator with a quoted value or a group, e.g. in:"~/My Projects" or type:(pdf OR docx)
+        if let Some(operator) = word.strip_suffix(':').filter(|operator| is_operator(operator)) {
+          match chars.get(position) {
+            Some('"') => {
+              let (value, next) = read_quoted(&chars, position + 1);
+              tokens.push(Token::Text { operator: Some(operator.to_lowercase()), value, quoted: true });
+              position = next;
+              continue;
+            }
+            Some('(') => {
+              tokens.push(Token::OperatorOpen(operator.to_lowercase()));
+              position += 1;
+              continue;
+            }
+            _ => {}
+          }
+        }
+        match word.as_str() {
+          "OR" | "|" | "||" => tokens.push(Token::Or),
+          // words are required anyway
+          "AND" | "&&" => {}
+          "NOT" => tokens.push(Token::Not),
+          _ => match word.split_once(':') {
+            Some((operator, value)) if is_operator(operator) => {
+              tokens.push(Token::Text { operator: Some(operator.to_lowercase()), value: value.to_string(), quoted: false });
+            }
+            _ => tokens.push(Token::Text { operator: None, value: word, quoted: false }),
+          },
+        }
+      }
+    }
+  }
+  tokens
+}
+
+// The text up to the closing quote, or to the end if there is none, and the position after it
+fn read_quoted(chars: &[char], start: usize) -> (String, usize) {
+  let end = chars[start.min(chars.len())..].iter().position(|c| *c == '"').map_or(chars.len(), |offset| start + offset);
+  (chars[start.min(end)..end].iter().collect(), (end + 1).min(chars.len()))
+}
+
+struct Parser {
+  tokens: Vec<Token>,
+  position: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.position)
+  }
+
+  fn next(&mut self) -> Option<Token> {
+    let token = self.tokens.get(self.position).cloned();
+    self.position += 1;
+    token
+  }
+
+  fn skip_close(&mut self) {
+    if self.peek() == Some(&Token::Close) {
+      self.position += 1;
+    }
+  }
+
+  // alternatives separated by OR, up to a ) or the end
+  fn parse_or(&mut self) -> Option<QueryNode> {
+    let mut alternatives = vec![];
+    loop {
+      // an OR at the start, at the end or after another OR has nothing on one side and is skipped
+      if let Some(node) = self.parse_and() {
+        alternatives.push(node);
+      }
+      if self.peek() == Some(&Token::Or) {
+        self.position += 1;
+      } else {
+        break;
+      }
+    }
+    match alternatives.len() {
+      0 => None,
+      1 => alternatives.pop(),
+      _ => Some(QueryNode::Or(alternatives)),
+    }
+  }
+
+  fn parse_and(&mut self) -> Option<QueryNode> {
+    let mut nodes = vec![];
+    while let Some(token) = self.peek() {
+      if matches!(token, Token::Or | Token::Close) {
+        break;
+      }
+      if let Some(node) = self.parse_unary() {
+        nodes.push(node);
+      }
+    }
+    match nodes.len() {
+      0 => None,
+      1 => nodes.pop(),
+      _ => Some(QueryNode::And(nodes)),
+    }
+  }
+
+  fn parse_unary(&mut self) -> Option<QueryNode> {
+    match self.next()? {
+      Token::Not => match self.peek() {
+        // nothing to exclude
+        None | Some(Token::Or) | Some(Token::Close) => None,
+        _ => self.parse_unary().map(|node| QueryNode::Not(Box::new(node))),
+      },
+      Token::Open => {
+        let node = self.parse_or();
+        // a missing ) is closed at the end
+        self.skip_close();
+        node
+      }
+      Token::OperatorOpen(operator) => {
+        let node = self.parse_or();
+        self.skip_close();
+        node.and_then(|node| apply_operator(&operator, node))
+      }
+      Token::Text { operator, value, quoted } => {
+        // the value of a filter is read as it was typed, in:~ has no letters or digits
+        let node = if operator.as_deref().map_or(false, |operator| FILTER_OPERATORS.contains(&operator)) && !value.trim().is_empty() {
+          QueryNode::Phrase(value.trim().to_string())
+        } else {
+          text_node(&value, quoted)?
+        };
+        match operator {
+          Some(operator) => apply_operator(&operator, node),
+          None => Some(node),
+        }
+      }
+      // OR and ) are handled by parse_or and parse_and
+      Token::Or | Token::Close => None,
+    }
+  }
+}
+
+// Parses the query typed by the user, see the top of this file
+pub fn parse_query(query: &str) -> QueryNode {
+  let mut parser = Parser { tokens: tokenize(query), position: 0 };
+  let mut nodes = vec![];
+  while parser.peek().is_some() {
+    if let Some(node) = parser.parse_or() {
+      nodes.push(node);
+    }
+    // a ) without a ( before it
+    parser.skip_close();
+  }
+  if nodes.len() == 1 {
+    nodes.pop().unwrap()
+  } else {
+    QueryNode::And(nodes)
+  }
+}
+
+// A word, or a phrase if it was quoted or has punctuation in it (like file.txt). Text without letters or digits is skipped
+fn text_node(text: &str, quoted: bool) -> Option<QueryNode> {
+  let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+  if !text.chars().any(|c| c.is_alphanumeric()) {
+    return None;
+  }
+  if quoted {
+    return Some(QueryNode::Phrase(text));
+  }
+  // a * at the end asks for what words already match
+  let word = text.trim_end_matches('*');
+  if word.chars().all(|c| c.is_alphanumeric()) {
+    Some(QueryNode::Word(word.to_string()))
+  } else {
+    Some(QueryNode::Phrase(text))
+  }
+}
+
+// Turns the words of `name:value` or `name:(...)` into field searches or filters
+fn apply_operator(operator: &str, node: QueryNode) -> Option<QueryNode> {
+  match node {
+    QueryNode::Word(text) | QueryNode::Phrase(text) if FILTER_OPERATORS.contains(&operator) => {
+      let filter = parse_filter(operator, &text);
+      if filter.is_none() {
+        println!("Ignoring {}:{}, the value can't be read", operator, text);
+      }
+      filter
+    }
+    QueryNode::Word(_) | QueryNode::Phrase(_) => Some(QueryNode::Field(operator.to_string(), Box::new(node))),
+    QueryNode::Not(inner) => apply_operator(operator, *inner).map(|inner| QueryNode::Not(Box::new(inner))),
+    QueryNode::And(nodes) => join_nodes(nodes.into_iter().filter_map(|node| apply_operator(operator, node)).collect(), QueryNode::And),
+    QueryNode::Or(nodes) => join_nodes(nodes.into_iter().filter_map(|node| apply_operator(operator, node)).collect(), QueryNode::Or),
+    // an operator inside the group wins
+    QueryNode::Field(..) | QueryNode::Filter(_) => Some(node),
+  }
+}
+
+fn join_nodes(mut nodes: Vec<QueryNode>, join: fn(Vec<QueryNode>) -> QueryNode) -> Option<QueryNode> {
+  match nodes.len() {
+    0 => None,
+    1 => nodes.pop(),
+    _ => Some(join(nodes)),
+  }
+}
+
+pub(crate) fn parse_filter(operator: &str, value: &str) -> Option<QueryNode> {
+  match operator {
+    "type" => {
+      let file_types: Vec<QueryNode> = value
+        .split(',')
+        .map(|file_type| file_type.trim().trim_start_matches('.').to_lowercase())
+        .filter(|file_type| !file_type.is_empty())
+        .map(|file_type| QueryNode::Filter(QueryFilter::FileType(file_type)))
+        .collect();
+      join_nodes(file_types, QueryNode::Or)
+    }
+    "in" => parse_folder(value).map(|folder| QueryNode::Filter(QueryFilter::Folder(folder))),
+    "modified" => parse_range(value, "=", parse_date).map(|range| QueryNode::Filter(QueryFilter::Modified(range))),
+    "created" => parse_range(value, "=", parse_date).map(|range| QueryNode::Filter(QueryFilter::Created(range))),
+    "opened" => parse_range(value, "=", parse_date).map(|range| QueryNode::Filter(QueryFilter::Opened(range))),
+    "size" => parse_range(value, ">=", parse_size).map(|range| QueryNode::Filter(QueryFilter::Size(range))),
+    _ => None,
+  }
+}
+
+fn parse_folder(value: &str) -> Option<PathBuf> {
+  let value = value.trim();
+  let home = dirs::home_dir();
+  let folder = if value == "~" {
+    home?
+  } else if let Some(relative) = value.strip_prefix("~/").or_else(|| value.strip_prefix("~\\")) {
+    home?.join(relative)
+  } else if Path::new(value).is_absolute() {
+    PathBuf::from(value)
+  } else {
+    home?.join(value)
+  };
+  Some(folder)
+}
+
+// `value` is `<op><unit>` or `<unit>..<unit>`, where the operator is one of >, >=, <, <= and =, and a unit is what
+// `parse_unit` reads, as the range of values it stands for (e.g. the timestamps of a month)
+fn parse_range(value: &str, default_operator: &str, parse_unit: fn(&str) -> Option<(i64, i64)>) -> Option<ValueRange> {
+  let value = value.trim();
+  if let Some((from, to)) = value.split_once("..") {
+    let start = if from.trim().is_empty() { None } else { Some(parse_unit(from.trim())?.0) };
+    let end = if to.trim().is_empty() { None } else { Some(parse_unit(to.trim())?.1) };
+    if start.is_none() && end.is_none() {
+      return None;
+    }
+    return Some(ValueRange { start, end });
+  }
+  let (operator, operand) = [">=", "<=", ">", "<", "="]
+    .iter()
+    .find_map(|operator| value.strip_prefix(operator).map(|operand| (*operator, operand)))
+    .unwrap_or((default_operator, value));
+  let (start, end) = parse_unit(operand.trim())?;
+  Some(match operator {
+    ">" => ValueRange { start: Some(end), end: None },
+    ">=" => ValueRange { start: Some(start), end: None },
+    "<" => ValueRange { start: None, end: Some(start) },
+    "<=" => ValueRange { start: None, end: Some(end) },
+    _ => ValueRange { start: Some(start), end: Some(end) },
+  })
+}
+
+// A year, month or day (2024, 2024-03, 2024-03-15, also with / or .) or a relative date (see `parse_relative_date`) as
+// the timestamps from its start to the start of the next one, in the user's time zone
+fn parse_date(value: &str) -> Option<(i64, i64)> {
+  if let Some((start, end)) = parse_relative_date(value, Local::now().date_naive()) {
+    return Some((local_timestamp(start)?, local_timestamp(end)?));
+  }
+  let parts: Vec<&str> = value.split(|c| matches!(c, '-' | '/' | '.')).collect();
+  if parts[0].len() != 4 {
+    return None;
+  }
+  let numbers: Vec<u32> = parts.iter().map(|part| part.parse::<u32>().ok()).collect::<Option<Vec<u32>>>()?;
+  let year = numbers[0] as i32;
+  let (start, end) = match numbers[1..] {
+    [] => (NaiveDate::from_ymd_opt(year, 1, 1)?, NaiveDate::from_ymd_opt(year + 1, 1, 1)?),
+    [month] => {
+      let start = NaiveDate::from_ymd_opt(year, month, 1)?;
+      (start, start.checked_add_months(Months::new(1))?)
+    }
+    [month, day] => {
+      let start = NaiveDate::from_ymd_opt(year, month, day)?;
+      (start, start.succ_opt()?)
+    }
+    _ => return None,
+  };
+  Some((local_timestamp(start)?, local_timestamp(end)?))
+}
+
+// today, yesterday, this week, this month, this year, and last or past [N] days, weeks, months or years up to and
+// including today, as the first day and the day after the last one. Words are separated by spaces, - or _
+fn parse_relative_date(value: &str, today: NaiveDate) -> Option<(NaiveDate, NaiveDate)> {
+  let value = value.to_lowercase();
+  let words: Vec<&str> = value.split(|c: char| c.is_whitespace() || matches!(c, '-' | '_')).filter(|word| !word.is_empty()).collect();
+  let tomorrow = today.succ_opt()?;
+  match words.as_slice() {
+    ["today"] => Some((today, tomorrow)),
+    ["yesterday"] => Some((today.pred_opt()?, today)),
+    ["this", "week"] => {
+      let start = today.checked_sub_days(Days::new(u64::from(today.weekday().num_days_from_monday())))?;
+      Some((start, start.checked_add_days(Days::new(7))?))
+    }
+    ["this", "month"] => {
+      let start = today.with_day(1)?;
+      Some((start, start.checked_add_months(Months::new(1))?))
+    }
+    ["this", "year"] => Some((NaiveDate::from_ymd_opt(today.year(), 1, 1)?, NaiveDate::from_ymd_opt(today.year() + 1, 1, 1)?)),
+    ["last" | "past", unit] => Some((days_before(today, 1, unit)?.succ_opt()?, tomorrow)),
+    ["last" | "past", count, unit] => {
+      let count: u32 = count.parse().ok().filter(|count| *count > 0)?;
+      Some((days_before(today, count, unit)?.succ_opt()?, tomorrow))
+    }
+    _ => None,
+  }
+}
+
+// The day `count` days, weeks, months or years before `today`
+fn days_before(today: NaiveDate, count: u32, unit: &str) -> Option<NaiveDate> {
+  match unit.trim_end_matches('s') {
+    "day" => today.checked_sub_days(Days::new(u64::from(count))),
+    "week" => today.checked_sub_days(Days::new(u64::from(count) * 7)),
+    "month" => today.checked_sub_months(Months::new(count)),
+    "year" => today.checked_sub_months(Months::new(count.checked_mul(12)?)),
+    _ => None,
+  }
+}
+
+fn local_timestamp(date: NaiveDate) -> Option<i64> {
+  Local.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).earliest().map(|datetime| datetime.timestamp())
+}
+
+// A size like 10MB, 1.5 GB or 500 (bytes), in powers of 1000 like the size facet
+fn parse_size(value: &str) -> Option<(i64, i64)> {
+  let value = value.to_lowercase();
+  let unit_start = value.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(value.len());
+  let (number, unit) = value.split_at(unit_start);
+  let number: f64 = number.parse().ok()?;
+  let multiplier = match unit.trim() {
+    "" | "b" => 1.0,
+    "k" | "kb" => 1e3,
+    "m" | "mb" => 1e6,
+    "g" | "gb" => 1e9,
+    "t" | "tb" => 1e12,
+    _ => return None,
+  };
+  let bytes = (number * multiplier).round() as i64;
+  Some((bytes, bytes.saturating_add(1)))
+}
+
+// The file types picked in the UI are one more filter of the query
+pub fn with_file_types(query: QueryNode, file_types: &[String]) -> QueryNode {
+  let filters: Vec<QueryNode> = file_types
+    .iter()
+    .map(|file_type| file_type.trim().to_lowercase())
+    .filter(|file_type| !file_type.is_empty())
+    .map(|file_type| QueryNode::Filter(QueryFilter::FileType(file_type)))
+    .collect();
+  with_filter(query, join_nodes(filters, QueryNode::Or))
+}
+
+// So is the date range picked in the UI, on the modification date
+pub fn with_date_limit(query: QueryNode, date_limit: &Option<DateLimit>) -> QueryNode {
+  let range = date_limit.as_ref().map(|date_limit| ValueRange {
+    start: date_limit.start,
+    // the end of the date limit is included
+    end: date_limit.end.map(|end| end + 1),
+  });
+  let filter = range.filter(|range| range.start.is_some() || range.end.is_some()).map(|range| QueryNode::Filter(QueryFilter::Modified(range)));
+  with_filter(query, filter)
+}
+
+// The query with one more filter every match must pass
+pub fn with_filter(query: QueryNode, filter: Option<QueryNode>) -> QueryNode {
+  match (query, filter) {
+    (query, Some(filter)) if query.is_empty() => filter,
+    (QueryNode::And(mut nodes), Some(filter)) => {
+      nodes.push(filter);
+      QueryNode::And(nodes)
+    }
+    (query, Some(filter)) => QueryNode::And(vec![query, filter]),
+    (query, None) => query,
+  }
+}
+
+// The ids of the files opened in each range of an `opened:` filter, the tantivy index can only filter by them
+pub type OpenedDocuments = HashMap<ValueRange, Vec<i64>>;
+
+// An SQL predicate with a ? for each of its values, which are bound in order, so no text typed by the user ends up in SQL
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SqlPredicate {
+  pub sql: String,
+  pub values: Vec<SqlValue>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqlValue {
+  Text(String),
+  Integer(i64),
+}
+
+impl SqlPredicate {
+  pub fn is_empty(&self) -> bool {
+    self.sql.is_empty()
+  }
+}
+
+// What a back end that can't express a whole query makes of a part of it
+enum Compiled {
+  // no restriction, e.g. the filters in an FTS expression, the SQL predicate applies them
+  Everything,
+  // matches nothing, e.g. a field that only the tantivy index has
+  Nothing,
+  Expression(String),
+}
+
+impl QueryNode {
+  pub fn is_empty(&self) -> bool {
+    matches!(self, QueryNode::And(nodes) if nodes.is_empty())
+  }
+
+  // Whether there are words to search for, not only filters
+  pub fn has_text(&self) -> bool {
+    match self {
+      QueryNode::Word(_) | QueryNode::Phrase(_) | QueryNode::Field(..) => true,
+      QueryNode::Filter(_) => false,
+      QueryNode::Not(inner) => inner.has_text(),
+      QueryNode::And(nodes) | QueryNode::Or(nodes) => nodes.iter().any(|node| node.has_text()),
+    }
+  }
+
+  // Whether there are words to search for that aren't excluded
+  pub fn has_included_text(&self) -> bool {
+    match self {
+      QueryNode::Word(_) | QueryNode::Phrase(_) | QueryNode::Field(..) => true,
+      QueryNode::Filter(_) | QueryNode::Not(_) => false,
+      QueryNode::And(nodes) | QueryNode::Or(nodes) => nodes.iter().any(|node| node.has_included_text()),
+    }
+  }
+
+  fn has_filters(&self) -> bool {
+    match self {
+      QueryNode::Filter(_) => true,
+      QueryNode::Word(_) | QueryNode::Phrase(_) | QueryNode::Field(..) => false,
+      QueryNode::Not(inner) => inner.has_filters(),
+      QueryNode::And(nodes) | QueryNode::Or(nodes) => nodes.iter().any(|node| node.has_filters()),
+    }
+  }
+
+  // The words and phrases every match must have, in the file name or anywhere
+  pub fn required_words(&self) -> Vec<String> {
+    match self {
+      QueryNode::Word(text) | QueryNode::Phrase(text) => vec![text.clone()],
+      QueryNode::Field(field, text) if field == "title" => text.required_words(),
+      QueryNode::And(nodes) => nodes.iter().flat_map(|node| node.required_words()).collect(),
+      _ => vec![],
+    }
+  }
+
+  // The ranges of the `opened:` filters, to look up the files opened in them for `to_tantivy_query`
+  pub fn opened_ranges(&self) -> Vec<ValueRange> {
+    match self {
+      QueryNode::Filter(QueryFilter::Opened(range)) => vec![*range],
+      QueryNode::Word(_) | QueryNode::Phrase(_) | QueryNode::Field(..) | QueryNode::Filter(_) => vec![],
+      QueryNode::Not(inner) => inner.opened_ranges(),
+      QueryNode::And(nodes) | QueryNode::Or(nodes) => nodes.iter().flat_map(|node| node.opened_ranges()).collect(),
+    }
+  }
+
+  // The MATCH expression for metadata_fts: None if no file name can match, empty if every file name matches
+  pub fn to_fts_match(&self) -> Option<String> {
+    match fts_expression(self) {
+      Compiled::Everything => Some(String::new()),
+      Compiled::Nothing => None,
+      Compiled::Expression(expression) => Some(expression),
+    }
+  }
+
+  // The filters as an SQL predicate on the document table, empty if there are none
+  pub fn to_sql_predicate(&self) -> SqlPredicate {
+    sql_predicate(self).unwrap_or_default()
+  }
+
+  // The whole query as an SQL predicate on the document table, with the words looked up in metadata_fts by a subquery
+  // Fields that only the tantivy index has match no file
+  pub fn to_sql_predicate_with_words(&self) -> SqlPredicate {
+    sql_predicate_with_words(self)
+  }
+
+  pub fn to_tantivy_query(&self, index: &Index, languages: &Vec<String>, opened_documents: &OpenedDocuments) -> Box<dyn Query> {
+    match self {
+      QueryNode::Word(text) | QueryNode::Phrase(text) => text_query(index, &DEFAULT_SEARCH_FIELDS, languages, text),
+      QueryNode::Field(field, text) => {
+        let text = match text.as_ref() {
+          QueryNode::Word(text) | QueryNode::Phrase(text) => text,
+          other => return other.to_tantivy_query(index, languages, opened_documents),
+        };
+        // a field that is missing in an older index is searched like any other word until it has been rebuilt
+        if index.schema().get_field(field).is_ok() {
+          text_query(index, &[field.as_str()], languages, text)
+        } else {
+          text_query(index, &DEFAULT_SEARCH_FIELDS, languages, text)
+        }
+      }
+      QueryNode::Filter(filter) => filter_query(&index.schema(), filter, opened_documents),
+      QueryNode::Not(_) => tantivy_conjunction(std::slice::from_ref(self), index, languages, opened_documents),
+      QueryNode::And(nodes) => tantivy_conjunction(nodes, index, languages, opened_documents),
+      QueryNode::Or(nodes) => Box::new(BooleanQuery::new(
+        nodes.iter().map(|node| (Occur::Should, node.to_tantivy_query(index, languages, opened_documents))).collect(),
+      )),
+    }
+  }
+}
+
+// FTS5 strings are double-quoted, so the words in them are never read as FTS5 syntax
+pub fn fts_string(text: &str) -> String {
+  format!("\"{}\"", text.replace('"', "\"\""))
+}
+
+fn fts_expression(node: &QueryNode) -> Compiled {
+  match node {
+    QueryNode::Word(text) => Compiled::Expression(format!("{}*", fts_string(text))),
+    QueryNode::Phrase(text) => Compiled::Expression(fts_string(text)),
+    QueryNode::Field(field, text) if FTS_COLUMNS.contains(&field.as_str()) => match fts_expression(text) {
+      Compiled::Expression(expression) => Compiled::Expression(format!("{} : ({})", field, expression)),
+      other => other,
+    },
+    QueryNode::Field(..) => Compiled::Nothing,
+    QueryNode::Filter(_) => Compiled::Everything,
+    QueryNode::Not(_) => fts_conjunction(std::slice::from_ref(node)),
+    QueryNode::And(nodes) => fts_conjunction(nodes),
+    QueryNode::Or(nodes) => {
+      let mut alternatives = vec![];
+      for node in nodes {
+        match fts_expression(node) {
+          Compiled::Everything => return Compiled::Everything,
+          Compiled::Nothing => {}
+          Compiled::Expression(expression) => alternatives.push(format!("({})", expression)),
+        }
+      }
+      if alternatives.is_empty() {
+        Compiled::Nothing
+      } else {
+        Compiled::Expression(alternatives.join(" OR "))
+      }
+    }
+  }
+}
+
+fn fts_conjunction(nodes: &[QueryNode]) -> Compiled {
+  let mut included = vec![];
+  let mut excluded = vec![];
+  for node in nodes {
+    match node {
+      // the SQL predicate applies negated filters and negated groups that mix words and filters
+      QueryNode::Not(inner) if inner.has_filters() => {}
+      QueryNode::Not(inner) => match fts_expression(inner) {
+        Compiled::Everything => return Compiled::Nothing,
+        Compiled::Nothing => {}
+        Compiled::Expression(expression) => excluded.push(format!("({})", expression)),
+      },
+      _ => match fts_expression(node) {
+        Compiled::Everything => {}
+        Compiled::Nothing => return Compiled::Nothing,
+        Compiled::Expression(expression) => included.push(format!("({})", expression)),
+      },
+    }
+  }
+  if included.is_empty() {
+    // FTS5 can only exclude words from other matches
+    return if excluded.is_empty() { Compiled::Everything } else { Compiled::Nothing };
+  }
+  let mut expression = included.join(" AND ");
+  if !excluded.is_empty() {
+    expression = format!("({}) NOT ({})", expression, excluded.join(" OR "));
+  }
+  Compiled::Expression(expression)
+}
+
+// The words are applied by MATCH, so they don't restrict the predicate. MATCH matches every file for an OR with a
+// filter in it, so the predicate applies the whole OR, words included
+fn sql_predicate(node: &QueryNode) -> Option<SqlPredicate> {
+  match node {
+    QueryNode::Filter(filter) => Some(filter_predicate(filter)),
+    QueryNode::Word(_) | QueryNode::Phrase(_) | QueryNode::Field(..) => None,
+    QueryNode::Or(_) if node.has_text() && node.has_filters() => Some(sql_predicate_with_words(node)),
+    QueryNode::Not(inner) if inner.has_text() && inner.has_filters() => Some(sql_predicate_with_words(node)),
+    QueryNode::Not(inner) if inner.has_text() => None,
+    QueryNode::Not(inner) => sql_predicate(inner).map(|predicate| SqlPredicate { sql: format!("NOT ({})", predicate.sql), values: predicate.values }),
+    QueryNode::And(nodes) => {
+      let predicates: Vec<SqlPredicate> = nodes.iter().filter_map(sql_predicate).collect();
+      if predicates.is_empty() {
+        None
+      } else {
+        Some(join_predicates(predicates, " AND "))
+      }
+    }
+    QueryNode::Or(nodes) => {
+      let predicates: Vec<SqlPredicate> = nodes.iter().map(sql_predicate).collect::<Option<Vec<SqlPredicate>>>()?;
+      Some(join_predicates(predicates, " OR "))
+    }
+  }
+}
+
+fn sql_predicate_with_words(node: &QueryNode) -> SqlPredicate {
+  match node {
+    QueryNode::Filter(filter) => filter_predicate(filter),
+    QueryNode::Word(_) | QueryNode::Phrase(_) | QueryNode::Field(..) => match fts_expression(node) {
+      Compiled::Expression(expression) => SqlPredicate {
+        sql: "document.id IN (SELECT source_id FROM metadata_fts WHERE metadata_fts MATCH ? AND source_table = 'document')".to_string(),
+        values: vec![SqlValue::Text(expression)],
+      },
+      Compiled::Everything => SqlPredicate { sql: "1".to_string(), values: vec![] },
+      Compiled::Nothing => SqlPredicate { sql: "0".to_string(), values: vec![] },
+    },
+    QueryNode::Not(inner) => {
+      let predicate = sql_predicate_with_words(inner);
+      SqlPredicate { sql: format!("NOT ({})", predicate.sql), values: predicate.values }
+    }
+    QueryNode::And(nodes) if nodes.is_empty() => SqlPredicate { sql: "1".to_string(), values: vec![] },
+    QueryNode::And(nodes) => join_predicates(nodes.iter().map(sql_predicate_with_words).collect(), " AND "),
+    QueryNode::Or(nodes) => join_predicates(nodes.iter().map(sql_predicate_with_words).collect(), " OR "),
+  }
+}
+
+fn join_predicates(predicates: Vec<SqlPredicate>, separator: &str) -> SqlPredicate {
+  let sql = predicates.iter().map(|predicate| predicate.sql.as_str()).collect::<Vec<&str>>().join(separator);
+  SqlPredicate { sql: format!("({})", sql), values: predicates.into_iter().flat_map(|predicate| predicate.values).collect() }
+}
+
+fn filter_predicate(filter: &QueryFilter) -> SqlPredicate {
+  match filter {
+    QueryFilter::FileType(file_type) => SqlPredicate { sql: "document.file_type = ?".to_string(), values: vec![SqlValue::Text(file_type.clone())] },
+    QueryFilter::Folder(folder) => {
+      let pattern = folder_like_pattern(&folder.to_string_lossy());
+      SqlPredicate { sql: "document.path LIKE ? ESCAPE '\\'".to_string(), values: vec![SqlValue::Text(pattern)] }
+    }
+    QueryFilter::Modified(range) => range_predicate("document.last_modified", range),
+    QueryFilter::Created(range) => range_predicate("document.created_at", range),
+    QueryFilter::Opened(range) => {
+      let predicate = range_predicate("document.last_opened", range);
+      SqlPredicate { sql: format!("document.last_opened > 0 AND {}", predicate.sql), values: predicate.values }
+    }
+    QueryFilter::Size(range) => range_predicate("document.size", range),
+    QueryFilter::Source(source) => SqlPredicate { sql: "document.source_domain = ?".to_string(), values: vec![SqlValue::Text(source.clone())] },
+  }
+}
+
+// LIKE pattern for the paths inside a folder, with '\' as the escape character. The trailing separator keeps
+// /home/a from matching /home/ab
+pub(crate) fn folder_like_pattern(folder: &str) -> String {
+  let mut folder = folder.to_string();
+  if !folder.ends_with(MAIN_SEPARATOR) {
+    folder.push(MAIN_SEPARATOR);
+  }
+  // escape the LIKE wildcards in the path
+  format!("{}%", folder.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"))
+}
+
+fn range_predicate(column: &str, range: &ValueRange) -> SqlPredicate {
+  let mut bounds = vec![];
+  let mut values = vec![];
+  if let Some(start) = range.start {
+    bounds.push(format!("{} >= ?", column));
+    values.push(SqlValue::Integer(start));
+  }
+  if let Some(end) = range.end {
+    bounds.push(format!("{} < ?", column));
+    values.push(SqlValue::Integer(end));
+  }
+  SqlPredicate { sql: bounds.join(" AND "), values }
+}
+
+// The words are searched as they were typed, quoted so they are never read as tantivy's query syntax
+fn text_query(index: &Index, field_names: &[&str], languages: &Vec<String>, text: &str) -> Box<dyn Query> {
+  let query_parser = create_query_parser(index, field_names, languages);
+  let (query, _) = query_parser.parse_query_lenient(&format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"")));
+  query
+}
+
+fn tantivy_conjunction(nodes: &[QueryNode], index: &Index, languages: &Vec<String>, opened_documents: &OpenedDocuments) -> Box<dyn Query> {
+  let mut clauses: Vec<(Occur, Box<dyn Query>)> = nodes
+    .iter()
+    .map(|node| match node {
+      QueryNode::Not(inner) => (Occur::MustNot, inner.to_tantivy_query(index, languages, opened_documents)),
+      _ => (Occur::Must, node.to_tantivy_query(index, languages, opened_documents)),
+    })
+    .collect();
+  // tantivy only excludes documents from the ones that matched something
+  if !clauses.iter().any(|(occur, _)| *occur == Occur::Must) {
+    clauses.push((Occur::Must, Box::new(AllQuery)));
+  }
+  Box::new(BooleanQuery::new(clauses))
+}
+
+// Filters on fields that are missing in an older index don't restrict it until it has been rebuilt
+fn filter_query(schema: &Schema, filter: &QueryFilter, opened_documents: &OpenedDocuments) -> Box<dyn Query> {
+  match filter {
+    QueryFilter::FileType(file_type) => match schema.get_field("file_type") {
+      Ok(field) => Box::new(TermQuery::new(Term::from_field_text(field, file_type), IndexRecordOption::Basic)),
+      Err(_) => Box::new(AllQuery),
+    },
+    QueryFilter::Folder(folder) => {
+      // facets are indexed with their parents, so the folder matches the files in its subfolders as well
+      let folders = folder_facet_path(folder);
+      match schema.get_field("folder_facet") {
+        Ok(field) if !folders.is_empty() => Box::new(TermQuery::new(Term::from_facet(field, &Facet::from_path(folders)), IndexRecordOption::Basic)),
+        _ => Box::new(AllQuery),
+      }
+    }
+    QueryFilter::Modified(range) => range_query(schema, "last_modified", range),
+    QueryFilter::Created(range) => range_query(schema, "created_at", range),
+    QueryFilter::Opened(range) => match schema.get_field("id") {
+      Ok(field) => {
+        let ids = opened_documents.get(range).map(|ids| ids.as_slice()).unwrap_or(&[]);
+        Box::new(TermSetQuery::new(ids.iter().map(|id| Term::from_field_i64(field, *id))))
+      }
+      Err(_) => Box::new(AllQuery),
+    },
+    QueryFilter::Size(range) => range_query(schema, "size", range),
+    QueryFilter::Source(source) => match schema.get_field("source_domain") {
+      Ok(field) => Box::new(TermQuery::new(Term::from_field_text(field, source), IndexRecordOption::Basic)),
+      Err(_) => Box::new(AllQuery),
+    },
+  }
+}
+
+fn range_query(schema: &Schema, field_name: &str, range: &ValueRange) -> Box<dyn Query> {
+  if schema.get_field(field_name).is_err() {
+    return Box::new(AllQuery);
+  }
+  let lower_bound = range.start.map_or(Bound::Unbounded, Bound::Included);
+  let upper_bound = range.end.map_or(Bound::Unbounded, Bound::Excluded);
+  Box::new(RangeQuery::new_i64_bounds(field_name.to_string(), lower_bound, upper_bound))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn word(text: &str) -> QueryNode {
+    QueryNode::Word(text.to_string())
+  }
+
+  fn phrase(text: &str) -> QueryNode {
+    QueryNode::Phrase(text.to_string())
+  }
+
+  fn not(node: QueryNode) -> QueryNode {
+    QueryNode::Not(Box::new(node))
+  }
+
+  fn file_type(file_type: &str) -> QueryNode {
+    QueryNode::Filter(QueryFilter::FileType(file_type.to_string()))
+  }
+
+  fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).unwrap()
+  }
+
+  fn timestamp(year: i32, month: u32, day: u32) -> i64 {
+    local_timestamp(date(year, month, day)).unwrap()
+  }
+
+  #[test]
+  fn words_are_required() {
+    assert_eq!(parse_query("budget report"), QueryNode::And(vec![word("budget"), word("report")]));
+    assert_eq!(parse_query("budget AND report"), parse_query("budget report"));
+    assert_eq!(parse_query("budget"), word("budget"));
+    assert_eq!(parse_query(""), QueryNode::And(vec![]));
+    assert!(parse_query("  ").is_empty());
+  }
+
+  #[test]
+  fn and_binds_tighter_than_or() {
+    assert_eq!(
+      parse_query("a b OR c"),
+      QueryNode::Or(vec![QueryNode::And(vec![word("a"), word("b")]), word("c")])
+    );
+    assert_eq!(
+      parse_query("(a OR b) c"),
+      QueryNode::And(vec![QueryNode::Or(vec![word("a"), word("b")]), word("c")])
+    );
+    assert_eq!(parse_query("a | b || c"), QueryNode::Or(vec![word("a"), word("b"), word("c")]));
+  }
+
+  #[test]
+  fn quotes_make_phrases() {
+    assert_eq!(parse_query("\"annual report\" 2024"), QueryNode::And(vec![phrase("annual report"), word("2024")]));
+    assert_eq!(parse_query("“annual   report”"), phrase("annual report"));
+    // punctuation makes a phrase of a word, a * at its end is dropped
+    assert_eq!(parse_query("notes.txt"), phrase("notes.txt"));
+    assert_eq!(parse_query("budg*"), word("budg"));
+    assert_eq!(parse_query("title:\"annual report\""), QueryNode::Field("title".to_string(), Box::new(phrase("annual report"))));
+  }
+
+  #[test]
+  fn negation() {
+    assert_eq!(parse_query("budget -draft"), QueryNode::And(vec![word("budget"), not(word("draft"))]));
+    assert_eq!(parse_query("budget NOT draft"), parse_query("budget -draft"));
+    assert_eq!(parse_query("-(a OR b)"), not(QueryNode::Or(vec![word("a"), word("b")])));
+    assert_eq!(parse_query("-type:pdf"), not(file_type("pdf")));
+    // a - before a space or inside a word isn't a negation
+    assert_eq!(parse_query("a - b"), QueryNode::And(vec![word("a"), word("b")]));
+    assert_eq!(parse_query("e-mail"), phrase("e-mail"));
+  }
+
+  #[test]
+  fn fields() {
+    assert_eq!(parse_query("author:smith"), QueryNode::Field("author".to_string(), Box::new(word("smith"))));
+    assert_eq!(parse_query("Title:budget"), QueryNode::Field("title".to_string(), Box::new(word("budget"))));
+    assert_eq!(
+      parse_query("title:(budget OR -draft)"),
+      QueryNode::Or(vec![
+        QueryNode::Field("title".to_string(), Box::new(word("budget"))),
+        not(QueryNode::Field("title".to_string(), Box::new(word("draft")))),
+      ])
+    );
+  }
+
+  #[test]
+  fn type_filter() {
+    assert_eq!(parse_query("type:pdf"), file_type("pdf"));
+    assert_eq!(parse_query("type:.PDF,docx"), QueryNode::Or(vec![file_type("pdf"), file_type("docx")]));
+    assert_eq!(parse_query("type:(pdf OR docx)"), parse_query("type:pdf,docx"));
+  }
+
+  #[test]
+  fn in_filter() {
+    let home = dirs::home_dir().unwrap();
+    let folder = |path: PathBuf| QueryNode::Filter(QueryFilter::Folder(path));
+    assert_eq!(parse_query("in:~/Projects"), folder(home.join("Projects")));
+    assert_eq!(parse_query("in:Projects"), folder(home.join("Projects")));
+    assert_eq!(parse_query("in:~"), folder(home.clone()));
+    assert_eq!(parse_query("in:\"~/My Projects\""), folder(home.join("My Projects")));
+    #[cfg(unix)]
+    assert_eq!(parse_query("in:/tmp/x"), folder(PathBuf::from("/tmp/x")));
+  }
+
+  #[test]
+  fn date_filters() {
+    let march = ValueRange { start: Some(timestamp(2024, 3, 1)), end: Some(timestamp(2024, 4, 1)) };
+    assert_eq!(parse_query("modified:2024-03"), QueryNode::Filter(QueryFilter::Modified(march)));
+    assert_eq!(parse_query("modified:2024/03"), parse_query("modified:2024-03"));
+    assert_eq!(
+      parse_query("modified:>2024-03"),
+      QueryNode::Filter(QueryFilter::Modified(ValueRange { start: Some(timestamp(2024, 4, 1)), end: None }))
+    );
+    assert_eq!(
+      parse_query("created:<=2024"),
+      QueryNode::Filter(QueryFilter::Created(ValueRange { start: None, end: Some(timestamp(2025, 1, 1)) }))
+    );
+    assert_eq!(
+      parse_query("opened:2024-01..2024-03-15"),
+      QueryNode::Filter(QueryFilter::Opened(ValueRange { start: Some(timestamp(2024, 1, 1)), end: Some(timestamp(2024, 3, 16)) }))
+    );
+    assert_eq!(
+      parse_query("modified:2024-02.."),
+      QueryNode::Filter(QueryFilter::Modified(ValueRange { start: Some(timestamp(2024, 2, 1)), end: None }))
+    );
+    assert!(matches!(parse_query("modified:last-2-weeks"), QueryNode::Filter(QueryFilter::Modified(_))));
+    assert_eq!(parse_query("modified:\"last 2 weeks\""), parse_query("modified:last-2-weeks"));
+  }
+
+  #[test]
+  fn relative_dates() {
+    // a Wednesday
+    let today = date(2024, 3, 13);
+    assert_eq!(parse_relative_date("today", today), Some((today, date(2024, 3, 14))));
+    assert_eq!(parse_relative_date("Yesterday", today), Some((date(2024, 3, 12), today)));
+    assert_eq!(parse_relative_date("this-week", today), Some((date(2024, 3, 11), date(2024, 3, 18))));
+    assert_eq!(parse_relative_date("this month", today), Some((date(2024, 3, 1), date(2024, 4, 1))));
+    assert_eq!(parse_relative_date("this_year", today), Some((date(2024, 1, 1), date(2025, 1, 1))));
+    assert_eq!(parse_relative_date("last-week", today), Some((date(2024, 3, 7), date(2024, 3, 14))));
+    assert_eq!(parse_relative_date("past 2 months", today), Some((date(2024, 1, 14), date(2024, 3, 14))));
+    assert_eq!(parse_relative_date("last-0-days", today), None);
+    assert_eq!(parse_relative_date("last-2-fortnights", today), None);
+  }
+
+  #[test]
+  fn size_filter() {
+    let size = |start: Option<i64>, end: Option<i64>| QueryNode::Filter(QueryFilter::Size(ValueRange { start, end }));
+    assert_eq!(parse_query("size:>10MB"), size(Some(10_000_001), None));
+    assert_eq!(parse_query("size:10mb"), size(Some(10_000_000), None));
+    assert_eq!(parse_query("size:<1.5 GB"), QueryNode::And(vec![size(None, Some(2)), word("GB")]));
+    assert_eq!(parse_query("size:\"<1.5 GB\""), size(None, Some(1_500_000_000)));
+    assert_eq!(parse_query("size:500..2k"), size(Some(500), Some(2001)));
+    assert_eq!(parse_size("1TB"), Some((1_000_000_000_000, 1_000_000_000_001)));
+    // sizes too large for an i64 are clamped instead of overflowing
+    assert_eq!(parse_size("99999999999tb"), Some((i64::MAX, i64::MAX)));
+  }
+
+  #[test]
+  fn malformed_input_degrades() {
+    // unbalanced quotes and parentheses are closed
+    assert_eq!(parse_query("\"annual report"), phrase("annual report"));
+    assert_eq!(parse_query("(a OR b"), QueryNode::Or(vec![word("a"), word("b")]));
+    assert_eq!(parse_query("a) b"), QueryNode::And(vec![word("a"), word("b")]));
+    // stray operators are ignored
+    assert_eq!(parse_query("OR a OR OR b OR"), QueryNode::Or(vec![word("a"), word("b")]));
+    assert_eq!(parse_query("a NOT"), word("a"));
+    assert_eq!(parse_query("a -"), word("a"));
+    // unknown operators are text and filters with a value that can't be read are left out
+    assert_eq!(parse_query("foo:bar"), phrase("foo:bar"));
+    assert_eq!(parse_query("budget modified:soon size:big type:"), word("budget"));
+    assert_eq!(parse_query("modified:2024-13"), QueryNode::And(vec![]));
+    // text without letters or digits
+    assert_eq!(parse_query("() \"\" ***"), QueryNode::And(vec![]));
+  }
+
+  #[test]
+  fn fts_match() {
+    assert_eq!(parse_query("budget \"annual report\"").to_fts_match().unwrap(), "(\"budget\"*) AND (\"annual report\")");
+    assert_eq!(parse_query("budget -draft").to_fts_match().unwrap(), "((\"budget\"*)) NOT ((\"draft\"*))");
+    assert_eq!(parse_query("title:budget").to_fts_match().unwrap(), "title : (\"budget\"*)");
+    assert_eq!(fts_string("say \"hi\""), "\"say \"\"hi\"\"\"");
+    // only the tantivy index has the author field, only filters match every file name
+    assert_eq!(parse_query("author:smith").to_fts_match(), None);
+    assert_eq!(parse_query("type:pdf").to_fts_match().unwrap(), "");
+    assert_eq!(parse_query("-draft").to_fts_match(), None);
+  }
+
+  #[test]
+  fn sql_predicate_of_filters() {
+    let predicate = parse_query("budget type:pdf,docx -type:txt").to_sql_predicate();
+    assert_eq!(predicate.sql, "((document.file_type = ? OR document.file_type = ?) AND NOT (document.file_type = ?))");
+    assert_eq!(
+      predicate.values,
+      vec![SqlValue::Text("pdf".to_string()), SqlValue::Text("docx".to_string()), SqlValue::Text("txt".to_string())]
+    );
+    assert!(parse_query("budget OR report").to_sql_predicate().is_empty());
+    let predicate = parse_query("size:10..20").to_sql_predicate();
+    assert_eq!(predicate.sql, "document.size >= ? AND document.size < ?");
+    assert_eq!(predicate.values, vec![SqlValue::Integer(10), SqlValue::Integer(21)]);
+  }
+
+  #[test]
+  fn mixed_or_applies_its_words_in_the_predicate() {
+    let query = parse_query("type:pdf OR budget");
+    // MATCH can't leave out the files that are neither, the predicate does
+    assert_eq!(query.to_fts_match().unwrap(), "");
+    let predicate = query.to_sql_predicate();
+    assert_eq!(
+      predicate.sql,
+      "(document.file_type = ? OR document.id IN (SELECT source_id FROM metadata_fts WHERE metadata_fts MATCH ? AND source_table = 'document'))"
+    );
+    assert_eq!(predicate.values, vec![SqlValue::Text("pdf".to_string()), SqlValue::Text("\"budget\"*".to_string())]);
+
+    let query = parse_query("report -(type:pdf draft)");
+    assert_eq!(query.to_fts_match().unwrap(), "(\"report\"*)");
+    let predicate = query.to_sql_predicate();
+    assert_eq!(
+      predicate.sql,
+      "(NOT ((document.file_type = ? AND document.id IN (SELECT source_id FROM metadata_fts WHERE metadata_fts MATCH ? AND source_table = 'document'))))"
+    );
+  }
+
+  #[test]
+  fn filters_added_from_the_ui() {
+    let query = with_file_types(parse_query("budget"), &["pdf".to_string(), " ".to_string()]);
+    assert_eq!(query, QueryNode::And(vec![word("budget"), file_type("pdf")]));
+    let date_limit = DateLimit { start: Some(10), end: Some(20), text: String::new() };
+    let query = with_date_limit(QueryNode::And(vec![]), &Some(date_limit));
+    assert_eq!(query, QueryNode::Filter(QueryFilter::Modified(ValueRange { start: Some(10), end: Some(21) })));
+  }
+
+  #[test]
+  fn folder_patterns_escape_like_wildcards() {
+    let separator = std::path::MAIN_SEPARATOR_STR.replace('\\', "\\\\");
+    assert_eq!(folder_like_pattern("a_b%"), format!("a\\_b\\%{}%", separator));
+  }
+}
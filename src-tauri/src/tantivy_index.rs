@@ -4,33 +4,57 @@
 // For e.g. if you are searching for bookmarks, then the search query should start with `source_table:bookmarks`.
 
 use tantivy::collector::TopDocs;
-use tantivy::query::QueryParser;
+use tantivy::query::{AllQuery, BooleanQuery, BoostQuery, ConstScoreQuery, FuzzyTermQuery, Occur, Query, QueryParser, TermQuery};
+use tantivy::snippet::SnippetGenerator;
+use tantivy::tokenizer::{AsciiFoldingFilter, Language, LowerCaser, RemoveLongFilter, SimpleTokenizer, Stemmer, StopWordFilter, TextAnalyzer};
 use tantivy::{schema::*, DocAddress};
 use tantivy::{doc, Index, IndexWriter, ReloadPolicy, Searcher, TantivyError};
 use crate::housekeeping::get_app_directory;
+use crate::language::SUPPORTED_LANGUAGES;
+use crate::cjk_tokenizer::{CjkBigramTokenizer, CJK_TOKENIZER_NAME};
 use crate::utils::norm;
+use crate::database::establish_connection;
+use crate::database::schema::{body, document, property};
+use diesel::prelude::*;
 use std::collections::HashMap;
+use std::fs;
 use std::path::PathBuf;
-use crate::custom_types::{Error, TantivyDocumentItem, TantivyDocumentSearchResult, TantivyBookmarkSearchResult};
+use crate::custom_types::{Error, SearchSnippet, TantivyDocumentItem, TantivyDocumentSearchResult, TantivyBookmarkSearchResult};
 use std::sync::Mutex;
 use tauri::Manager;
-use crate::custom_types::TantivyReaderState;
+use crate::custom_types::{IndexWriterState, TantivyReaderState};
+use crate::index_writer::IndexCommand;
+use crate::facets::{document_facets, with_facet_filters, FacetFilter, FACET_FIELDS};
 
 pub fn create_tantivy_schema() -> Schema {
   let mut schema_builder = Schema::builder();
 
+  // free text fields that may contain CJK text are tokenized with the CJK bigram tokenizer
+  // it splits all other text the same way as the default tokenizer
+  let cjk_text_options = TextOptions::default().set_indexing_options(
+    TextFieldIndexing::default()
+      .set_tokenizer(CJK_TOKENIZER_NAME)
+      .set_index_option(IndexRecordOption::WithFreqsAndPositions)
+  );
+
   // common attributes
   // ID here refers to Source_ID in the Metadata Table in the SQLite database
-  schema_builder.add_i64_field("id", INDEXED | STORED);
+  // it is a fast field so that facet counts can count every file once, see facets.rs
+  schema_builder.add_i64_field("id", INDEXED | STORED | FAST);
   schema_builder.add_text_field("source_table", STRING);
   schema_builder.add_text_field("source_domain", STRING);
-  schema_builder.add_text_field("comment", TEXT);
+  // the comment and the body are not stored, their snippets are built from the SQLite tables
+  schema_builder.add_text_field("comment", cjk_text_options.clone());
 
   // documents attributes
-  schema_builder.add_text_field("title", TEXT);
-  schema_builder.add_text_field("body", TEXT);
+  schema_builder.add_text_field("title", cjk_text_options.clone());
+  schema_builder.add_text_field("body", cjk_text_options.clone());
   schema_builder.add_text_field("file_type", STRING);
-  schema_builder.add_i64_field("last_modified", INDEXED | STORED);
+  // fast fields for the range queries of modified:, created: and size: filters
+  schema_builder.add_i64_field("last_modified", INDEXED | STORED | FAST);
+  schema_builder.add_i64_field("created_at", INDEXED | FAST);
+  // in bytes
+  schema_builder.add_i64_field("size", INDEXED | FAST);
 
   // additional browser history attributes
   // URL doubles up as PATH for documents
@@ -40,23 +64,289 @@ pub fn create_tantivy_schema() -> Schema {
   schema_builder.add_text_field("tags", TEXT);
 
   // additional email attributes
-  schema_builder.add_text_field("sender", STRING);
-  schema_builder.add_text_field("recipient", STRING);
+  schema_builder.add_text_field("sender", STRING | STORED);
+  schema_builder.add_text_field("recipient", STRING | STORED);
   schema_builder.add_text_field("cc", STRING);
   schema_builder.add_text_field("bcc", STRING);
-  schema_builder.add_text_field("subject", TEXT);
-  schema_builder.add_text_field("attachments", TEXT);
+  schema_builder.add_text_field("subject", TEXT | STORED);
+  schema_builder.add_text_field("attachments", TEXT | STORED);
+
+  // document properties (e.g. docProps/core.xml of Office files)
+  // the property title is added as a second value of `title` and the subject goes into `subject`
+  schema_builder.add_text_field("author", cjk_text_options.clone());
+  schema_builder.add_text_field("keywords", cjk_text_options.clone());
+  schema_builder.add_text_field("last_modified_by", cjk_text_options.clone());
+  // e-book metadata, the ISBN is stored as digits only
+  schema_builder.add_text_field("publisher", cjk_text_options.clone());
+  schema_builder.add_text_field("isbn", STRING);
+  // markdown notes: headings are a separate field so they can be boosted, links are [[wiki-link]] targets
+  schema_builder.add_text_field("headings", cjk_text_options.clone());
+  schema_builder.add_text_field("links", cjk_text_options.clone());
+  schema_builder.add_text_field("aliases", cjk_text_options.clone());
+  // source code and notebooks: `outputs` (notebook cell outputs) is only searched when asked for, e.g. `outputs:Traceback`
+  schema_builder.add_text_field("code_language", STRING);
+  schema_builder.add_text_field("code_comments", cjk_text_options.clone());
+  // the words of compound identifiers, e.g. "get user name" for getUserName, only searched
+  schema_builder.add_text_field("identifiers", cjk_text_options.clone());
+  schema_builder.add_text_field("outputs", cjk_text_options.clone());
+  // LaTeX papers and BibTeX entries: each entry of a .bib file is a section, labelled with its citekey
+  schema_builder.add_text_field("abstract", cjk_text_options.clone());
+  schema_builder.add_text_field("journal", cjk_text_options.clone());
+  schema_builder.add_text_field("year", STRING);
+  schema_builder.add_text_field("citekey", STRING);
+  // calendar events and contacts: every event of an .ics file and every contact of a .vcf file is a section
+  schema_builder.add_text_field("location", cjk_text_options.clone());
+  schema_builder.add_text_field("attendees", cjk_text_options.clone());
+  schema_builder.add_text_field("organization", cjk_text_options.clone());
+  schema_builder.add_text_field("emails", cjk_text_options.clone());
+  schema_builder.add_text_field("phones", cjk_text_options.clone());
+  // PDF notes and highlights, e.g. `annotations:budget`. Annotated pages are sections, so a hit knows its page
+  // `annotation_author` holds who wrote them, e.g. `annotation_author:priya`
+  schema_builder.add_text_field("annotations", cjk_text_options.clone());
+  schema_builder.add_text_field("annotation_author", cjk_text_options.clone());
+
+  // section attributes (for files split into slides, chapters, pages etc.)
+  // `section` is a readable label like "slide 17" and `section_number` is used to deep-link into the file
+  schema_builder.add_text_field("section", STRING | STORED);
+  schema_builder.add_i64_field("section_number", INDEXED | STORED);
+  schema_builder.add_text_field("section_title", cjk_text_options.clone());
+  schema_builder.add_text_field("notes", cjk_text_options.clone());
+
+  // language attributes
+  // `language` is the detected language of the document (empty if unknown)
+  // Each supported language gets its own title and body fields with a stemming analyzer
+  // A document only fills the fields of its own language, so `Häuser` in a German document matches a query for `Haus`
+  schema_builder.add_text_field("language", STRING | STORED);
+  for language in SUPPORTED_LANGUAGES.iter() {
+    let text_options = TextOptions::default().set_indexing_options(
+      TextFieldIndexing::default()
+        .set_tokenizer(&language_tokenizer_name(language))
+        .set_index_option(IndexRecordOption::WithFreqsAndPositions)
+    );
+    schema_builder.add_text_field(&format!("title_{}", language), text_options.clone());
+    schema_builder.add_text_field(&format!("body_{}", language), text_options);
+  }
+
+  // facets of the file (type, folder, modified month, size and source) to count search results by
+  for (_, field_name) in FACET_FIELDS {
+    schema_builder.add_facet_field(field_name, FacetOptions::default());
+  }
 
   schema_builder.build()
 }
 
+fn language_tokenizer_name(language: &str) -> String {
+  format!("buzee_{}", language)
+}
+
+fn tantivy_language(language: &str) -> Option<Language> {
+  match language {
+    "en" => Some(Language::English),
+    "de" => Some(Language::German),
+    "fr" => Some(Language::French),
+    "es" => Some(Language::Spanish),
+    _ => None,
+  }
+}
+
+// Tokenizers are not persisted in the index, so they have to be registered every time the index is opened
+// Stemming runs before ASCII folding because the snowball stemmers expect the original accented characters
+fn register_tokenizers(index: &Index) {
+  for language in SUPPORTED_LANGUAGES.iter() {
+    let tantivy_lang = match tantivy_language(language) {
+      Some(tantivy_lang) => tantivy_lang,
+      None => continue,
+    };
+    let mut analyzer_builder = TextAnalyzer::builder(SimpleTokenizer::default())
+      .filter(RemoveLongFilter::limit(40))
+      .filter(LowerCaser)
+      .dynamic();
+    if let Some(stop_word_filter) = StopWordFilter::new(tantivy_lang) {
+      analyzer_builder = analyzer_builder.filter_dynamic(stop_word_filter);
+    }
+    let analyzer = analyzer_builder
+      .filter_dynamic(Stemmer::new(tantivy_lang))
+      .filter_dynamic(AsciiFoldingFilter)
+      .build();
+    index.tokenizers().register(&language_tokenizer_name(language), analyzer);
+  }
+
+  let cjk_analyzer = TextAnalyzer::builder(CjkBigramTokenizer)
+    .filter(RemoveLongFilter::limit(40))
+    .filter(LowerCaser)
+    .build();
+  index.tokenizers().register(CJK_TOKENIZER_NAME, cjk_analyzer);
+}
+
+// Bump this whenever create_tantivy_schema changes. The version is written to a file next to meta.json, and an index
+// with another version (or none, from before versions were recorded) is rebuilt from the body table in the background
+pub const TANTIVY_SCHEMA_VERSION: u32 = 8;
+const SCHEMA_VERSION_FILE: &str = "schema_version";
+const DEFAULT_INDEX_DIRECTORY: &str = "buzee_tantivy_index";
+// holds the name of the directory of the live index, so a rebuilt index is swapped in with a single rename
+const INDEX_LOCATION_FILE: &str = "tantivy_index_location";
+const REBUILD_BATCH_SIZE: i64 = 1000;
+
+pub(crate) fn index_directory_name() -> String {
+  fs::read_to_string(norm(&format!("{}/{}", get_app_directory(), INDEX_LOCATION_FILE)))
+    .map(|name| name.trim().to_string())
+    .ok()
+    .filter(|name| !name.is_empty())
+    .unwrap_or(DEFAULT_INDEX_DIRECTORY.to_string())
+}
+
+fn index_path(directory_name: &str) -> PathBuf {
+  PathBuf::from(norm(&format!("{}/{}", get_app_directory(), directory_name)))
+}
+
+fn read_schema_version(index_path: &PathBuf) -> Option<u32> {
+  fs::read_to_string(index_path.join(SCHEMA_VERSION_FILE)).ok()?.trim().parse().ok()
+}
+
+fn write_schema_version(index_path: &PathBuf) -> std::io::Result<()> {
+  fs::write(index_path.join(SCHEMA_VERSION_FILE), TANTIVY_SCHEMA_VERSION.to_string())
+}
+
 pub fn get_tantivy_index(schema: Schema) -> tantivy::Result<Index> {
-  let index_path = PathBuf::from(norm(format!("{}/{}", get_app_directory(), "buzee_tantivy_index").as_str()));
-  let meta_file_path = PathBuf::from(norm(format!("{}/{}/meta.json", get_app_directory(), "buzee_tantivy_index").as_str()));
-  if index_path.exists() && meta_file_path.exists() {
-    return Index::open_in_dir(&index_path);
+  open_index_in(&index_directory_name(), schema)
+}
+
+pub(crate) fn open_index_in(directory_name: &str, schema: Schema) -> tantivy::Result<Index> {
+  let index_path = index_path(directory_name);
+  let index = if index_path.join("meta.json").exists() {
+    Index::open_in_dir(&index_path)?
   } else {
-    return Index::create_in_dir(index_path, schema);
+    fs::create_dir_all(&index_path)?;
+    let index = Index::create_in_dir(&index_path, schema)?;
+    write_schema_version(&index_path)?;
+    index
+  };
+  register_tokenizers(&index);
+  Ok(index)
+}
+
+// Called once at startup, after the index writer has started. Searches keep using the old index until the new one is ready
+pub fn rebuild_index_if_outdated(app: &tauri::AppHandle) {
+  let live_directory = index_directory_name();
+  let version = read_schema_version(&index_path(&live_directory));
+  if version == Some(TANTIVY_SCHEMA_VERSION) {
+    remove_unused_indexes(&live_directory);
+    return;
+  }
+  println!("Tantivy index schema is at version {:?}, rebuilding it for version {}", version, TANTIVY_SCHEMA_VERSION);
+  if let Err(e) = send_to_index_writer(app, IndexCommand::Rebuild) {
+    println!("Error rebuilding the Tantivy index: {:?}", e);
+  }
+}
+
+// Builds a new index in its own directory from the chunks in the body table and returns the name of the directory.
+// Fields that only live in the index (the per-section fields like `annotations`) come back on the next parse of their
+// files, their text is already part of the body chunks
+// Runs on a thread of its own while the index writer keeps serving the live index, see `IndexCommand::Rebuild`
+pub(crate) fn build_index(app: &tauri::AppHandle) -> Result<String, Box<dyn std::error::Error>> {
+  let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs();
+  let new_directory = format!("{}_v{}_{}", DEFAULT_INDEX_DIRECTORY, TANTIVY_SCHEMA_VERSION, timestamp);
+  let new_path = index_path(&new_directory);
+  fs::create_dir_all(&new_path)?;
+  let index = Index::create_in_dir(&new_path, create_tantivy_schema())?;
+  register_tokenizers(&index);
+  let mut index_writer: IndexWriter = index.writer(150_000_000)?;
+
+  let mut conn = establish_connection(app);
+  let mut last_body_id = 0;
+  let mut items_added = 0;
+  loop {
+    let rows: Vec<(i32, i32, String, String, i64, String, String, String, String, i64, Option<String>, String, Option<f64>, i64)> = body::table
+      .inner_join(document::table.on(document::id.eq(body::source_id)))
+      .filter(body::id.gt(last_body_id))
+      .order(body::id.asc())
+      .limit(REBUILD_BATCH_SIZE)
+      .select((
+        body::id,
+        body::source_id,
+        body::text,
+        body::section,
+        body::section_number,
+        document::source_domain,
+        document::name,
+        document::path,
+        document::file_type,
+        document::last_modified,
+        document::comment,
+        document::language,
+        document::size,
+        document::created_at,
+      ))
+      .load(&mut conn)?;
+    if rows.is_empty() {
+      break;
+    }
+    last_body_id = rows.last().map(|row| row.0).unwrap_or(last_body_id);
+
+    let source_ids: Vec<i32> = rows.iter().map(|row| row.1).collect();
+    let mut properties: HashMap<i32, Vec<(String, String)>> = HashMap::new();
+    for (source_id, name, value) in property::table
+      .filter(property::source_id.eq_any(&source_ids))
+      .select((property::source_id, property::name, property::value))
+      .load::<(i32, String, String)>(&mut conn)?
+    {
+      properties.entry(source_id).or_default().push((name, value));
+    }
+
+    let items: Vec<TantivyDocumentItem> = rows
+      .into_iter()
+      .map(|(_, source_id, text, section, section_number, source_domain, name, path, file_type, last_modified, comment, language, size, created_at)| TantivyDocumentItem {
+        source_id: i64::from(source_id),
+        source_table: "document".to_string(),
+        source_domain,
+        name,
+        url: path,
+        body: text,
+        file_type,
+        last_modified,
+        created_at,
+        size,
+        comment: comment.unwrap_or_default(),
+        language,
+        fields: properties.get(&source_id).cloned().unwrap_or_default(),
+        section,
+        section_number,
+      })
+      .collect();
+    add_items_to_writer(&index, &mut index_writer, &items)?;
+    items_added += items.len();
+  }
+  index_writer.commit()?;
+  index_writer.wait_merging_threads()?;
+  write_schema_version(&new_path)?;
+
+  println!("Built a new Tantivy index with {} items", items_added);
+  Ok(new_directory)
+}
+
+// The swap: the location file is replaced in one rename, then the reader moves to the index in `directory_name`
+pub(crate) fn make_index_live(app: &tauri::AppHandle, directory_name: &str, index: &Index) -> Result<(), Box<dyn std::error::Error>> {
+  let location_file = norm(&format!("{}/{}", get_app_directory(), INDEX_LOCATION_FILE));
+  let temporary_location_file = format!("{}.tmp", location_file);
+  fs::write(&temporary_location_file, directory_name)?;
+  fs::rename(&temporary_location_file, &location_file)?;
+  app.state::<Mutex<TantivyReaderState>>().lock().unwrap().reader = get_reader_for_index(index)?;
+  Ok(())
+}
+
+// Old and half-built indexes. On Windows an index that is still open can't be removed, so it's tried again next launch
+pub(crate) fn remove_unused_indexes(live_directory: &str) {
+  let entries = match fs::read_dir(get_app_directory()) {
+    Ok(entries) => entries,
+    Err(_) => return,
+  };
+  for entry in entries.flatten() {
+    let name = entry.file_name().to_string_lossy().to_string();
+    if name.starts_with(DEFAULT_INDEX_DIRECTORY) && name != live_directory && entry.path().is_dir() {
+      if let Err(e) = fs::remove_dir_all(entry.path()) {
+        println!("Could not remove the unused index {}: {}", name, e);
+      }
+    }
   }
 }
 
@@ -68,13 +358,11 @@ pub fn get_reader_for_index(index: &Index) -> tantivy::Result<tantivy::IndexRead
   Ok(reader)
 }
 
-pub fn _reload_reader(app: &tauri::AppHandle) {
+pub fn reload_reader(app: &tauri::AppHandle) -> tantivy::Result<()> {
   // Acquire reader from the mutex and reload it
   let state_mutex = app.state::<Mutex<TantivyReaderState>>();
-  let mut state = state_mutex.lock().unwrap();
-  let reader = &state.reader;
-  reader.reload().unwrap();
-  state.reader = reader.clone();
+  let state = state_mutex.lock().unwrap();
+  state.reader.reload()
 }
 
 pub fn acquire_searcher_from_reader(app: &tauri::AppHandle) -> Result<Searcher, Error> {
@@ -86,24 +374,38 @@ pub fn acquire_searcher_from_reader(app: &tauri::AppHandle) -> Result<Searcher,
   Ok(searcher)
 }
 
-pub fn add_docs_to_index(files_array: &Vec<TantivyDocumentItem>,) -> tantivy::Result<()> {
-  let index = get_tantivy_index(create_tantivy_schema()).unwrap();
-  let mut index_writer: IndexWriter = index.writer(150_000_000)?;
+pub(crate) fn send_to_index_writer(app: &tauri::AppHandle, command: IndexCommand) -> tantivy::Result<()> {
+  let state_mutex = app.state::<Mutex<IndexWriterState>>();
+  let state = state_mutex.lock().unwrap();
+  state.sender.send(command).map_err(|_| TantivyError::SystemError("The index writer has stopped".to_string()))
+}
+
+// Changes are committed in batches by the index writer, call `commit_index` to make them searchable right away
+pub fn add_docs_to_index(app: &tauri::AppHandle, files_array: &Vec<TantivyDocumentItem>) -> tantivy::Result<()> {
+  send_to_index_writer(app, IndexCommand::Add(files_array.clone()))
+}
 
+pub(crate) fn add_items_to_writer(index: &Index, index_writer: &mut IndexWriter, files_array: &[TantivyDocumentItem]) -> tantivy::Result<()> {
   // Get the fields
-  let id = index.schema().get_field("id").unwrap();
-  let source_table = index.schema().get_field("source_table").unwrap();
-  let source_domain = index.schema().get_field("source_domain").unwrap();
-  let title = index.schema().get_field("title").unwrap();
-  let body = index.schema().get_field("body").unwrap();
-  let url = index.schema().get_field("url").unwrap();
-  let file_type = index.schema().get_field("file_type").unwrap();
-  let last_modified = index.schema().get_field("last_modified").unwrap();
-  let comment = index.schema().get_field("comment").unwrap();
+  let id = index.schema().get_field("id")?;
+  let source_table = index.schema().get_field("source_table")?;
+  let source_domain = index.schema().get_field("source_domain")?;
+  let title = index.schema().get_field("title")?;
+  let body = index.schema().get_field("body")?;
+  let url = index.schema().get_field("url")?;
+  let file_type = index.schema().get_field("file_type")?;
+  let last_modified = index.schema().get_field("last_modified")?;
+  let comment = index.schema().get_field("comment")?;
+  // language and section fields may be missing in an index created by an older version
+  let language = index.schema().get_field("language").ok();
+  let section = index.schema().get_field("section").ok();
+  let section_number = index.schema().get_field("section_number").ok();
+  let size = index.schema().get_field("size").ok();
+  let created_at = index.schema().get_field("created_at").ok();
 
   // for each document in the array, add it to the index
   for doc in files_array {
-    index_writer.add_document(doc!(
+    let mut document = doc!(
       id => doc.source_id,
       source_table => doc.source_table.as_str(),
       source_domain => doc.source_domain.as_str(),
@@ -113,92 +415,207 @@ pub fn add_docs_to_index(files_array: &Vec<TantivyDocumentItem>,) -> tantivy::Re
       file_type => doc.file_type.as_str(),
       last_modified => doc.last_modified,
       comment => doc.comment.as_str(),
-    ))?;
+    );
+    if let Some(language) = language {
+      document.add_text(language, doc.language.as_str());
+    }
+    if let (Some(section), Some(section_number)) = (section, section_number) {
+      document.add_text(section, doc.section.as_str());
+      document.add_i64(section_number, doc.section_number);
+    }
+    if let (Some(size), Some(file_size)) = (size, doc.size) {
+      document.add_i64(size, file_size as i64);
+    }
+    if let Some(created_at) = created_at {
+      document.add_i64(created_at, doc.created_at);
+    }
+    // also add the title and body to the fields of the detected language (if it is supported)
+    let title_language = index.schema().get_field(&format!("title_{}", doc.language)).ok();
+    let body_language = index.schema().get_field(&format!("body_{}", doc.language)).ok();
+    if let (Some(title_language), Some(body_language)) = (title_language, body_language) {
+      document.add_text(title_language, doc.name.as_str());
+      document.add_text(body_language, doc.body.as_str());
+    }
+    for (facet_name, facet) in document_facets(&doc.url, &doc.file_type, doc.last_modified, doc.size, &doc.source_domain) {
+      let facet_field = FACET_FIELDS.iter().find(|(name, _)| *name == facet_name).and_then(|(_, field_name)| index.schema().get_field(field_name).ok());
+      if let Some(facet_field) = facet_field {
+        document.add_facet(facet_field, facet);
+      }
+    }
+    // searchable fields extracted from the file, e.g. ("author", "Priya")
+    // fields that are not in the schema of this index are skipped
+    for (field_name, value) in &doc.fields {
+      if let Ok(field) = index.schema().get_field(field_name) {
+        document.add_text(field, value.as_str());
+      }
+    }
+    index_writer.add_document(document)?;
   }
+  Ok(())
+}
 
-  let commit_stamp = index_writer.commit()?;
+pub fn delete_docs_from_index_with_ids(app: &tauri::AppHandle, ids_to_delete: &Vec<i32>) -> tantivy::Result<()> {
+  send_to_index_writer(app, IndexCommand::Delete(ids_to_delete.clone()))
+}
 
-  if commit_stamp > 0 {
-    return Ok(());
-  } else {
-    return Err(tantivy::TantivyError::SystemError("Failed to commit changes to the index".to_string()));
-  }
+pub fn delete_all_docs_from_index(app: &tauri::AppHandle) -> tantivy::Result<()> {
+  println!("WARNING: Deleting all documents from the index");
+  send_to_index_writer(app, IndexCommand::DeleteAll)?;
+  commit_index(app)
 }
 
-pub fn delete_docs_from_index_with_ids(ids_to_delete: &Vec<i32>) -> tantivy::Result<()> {
-  let index = get_tantivy_index(create_tantivy_schema()).unwrap();
-  let mut index_writer: IndexWriter = index.writer(150_000_000)?;
+// Commits the pending changes and waits until they are searchable
+pub fn commit_index(app: &tauri::AppHandle) -> tantivy::Result<()> {
+  let (reply_sender, reply_receiver) = std::sync::mpsc::channel();
+  send_to_index_writer(app, IndexCommand::Commit(reply_sender))?;
+  reply_receiver.recv().map_err(|_| TantivyError::SystemError("The index writer has stopped".to_string()))?
+}
 
-  // for each ID in the array, find the document and delete it
-  for id in ids_to_delete {
-    index_writer.delete_term(Term::from_field_i64(index.schema().get_field("id").unwrap(), i64::from(*id)));
+// The fields a query searches when it doesn't name one
+pub const DEFAULT_SEARCH_FIELDS: [&str; 28] = ["comment", "title", "body", "file_type", "url", "tags", "sender", "recipient", "cc", "bcc", "subject", "attachments", "author", "keywords", "last_modified_by", "publisher", "section_title", "notes", "headings", "links", "aliases", "code_comments", "identifiers", "abstract", "journal", "citekey", "annotations", "annotation_author"];
+
+// A query parser that searches these fields. `languages` are the user's search languages: if the title or the body is
+// searched, it is also analysed with the stemmer of each of them
+pub fn create_query_parser(index: &Index, field_names: &[&str], languages: &Vec<String>) -> QueryParser {
+  // fields that were added later may be missing in an older index until it has been rebuilt
+  let mut fields: Vec<Field> = vec![];
+  for field_name in field_names {
+    if let Ok(field) = index.schema().get_field(field_name) {
+      fields.push(field);
+    }
+  }
+  for language in languages {
+    for field_name in ["title", "body"] {
+      if !field_names.contains(&field_name) {
+        continue;
+      }
+      if let Ok(language_field) = index.schema().get_field(&format!("{}_{}", field_name, language)) {
+        fields.push(language_field);
+      }
+    }
   }
 
-  let commit_stamp = index_writer.commit()?;
-
-  if commit_stamp > 0 {
-    return Ok(());
-  } else {
-    return Err(tantivy::TantivyError::SystemError("Failed to commit changes to the index".to_string()));
+  let mut query_parser = QueryParser::for_index(&index, fields);
+  query_parser.set_conjunction_by_default();
+  // a match in a heading or a note alias says more about the document than a match in the body
+  for (field_name, boost) in [("headings", 2.0), ("aliases", 2.0)] {
+    if let Ok(boosted_field) = index.schema().get_field(field_name) {
+      query_parser.set_field_boost(boosted_field, boost);
+    }
   }
+  query_parser
 }
 
-pub fn delete_all_docs_from_index() -> tantivy::Result<()> {
-  println!("WARNING: Deleting all documents from the index");
-  let index = get_tantivy_index(create_tantivy_schema()).unwrap();
-  let mut index_writer: IndexWriter = index.writer(150_000_000)?;
+// Parses a query in tantivy's query syntax and searches the default fields, see `get_top_docs`
+pub fn parse_query_and_get_top_docs(index: &Index, searcher: &Searcher, user_query: String, languages: &Vec<String>, fuzzy: bool, facet_filters: &[FacetFilter], result_limit: i32, result_offset: i32) -> Result<(Vec<(f32, DocAddress)>, Box<dyn Query>), TantivyError> {
+  let query = create_query_parser(index, &DEFAULT_SEARCH_FIELDS, languages).parse_query(&user_query)?;
+  get_top_docs(index, searcher, query, fuzzy, facet_filters, result_limit, result_offset)
+}
 
-  let _ = index_writer.delete_all_documents().unwrap();
+// With `fuzzy` the words of the query also match misspelled words in titles and bodies, see `add_fuzzy_terms`
+// `facet_filters` are the facets picked by the user, only documents that have them are returned
+// The final query is returned with the top documents so their snippets can be built from it
+pub fn get_top_docs(index: &Index, searcher: &Searcher, mut query: Box<dyn Query>, fuzzy: bool, facet_filters: &[FacetFilter], result_limit: i32, result_offset: i32) -> Result<(Vec<(f32, DocAddress)>, Box<dyn Query>), TantivyError> {
+  if fuzzy {
+    query = add_fuzzy_terms(index, query);
+  }
+  query = with_facet_filters(index, query, facet_filters);
 
-  let commit_stamp = index_writer.commit()?;
+  // Search the index
+  let top_docs = searcher.search(&query, &TopDocs::with_limit(result_limit.try_into().unwrap()).and_offset(result_offset.try_into().unwrap()))?;
 
-  if commit_stamp > 0 {
-    return Ok(());
-  } else {
-    return Err(tantivy::TantivyError::SystemError("Failed to commit changes to the index".to_string()));
-  }
+  Ok((top_docs, query))
 }
 
-pub fn parse_query_and_get_top_docs(index: &Index, searcher: &Searcher, user_query: String, result_limit: i32, result_offset: i32) -> Result<Vec<(f32, DocAddress)>, TantivyError> {
-  // Get the fields
-  let comment = index.schema().get_field("comment").unwrap();
-  let title = index.schema().get_field("title").unwrap();
-  let body = index.schema().get_field("body").unwrap();
-  let file_type = index.schema().get_field("file_type").unwrap();
-  let url = index.schema().get_field("url").unwrap();
-  let tags = index.schema().get_field("tags").unwrap();
-  let sender = index.schema().get_field("sender").unwrap();
-  let recipient = index.schema().get_field("recipient").unwrap();
-  let cc = index.schema().get_field("cc").unwrap();
-  let bcc = index.schema().get_field("bcc").unwrap();
-  let subject = index.schema().get_field("subject").unwrap();
-  let attachments = index.schema().get_field("attachments").unwrap();
-
-  // Parse the query
-  let mut query_parser = QueryParser::for_index(&index, vec![comment, title, body, file_type, url, tags, sender, recipient, cc, bcc, subject, attachments]);
-  query_parser.set_conjunction_by_default();
-  let query = query_parser.parse_query(&user_query)?;
+// Every document that matches the query as typed gets this on top of its score, so exact matches rank above the
+// documents that only match with typos
+const EXACT_MATCH_SCORE: f32 = 1000.0;
+// A fuzzy match scores a constant instead of BM25, kept small so it only orders documents that match the same way
+const FUZZY_MATCH_BOOST: f32 = 0.1;
+
+// Lets every word of the query that is searched in the title or the body also match words a few typos away
+// ("recieve" finds "receive"). The structure of the parsed query is kept, so every word must still match somewhere and
+// excluded words and file type filters still apply
+fn add_fuzzy_terms(index: &Index, query: Box<dyn Query>) -> Box<dyn Query> {
+  let fuzzy_fields: Vec<Field> = ["title", "body"].iter().filter_map(|field_name| index.schema().get_field(field_name).ok()).collect();
+  if fuzzy_fields.is_empty() {
+    return query;
+  }
+  let fuzzy_query = with_fuzzy_terms(query.as_ref(), &fuzzy_fields);
+  Box::new(BooleanQuery::new(vec![
+    (Occur::Should, Box::new(ConstScoreQuery::new(query, EXACT_MATCH_SCORE)) as Box<dyn Query>),
+    (Occur::Must, fuzzy_query),
+  ]))
+}
 
-  // Search the index
-  let top_docs = searcher.search(&query, &TopDocs::with_limit(result_limit.try_into().unwrap()).and_offset(result_offset.try_into().unwrap()))?;
+fn with_fuzzy_terms(query: &dyn Query, fuzzy_fields: &Vec<Field>) -> Box<dyn Query> {
+  if let Some(boolean_query) = query.downcast_ref::<BooleanQuery>() {
+    let subqueries = boolean_query
+      .clauses()
+      .iter()
+      .map(|(occur, subquery)| {
+        // words that must not be there are only excluded as typed
+        let subquery = if *occur == Occur::MustNot { subquery.box_clone() } else { with_fuzzy_terms(subquery.as_ref(), fuzzy_fields) };
+        (*occur, subquery)
+      })
+      .collect();
+    return Box::new(BooleanQuery::new(subqueries));
+  }
+  if let Some(term_query) = query.downcast_ref::<TermQuery>() {
+    let term = term_query.term();
+    if let (true, Some(text)) = (fuzzy_fields.contains(&term.field()), term.value().as_str()) {
+      if let Some(distance) = typo_distance(text) {
+        let fuzzy_term_query = FuzzyTermQuery::new(term.clone(), distance, true);
+        return Box::new(BooleanQuery::new(vec![
+          (Occur::Should, Box::new(term_query.clone()) as Box<dyn Query>),
+          (Occur::Should, Box::new(BoostQuery::new(Box::new(fuzzy_term_query), FUZZY_MATCH_BOOST))),
+        ]));
+      }
+    }
+  }
+  query.box_clone()
+}
 
-  Ok(top_docs)
+// The number of typos a word may have grows with its length: short words would match too many other words
+fn typo_distance(word: &str) -> Option<u8> {
+  match word.chars().count() {
+    0..=3 => None,
+    4..=7 => Some(1),
+    _ => Some(2),
+  }
 }
 
-pub fn return_document_search_results(index: &Index, searcher: &Searcher, top_docs: Vec<(f32, DocAddress)>) -> Result<Vec<TantivyDocumentSearchResult>, TantivyError> {
+// Fields whose matching passages are shown under a result. The body and the comment are read from SQLite, the other
+// fields are stored in the index for this
+const SNIPPET_FIELDS: [&str; 6] = ["body", "comment", "subject", "attachments", "sender", "recipient"];
+const SQLITE_SNIPPET_FIELDS: [&str; 2] = ["body", "comment"];
+const SNIPPET_LENGTH: usize = 180;
+
+pub fn return_document_search_results(index: &Index, searcher: &Searcher, query: &dyn Query, languages: &Vec<String>, top_docs: Vec<(f32, DocAddress)>, conn: &mut SqliteConnection) -> Result<Vec<TantivyDocumentSearchResult>, TantivyError> {
   // Get the fields
-  let id = index.schema().get_field("id").unwrap();
-  let last_modified = index.schema().get_field("last_modified").unwrap();
+  let id = index.schema().get_field("id")?;
+  let last_modified = index.schema().get_field("last_modified")?;
+  let section = index.schema().get_field("section").ok();
+  let section_number = index.schema().get_field("section_number").ok();
+  let snippet_generators = create_snippet_generators(index, searcher, query, languages);
 
   // Retrieve the search results
   let mut search_results = Vec::new();
   for (_score, doc_address) in top_docs {
-    let retrieved_doc: TantivyDocument = searcher.doc(doc_address).ok().unwrap();
+    let retrieved_doc: TantivyDocument = searcher.doc(doc_address)?;
     // println!("Retrieved doc: {:?}", retrieved_doc.to_json(&index.schema()));
+    let id_value = retrieved_doc.get_first(id).and_then(|value| value.as_i64()).unwrap_or_else(|| {return 0_i64 ;});
+    let section_value = first_value(&retrieved_doc, section).and_then(|value| value.as_str()).unwrap_or("").to_string();
+    let section_number_value = first_value(&retrieved_doc, section_number).and_then(|value| value.as_i64()).unwrap_or(0);
+    let sqlite_texts = snippet_texts_from_sqlite(conn, id_value, &section_value, section_number_value);
     let result: TantivyDocumentSearchResult = {
       TantivyDocumentSearchResult {
-        id: retrieved_doc.get_first(id).and_then(|value| value.as_i64()).unwrap_or_else(|| {return 0_i64 ;}),
+        id: id_value,
         last_modified: retrieved_doc.get_first(last_modified).and_then(|value| value.as_i64()).unwrap_or_else(|| {return 0_i64 ;}),
+        // chunks of files without sections have an empty label
+        section: Some(section_value).filter(|label| !label.is_empty()),
+        section_number: Some(section_number_value).filter(|number| *number > 0),
+        snippets: document_snippets(index, &snippet_generators, &retrieved_doc, &sqlite_texts),
       }
     };
     search_results.push(result);
@@ -207,50 +624,138 @@ pub fn return_document_search_results(index: &Index, searcher: &Searcher, top_do
   Ok(search_results)
 }
 
+// One generator per snippet field, holding the terms the query looks for in that field
+// A stemmed match (`Häuser` for `Haus`) is only a term of the language fields, so the body also gets the generators of
+// the search languages. They run on the body text with the stemmer of their language
+fn create_snippet_generators(index: &Index, searcher: &Searcher, query: &dyn Query, languages: &Vec<String>) -> Vec<(String, SnippetGenerator)> {
+  let schema = index.schema();
+  let mut generators = vec![];
+  for (field_name, source_field_name) in SNIPPET_FIELDS
+    .iter()
+    .map(|field_name| (field_name.to_string(), field_name.to_string()))
+    .chain(languages.iter().map(|language| (format!("body_{}", language), "body".to_string())))
+  {
+    let field = match schema.get_field(&field_name) {
+      Ok(field) => field,
+      Err(_) => continue,
+    };
+    let source_is_stored = schema.get_field(&source_field_name).map(|source_field| schema.get_field_entry(source_field).is_stored()).unwrap_or(false);
+    if !source_is_stored && !SQLITE_SNIPPET_FIELDS.contains(&source_field_name.as_str()) {
+      continue;
+    }
+    if let Ok(mut generator) = SnippetGenerator::create(searcher, query, field) {
+      generator.set_max_num_chars(SNIPPET_LENGTH);
+      generators.push((source_field_name, generator));
+    }
+  }
+  generators
+}
+
+// The body chunks of the section a hit is in and the comment of its file, by field name
+// A chunk doesn't know which row of the body table it came from, so every chunk of its section is a candidate
+fn snippet_texts_from_sqlite(conn: &mut SqliteConnection, id: i64, section: &str, section_number: i64) -> Vec<(String, String)> {
+  let Ok(source_id) = i32::try_from(id) else {
+    return vec![];
+  };
+  let body_texts = body::table
+    .filter(body::source_id.eq(source_id))
+    .filter(body::section.eq(section))
+    .filter(body::section_number.eq(section_number))
+    .order(body::id.asc())
+    .select(body::text)
+    .load::<String>(conn)
+    .unwrap_or_default();
+  let comment = document::table
+    .filter(document::id.eq(source_id))
+    .select(document::comment)
+    .first::<Option<String>>(conn)
+    .ok()
+    .flatten();
+  body_texts
+    .into_iter()
+    .map(|text| ("body".to_string(), text))
+    .chain(comment.map(|comment| ("comment".to_string(), comment)))
+    .collect()
+}
+
+// The best passage of each field that matched, a field matched through several generators shows up once
+fn document_snippets(index: &Index, generators: &Vec<(String, SnippetGenerator)>, document: &TantivyDocument, sqlite_texts: &[(String, String)]) -> Vec<SearchSnippet> {
+  let mut snippets: Vec<SearchSnippet> = vec![];
+  for (field_name, generator) in generators {
+    if snippets.iter().any(|snippet| snippet.field == *field_name) {
+      continue;
+    }
+    let field = match index.schema().get_field(field_name) {
+      Ok(field) => field,
+      Err(_) => continue,
+    };
+    let snippet = sqlite_texts
+      .iter()
+      .filter(|(text_field_name, _)| text_field_name == field_name)
+      .map(|(_, text)| text.as_str())
+      .chain(document.get_all(field).filter_map(|value| value.as_str()))
+      .map(|text| generator.snippet(text))
+      .find(|snippet| !snippet.is_empty());
+    if let Some(snippet) = snippet {
+      let fragment = snippet.fragment();
+      // byte offsets become UTF-16 offsets, the units of a JavaScript string
+      let utf16_offset = |byte_offset: usize| fragment[..byte_offset].encode_utf16().count();
+      snippets.push(SearchSnippet {
+        field: field_name.clone(),
+        text: fragment.to_string(),
+        highlights: snippet.highlighted().iter().map(|range| (utf16_offset(range.start), utf16_offset(range.end))).collect(),
+      });
+    }
+  }
+  snippets
+}
+
 pub fn return_bookmark_search_results(index: &Index, searcher: &Searcher, top_docs: Vec<(f32, DocAddress)>) -> Result<Vec<TantivyBookmarkSearchResult>, TantivyError> {
   // Get the fields
-  let id = index.schema().get_field("id").unwrap();
-  let source_table = index.schema().get_field("source_table").unwrap();
-  let source_domain = index.schema().get_field("source_domain").unwrap();
-  let is_pinned = index.schema().get_field("is_pinned").unwrap();
-  let comment = index.schema().get_field("comment").unwrap();
-  let frecency_rank = index.schema().get_field("frecency_rank").unwrap();
-  let frecency_last_accessed = index.schema().get_field("frecency_last_accessed").unwrap();
-
-  let title = index.schema().get_field("title").unwrap();
-  let body = index.schema().get_field("body").unwrap();
-  let url = index.schema().get_field("url").unwrap();
-  let saved_at = index.schema().get_field("saved_at").unwrap();
-  let last_opened = index.schema().get_field("last_opened").unwrap();
-  let word_count = index.schema().get_field("word_count").unwrap();
-  let is_favorite = index.schema().get_field("is_favorite").unwrap();
-  let is_archived = index.schema().get_field("is_archived").unwrap();
-  let is_read = index.schema().get_field("is_read").unwrap();
-  let tags = index.schema().get_field("tags").unwrap();
+  // only id, source_table and source_domain are required, the bookmark fields are not in the current schema
+  let schema = index.schema();
+  let id = schema.get_field("id")?;
+  let source_table = schema.get_field("source_table")?;
+  let source_domain = schema.get_field("source_domain")?;
+  let is_pinned = schema.get_field("is_pinned").ok();
+  let comment = schema.get_field("comment").ok();
+  let frecency_rank = schema.get_field("frecency_rank").ok();
+  let frecency_last_accessed = schema.get_field("frecency_last_accessed").ok();
+
+  let title = schema.get_field("title").ok();
+  let body = schema.get_field("body").ok();
+  let url = schema.get_field("url").ok();
+  let saved_at = schema.get_field("saved_at").ok();
+  let last_opened = schema.get_field("last_opened").ok();
+  let word_count = schema.get_field("word_count").ok();
+  let is_favorite = schema.get_field("is_favorite").ok();
+  let is_archived = schema.get_field("is_archived").ok();
+  let is_read = schema.get_field("is_read").ok();
+  let tags = schema.get_field("tags").ok();
 
   // Retrieve the search results
   let mut search_results = Vec::new();
   for (_score, doc_address) in top_docs {
-    let retrieved_doc: TantivyDocument = searcher.doc(doc_address).ok().unwrap();
+    let retrieved_doc: TantivyDocument = searcher.doc(doc_address)?;
     let result: TantivyBookmarkSearchResult = {
       TantivyBookmarkSearchResult {
         id: retrieved_doc.get_first(id).and_then(|value| value.as_i64()).unwrap_or_else(|| {return 0_i64 ;}),
         source_table: retrieved_doc.get_first(source_table).and_then(|value| value.as_str()).unwrap_or_else(|| {return "null" ;}).to_string(),
         source_domain: retrieved_doc.get_first(source_domain).and_then(|value| value.as_str()).unwrap_or_else(|| {return "null" ;}).to_string(),
-        is_pinned: retrieved_doc.get_first(is_pinned).and_then(|value| value.as_bool()).or(None),
-        comment: retrieved_doc.get_first(comment).and_then(|value| value.as_str().map(|s| s.to_string())).or(None),
-        title: retrieved_doc.get_first(title).and_then(|value| value.as_str().map(|s| s.to_string())).or(None),
-        body: retrieved_doc.get_first(body).and_then(|value| value.as_str().map(|s| s.to_string())).or(None),
-        url: retrieved_doc.get_first(url).and_then(|value| value.as_str().map(|s| s.to_string())).or(None),
-        saved_at: retrieved_doc.get_first(saved_at).and_then(|value| value.as_i64()).or(None),
-        last_opened: retrieved_doc.get_first(last_opened).and_then(|value| value.as_i64()).or(None),
-        word_count: retrieved_doc.get_first(word_count).and_then(|value| value.as_i64()).or(None),
-        is_favorite: retrieved_doc.get_first(is_favorite).and_then(|value| value.as_bool()).or(None),
-        is_archived: retrieved_doc.get_first(is_archived).and_then(|value| value.as_bool()).or(None),
-        is_read: retrieved_doc.get_first(is_read).and_then(|value| value.as_bool()).or(None),
-        tags: retrieved_doc.get_first(tags).and_then(|value| value.as_str().map(|s| s.to_string())).or(None),
-        frecency_rank: retrieved_doc.get_first(frecency_rank).and_then(|value| value.as_f64()).or(None),
-        frecency_last_accessed: retrieved_doc.get_first(frecency_last_accessed).and_then(|value| value.as_i64()).or(None),
+        is_pinned: first_value(&retrieved_doc, is_pinned).and_then(|value| value.as_bool()),
+        comment: first_value(&retrieved_doc, comment).and_then(|value| value.as_str().map(|s| s.to_string())),
+        title: first_value(&retrieved_doc, title).and_then(|value| value.as_str().map(|s| s.to_string())),
+        body: first_value(&retrieved_doc, body).and_then(|value| value.as_str().map(|s| s.to_string())),
+        url: first_value(&retrieved_doc, url).and_then(|value| value.as_str().map(|s| s.to_string())),
+        saved_at: first_value(&retrieved_doc, saved_at).and_then(|value| value.as_i64()),
+        last_opened: first_value(&retrieved_doc, last_opened).and_then(|value| value.as_i64()),
+        word_count: first_value(&retrieved_doc, word_count).and_then(|value| value.as_i64()),
+        is_favorite: first_value(&retrieved_doc, is_favorite).and_then(|value| value.as_bool()),
+        is_archived: first_value(&retrieved_doc, is_archived).and_then(|value| value.as_bool()),
+        is_read: first_value(&retrieved_doc, is_read).and_then(|value| value.as_bool()),
+        tags: first_value(&retrieved_doc, tags).and_then(|value| value.as_str().map(|s| s.to_string())),
+        frecency_rank: first_value(&retrieved_doc, frecency_rank).and_then(|value| value.as_f64()),
+        frecency_last_accessed: first_value(&retrieved_doc, frecency_last_accessed).and_then(|value| value.as_i64()),
       }
     };
 
@@ -260,63 +765,23 @@ pub fn return_bookmark_search_results(index: &Index, searcher: &Searcher, top_do
   Ok(search_results)
 }
 
-pub fn _get_all_unique_ids_from_index(searcher: &Searcher) -> Vec<i64> {
-  let index = get_tantivy_index(create_tantivy_schema()).unwrap();
-  let id = index.schema().get_field("id").unwrap();
-
-  // find all documents in the index using tantivy::query::AllQuery
-  let query = QueryParser::for_index(&index, vec![id]).parse_query("*").unwrap();
-  let top_docs = searcher.search(&query, &TopDocs::with_limit(100_000_000)).unwrap();
-
-  let all_ids: Vec<i64> = top_docs.iter().map(|(_score, doc_address)| {
-    let retrieved_doc: TantivyDocument = searcher.doc(*doc_address).ok().unwrap();
-    retrieved_doc.get_first(id).and_then(|value| value.as_i64()).unwrap_or_else(|| {return 0_i64 ;})
-  }).collect();
-
-  let unique_ids = all_ids.into_iter().collect::<std::collections::HashSet<i64>>().into_iter().collect();
-
-  unique_ids
-}
-
-pub fn _get_body_values_from_id(searcher: &Searcher, given_id: i64) -> Vec<String> {
-  let index = get_tantivy_index(create_tantivy_schema()).unwrap();
-  let id = index.schema().get_field("id").unwrap();
-  let body = index.schema().get_field("body").unwrap();
-  let chunk_id = index.schema().get_field("chunk_id").unwrap();
-
-  // find the document in the index where the ID matches the given ID
-  let query = QueryParser::for_index(&index, vec![id]).parse_query(format!("id:{}", given_id).as_str()).unwrap();
-  let top_docs = searcher.search(&query, &TopDocs::with_limit(10000)).unwrap();
-
-  // create a hashmap with chunk_id as key and body_value as value
-  let mut body_values = HashMap::new();
-  for (_score, doc_address) in top_docs {
-    let retrieved_doc: TantivyDocument = searcher.doc(doc_address).ok().unwrap();
-    let body_value = retrieved_doc.get_first(body).and_then(|value| value.as_str()).unwrap_or_else(|| {return "" ;}).to_string();
-    let chunk_id_value = retrieved_doc.get_first(chunk_id).and_then(|value| value.as_i64()).unwrap_or_else(|| {return 0_i64 ;});
-    body_values.insert(chunk_id_value, body_value);
-  }
-
-  // sort the hashmap by chunk_id and return the values
-  let mut sorted_body_values = body_values.into_iter().collect::<Vec<_>>();
-  sorted_body_values.sort_by(|a, b| a.0.cmp(&b.0));
-  let body_values = sorted_body_values.into_iter().map(|(_k, v)| v).collect::<Vec<String>>();
-
-  body_values
+// The first value of a field that may not be in the schema of this index
+fn first_value(document: &TantivyDocument, field: Option<Field>) -> Option<&OwnedValue> {
+  field.and_then(|field| document.get_first(field))
 }
 
 pub fn internal_test_create_csv_dump_from_index(searcher: &Searcher) {
   println!("Creating a CSV dump from the index");
   let index = get_tantivy_index(create_tantivy_schema()).unwrap();
 
-  let id = index.schema().get_field("id").unwrap();
-  let url = index.schema().get_field("url").unwrap();
-  let last_parsed = index.schema().get_field("last_parsed").unwrap();
-  let chunk_id = index.schema().get_field("chunk_id").unwrap();
+  let schema = index.schema();
+  let id = schema.get_field("id").ok();
+  let url = schema.get_field("url").ok();
+  let last_parsed = schema.get_field("last_parsed").ok();
+  let chunk_id = schema.get_field("chunk_id").ok();
 
   // find all documents in the index using tantivy::query::AllQuery
-  let query = QueryParser::for_index(&index, vec![url, last_parsed, chunk_id]).parse_query("*").unwrap();
-  let top_docs = searcher.search(&query, &TopDocs::with_limit(100_000)).unwrap();
+  let top_docs = searcher.search(&AllQuery, &TopDocs::with_limit(100_000)).unwrap();
 
   println!("Found {} items in the index", top_docs.len());
 
@@ -325,10 +790,10 @@ pub fn internal_test_create_csv_dump_from_index(searcher: &Searcher) {
   wtr.write_record(&["id", "last_parsed", "chunk_id", "url"]).unwrap();
   for (_score, doc_address) in top_docs {
     let retrieved_doc: TantivyDocument = searcher.doc(doc_address).ok().unwrap();
-    let id_value = retrieved_doc.get_first(id).and_then(|value| value.as_i64()).unwrap_or_else(|| {return 0_i64 ;});
-    let url_value = retrieved_doc.get_first(url).and_then(|value| value.as_str()).unwrap_or_else(|| {return "null" ;}).to_string();
-    let last_parsed_value = retrieved_doc.get_first(last_parsed).and_then(|value| value.as_i64()).unwrap_or_else(|| {return 0_i64 ;});
-    let chunk_id_value = retrieved_doc.get_first(chunk_id).and_then(|value| value.as_i64()).unwrap_or_else(|| {return 0_i64 ;});
+    let id_value = first_value(&retrieved_doc, id).and_then(|value| value.as_i64()).unwrap_or_else(|| {return 0_i64 ;});
+    let url_value = first_value(&retrieved_doc, url).and_then(|value| value.as_str()).unwrap_or_else(|| {return "null" ;}).to_string();
+    let last_parsed_value = first_value(&retrieved_doc, last_parsed).and_then(|value| value.as_i64()).unwrap_or_else(|| {return 0_i64 ;});
+    let chunk_id_value = first_value(&retrieved_doc, chunk_id).and_then(|value| value.as_i64()).unwrap_or_else(|| {return 0_i64 ;});
     wtr.write_record(&[id_value.to_string(), last_parsed_value.to_string(), chunk_id_value.to_string(), url_value]).unwrap();
   }
   wtr.flush().unwrap();
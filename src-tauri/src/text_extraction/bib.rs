@@ -0,0 +1,246 @@
+// Extract BibTeX (.bib) entries, one section per entry
+// Each section is labelled with the citekey and carries the entry's fields (author, year, journal, citekey, entry_type),
+// which are also used to store every entry as its own item in the `bib_entry` table
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::read_to_string;
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::text_extraction::{ContentSection, ExtractedContent};
+
+lazy_static! {
+  static ref ENTRY_START: Regex = Regex::new(r"@([A-Za-z]+)\s*[{(]").unwrap();
+  static ref AUTHOR_SEPARATOR: Regex = Regex::new(r"(?i)\s+and\s+").unwrap();
+  // accents and formatting commands inside values, e.g. {\"o} or \emph{word}
+  static ref LATEX_COMMAND: Regex = Regex::new(r#"\\([A-Za-z]+|["'`^~=.])\s*"#).unwrap();
+  static ref WHITESPACE: Regex = Regex::new(r"\s+").unwrap();
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BibEntry {
+  pub citekey: String,
+  pub entry_type: String,
+  pub fields: HashMap<String, String>,
+}
+
+pub fn extract(file: &String, _app: &tauri::AppHandle) -> Result<ExtractedContent, Box<dyn Error>> {
+  let source = read_to_string(file)?;
+  let sections: Vec<ContentSection> = parse_entries(&source)
+    .into_iter()
+    .enumerate()
+    .map(|(index, entry)| entry_section(entry, index as i64 + 1))
+    .collect();
+
+  Ok(ExtractedContent {
+    text: sections.iter().map(|section| section.text.as_str()).collect::<Vec<&str>>().join("\n"),
+    fields: vec![],
+    sections,
+    language: None,
+  })
+}
+
+fn entry_section(entry: BibEntry, number: i64) -> ContentSection {
+  let value = |name: &str| entry.fields.get(name).cloned().unwrap_or_default();
+  let title = value("title");
+  let authors = split_authors(&value("author"));
+  // conference papers name the proceedings in booktitle
+  let journal = [value("journal"), value("booktitle")].into_iter().find(|journal| !journal.is_empty()).unwrap_or_default();
+  let year = value("year");
+
+  let mut fields: Vec<(String, String)> = vec![
+    ("citekey".to_string(), entry.citekey.clone()),
+    ("entry_type".to_string(), entry.entry_type.clone()),
+    ("section_title".to_string(), title.clone()),
+    ("year".to_string(), year.clone()),
+    ("journal".to_string(), journal.clone()),
+    ("abstract".to_string(), value("abstract")),
+    ("keywords".to_string(), value("keywords")),
+  ];
+  for author in &authors {
+    fields.push(("author".to_string(), author.clone()));
+  }
+  fields.retain(|(_, value)| !value.is_empty());
+
+  let text = [title, authors.join(", "), journal, year, value("abstract"), value("note")]
+    .into_iter()
+    .filter(|line| !line.is_empty())
+    .collect::<Vec<String>>()
+    .join("\n");
+  ContentSection {
+    label: entry.citekey,
+    number,
+    text,
+    fields,
+  }
+}
+
+// Entries in file order. @string macros are expanded, @comment and @preamble are skipped
+pub fn parse_entries(source: &str) -> Vec<BibEntry> {
+  let mut entries: Vec<BibEntry> = vec![];
+  let mut macros: HashMap<String, String> = month_macros();
+  let mut position = 0;
+  while let Some(start) = ENTRY_START.captures_at(source, position) {
+    let entry_type = start[1].to_lowercase();
+    let body_start = start.get(0).unwrap().end();
+    let body_end = match matching_close(source, body_start) {
+      Some(body_end) => body_end,
+      None => break,
+    };
+    position = body_end + 1;
+    let body = &source[body_start..body_end];
+
+    match entry_type.as_str() {
+      "comment" | "preamble" => continue,
+      "string" => {
+        for (name, value) in parse_fields(body, &macros) {
+          macros.insert(name, value);
+        }
+      }
+      _ => {
+        let (citekey, field_list) = body.split_once(',').unwrap_or((body, ""));
+        let citekey = citekey.trim().to_string();
+        if citekey.is_empty() {
+          continue;
+        }
+        entries.push(BibEntry {
+          citekey,
+          entry_type,
+          fields: parse_fields(field_list, &macros).into_iter().collect(),
+        });
+      }
+    }
+  }
+  entries
+}
+
+// Index of the brace or parenthesis that closes the entry opened just before `start`
+fn matching_close(source: &str, start: usize) -> Option<usize> {
+  let mut depth = 1;
+  for (offset, c) in source[start..].char_indices() {
+    match c {
+      '{' | '(' => depth += 1,
+      '}' | ')' => {
+        depth -= 1;
+        if depth == 0 {
+          return Some(start + offset);
+        }
+      }
+      _ => {}
+    }
+  }
+  None
+}
+
+// `name = value` pairs separated by commas. A value is {braced}, "quoted", a number or a macro,
+// and parts can be joined with #
+fn parse_fields(field_list: &str, macros: &HashMap<String, String>) -> Vec<(String, String)> {
+  let chars: Vec<char> = field_list.chars().collect();
+  let mut fields: Vec<(String, String)> = vec![];
+  let mut index = 0;
+  while index < chars.len() {
+    // field name
+    while index < chars.len() && (chars[index].is_whitespace() || chars[index] == ',') {
+      index += 1;
+    }
+    let name_start = index;
+    while index < chars.len() && chars[index] != '=' && chars[index] != ',' {
+      index += 1;
+    }
+    if index >= chars.len() || chars[index] == ',' {
+      continue;
+    }
+    let name: String = chars[name_start..index].iter().collect::<String>().trim().to_lowercase();
+    index += 1;
+
+    // value parts joined with #
+    let mut value = String::new();
+    loop {
+      while index < chars.len() && chars[index].is_whitespace() {
+        index += 1;
+      }
+      if index >= chars.len() {
+        break;
+      }
+      match chars[index] {
+        '{' => {
+          let mut depth = 0;
+          let part_start = index + 1;
+          while index < chars.len() {
+            match chars[index] {
+              '{' => depth += 1,
+              '}' => depth -= 1,
+              _ => {}
+            }
+            index += 1;
+            if depth == 0 {
+              break;
+            }
+          }
+          value.push_str(&chars[part_start..index.saturating_sub(1).max(part_start)].iter().collect::<String>());
+        }
+        '"' => {
+          let part_start = index + 1;
+          let mut depth = 0;
+          index += 1;
+          while index < chars.len() && !(chars[index] == '"' && depth == 0) {
+            match chars[index] {
+              '{' => depth += 1,
+              '}' => depth -= 1,
+              _ => {}
+            }
+            index += 1;
+          }
+          value.push_str(&chars[part_start..index.min(chars.len())].iter().collect::<String>());
+          index += 1;
+        }
+        _ => {
+          let part_start = index;
+          while index < chars.len() && chars[index] != ',' && chars[index] != '#' && !chars[index].is_whitespace() {
+            index += 1;
+          }
+          let part: String = chars[part_start..index].iter().collect();
+          value.push_str(macros.get(&part.to_lowercase()).map(|expanded| expanded.as_str()).unwrap_or(&part));
+        }
+      }
+      while index < chars.len() && chars[index].is_whitespace() {
+        index += 1;
+      }
+      if index < chars.len() && chars[index] == '#' {
+        index += 1;
+        continue;
+      }
+      break;
+    }
+    if !name.is_empty() {
+      fields.push((name, clean_value(&value)));
+    }
+  }
+  fields
+}
+
+// Remove LaTeX markup from a value: {\"o}zg{\"u}r -> ozgur, {DNA} -> DNA
+fn clean_value(value: &str) -> String {
+  let value = LATEX_COMMAND.replace_all(value, "");
+  let value = value.replace(['{', '}'], "").replace('~', " ").replace("--", "–");
+  WHITESPACE.replace_all(&value, " ").trim().to_string()
+}
+
+// "Vaswani, Ashish and Shazeer, Noam" -> ["Ashish Vaswani", "Noam Shazeer"]
+fn split_authors(authors: &str) -> Vec<String> {
+  AUTHOR_SEPARATOR
+    .split(authors)
+    .map(|author| match author.split_once(',') {
+      Some((last, first)) => format!("{} {}", first.trim(), last.trim()),
+      None => author.trim().to_string(),
+    })
+    .filter(|author| !author.is_empty() && author.as_str() != "others")
+    .collect()
+}
+
+// Months can be written as macros without braces: month = jan
+fn month_macros() -> HashMap<String, String> {
+  ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"]
+    .iter()
+    .map(|month| (month.to_string(), month.to_string()))
+    .collect()
+}
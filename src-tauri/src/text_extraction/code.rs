@@ -0,0 +1,166 @@
+// Extract text from source code files (.rs, .py, .ts)
+// The words of compound identifiers (parseQuery -> parse query) go into the `identifiers` field so that a search for
+// `parse query` finds them, while the text stays the source as written. Comments and docstrings also go into their own field.
+// The source is split into sections of whole lines that each fit in one chunk, so the comments and identifiers of a
+// section are only indexed with the chunk they are in
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{read_to_string, File};
+use std::io::Read;
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::text_extraction::{ContentSection, ExtractedContent};
+
+lazy_static! {
+  static ref IDENTIFIER: Regex = Regex::new(r"[A-Za-z_][A-Za-z0-9_]*").unwrap();
+  static ref C_STYLE_COMMENT: Regex = Regex::new(r"(?s)//[^\n]*|/\*.*?\*/").unwrap();
+  static ref PYTHON_COMMENT: Regex = Regex::new(r#"(?s)#[^\n]*|""".*?"""|'''.*?'''"#).unwrap();
+  // opening markers at the start of a line and closing markers at the end of one
+  static ref COMMENT_MARKERS: Regex = Regex::new(r#"(?m)^\s*(///?!?|/\*\*?|\*/|\*|#|"""|''')|(\*/|"""|''')\s*$"#).unwrap();
+}
+
+// Below the 2000 characters of a chunk, see `chunk_text` in indexing.rs
+const SECTION_LENGTH: usize = 1800;
+// MPEG transport streams (.ts videos) start with a sync byte every 188 bytes
+const TS_PACKET_LENGTH: usize = 188;
+const TS_SYNC_BYTE: u8 = 0x47;
+const SNIFF_LENGTH: usize = 8192;
+
+// Programming language of a file type, as stored in the `code_language` field
+pub fn code_language(file_type: &str) -> Option<&'static str> {
+  match file_type {
+    "rs" => Some("rust"),
+    "py" => Some("python"),
+    "ts" => Some("typescript"),
+    _ => None,
+  }
+}
+
+pub fn extract(file: &String, file_type: &str, _app: &tauri::AppHandle) -> Result<ExtractedContent, Box<dyn Error>> {
+  let language = code_language(file_type).ok_or("Not a source code file")?;
+  // .ts is also the extension of MPEG transport stream videos, which are not read into memory
+  if is_binary(file)? {
+    return Err("Not a source code file".into());
+  }
+  let source = read_to_string(file)?;
+  Ok(extract_from_source(&source, language))
+}
+
+// Whether the start of the file is an MPEG transport stream or has a NUL byte, which source code never has
+fn is_binary(file: &String) -> Result<bool, Box<dyn Error>> {
+  let mut start = Vec::with_capacity(SNIFF_LENGTH);
+  File::open(file)?.take(SNIFF_LENGTH as u64).read_to_end(&mut start)?;
+  let transport_stream = start.len() > TS_PACKET_LENGTH
+    && start.iter().step_by(TS_PACKET_LENGTH).all(|byte| *byte == TS_SYNC_BYTE);
+  Ok(transport_stream || start.contains(&0))
+}
+
+// Also used for the code cells of Jupyter notebooks
+pub fn extract_from_source(source: &str, language: &str) -> ExtractedContent {
+  let comments = extract_comments(source, language);
+  let sections = line_sections(source)
+    .into_iter()
+    .map(|(start, end, line)| {
+      let mut fields: Vec<(String, String)> = comments
+        .iter()
+        .filter(|(comment_start, _)| (start..end).contains(comment_start))
+        .map(|(_, comment)| ("code_comments".to_string(), comment.clone()))
+        .collect();
+      let identifiers = identifier_words(&source[start..end]);
+      if !identifiers.is_empty() {
+        fields.push(("identifiers".to_string(), identifiers));
+      }
+      ContentSection {
+        label: format!("line {}", line),
+        number: line as i64,
+        text: source[start..end].to_string(),
+        fields,
+      }
+    })
+    .collect();
+
+  ExtractedContent {
+    text: source.to_string(),
+    fields: vec![("code_language".to_string(), language.to_string())],
+    sections,
+    language: None,
+  }
+}
+
+// The byte ranges of runs of whole lines up to SECTION_LENGTH long (a longer line is a section of its own), with the
+// number of their first line
+fn line_sections(source: &str) -> Vec<(usize, usize, usize)> {
+  let mut sections = vec![];
+  let (mut start, mut end, mut first_line) = (0, 0, 1);
+  for (index, line) in source.split_inclusive('\n').enumerate() {
+    if end > start && end - start + line.len() > SECTION_LENGTH {
+      sections.push((start, end, first_line));
+      start = end;
+      first_line = index + 1;
+    }
+    end += line.len();
+  }
+  if end > start {
+    sections.push((start, end, first_line));
+  }
+  // blank lines have nothing to index
+  sections.retain(|(start, end, _)| !source[*start..*end].trim().is_empty());
+  sections
+}
+
+// The comments with their byte offset in the source
+fn extract_comments(source: &str, language: &str) -> Vec<(usize, String)> {
+  let comment_pattern: &Regex = match language {
+    // notebook kernels with # comments
+    "python" | "r" | "julia" => &PYTHON_COMMENT,
+    _ => &C_STYLE_COMMENT,
+  };
+  comment_pattern
+    .find_iter(source)
+    .map(|comment| (comment.start(), COMMENT_MARKERS.replace_all(comment.as_str(), "").trim().to_string()))
+    .filter(|(_, comment)| !comment.is_empty())
+    .collect()
+}
+
+// The words of every compound identifier, once per identifier, e.g. "get user name" for getUserName
+pub fn identifier_words(source: &str) -> String {
+  let mut seen: HashSet<&str> = HashSet::new();
+  IDENTIFIER
+    .find_iter(source)
+    .map(|identifier| identifier.as_str())
+    .filter(|identifier| seen.insert(*identifier))
+    .map(split_identifier)
+    .filter(|words| words.len() > 1)
+    .map(|words| words.join(" "))
+    .collect::<Vec<String>>()
+    .join("\n")
+}
+
+// Split camelCase, PascalCase and snake_case identifiers into lowercase words
+// An acronym stays one word: HTTPServer -> http server
+pub fn split_identifier(identifier: &str) -> Vec<String> {
+  let mut words: Vec<String> = vec![];
+  for part in identifier.split('_').filter(|part| !part.is_empty()) {
+    let chars: Vec<char> = part.chars().collect();
+    let mut word = String::new();
+    for (index, c) in chars.iter().enumerate() {
+      let previous = if index > 0 { Some(chars[index - 1]) } else { None };
+      let next = chars.get(index + 1);
+      let starts_word = match previous {
+        Some(previous) if c.is_uppercase() => {
+          previous.is_lowercase() || previous.is_ascii_digit() || (previous.is_uppercase() && next.map_or(false, |next| next.is_lowercase()))
+        }
+        _ => false,
+      };
+      if starts_word && !word.is_empty() {
+        words.push(word.to_lowercase());
+        word = String::new();
+      }
+      word.push(*c);
+    }
+    if !word.is_empty() {
+      words.push(word.to_lowercase());
+    }
+  }
+  words
+}
@@ -1,11 +1,36 @@
-use dotext::*;
-use std::io::Read;
+// Extract text from a .docx file
+// Reads the package directly instead of going through dotext so that headers, footers, footnotes,
+// endnotes, reviewer comments and the core properties (author, title etc.) are indexed too
 use std::error::Error;
+use crate::text_extraction::ExtractedContent;
+use crate::text_extraction::ooxml::{open_package, part_names, part_text, read_core_properties, read_part};
 
-pub fn extract(file: &String, _app: &tauri::AppHandle) -> Result<String, Box<dyn Error>> {
-  /* Usage of ? at the end of the open() method call implies that if there is an error during method execution, then halt the execution and return the error. */ 
-  let mut file_buffer = Docx::open(file)?;
-  let mut text = String::new();
-  file_buffer.read_to_string(&mut text)?;
-  Ok(text)
-}
\ No newline at end of file
+// Parts that are indexed after the main document, in this order
+const SECONDARY_PART_PREFIXES: [&str; 5] = ["word/header", "word/footer", "word/footnotes", "word/endnotes", "word/comments"];
+
+pub fn extract(file: &String, app: &tauri::AppHandle) -> Result<ExtractedContent, Box<dyn Error>> {
+  let mut archive = open_package(file, app)?;
+  let mut texts: Vec<String> = vec![];
+
+  // Tracked insertions and text boxes are nested inside the document body, so they are picked up here
+  let document_xml = read_part(&mut archive, "word/document.xml").ok_or("word/document.xml not found")?;
+  texts.push(part_text(&document_xml));
+
+  for prefix in SECONDARY_PART_PREFIXES.iter() {
+    for name in part_names(&archive, prefix) {
+      if let Some(xml) = read_part(&mut archive, &name) {
+        let text = part_text(&xml);
+        if !text.trim().is_empty() {
+          texts.push(text);
+        }
+      }
+    }
+  }
+
+  Ok(ExtractedContent {
+    text: texts.join("\n"),
+    fields: read_core_properties(&mut archive),
+    sections: vec![],
+    language: None,
+  })
+}
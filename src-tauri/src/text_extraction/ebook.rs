@@ -0,0 +1,100 @@
+// Helpers shared by the e-book extractors (epub, mobi, azw3)
+// E-book content is (X)HTML, so it is turned into plain text and split into chapters here
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+  static ref HIDDEN_BLOCKS: Regex = Regex::new(r"(?is)<(script|style|head)[^>]*>.*?</(script|style|head)>").unwrap();
+  static ref BLOCK_TAGS: Regex = Regex::new(r"(?i)</?(p|div|br|h[1-6]|li|tr|blockquote|section)\b[^>]*>").unwrap();
+  static ref TAGS: Regex = Regex::new(r"(?s)<[^>]*>").unwrap();
+  static ref BLANK_LINES: Regex = Regex::new(r"\n\s*\n+").unwrap();
+  static ref HEADING: Regex = Regex::new(r"(?is)<h([1-3])\b[^>]*>(.*?)</h[1-3]>").unwrap();
+  // MOBI marks chapters with page breaks, KF8 (azw3) files usually only have headings
+  static ref PAGE_BREAK: Regex = Regex::new(r"(?i)<mbp:pagebreak[^>]*>").unwrap();
+  static ref CHAPTER_HEADING_START: Regex = Regex::new(r"(?i)<h[12]\b").unwrap();
+}
+
+pub fn html_to_text(html: &str) -> String {
+  let text = HIDDEN_BLOCKS.replace_all(html, "");
+  let text = BLOCK_TAGS.replace_all(&text, "\n");
+  let text = TAGS.replace_all(&text, "");
+  let text = decode_entities(&text);
+  BLANK_LINES.replace_all(text.trim(), "\n").to_string()
+}
+
+fn decode_entities(text: &str) -> String {
+  text
+    .replace("&nbsp;", " ")
+    .replace("&#160;", " ")
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&#39;", "'")
+    .replace("&apos;", "'")
+    .replace("&amp;", "&")
+}
+
+// Text of the first h1-h3 heading in the given HTML, if any
+pub fn first_heading(html: &str) -> Option<String> {
+  HEADING
+    .captures(html)
+    .map(|captures| html_to_text(&captures[2]).replace("\n", " "))
+    .filter(|heading| !heading.trim().is_empty())
+}
+
+// Split the HTML of a whole book into (chapter name, chapter HTML) pairs
+// Chapters without a heading are named by their position, e.g. "chapter 4"
+pub fn split_html_chapters(html: &str) -> Vec<(String, String)> {
+  let mut parts: Vec<&str> = PAGE_BREAK.split(html).collect();
+  if parts.len() <= 1 {
+    let mut starts: Vec<usize> = CHAPTER_HEADING_START.find_iter(html).map(|heading| heading.start()).collect();
+    if starts.first() != Some(&0) {
+      starts.insert(0, 0);
+    }
+    starts.push(html.len());
+    parts = starts.windows(2).map(|range| &html[range[0]..range[1]]).collect();
+  }
+
+  parts
+    .into_iter()
+    .filter(|part| !html_to_text(part).is_empty())
+    .enumerate()
+    .map(|(index, part)| {
+      let name = first_heading(part).unwrap_or(format!("chapter {}", index + 1));
+      (name, part.to_string())
+    })
+    .collect()
+}
+
+// Keep only the digits (and a trailing X) of an ISBN so that `isbn:9780306406157` matches 978-0-306-40615-7
+// Returns None for identifiers that are not ISBNs (UUIDs, ASINs etc.)
+pub fn normalize_isbn(identifier: &str) -> Option<String> {
+  let identifier = identifier.trim();
+  let identifier = identifier
+    .strip_prefix("urn:isbn:")
+    .or(identifier.strip_prefix("isbn:"))
+    .unwrap_or(identifier);
+  if identifier.chars().any(|c| c.is_alphabetic() && c != 'X' && c != 'x') {
+    return None;
+  }
+  let isbn: String = identifier
+    .chars()
+    .filter(|c| c.is_ascii_digit() || *c == 'X' || *c == 'x')
+    .map(|c| c.to_ascii_uppercase())
+    .collect();
+  if isbn.len() == 10 || isbn.len() == 13 {
+    Some(isbn)
+  } else {
+    None
+  }
+}
+
+// The primary language subtag in lowercase, e.g. "en" for "en-US"
+pub fn primary_language(language: &str) -> String {
+  language
+    .trim()
+    .split(|c| c == '-' || c == '_')
+    .next()
+    .unwrap_or("")
+    .to_lowercase()
+}
@@ -0,0 +1,722 @@
+// Detection and decryption of password-protected PDFs and Office files
+// Both are tried with an empty password first (PDFs that only restrict printing or copying open without one) and then
+// with the passwords the user saved for the file or its folders (see password_store.rs). The saved passwords are only
+// looked up once a file turns out to be encrypted
+// When no password works the extractor returns EncryptedDocumentError, so the file is flagged as encrypted instead of
+// being re-tried on every sync
+use std::error::Error;
+use std::fmt;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Read;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use aes::{Aes128, Aes192, Aes256};
+use cbc::cipher::{block_padding::{NoPadding, Pkcs7}, BlockDecryptMut, BlockEncryptMut, KeyInit, KeyIvInit};
+use lopdf::{Document, Object, ObjectId, ObjectStream};
+use md5::Md5;
+use rc4::{consts::U16, Rc4, StreamCipher};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use xml::reader::{EventReader, XmlEvent};
+use crate::password_store::passwords_for_path;
+
+#[derive(Debug)]
+pub struct EncryptedDocumentError;
+
+impl fmt::Display for EncryptedDocumentError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "The document is encrypted and none of the saved passwords open it")
+  }
+}
+
+impl Error for EncryptedDocumentError {}
+
+pub fn is_encrypted_error(error: &(dyn Error + 'static)) -> bool {
+  error.downcast_ref::<EncryptedDocumentError>().is_some()
+}
+
+// ---------- PDF ----------
+// lopdf decrypts the standard security handler with RC4 (V 1 and 2). AES-128 (V 4, AESV2) and AES-256 (V 5, AESV3) are
+// decrypted here. Unlike lopdf, the strings nested in dictionaries and arrays (e.g. the text of annotations) are
+// decrypted too, and the objects packed in object streams are read once their stream is decrypted
+// Both the user and the owner password open an AES file. AES-256 passwords are used as UTF-8 without SASLprep, which
+// only matters for passwords with unusual Unicode characters
+
+// Decrypts the document in place when its trailer has an /Encrypt dictionary
+pub fn decrypt_pdf(document: &mut Document, file: &String, app: &tauri::AppHandle) -> Result<(), Box<dyn Error>> {
+  if !document.is_encrypted() {
+    return Ok(());
+  }
+  let security_handler = match AesSecurityHandler::from_document(document) {
+    Some(security_handler) => security_handler,
+    None => {
+      // a wrong password is rejected before any object is touched, so the next one can be tried on the same document
+      if document.decrypt("").is_ok() || passwords_for_path(file, app).iter().any(|password| document.decrypt(password).is_ok()) {
+        return Ok(());
+      }
+      return Err(EncryptedDocumentError.into());
+    }
+  };
+  let file_key = security_handler
+    .file_key("")
+    .or_else(|| passwords_for_path(file, app).iter().find_map(|password| security_handler.file_key(password)))
+    .ok_or(EncryptedDocumentError)?;
+  *document = load_decrypted_pdf(file, &security_handler, &file_key)?;
+  Ok(())
+}
+
+const PASSWORD_PADDING: [u8; 32] = [
+  0x28, 0xBF, 0x4E, 0x5E, 0x4E, 0x75, 0x8A, 0x41, 0x64, 0x00, 0x4E, 0x56, 0xFF, 0xFA, 0x01, 0x08,
+  0x2E, 0x2E, 0x00, 0xB6, 0xD0, 0x68, 0x3E, 0x80, 0x2F, 0x0C, 0xA9, 0xFE, 0x64, 0x53, 0x69, 0x7A,
+];
+const HIDDEN_OBJECT_STREAM: &[u8] = b"EncryptedObjStm";
+
+// The values of the /Encrypt dictionary and the file ID that the keys are made from
+struct AesSecurityHandler {
+  revision: i64,
+  owner: Vec<u8>,
+  user: Vec<u8>,
+  owner_key: Vec<u8>,
+  user_key: Vec<u8>,
+  permissions: i32,
+  file_id: Vec<u8>,
+  encrypt_metadata: bool,
+  encrypted_strings: bool,
+  encrypted_streams: bool,
+  encryption_id: ObjectId,
+}
+
+impl AesSecurityHandler {
+  // None for RC4 and for security handlers other than the standard one
+  fn from_document(document: &Document) -> Option<Self> {
+    let encryption_id = document.trailer.get(b"Encrypt").and_then(Object::as_reference).ok()?;
+    let dictionary = document.get_encrypted().ok()?;
+    if dictionary.get(b"Filter").and_then(Object::as_name).ok()? != b"Standard" {
+      return None;
+    }
+    let method: &[u8] = match dictionary.get(b"V").and_then(Object::as_i64).ok()? {
+      4 => b"AESV2",
+      5 => b"AESV3",
+      _ => return None,
+    };
+    // StmF and StrF name the crypt filters of streams and strings in CF, Identity leaves them as they are
+    let uses_aes = |key: &[u8]| -> Option<bool> {
+      let filter = dictionary.get(key).and_then(Object::as_name).unwrap_or(&b"Identity"[..]);
+      if filter == b"Identity" {
+        return Some(false);
+      }
+      let crypt_filter_method = dictionary
+        .get(b"CF")
+        .and_then(Object::as_dict)
+        .and_then(|crypt_filters| crypt_filters.get(filter))
+        .and_then(Object::as_dict)
+        .and_then(|crypt_filter| crypt_filter.get(b"CFM"))
+        .and_then(Object::as_name)
+        .ok()?;
+      // RC4 crypt filters in a V 4 dictionary aren't supported
+      (crypt_filter_method == method).then_some(true)
+    };
+    let bytes = |key: &[u8]| dictionary.get(key).and_then(Object::as_str).map(|value| value.to_vec()).unwrap_or_default();
+    Some(AesSecurityHandler {
+      revision: dictionary.get(b"R").and_then(Object::as_i64).ok()?,
+      owner: bytes(b"O"),
+      user: bytes(b"U"),
+      owner_key: bytes(b"OE"),
+      user_key: bytes(b"UE"),
+      permissions: dictionary.get(b"P").and_then(Object::as_i64).ok()? as i32,
+      file_id: document
+        .trailer
+        .get(b"ID")
+        .and_then(Object::as_array)
+        .ok()
+        .and_then(|ids| ids.first())
+        .and_then(|id| id.as_str().ok())
+        .map(|id| id.to_vec())
+        .unwrap_or_default(),
+      encrypt_metadata: dictionary.get(b"EncryptMetadata").and_then(Object::as_bool).unwrap_or(true),
+      encrypted_strings: uses_aes(b"StrF")?,
+      encrypted_streams: uses_aes(b"StmF")?,
+      encryption_id,
+    })
+  }
+
+  // The key of the file if the password is its user or owner password
+  fn file_key(&self, password: &str) -> Option<Vec<u8>> {
+    match self.revision {
+      4 => self.authenticate_owner(password.as_bytes()).or_else(|| self.authenticate_user(password.as_bytes())),
+      5 | 6 => self.aes256_file_key(&password.as_bytes()[..password.len().min(127)]),
+      _ => None,
+    }
+  }
+
+  // Algorithm 2: MD5 of the padded user password, O, P and the file ID, then 50 more rounds of MD5
+  fn aes128_file_key(&self, password: &[u8]) -> Vec<u8> {
+    let mut hasher = Md5::new();
+    hasher.update(padded_password(password));
+    hasher.update(self.owner.get(..32).unwrap_or(&self.owner[..]));
+    hasher.update(self.permissions.to_le_bytes());
+    hasher.update(&self.file_id);
+    if !self.encrypt_metadata {
+      hasher.update([0xff; 4]);
+    }
+    let mut key = hasher.finalize().to_vec();
+    for _ in 0..50 {
+      key = Md5::digest(&key).to_vec();
+    }
+    key
+  }
+
+  // Algorithm 6: the key is right if it encrypts the padding and the file ID into U
+  fn authenticate_user(&self, password: &[u8]) -> Option<Vec<u8>> {
+    let key = self.aes128_file_key(password);
+    let mut check = hash_md5(&[&PASSWORD_PADDING, &self.file_id]);
+    for round in 0..20 {
+      rc4(&xor_key(&key, round), &mut check);
+    }
+    (self.user.get(..16)? == check.as_slice()).then_some(key)
+  }
+
+  // Algorithm 7: O is the padded user password, encrypted with a key made from the owner password
+  fn authenticate_owner(&self, password: &[u8]) -> Option<Vec<u8>> {
+    let mut key = Md5::digest(padded_password(password)).to_vec();
+    for _ in 0..50 {
+      key = Md5::digest(&key).to_vec();
+    }
+    let mut user_password = self.owner.get(..32)?.to_vec();
+    for round in (0..20).rev() {
+      rc4(&xor_key(&key, round), &mut user_password);
+    }
+    self.authenticate_user(&user_password)
+  }
+
+  // Algorithm 2.A: U and O start with a hash of the password and a validation salt, and UE and OE hold the file key
+  // encrypted with a hash of the password and a key salt
+  fn aes256_file_key(&self, password: &[u8]) -> Option<Vec<u8>> {
+    let user = self.user.get(..48)?;
+    let owner = self.owner.get(..48)?;
+    if self.password_hash(password, &owner[32..40], user) == owner[..32] {
+      return aes_cbc_decrypt(&self.password_hash(password, &owner[40..48], user), &[0; 16], &self.owner_key);
+    }
+    if self.password_hash(password, &user[32..40], &[]) == user[..32] {
+      return aes_cbc_decrypt(&self.password_hash(password, &user[40..48], &[]), &[0; 16], &self.user_key);
+    }
+    None
+  }
+
+  // SHA-256 for revision 5, algorithm 2.B for revision 6: at least 64 rounds of AES-128 and a SHA-2 picked by the result
+  fn password_hash(&self, password: &[u8], salt: &[u8], user: &[u8]) -> Vec<u8> {
+    let mut hash = hash_parts(HashAlgorithm::Sha256, &[password, salt, user]);
+    if self.revision == 5 {
+      return hash;
+    }
+    let mut round = 0;
+    loop {
+      let block = [password, hash.as_slice(), user].concat().repeat(64);
+      let encrypted = cbc::Encryptor::<Aes128>::new_from_slices(&hash[..16], &hash[16..32])
+        .unwrap()
+        .encrypt_padded_vec_mut::<NoPadding>(&block);
+      // the first 16 bytes as a number, modulo 3
+      let hash_algorithm = match encrypted[..16].iter().map(|byte| *byte as u32).sum::<u32>() % 3 {
+        0 => HashAlgorithm::Sha256,
+        1 => HashAlgorithm::Sha384,
+        _ => HashAlgorithm::Sha512,
+      };
+      hash = hash_parts(hash_algorithm, &[&encrypted]);
+      round += 1;
+      if round >= 64 && *encrypted.last().unwrap() as u32 + 32 <= round {
+        break;
+      }
+    }
+    hash.truncate(32);
+    hash
+  }
+
+  // AES-128 keys are made per object from the file key, AES-256 uses the file key for everything
+  fn object_key(&self, file_key: &[u8], id: ObjectId) -> Vec<u8> {
+    if self.revision >= 5 {
+      return file_key.to_vec();
+    }
+    hash_md5(&[file_key, &id.0.to_le_bytes()[..3], &id.1.to_le_bytes(), b"sAlT"])
+  }
+
+  fn decrypt_document(&self, document: &mut Document, file_key: &[u8]) {
+    for (&id, object) in document.objects.iter_mut() {
+      if id != self.encryption_id {
+        self.decrypt_object(&self.object_key(file_key, id), object);
+      }
+    }
+    // the objects in object streams aren't encrypted on their own, they are readable once their stream is
+    let mut packed_objects = BTreeMap::new();
+    for object in document.objects.values_mut() {
+      if let Ok(stream) = object.as_stream_mut() {
+        if stream.dict.type_is(HIDDEN_OBJECT_STREAM) {
+          stream.dict.set("Type", Object::Name(b"ObjStm".to_vec()));
+          if let Ok(object_stream) = ObjectStream::new(stream) {
+            packed_objects.extend(object_stream.objects);
+          }
+        }
+      }
+    }
+    for (id, object) in packed_objects {
+      document.objects.entry(id).or_insert(object);
+    }
+    document.trailer.remove(b"Encrypt");
+    document.objects.remove(&self.encryption_id);
+  }
+
+  fn decrypt_object(&self, key: &[u8], object: &mut Object) {
+    match object {
+      Object::String(content, _) if self.encrypted_strings => {
+        if let Some(decrypted) = aes_decrypt_data(key, content) {
+          *content = decrypted;
+        }
+      }
+      Object::Array(items) => items.iter_mut().for_each(|item| self.decrypt_object(key, item)),
+      Object::Dictionary(dictionary) => dictionary.iter_mut().for_each(|(_, value)| self.decrypt_object(key, value)),
+      Object::Stream(stream) => {
+        stream.dict.iter_mut().for_each(|(_, value)| self.decrypt_object(key, value));
+        // cross-reference streams are never encrypted, and XMP metadata only when EncryptMetadata says so
+        let skipped = stream.dict.type_is(b"XRef") || (!self.encrypt_metadata && stream.dict.type_is(b"Metadata"));
+        if self.encrypted_streams && !skipped {
+          if let Some(decrypted) = aes_decrypt_data(key, &stream.content) {
+            stream.set_content(decrypted);
+          }
+        }
+      }
+      _ => {}
+    }
+  }
+}
+
+// lopdf drops the object streams of an encrypted file when it can't read them, so the file is loaded again with them
+// renamed and they are read after decryption
+fn load_decrypted_pdf(file: &str, security_handler: &AesSecurityHandler, file_key: &[u8]) -> Result<Document, Box<dyn Error>> {
+  let mut document = match catch_unwind(AssertUnwindSafe(|| Document::load_filtered(file, hide_object_streams))) {
+    Ok(Ok(document)) => document,
+    _ => return Err(format!("Could not parse {}", file).into()),
+  };
+  security_handler.decrypt_document(&mut document, file_key);
+  Ok(document)
+}
+
+// lopdf keeps the object if the filter returns Some, the object itself is changed in place
+fn hide_object_streams(id: ObjectId, object: &mut Object) -> Option<(ObjectId, Object)> {
+  if let Ok(stream) = object.as_stream_mut() {
+    if stream.dict.type_is(b"ObjStm") {
+      stream.dict.set("Type", Object::Name(HIDDEN_OBJECT_STREAM.to_vec()));
+    }
+  }
+  Some((id, Object::Null))
+}
+
+fn padded_password(password: &[u8]) -> Vec<u8> {
+  password.iter().chain(PASSWORD_PADDING.iter()).take(32).copied().collect()
+}
+
+fn xor_key(key: &[u8], round: u8) -> Vec<u8> {
+  key.iter().map(|byte| byte ^ round).collect()
+}
+
+fn rc4(key: &[u8], data: &mut [u8]) {
+  if let Ok(mut cipher) = Rc4::<U16>::new_from_slice(key) {
+    cipher.apply_keystream(data);
+  }
+}
+
+fn hash_md5(parts: &[&[u8]]) -> Vec<u8> {
+  let mut hasher = Md5::new();
+  parts.iter().for_each(|part| hasher.update(part));
+  hasher.finalize().to_vec()
+}
+
+// Strings and streams start with the IV and are padded with PKCS#7. Data that doesn't decrypt is left as it is
+fn aes_decrypt_data(key: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+  if data.len() < 32 || data.len() % 16 != 0 {
+    return None;
+  }
+  let (iv, data) = data.split_at(16);
+  match key.len() {
+    16 => cbc::Decryptor::<Aes128>::new_from_slices(key, iv).ok()?.decrypt_padded_vec_mut::<Pkcs7>(data).ok(),
+    32 => cbc::Decryptor::<Aes256>::new_from_slices(key, iv).ok()?.decrypt_padded_vec_mut::<Pkcs7>(data).ok(),
+    _ => None,
+  }
+}
+
+// ---------- Office ----------
+// A password-protected docx, pptx or xlsx is a compound file (the OLE container of old Office files) instead of a zip
+// archive. Its EncryptionInfo stream describes how the key is derived from the password and its EncryptedPackage stream
+// is the zip archive, encrypted with AES
+// Agile encryption (Office 2010 and later) and standard encryption (Office 2007) are supported
+
+const COMPOUND_FILE_SIGNATURE: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+
+// Ok(None) if the file isn't an encrypted Office file, the decrypted zip archive if a password opens it
+// Only the signature of a zip archive is read, the caller then reads the file as usual
+pub fn decrypt_office_file(file: &String, app: &tauri::AppHandle) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+  let mut signature = [0u8; 8];
+  if File::open(file)?.read_exact(&mut signature).is_err() || signature != COMPOUND_FILE_SIGNATURE {
+    return Ok(None);
+  }
+  let mut compound_file = cfb::open(file)?;
+  if !compound_file.is_stream("EncryptionInfo") {
+    return Ok(None);
+  }
+  let encryption_info = read_stream(&mut compound_file, "EncryptionInfo")?;
+  let encrypted_package = read_stream(&mut compound_file, "EncryptedPackage")?;
+
+  // Office uses "VelvetSweatshop" when a workbook is only protected against changes
+  let default_package = ["", "VelvetSweatshop"]
+    .into_iter()
+    .find_map(|password| decrypt_package(&encryption_info, &encrypted_package, password));
+  if let Some(package) = default_package {
+    return Ok(Some(package));
+  }
+  let package = passwords_for_path(file, app)
+    .iter()
+    .find_map(|password| decrypt_package(&encryption_info, &encrypted_package, password))
+    .ok_or(EncryptedDocumentError)?;
+  Ok(Some(package))
+}
+
+fn read_stream(compound_file: &mut cfb::CompoundFile<File>, name: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+  let mut data = vec![];
+  compound_file.open_stream(name)?.read_to_end(&mut data)?;
+  Ok(data)
+}
+
+fn decrypt_package(encryption_info: &[u8], encrypted_package: &[u8], password: &str) -> Option<Vec<u8>> {
+  let major_version = read_u16(encryption_info, 0)?;
+  let minor_version = read_u16(encryption_info, 2)?;
+  match (major_version, minor_version) {
+    (4, 4) => decrypt_agile_package(encryption_info, encrypted_package, password),
+    (2..=4, 2) => decrypt_standard_package(encryption_info, encrypted_package, password),
+    _ => None,
+  }
+}
+
+// The attributes of <keyData> and of the password <encryptedKey> in the XML of the EncryptionInfo stream
+#[derive(Default)]
+struct AgileEncryptionInfo {
+  key_data: Vec<(String, String)>,
+  password_key: Vec<(String, String)>,
+}
+
+fn attribute<'a>(attributes: &'a [(String, String)], name: &str) -> &'a str {
+  attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str()).unwrap_or("")
+}
+
+fn read_agile_encryption_info(encryption_info: &[u8]) -> AgileEncryptionInfo {
+  let mut info = AgileEncryptionInfo::default();
+  let xml = encryption_info.get(8..).unwrap_or_default();
+  for event in EventReader::new(xml) {
+    match event {
+      Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+        let attributes: Vec<(String, String)> = attributes.into_iter().map(|attribute| (attribute.name.local_name, attribute.value)).collect();
+        match name.local_name.as_str() {
+          "keyData" => info.key_data = attributes,
+          "encryptedKey" if attributes.iter().any(|(key, _)| key == "spinCount") => info.password_key = attributes,
+          _ => {}
+        }
+      }
+      Err(e) => {
+        println!("Error reading encryption info: {}", e);
+        break;
+      }
+      _ => {}
+    }
+  }
+  info
+}
+
+fn decrypt_agile_package(encryption_info: &[u8], encrypted_package: &[u8], password: &str) -> Option<Vec<u8>> {
+  use base64::prelude::*;
+  let info = read_agile_encryption_info(encryption_info);
+  let decode = |attributes: &[(String, String)], name: &str| BASE64_STANDARD.decode(attribute(attributes, name)).ok();
+  let key = &info.password_key;
+  let hash_algorithm = HashAlgorithm::from_name(attribute(key, "hashAlgorithm"))?;
+  let spin_count: u32 = attribute(key, "spinCount").parse().ok()?;
+  let key_bytes = attribute(key, "keyBits").parse::<usize>().ok()? / 8;
+  let salt = decode(key, "saltValue")?;
+
+  let password_units: Vec<u8> = password.encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect();
+  let mut hash = hash_parts(hash_algorithm, &[&salt, &password_units]);
+  for iteration in 0..spin_count {
+    hash = hash_parts(hash_algorithm, &[&iteration.to_le_bytes(), &hash]);
+  }
+  let block_key = |block: &[u8]| resize_key(hash_parts(hash_algorithm, &[&hash, block]), key_bytes);
+
+  let verifier_input = aes_cbc_decrypt(&block_key(&[0xfe, 0xa7, 0xd2, 0x76, 0x3b, 0x4b, 0x9e, 0x79]), &salt, &decode(key, "encryptedVerifierHashInput")?)?;
+  let verifier_hash = aes_cbc_decrypt(&block_key(&[0xd7, 0xaa, 0x0f, 0x6d, 0x30, 0x61, 0x34, 0x4e]), &salt, &decode(key, "encryptedVerifierHashValue")?)?;
+  let expected_hash = hash_parts(hash_algorithm, &[&verifier_input[..salt.len().min(verifier_input.len())]]);
+  if verifier_hash.get(..expected_hash.len())? != expected_hash.as_slice() {
+    return None;
+  }
+  let mut package_key = aes_cbc_decrypt(&block_key(&[0x14, 0x6e, 0x0b, 0xe7, 0xab, 0xac, 0xd0, 0xd6]), &salt, &decode(key, "encryptedKeyValue")?)?;
+  package_key.truncate(key_bytes);
+
+  // the package is encrypted in 4096-byte segments, each with an IV made from the key data salt and the segment number
+  let key_data = &info.key_data;
+  let key_data_hash = HashAlgorithm::from_name(attribute(key_data, "hashAlgorithm"))?;
+  let key_data_salt = decode(key_data, "saltValue")?;
+  let block_size: usize = attribute(key_data, "blockSize").parse().ok()?;
+  let package_size = read_u64(encrypted_package, 0)? as usize;
+  let mut package: Vec<u8> = Vec::with_capacity(package_size);
+  for (segment, data) in encrypted_package.get(8..)?.chunks(4096).enumerate() {
+    let iv = segment_iv(key_data_hash, &key_data_salt, segment as u32, block_size);
+    package.extend(aes_cbc_decrypt(&package_key, &iv, data)?);
+  }
+  package.truncate(package_size);
+  Some(package)
+}
+
+fn segment_iv(hash_algorithm: HashAlgorithm, salt: &[u8], segment: u32, block_size: usize) -> Vec<u8> {
+  resize_key(hash_parts(hash_algorithm, &[salt, &segment.to_le_bytes()]), block_size)
+}
+
+// Hashes are cut to the key length, or padded with 0x36 when the key is longer
+fn resize_key(mut key: Vec<u8>, length: usize) -> Vec<u8> {
+  key.resize(length, 0x36);
+  key
+}
+
+// Standard encryption: a fixed 50000 rounds of SHA-1 and the package encrypted with AES in ECB mode
+fn decrypt_standard_package(encryption_info: &[u8], encrypted_package: &[u8], password: &str) -> Option<Vec<u8>> {
+  let header_size = read_u32(encryption_info, 8)? as usize;
+  let key_bits = read_u32(encryption_info, 12 + 16)? as usize;
+  let verifier = encryption_info.get(12 + header_size..)?;
+  let salt_size = read_u32(verifier, 0)? as usize;
+  let salt = verifier.get(4..4 + salt_size)?;
+  let encrypted_verifier = verifier.get(4 + salt_size..20 + salt_size)?;
+  let verifier_hash_size = read_u32(verifier, 20 + salt_size)? as usize;
+  let encrypted_verifier_hash = verifier.get(24 + salt_size..24 + salt_size + 32)?;
+
+  let password_units: Vec<u8> = password.encode_utf16().flat_map(|unit| unit.to_le_bytes()).collect();
+  let mut hash = hash_parts(HashAlgorithm::Sha1, &[salt, &password_units]);
+  for iteration in 0..50000u32 {
+    hash = hash_parts(HashAlgorithm::Sha1, &[&iteration.to_le_bytes(), &hash]);
+  }
+  hash = hash_parts(HashAlgorithm::Sha1, &[&hash, &0u32.to_le_bytes()]);
+  let derive = |fill: u8| -> Vec<u8> {
+    let mut buffer = vec![fill; 64];
+    buffer.iter_mut().zip(hash.iter()).for_each(|(byte, hash_byte)| *byte ^= hash_byte);
+    hash_parts(HashAlgorithm::Sha1, &[&buffer])
+  };
+  let mut key = [derive(0x36), derive(0x5c)].concat();
+  key.truncate(key_bits / 8);
+
+  let verifier = aes_ecb_decrypt(&key, encrypted_verifier)?;
+  let verifier_hash = aes_ecb_decrypt(&key, encrypted_verifier_hash)?;
+  if hash_parts(HashAlgorithm::Sha1, &[&verifier]) != verifier_hash[..verifier_hash_size.min(20)] {
+    return None;
+  }
+  let package_size = read_u64(encrypted_package, 0)? as usize;
+  let mut package = aes_ecb_decrypt(&key, encrypted_package.get(8..)?)?;
+  package.truncate(package_size);
+  Some(package)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+  Some(u16::from_le_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+  Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+  Some(u64::from_le_bytes(data.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+#[derive(Debug, Clone, Copy)]
+enum HashAlgorithm {
+  Sha1,
+  Sha256,
+  Sha384,
+  Sha512,
+}
+
+impl HashAlgorithm {
+  fn from_name(name: &str) -> Option<Self> {
+    match name.replace("-", "").to_uppercase().as_str() {
+      "SHA1" => Some(HashAlgorithm::Sha1),
+      "SHA256" => Some(HashAlgorithm::Sha256),
+      "SHA384" => Some(HashAlgorithm::Sha384),
+      "SHA512" => Some(HashAlgorithm::Sha512),
+      _ => None,
+    }
+  }
+}
+
+fn hash_parts(algorithm: HashAlgorithm, parts: &[&[u8]]) -> Vec<u8> {
+  fn digest<D: Digest>(parts: &[&[u8]]) -> Vec<u8> {
+    let mut hasher = D::new();
+    parts.iter().for_each(|part| hasher.update(part));
+    hasher.finalize().to_vec()
+  }
+  match algorithm {
+    HashAlgorithm::Sha1 => digest::<Sha1>(parts),
+    HashAlgorithm::Sha256 => digest::<Sha256>(parts),
+    HashAlgorithm::Sha384 => digest::<Sha384>(parts),
+    HashAlgorithm::Sha512 => digest::<Sha512>(parts),
+  }
+}
+
+// The key length picks AES-128, AES-192 or AES-256. There is no padding, a trailing partial block is dropped
+fn aes_cbc_decrypt(key: &[u8], iv: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+  let iv = iv.get(..16)?;
+  let data = &data[..data.len() - data.len() % 16];
+  match key.len() {
+    16 => cbc::Decryptor::<Aes128>::new_from_slices(key, iv).ok()?.decrypt_padded_vec_mut::<NoPadding>(data).ok(),
+    24 => cbc::Decryptor::<Aes192>::new_from_slices(key, iv).ok()?.decrypt_padded_vec_mut::<NoPadding>(data).ok(),
+    32 => cbc::Decryptor::<Aes256>::new_from_slices(key, iv).ok()?.decrypt_padded_vec_mut::<NoPadding>(data).ok(),
+    _ => None,
+  }
+}
+
+fn aes_ecb_decrypt(key: &[u8], data: &[u8]) -> Option<Vec<u8>> {
+  let data = &data[..data.len() - data.len() % 16];
+  match key.len() {
+    16 => ecb::Decryptor::<Aes128>::new_from_slice(key).ok()?.decrypt_padded_vec_mut::<NoPadding>(data).ok(),
+    24 => ecb::Decryptor::<Aes192>::new_from_slice(key).ok()?.decrypt_padded_vec_mut::<NoPadding>(data).ok(),
+    32 => ecb::Decryptor::<Aes256>::new_from_slice(key).ok()?.decrypt_padded_vec_mut::<NoPadding>(data).ok(),
+    _ => None,
+  }
+}
+
+// Known answers made by encrypting with an independent implementation of MS-OFFCRYPTO, with fixed salts and keys
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const PASSWORD: &str = "Secret 123";
+  const PACKAGE: &[u8] = b"PK\x03\x04 the decrypted package";
+
+  fn hex(text: &str) -> Vec<u8> {
+    (0..text.len()).step_by(2).map(|index| u8::from_str_radix(&text[index..index + 2], 16).unwrap()).collect()
+  }
+
+  fn agile_encryption_info(hash_algorithm: &str, key_bits: u32, verifier_input: &str, verifier_hash: &str, key_value: &str) -> Vec<u8> {
+    let xml = format!(
+      concat!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>"#,
+        r#"<encryption xmlns="http://schemas.microsoft.com/office/2006/encryption" xmlns:p="http://schemas.microsoft.com/office/2006/keyEncryptor/password">"#,
+        r#"<keyData saltSize="16" blockSize="16" keyBits="{bits}" cipherAlgorithm="AES" cipherChaining="ChainingModeCBC" hashAlgorithm="{hash}" saltValue="EBESExQVFhcYGRobHB0eHw=="/>"#,
+        r#"<keyEncryptors><keyEncryptor uri="http://schemas.microsoft.com/office/2006/keyEncryptor/password">"#,
+        r#"<p:encryptedKey spinCount="1000" saltSize="16" blockSize="16" keyBits="{bits}" cipherAlgorithm="AES" cipherChaining="ChainingModeCBC" hashAlgorithm="{hash}" saltValue="ICEiIyQlJicoKSorLC0uLw==" encryptedVerifierHashInput="{input}" encryptedVerifierHashValue="{value}" encryptedKeyValue="{key}"/>"#,
+        r#"</keyEncryptor></keyEncryptors></encryption>"#,
+      ),
+      bits = key_bits, hash = hash_algorithm, input = verifier_input, value = verifier_hash, key = key_value,
+    );
+    [vec![4, 0, 4, 0, 0x40, 0, 0, 0], xml.into_bytes()].concat()
+  }
+
+  fn standard_encryption_info(algorithm_id: u32, key_bits: u32, encrypted_verifier: &str, encrypted_verifier_hash: &str) -> Vec<u8> {
+    let mut header: Vec<u8> = [0x24, 0, algorithm_id, 0x8004, key_bits, 0x18, 0, 0].iter().flat_map(|value: &u32| value.to_le_bytes()).collect();
+    header.extend("Microsoft Enhanced RSA and AES Cryptographic Provider\0".encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+    let mut info = vec![3, 0, 2, 0, 0x24, 0, 0, 0];
+    info.extend((header.len() as u32).to_le_bytes());
+    info.extend(header);
+    info.extend(16u32.to_le_bytes());
+    info.extend(0x50..0x60u8);
+    info.extend(hex(encrypted_verifier));
+    info.extend(20u32.to_le_bytes());
+    info.extend(hex(encrypted_verifier_hash));
+    info
+  }
+
+  #[test]
+  fn agile_sha512_aes256() {
+    let info = agile_encryption_info(
+      "SHA512",
+      256,
+      "a6nd4updyXgs7cx/nWQ3yg==",
+      "eUfmSwvV8LjKiR5UxxJGE2dXqbw4RTzUrs1yVYtC2yhyHzlzk6ywlC3P2Bn1mQoaAnsfx1J8+AD/DW4HFQ+qXw==",
+      "OoIuStJawDT945UuU94FXA9kEBXqeG07zJABBddYquA=",
+    );
+    let package = hex("1a000000000000004395e4005b93f63de6166c2f5250c97fb5a1bb0e88e459232fa961cf168f789e");
+    assert_eq!(decrypt_package(&info, &package, PASSWORD).as_deref(), Some(PACKAGE));
+    assert_eq!(decrypt_package(&info, &package, "secret 123"), None);
+  }
+
+  #[test]
+  fn agile_sha1_aes128() {
+    let info = agile_encryption_info(
+      "SHA1",
+      128,
+      "AV17AdCANmn48mRKt48wEA==",
+      "ZQ3Co+ues9sXJ9vsdjJ0iQbIv56nuqh1xZ589OzL8ZI=",
+      "CpC/Fs1NluhOiv44RZH7mA==",
+    );
+    let package = hex("1a000000000000004e846dd84b4bf15b2ff2de00a244c6da588869991b7a9d56ea01d78cc0410676");
+    assert_eq!(decrypt_package(&info, &package, PASSWORD).as_deref(), Some(PACKAGE));
+    assert_eq!(decrypt_package(&info, &package, ""), None);
+  }
+
+  #[test]
+  fn agile_segment_iv() {
+    let salt: Vec<u8> = (0x10..0x20).collect();
+    assert_eq!(segment_iv(HashAlgorithm::Sha512, &salt, 1, 16), hex("da22ccba062aff577d207b2dee4e165b"));
+  }
+
+  #[test]
+  fn standard_aes128() {
+    let info = standard_encryption_info(
+      0x660E,
+      128,
+      "77c306a504a5869091657a7c5ef1529a",
+      "f852be974659d7a6b86831c9f5e06868f5667a0a95bff16d932390cc8082e3e3",
+    );
+    let package = hex("1a000000000000003564f36bbfadd16b6c062c955a48a4d6bf9806725c17f5c4299ec179d2905944");
+    assert_eq!(decrypt_package(&info, &package, PASSWORD).as_deref(), Some(PACKAGE));
+    assert_eq!(decrypt_package(&info, &package, "VelvetSweatshop"), None);
+  }
+
+  #[test]
+  fn standard_aes256() {
+    let info = standard_encryption_info(
+      0x6610,
+      256,
+      "331e650c1d540454b1808b2d5bf63a5a",
+      "fb535aee00953d3698054a566db13a58dc91d7564fd206e43d9e88631eab59fa",
+    );
+    let package = hex("1a0000000000000043447644283d34512fe33ce9501047a0a14ff627a4ec9baeb26b561ae3c89da7");
+    assert_eq!(decrypt_package(&info, &package, PASSWORD).as_deref(), Some(PACKAGE));
+    assert_eq!(decrypt_package(&info, &package, "Secret 124"), None);
+  }
+
+  // The PDF fixtures are made by an independent implementation of the standard security handler. Their catalog, page and
+  // one annotation are packed in an object stream, the other annotation and the content stream are objects of their own
+  fn check_aes_pdf(name: &str) {
+    let file = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let document = Document::load(&file).unwrap();
+    let security_handler = AesSecurityHandler::from_document(&document).unwrap();
+    assert_eq!(security_handler.file_key(""), None);
+    assert_eq!(security_handler.file_key("secret 123"), None);
+    let file_key = security_handler.file_key(PASSWORD).unwrap();
+    assert_eq!(security_handler.file_key("Owner 456"), Some(file_key.clone()));
+
+    let document = load_decrypted_pdf(&file, &security_handler, &file_key).unwrap();
+    assert!(!document.is_encrypted());
+    assert!(document.extract_text(&[1]).unwrap().contains("Quarterly budget"));
+    let mut notes: Vec<String> = document
+      .objects
+      .values()
+      .filter_map(|object| object.as_dict().ok())
+      .filter(|dictionary| dictionary.type_is(b"Annot"))
+      .filter_map(|dictionary| dictionary.get(b"Contents").and_then(Object::as_str).ok())
+      .map(|contents| String::from_utf8_lossy(contents).to_string())
+      .collect();
+    notes.sort();
+    assert_eq!(notes, vec!["Check the totals", "Packed note"]);
+  }
+
+  #[test]
+  fn pdf_aes128() {
+    check_aes_pdf("aes128.pdf");
+  }
+
+  #[test]
+  fn pdf_aes256() {
+    check_aes_pdf("aes256.pdf");
+  }
+}
@@ -1,19 +1,94 @@
-// Extract text from a .epub file
+// Extract text from a .epub file, one section per chapter
+// Chapters are named after the table of contents. Spine items without a TOC entry continue the previous chapter.
 use std::error::Error;
-use epub::doc::EpubDoc;
-
-pub fn extract(file: &String, _app: &tauri::AppHandle) -> Result<String, Box<dyn Error>> {
-  let doc = EpubDoc::new(file);
-  let mut doc = doc.unwrap();
-  let mut text = String::new();
-  while doc.go_next() {
-    let current = doc.get_current_str();
-    match current {
-      Some((v, _m)) => {
-        text.push_str(&v);
-      },
-      None => println!("Could not get value"),
+use std::path::PathBuf;
+use epub::doc::{EpubDoc, NavPoint};
+use crate::text_extraction::{ContentSection, ExtractedContent};
+use crate::text_extraction::ebook::{first_heading, html_to_text, normalize_isbn, primary_language};
+
+pub fn extract(file: &String, _app: &tauri::AppHandle) -> Result<ExtractedContent, Box<dyn Error>> {
+  let mut doc = EpubDoc::new(file)?;
+  let fields = read_metadata(&doc);
+  // the first declared language, e.g. "en" for en-GB
+  let language = doc.metadata.get("language").and_then(|languages| languages.first()).map(|language| primary_language(language)).filter(|language| !language.is_empty());
+
+  let mut toc_labels: Vec<(PathBuf, String)> = vec![];
+  flatten_toc(&doc.toc, &mut toc_labels);
+
+  let mut sections: Vec<ContentSection> = vec![];
+  loop {
+    let current_path = doc.get_current_path();
+    if let Some((html, _mime)) = doc.get_current_str() {
+      let text = html_to_text(&html);
+      let toc_label = current_path
+        .and_then(|path| toc_labels.iter().find(|(toc_path, _)| toc_path == &path).map(|(_, label)| label.clone()));
+
+      match (toc_label, sections.last_mut()) {
+        // no TOC entry, so this is a continuation of the previous chapter
+        (None, Some(section)) => {
+          section.text.push_str("\n");
+          section.text.push_str(&text);
+        }
+        (label, _) => {
+          let number = sections.len() as i64 + 1;
+          let label = label
+            .or(first_heading(&html))
+            .unwrap_or(format!("chapter {}", number));
+          sections.push(ContentSection {
+            label: label.clone(),
+            number,
+            text,
+            fields: vec![("section_title".to_string(), label)],
+          });
+        }
+      }
+    }
+    if !doc.go_next() {
+      break;
+    }
+  }
+  sections.retain(|section| !section.text.trim().is_empty());
+
+  Ok(ExtractedContent {
+    text: sections.iter().map(|section| section.text.as_str()).collect::<Vec<&str>>().join("\n"),
+    fields,
+    sections,
+    language,
+  })
+}
+
+// TOC entries point to a file and optionally an anchor inside it (chapter1.xhtml#start)
+// Only the first entry of each file is kept, nested entries come after their parent
+fn flatten_toc(nav_points: &Vec<NavPoint>, toc_labels: &mut Vec<(PathBuf, String)>) {
+  for nav_point in nav_points {
+    let content = nav_point.content.to_string_lossy().to_string();
+    let path = PathBuf::from(content.split("#").next().unwrap_or(&content));
+    if !toc_labels.iter().any(|(toc_path, _)| toc_path == &path) {
+      toc_labels.push((path, nav_point.label.trim().to_string()));
+    }
+    flatten_toc(&nav_point.children, toc_labels);
+  }
+}
+
+// Title, authors, publisher and ISBN from the OPF metadata
+fn read_metadata<R: std::io::Read + std::io::Seek>(doc: &EpubDoc<R>) -> Vec<(String, String)> {
+  let mut fields: Vec<(String, String)> = vec![];
+  let values = |name: &str| doc.metadata.get(name).cloned().unwrap_or_default();
+
+  for title in values("title") {
+    fields.push(("title".to_string(), title));
+  }
+  for author in values("creator") {
+    fields.push(("author".to_string(), author));
+  }
+  for publisher in values("publisher") {
+    fields.push(("publisher".to_string(), publisher));
+  }
+  for identifier in values("identifier") {
+    if let Some(isbn) = normalize_isbn(&identifier) {
+      fields.push(("isbn".to_string(), isbn));
     }
   }
-  Ok(text)
-}
\ No newline at end of file
+  fields.retain(|(_, value)| !value.trim().is_empty());
+  fields
+}
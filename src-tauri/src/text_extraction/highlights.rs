@@ -0,0 +1,678 @@
+// Extract the highlights and notes of e-readers, one section per highlight
+// Kindle appends every highlight, note and bookmark of every book to a single `My Clippings.txt` on the device.
+// KOReader keeps them per book in a sidecar folder next to it: book.epub -> book.sdr/metadata.epub.lua (a Lua table).
+// Each section carries the book title, author, location and date of its highlight,
+// which are also used to store every highlight as its own item in the `highlight` table
+use std::error::Error;
+use std::fs::read_to_string;
+use std::path::Path;
+use chrono::{Local, NaiveDateTime, TimeZone};
+use crate::text_extraction::{ContentSection, ExtractedContent};
+
+pub const KINDLE_CLIPPINGS_FILE_TYPE: &str = "clippings";
+pub const KOREADER_FILE_TYPE: &str = "koreader";
+const KINDLE_CLIPPINGS_FILE_NAME: &str = "My Clippings.txt";
+const KINDLE_SEPARATOR: &str = "==========";
+
+#[derive(Debug, Clone, Default)]
+struct Highlight {
+  book_title: String,
+  author: String,
+  // "page 12", "Location 123-125" or "page 12, Location 123-125"
+  location: String,
+  chapter: String,
+  // "highlight" or "note"
+  kind: String,
+  text: String,
+  note: String,
+  // UNIX timestamp, 0 if unknown
+  highlighted_at: i64,
+}
+
+// The file type of e-reader highlight files, which are recognised by their name rather than their extension
+pub fn highlights_file_type(path: &Path) -> Option<&'static str> {
+  let file_name = path.file_name()?.to_str()?;
+  if file_name.eq_ignore_ascii_case(KINDLE_CLIPPINGS_FILE_NAME) {
+    return Some(KINDLE_CLIPPINGS_FILE_TYPE);
+  }
+  let folder_name = path.parent()?.file_name()?.to_str()?;
+  if folder_name.ends_with(".sdr") && file_name.starts_with("metadata.") && file_name.ends_with(".lua") {
+    return Some(KOREADER_FILE_TYPE);
+  }
+  None
+}
+
+pub fn extract(file: &String, file_type: &str, _app: &tauri::AppHandle) -> Result<ExtractedContent, Box<dyn Error>> {
+  let source = read_to_string(file)?;
+  let highlights = match file_type {
+    KOREADER_FILE_TYPE => koreader_highlights(&source, Path::new(file))?,
+    _ => kindle_highlights(&source),
+  };
+  let sections: Vec<ContentSection> = highlights
+    .into_iter()
+    .enumerate()
+    .map(|(index, highlight)| highlight_section(highlight, index as i64 + 1))
+    .collect();
+
+  Ok(ExtractedContent {
+    text: sections.iter().map(|section| section.text.as_str()).collect::<Vec<&str>>().join("\n\n"),
+    fields: vec![],
+    sections,
+    language: None,
+  })
+}
+
+fn highlight_section(highlight: Highlight, number: i64) -> ContentSection {
+  let mut fields: Vec<(String, String)> = vec![
+    ("section_title".to_string(), highlight.book_title.clone()),
+    ("author".to_string(), highlight.author.clone()),
+    ("headings".to_string(), highlight.chapter.clone()),
+    ("notes".to_string(), highlight.note.clone()),
+    ("highlight_kind".to_string(), highlight.kind.clone()),
+    ("highlight_location".to_string(), highlight.location.clone()),
+    ("highlight_text".to_string(), highlight.text.clone()),
+  ];
+  if highlight.highlighted_at > 0 {
+    fields.push(("highlighted_at".to_string(), highlight.highlighted_at.to_string()));
+  }
+  fields.retain(|(_, value)| !value.is_empty());
+
+  let text = [highlight.text, highlight.note]
+    .into_iter()
+    .filter(|text| !text.is_empty())
+    .collect::<Vec<String>>()
+    .join("\n");
+  ContentSection {
+    label: if highlight.location.is_empty() { format!("highlight {}", number) } else { highlight.location },
+    number,
+    text,
+    fields,
+  }
+}
+
+// Every clipping is the book line, a line describing the clipping, a blank line and the text, followed by ==========
+//   The Book Title (Author Name)
+//   - Your Highlight on page 12 | Location 123-125 | Added on Sunday, March 3, 2024 10:15:32 PM
+//
+//   The highlighted text
+// Bookmarks have no text and are skipped. Notes are their own clippings at the location where the highlight ends
+fn kindle_highlights(source: &str) -> Vec<Highlight> {
+  let mut highlights: Vec<Highlight> = vec![];
+  for clipping in source.replace("\r\n", "\n").split(KINDLE_SEPARATOR) {
+    let mut lines = clipping.lines().map(|line| line.trim_start_matches('\u{feff}').trim()).skip_while(|line| line.is_empty());
+    let (book_line, description) = match (lines.next(), lines.next()) {
+      (Some(book_line), Some(description)) => (book_line, description),
+      _ => continue,
+    };
+    let text = lines.filter(|line| !line.is_empty()).collect::<Vec<&str>>().join("\n");
+    if text.is_empty() {
+      continue;
+    }
+    let (book_title, author) = split_book_line(book_line);
+    let (kind, location, highlighted_at) = parse_kindle_description(description);
+    let (text, note) = if kind == "note" { ("".to_string(), text) } else { (text, "".to_string()) };
+
+    // extending or shortening a highlight on the Kindle adds a new clipping over the same locations without removing
+    // the old one, so of two overlapping highlights the longer one is kept
+    let overlapping = highlights.iter_mut().rev().find(|previous| previous.book_title == book_title && previous.kind == kind).filter(|previous| {
+      !previous.text.is_empty() && locations_overlap(&previous.location, &location)
+    });
+    if let Some(previous) = overlapping {
+      if text.chars().count() > previous.text.chars().count() {
+        previous.text = text;
+        previous.location = location;
+        previous.highlighted_at = highlighted_at;
+      }
+      continue;
+    }
+    if highlights.iter().any(|previous| previous.book_title == book_title && previous.location == location && previous.text == text && previous.note == note) {
+      continue;
+    }
+    highlights.push(Highlight {
+      book_title,
+      author,
+      location,
+      chapter: "".to_string(),
+      kind: kind.to_string(),
+      text,
+      note,
+      highlighted_at,
+    });
+  }
+  highlights
+}
+
+// "The Book Title (Author Name)" -> ("The Book Title", "Author Name")
+// The author is the last parenthesised part, titles may have their own parentheses
+fn split_book_line(book_line: &str) -> (String, String) {
+  if let (true, Some(open)) = (book_line.ends_with(')'), book_line.rfind('(')) {
+    let title = book_line[..open].trim();
+    let author = book_line[open + 1..book_line.len() - 1].trim();
+    if !title.is_empty() {
+      return (title.to_string(), author.to_string());
+    }
+  }
+  (book_line.to_string(), "".to_string())
+}
+
+// "- Your Highlight on page 12 | Location 123-125 | Added on Sunday, March 3, 2024 10:15:32 PM"
+// -> ("highlight", "page 12, Location 123-125", timestamp)
+// Devices set to other languages word this differently, so the location is taken from the word before the first
+// number of the first part, and a date that can not be read is 0
+fn parse_kindle_description(description: &str) -> (&'static str, String, i64) {
+  let parts: Vec<&str> = description.trim_start_matches('-').split('|').map(|part| part.trim()).collect();
+  let first = parts.first().copied().unwrap_or("").to_lowercase();
+  let kind = if first.contains("note") || first.contains("notiz") {
+    "note"
+  } else {
+    "highlight"
+  };
+
+  let mut locations: Vec<String> = vec![];
+  let mut highlighted_at = 0;
+  for (index, part) in parts.iter().enumerate() {
+    if index > 0 && index == parts.len() - 1 {
+      highlighted_at = parse_kindle_date(part);
+      if highlighted_at > 0 || !part.chars().any(|c| c.is_ascii_digit()) || part.contains(':') {
+        continue;
+      }
+    }
+    let digit = match part.find(|c: char| c.is_ascii_digit()) {
+      Some(digit) => digit,
+      None => continue,
+    };
+    let word_start = if index == 0 {
+      // the whitespace can be wider than a byte, e.g. a no-break space
+      part[..digit]
+        .trim_end()
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map(|(space, c)| space + c.len_utf8())
+        .unwrap_or(0)
+    } else {
+      0
+    };
+    locations.push(part[word_start..].trim().to_string());
+  }
+  (kind, locations.join(", "), highlighted_at)
+}
+
+// "page 12, Location 123-125" and "Location 124-130" overlap, the last range of a location is its most precise one
+fn locations_overlap(first: &str, second: &str) -> bool {
+  match (location_range(first), location_range(second)) {
+    (Some((first_start, first_end)), Some((second_start, second_end))) => first_start <= second_end && second_start <= first_end,
+    _ => false,
+  }
+}
+
+// "Location 123-125" -> (123, 125), "page 12" -> (12, 12)
+fn location_range(location: &str) -> Option<(i64, i64)> {
+  let last = location.rsplit(',').next()?;
+  let digits = last.find(|c: char| c.is_ascii_digit())?;
+  let mut bounds = last[digits..].split('-').map(|bound| bound.trim().parse::<i64>().ok());
+  let start = bounds.next()??;
+  let end = match bounds.next() {
+    Some(end) => end?,
+    None => start,
+  };
+  Some((start, end.max(start)))
+}
+
+fn parse_kindle_date(part: &str) -> i64 {
+  let date = part.trim_start_matches("Added on").trim();
+  let formats = ["%A, %B %d, %Y %I:%M:%S %p", "%A, %B %d, %Y, %I:%M %p", "%A, %d %B %Y %H:%M:%S", "%A, %d %B %Y %I:%M:%S %p"];
+  formats
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+    .and_then(|time| Local.from_local_datetime(&time).earliest())
+    .map(|time| time.timestamp())
+    .unwrap_or(0)
+}
+
+// KOReader 2024.x stores an `annotations` list, older versions a `highlight` table by page
+// (the notes of old highlights are the `text` of the bookmark with the same datetime)
+fn koreader_highlights(source: &str, path: &Path) -> Result<Vec<Highlight>, Box<dyn Error>> {
+  let settings = LuaParser::new(source).parse_file().ok_or("Could not read the KOReader metadata")?;
+  let doc_props = settings.get("doc_props");
+  let stats = settings.get("stats");
+  let book_title = [doc_props.and_then(|props| props.get_str("title")), stats.and_then(|stats| stats.get_str("title"))]
+    .into_iter()
+    .flatten()
+    .find(|title| !title.trim().is_empty())
+    .map(|title| title.trim().to_string())
+    // book.epub -> book.sdr
+    .or_else(|| path.parent()?.file_name()?.to_str().map(|folder| folder.trim_end_matches(".sdr").to_string()))
+    .unwrap_or_default();
+  let author = [doc_props.and_then(|props| props.get_str("authors")), stats.and_then(|stats| stats.get_str("authors"))]
+    .into_iter()
+    .flatten()
+    .find(|authors| !authors.trim().is_empty())
+    .map(|authors| authors.lines().map(|author| author.trim()).filter(|author| !author.is_empty()).collect::<Vec<&str>>().join("; "))
+    .unwrap_or_default();
+
+  let highlight = |entry: &LuaValue, page: Option<i64>, note: String| -> Option<Highlight> {
+    let text = entry.get_str("text").unwrap_or("").trim().to_string();
+    let note = entry.get_str("note").map(|note| note.trim().to_string()).unwrap_or(note);
+    if text.is_empty() && note.is_empty() {
+      return None;
+    }
+    let page = entry.get("pageno").and_then(LuaValue::as_number).or(page);
+    Some(Highlight {
+      book_title: book_title.clone(),
+      author: author.clone(),
+      location: page.map(|page| format!("page {}", page)).unwrap_or_default(),
+      chapter: entry.get_str("chapter").unwrap_or("").trim().to_string(),
+      kind: if text.is_empty() { "note".to_string() } else { "highlight".to_string() },
+      text,
+      note,
+      highlighted_at: entry.get_str("datetime").map(parse_koreader_date).unwrap_or(0),
+    })
+  };
+
+  let mut highlights: Vec<Highlight> = vec![];
+  if let Some(annotations) = settings.get("annotations") {
+    highlights.extend(annotations.values().filter_map(|entry| highlight(entry, None, "".to_string())));
+  } else if let Some(pages) = settings.get("highlight") {
+    let bookmarks: Vec<&LuaValue> = settings.get("bookmarks").map(|bookmarks| bookmarks.values().collect()).unwrap_or_default();
+    let mut pages: Vec<(Option<i64>, &LuaValue)> = pages.entries().map(|(page, entries)| (page.as_number(), entries)).collect();
+    pages.sort_by_key(|(page, _)| *page);
+    for (page, entries) in pages {
+      for entry in entries.values() {
+        // unless it was edited, the text of a bookmark is "Page 12 ..." rather than a note
+        let note = bookmarks
+          .iter()
+          .find(|bookmark| bookmark.get_str("datetime").is_some() && bookmark.get_str("datetime") == entry.get_str("datetime"))
+          .and_then(|bookmark| bookmark.get_str("text"))
+          .map(|text| text.trim())
+          .filter(|text| !text.starts_with("Page ") && Some(*text) != entry.get_str("text").map(|text| text.trim()))
+          .unwrap_or("")
+          .to_string();
+        highlights.extend(highlight(entry, page, note));
+      }
+    }
+  }
+  Ok(highlights)
+}
+
+// "2024-03-03 22:15:32" in local time
+fn parse_koreader_date(datetime: &str) -> i64 {
+  NaiveDateTime::parse_from_str(datetime.trim(), "%Y-%m-%d %H:%M:%S")
+    .ok()
+    .and_then(|time| Local.from_local_datetime(&time).earliest())
+    .map(|time| time.timestamp())
+    .unwrap_or(0)
+}
+
+// The values KOReader writes into its settings files: `return { ["key"] = "value", [1] = 12, ... }`
+#[derive(Debug, Clone)]
+enum LuaValue {
+  Nil,
+  Bool(bool),
+  Number(f64),
+  Str(String),
+  Table(Vec<(LuaValue, LuaValue)>),
+}
+
+impl LuaValue {
+  fn get(&self, key: &str) -> Option<&LuaValue> {
+    match self {
+      LuaValue::Table(entries) => entries.iter().find(|(entry_key, _)| matches!(entry_key, LuaValue::Str(name) if name == key)).map(|(_, value)| value),
+      _ => None,
+    }
+  }
+
+  fn get_str(&self, key: &str) -> Option<&str> {
+    match self.get(key) {
+      Some(LuaValue::Str(value)) => Some(value.as_str()),
+      _ => None,
+    }
+  }
+
+  fn as_number(&self) -> Option<i64> {
+    match self {
+      LuaValue::Number(number) => Some(*number as i64),
+      LuaValue::Str(number) => number.trim().parse::<i64>().ok(),
+      _ => None,
+    }
+  }
+
+  fn entries(&self) -> impl Iterator<Item = &(LuaValue, LuaValue)> {
+    let entries: &[(LuaValue, LuaValue)] = match self {
+      LuaValue::Table(entries) => entries,
+      _ => &[],
+    };
+    entries.iter()
+  }
+
+  // the values of a list (`[1] = ..., [2] = ...`) in the order of their keys
+  fn values(&self) -> impl Iterator<Item = &LuaValue> {
+    let mut entries: Vec<&(LuaValue, LuaValue)> = self.entries().collect();
+    entries.sort_by_key(|(key, _)| key.as_number().unwrap_or(i64::MAX));
+    entries.into_iter().map(|(_, value)| value)
+  }
+}
+
+struct LuaParser<'a> {
+  chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl<'a> LuaParser<'a> {
+  fn new(source: &'a str) -> Self {
+    LuaParser { chars: source.chars().peekable() }
+  }
+
+  // "-- comment\nreturn { ... }"
+  fn parse_file(&mut self) -> Option<LuaValue> {
+    self.skip_whitespace();
+    let keyword = self.identifier();
+    if keyword != "return" {
+      return None;
+    }
+    self.value()
+  }
+
+  fn skip_whitespace(&mut self) {
+    loop {
+      match self.chars.peek() {
+        Some(c) if c.is_whitespace() => {
+          self.chars.next();
+        }
+        Some('-') => {
+          let mut lookahead = self.chars.clone();
+          lookahead.next();
+          if lookahead.peek() != Some(&'-') {
+            return;
+          }
+          for c in self.chars.by_ref() {
+            if c == '\n' {
+              break;
+            }
+          }
+        }
+        _ => return,
+      }
+    }
+  }
+
+  fn identifier(&mut self) -> String {
+    let mut identifier = String::new();
+    while let Some(c) = self.chars.peek().copied().filter(|c| c.is_alphanumeric() || *c == '_') {
+      identifier.push(c);
+      self.chars.next();
+    }
+    identifier
+  }
+
+  fn value(&mut self) -> Option<LuaValue> {
+    self.skip_whitespace();
+    match self.chars.peek()? {
+      '{' => self.table(),
+      '"' | '\'' => self.string().map(LuaValue::Str),
+      '[' => self.long_string().map(LuaValue::Str),
+      c if c.is_ascii_digit() || *c == '-' || *c == '.' => self.number(),
+      _ => match self.identifier().as_str() {
+        "true" => Some(LuaValue::Bool(true)),
+        "false" => Some(LuaValue::Bool(false)),
+        "nil" => Some(LuaValue::Nil),
+        _ => None,
+      },
+    }
+  }
+
+  fn table(&mut self) -> Option<LuaValue> {
+    self.chars.next();
+    let mut entries: Vec<(LuaValue, LuaValue)> = vec![];
+    let mut position = 1.0;
+    loop {
+      self.skip_whitespace();
+      match self.chars.peek()? {
+        '}' => {
+          self.chars.next();
+          return Some(LuaValue::Table(entries));
+        }
+        ',' | ';' => {
+          self.chars.next();
+        }
+        '[' => {
+          // [[long string]] as a list value, otherwise [key] =
+          let mut lookahead = self.chars.clone();
+          lookahead.next();
+          if matches!(lookahead.peek(), Some('[') | Some('=')) {
+            let value = self.value()?;
+            entries.push((LuaValue::Number(position), value));
+            position += 1.0;
+            continue;
+          }
+          self.chars.next();
+          let key = self.value()?;
+          self.skip_whitespace();
+          if self.chars.next()? != ']' {
+            return None;
+          }
+          self.expect_equals()?;
+          let value = self.value()?;
+          entries.push((key, value));
+        }
+        c if c.is_alphabetic() || *c == '_' => {
+          let checkpoint = self.chars.clone();
+          let name = self.identifier();
+          self.skip_whitespace();
+          if self.chars.peek() == Some(&'=') {
+            self.expect_equals()?;
+            let value = self.value()?;
+            entries.push((LuaValue::Str(name), value));
+          } else {
+            // a bare value such as true or nil
+            self.chars = checkpoint;
+            let value = self.value()?;
+            entries.push((LuaValue::Number(position), value));
+            position += 1.0;
+          }
+        }
+        _ => {
+          let value = self.value()?;
+          entries.push((LuaValue::Number(position), value));
+          position += 1.0;
+        }
+      }
+    }
+  }
+
+  fn expect_equals(&mut self) -> Option<()> {
+    self.skip_whitespace();
+    (self.chars.next()? == '=').then_some(())
+  }
+
+  // "text" with \n, \t, \", \\, \<newline> and \ddd escapes (\ddd are bytes of UTF-8 text)
+  fn string(&mut self) -> Option<String> {
+    let quote = self.chars.next()?;
+    let mut bytes: Vec<u8> = vec![];
+    let push = |bytes: &mut Vec<u8>, c: char| bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+    loop {
+      match self.chars.next()? {
+        c if c == quote => return Some(String::from_utf8_lossy(&bytes).to_string()),
+        '\\' => match self.chars.next()? {
+          'n' | '\n' => push(&mut bytes, '\n'),
+          't' => push(&mut bytes, '\t'),
+          'r' => push(&mut bytes, '\r'),
+          c if c.is_ascii_digit() => {
+            let mut digits = c.to_string();
+            while digits.len() < 3 {
+              match self.chars.peek().copied().filter(|c| c.is_ascii_digit()) {
+                Some(digit) => {
+                  digits.push(digit);
+                  self.chars.next();
+                }
+                None => break,
+              }
+            }
+            if let Ok(byte) = digits.parse::<u8>() {
+              bytes.push(byte);
+            }
+          }
+          c => push(&mut bytes, c),
+        },
+        c => push(&mut bytes, c),
+      }
+    }
+  }
+
+  // [[text]] or [==[text]==]
+  fn long_string(&mut self) -> Option<String> {
+    self.chars.next();
+    let mut level = 0;
+    while self.chars.peek() == Some(&'=') {
+      level += 1;
+      self.chars.next();
+    }
+    if self.chars.next()? != '[' {
+      return None;
+    }
+    let closing = format!("]{}]", "=".repeat(level));
+    let mut string = String::new();
+    loop {
+      string.push(self.chars.next()?);
+      if string.ends_with(&closing) {
+        string.truncate(string.len() - closing.len());
+        return Some(string.strip_prefix('\n').map(|string| string.to_string()).unwrap_or(string));
+      }
+    }
+  }
+
+  fn number(&mut self) -> Option<LuaValue> {
+    let mut number = String::new();
+    while let Some(c) = self.chars.peek().copied().filter(|c| c.is_ascii_alphanumeric() || *c == '.' || *c == '-' || *c == '+') {
+      number.push(c);
+      self.chars.next();
+    }
+    number.parse::<f64>().ok().map(LuaValue::Number)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn local_timestamp(datetime: &str) -> i64 {
+    let time = NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M:%S").unwrap();
+    Local.from_local_datetime(&time).earliest().unwrap().timestamp()
+  }
+
+  // The settings files in tests/fixtures/koreader are laid out the way KOReader writes them
+  fn koreader_fixture(name: &str) -> Vec<Highlight> {
+    let file = format!("{}/tests/fixtures/koreader/{}", env!("CARGO_MANIFEST_DIR"), name);
+    koreader_highlights(&read_to_string(&file).unwrap(), Path::new(&file)).unwrap()
+  }
+
+  fn clipping(book_line: &str, description: &str, text: &str) -> String {
+    format!("{}\r\n- {} | Added on Sunday, March 3, 2024 10:15:32 PM\r\n\r\n{}\r\n{}\r\n", book_line, description, text, KINDLE_SEPARATOR)
+  }
+
+  #[test]
+  fn lua_values() {
+    let source = r#"-- comment
+      return {
+        ["title"] = "caf\195\169 \"quoted\" \\ back\
+slash",
+        name = 'single \'quote\'',
+        ["number"] = -2.5,
+        ["list"] = {
+          [[long
+string]],
+          [==[with ]] inside]==],
+          true, nil;
+          { 1, 2; 3 },
+        },
+        ["nested"] = { ["depth"] = { ["x"] = 1e2 } },
+      }"#;
+    let value = LuaParser::new(source).parse_file().unwrap();
+    assert_eq!(value.get_str("title"), Some("café \"quoted\" \\ back\nslash"));
+    assert_eq!(value.get_str("name"), Some("single 'quote'"));
+    assert!(matches!(value.get("number"), Some(LuaValue::Number(number)) if *number == -2.5));
+    let list: Vec<&LuaValue> = value.get("list").unwrap().values().collect();
+    assert!(matches!(list[0], LuaValue::Str(text) if text == "long\nstring"));
+    assert!(matches!(list[1], LuaValue::Str(text) if text == "with ]] inside"));
+    assert!(matches!(list[2], LuaValue::Bool(true)));
+    assert!(matches!(list[3], LuaValue::Nil));
+    assert_eq!(list[4].values().filter_map(LuaValue::as_number).collect::<Vec<i64>>(), vec![1, 2, 3]);
+    assert_eq!(value.get("nested").and_then(|nested| nested.get("depth")).and_then(|depth| depth.get("x")).and_then(LuaValue::as_number), Some(100));
+  }
+
+  #[test]
+  fn lua_files_that_are_not_settings() {
+    assert!(LuaParser::new("local x = { 1 }").parse_file().is_none());
+    assert!(LuaParser::new("return { [\"a\"] = 1").parse_file().is_none());
+    assert!(LuaParser::new("return { [\"a\"] 1 }").parse_file().is_none());
+    assert!(LuaParser::new("return \"unterminated").parse_file().is_none());
+  }
+
+  #[test]
+  fn koreader_annotations() {
+    let highlights = koreader_fixture("tale.sdr/metadata.epub.lua");
+    // the last annotation is a bookmark without text or note
+    assert_eq!(highlights.len(), 2);
+    let first = &highlights[0];
+    assert_eq!(first.book_title, "A Tale of Two Cities");
+    assert_eq!(first.author, "Charles Dickens");
+    assert_eq!(first.location, "page 12");
+    assert_eq!(first.chapter, "Book the First");
+    assert_eq!(first.kind, "highlight");
+    assert_eq!(first.text, "It was the best of times,\nit was the worst of times");
+    assert_eq!(first.note, "");
+    assert_eq!(first.highlighted_at, local_timestamp("2024-03-03 22:15:32"));
+    assert_eq!(highlights[1].location, "page 27");
+    assert_eq!(highlights[1].note, "Compare with the \"Carlyle\" passage — p. 40");
+  }
+
+  #[test]
+  fn koreader_highlights_by_page() {
+    let highlights = koreader_fixture("field-notes.sdr/metadata.pdf.lua");
+    assert_eq!(highlights.len(), 2);
+    // no title in the file, so it comes from the folder
+    assert_eq!(highlights[0].book_title, "field-notes");
+    assert_eq!(highlights[0].author, "Dana Whitfield; Sam Okafor");
+    assert_eq!(highlights[0].location, "page 3");
+    assert_eq!(highlights[0].chapter, "Methods");
+    assert_eq!(highlights[0].text, "Rainfall was twice the average");
+    // the text of an unedited bookmark is not a note
+    assert_eq!(highlights[0].note, "");
+    assert_eq!(highlights[1].location, "page 15");
+    assert_eq!(highlights[1].note, "Ask Dana about the pH readings");
+    assert_eq!(highlights[1].highlighted_at, local_timestamp("2023-05-01 09:12:44"));
+  }
+
+  #[test]
+  fn kindle_keeps_the_longer_of_overlapping_highlights() {
+    let source = [
+      clipping("Walden (Henry David Thoreau)", "Your Highlight on page 12 | Location 180-182", "I went to the woods"),
+      clipping("Walden (Henry David Thoreau)", "Your Highlight on page 12 | Location 180-184", "I went to the woods because I wished to live deliberately"),
+      clipping("Walden (Henry David Thoreau)", "Your Highlight on page 12 | Location 181-183", "the woods because"),
+      clipping("Walden (Henry David Thoreau)", "Your Note on page 12 | Location 184", "the opening"),
+    ]
+    .concat();
+    let highlights = kindle_highlights(&source);
+    assert_eq!(highlights.len(), 2);
+    assert_eq!(highlights[0].text, "I went to the woods because I wished to live deliberately");
+    assert_eq!(highlights[0].location, "page 12, Location 180-184");
+    assert_eq!(highlights[0].author, "Henry David Thoreau");
+    assert_eq!(highlights[1].kind, "note");
+    assert_eq!(highlights[1].note, "the opening");
+  }
+
+  #[test]
+  fn kindle_keeps_separate_highlights_of_the_same_text() {
+    let source = [
+      clipping("Walden (Henry David Thoreau)", "Your Highlight on page 12 | Location 180-182", "Simplify, simplify"),
+      clipping("Walden (Henry David Thoreau)", "Your Highlight on page 90 | Location 1370-1371", "simplify"),
+      clipping("Walden (Henry David Thoreau)", "Your Highlight on page 90 | Location 1372-1373", "Simplify, simplify"),
+    ]
+    .concat();
+    let highlights = kindle_highlights(&source);
+    assert_eq!(highlights.iter().map(|highlight| highlight.location.as_str()).collect::<Vec<&str>>(), vec![
+      "page 12, Location 180-182",
+      "page 90, Location 1370-1371",
+      "page 90, Location 1372-1373",
+    ]);
+  }
+}
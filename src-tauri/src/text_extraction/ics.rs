@@ -0,0 +1,124 @@
+// Extract the events of an iCalendar (.ics) file, one section per event
+// Each section is labelled with the start of the event and carries its summary, location, attendees and start/end,
+// which are also used to store every event as its own item in the `calendar_event` table
+// Recurring events are one item each, their repetitions are not expanded
+use std::error::Error;
+use std::fs::read_to_string;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use crate::text_extraction::vobject::{components, unescape_text, ContentLine};
+use crate::text_extraction::{ContentSection, ExtractedContent};
+
+pub fn extract(file: &String, _app: &tauri::AppHandle) -> Result<ExtractedContent, Box<dyn Error>> {
+  let source = read_to_string(file)?;
+  let sections: Vec<ContentSection> = components(&source, "VEVENT")
+    .into_iter()
+    .enumerate()
+    .map(|(index, lines)| event_section(&lines, index as i64 + 1))
+    .collect();
+
+  Ok(ExtractedContent {
+    text: sections.iter().map(|section| section.text.as_str()).collect::<Vec<&str>>().join("\n\n"),
+    fields: vec![],
+    sections,
+    language: None,
+  })
+}
+
+fn event_section(lines: &Vec<ContentLine>, number: i64) -> ContentSection {
+  let value = |name: &str| lines.iter().find(|line| line.name == name).map(|line| unescape_text(&line.value)).unwrap_or_default();
+  let summary = value("SUMMARY");
+  let location = value("LOCATION");
+  let description = value("DESCRIPTION");
+  let start = lines.iter().find(|line| line.name == "DTSTART").and_then(parse_time);
+  let end = lines.iter().find(|line| line.name == "DTEND").and_then(parse_time);
+  // the organizer is listed first, then everyone who was invited
+  let attendees: Vec<String> = lines
+    .iter()
+    .filter(|line| line.name == "ORGANIZER" || line.name == "ATTENDEE")
+    .map(person_name)
+    .filter(|attendee| !attendee.is_empty())
+    .fold(vec![], |mut attendees, attendee| {
+      if !attendees.contains(&attendee) {
+        attendees.push(attendee);
+      }
+      attendees
+    });
+
+  let when = match (start, end) {
+    (Some(start), Some(end)) => format!("{} – {}", format_time(start), format_time(end)),
+    (Some(start), None) => format_time(start),
+    _ => "".to_string(),
+  };
+  let mut fields: Vec<(String, String)> = vec![
+    ("section_title".to_string(), summary.clone()),
+    ("location".to_string(), location.clone()),
+    ("starts_at".to_string(), start.map(|(timestamp, _)| timestamp.to_string()).unwrap_or_default()),
+    ("ends_at".to_string(), end.map(|(timestamp, _)| timestamp.to_string()).unwrap_or_default()),
+  ];
+  for attendee in &attendees {
+    fields.push(("attendees".to_string(), attendee.clone()));
+  }
+  fields.retain(|(_, value)| !value.is_empty());
+
+  let text = [summary, when, location, attendees.join(", "), description]
+    .into_iter()
+    .filter(|line| !line.is_empty())
+    .collect::<Vec<String>>()
+    .join("\n");
+  ContentSection {
+    label: start.map(format_time).unwrap_or_else(|| format!("event {}", number)),
+    number,
+    text,
+    fields,
+  }
+}
+
+// ATTENDEE;CN=Priya Shah:mailto:priya@example.com -> "Priya Shah <priya@example.com>"
+fn person_name(line: &ContentLine) -> String {
+  let email = line.value.trim().trim_start_matches("mailto:").trim_start_matches("MAILTO:").to_string();
+  match line.param("CN").map(|name| name.trim()).filter(|name| !name.is_empty() && *name != email) {
+    Some(name) if !email.is_empty() => format!("{} <{}>", name, email),
+    Some(name) => name.to_string(),
+    None => email,
+  }
+}
+
+// (UNIX timestamp, is all-day)
+// 20240315T090000Z is UTC. 20240315T090000 with a TZID is in that time zone, a floating one or one whose TZID isn't an
+// IANA name (e.g. the Windows names of Outlook) is read as local time. 20240315 (VALUE=DATE) is an all-day date
+fn parse_time(line: &ContentLine) -> Option<(i64, bool)> {
+  let value = line.value.trim();
+  if let Some(utc) = value.strip_suffix('Z') {
+    let time = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?;
+    return Some((Utc.from_utc_datetime(&time).timestamp(), false));
+  }
+  if let Ok(time) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+    if let Some(time_zone) = line.param("TZID").and_then(time_zone) {
+      return time_zone.from_local_datetime(&time).earliest().map(|time| (time.timestamp(), false));
+    }
+    return Local.from_local_datetime(&time).earliest().map(|time| (time.timestamp(), false));
+  }
+  let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+  Local.from_local_datetime(&date.and_hms_opt(0, 0, 0)?).earliest().map(|time| (time.timestamp(), true))
+}
+
+// "Europe/Berlin", also when quoted or behind a prefix like "/mozilla.org/20050126_1/Europe/Berlin"
+fn time_zone(tzid: &str) -> Option<Tz> {
+  let tzid = tzid.trim().trim_matches('"');
+  let parts: Vec<&str> = tzid.split('/').filter(|part| !part.is_empty()).collect();
+  (0..parts.len()).find_map(|start| parts[start..].join("/").parse::<Tz>().ok())
+}
+
+// "2024-03-15 09:00" in local time, or "2024-03-15" for all-day events
+fn format_time((timestamp, all_day): (i64, bool)) -> String {
+  let time: DateTime<Local> = match Local.timestamp_opt(timestamp, 0).single() {
+    Some(time) => time,
+    None => return "".to_string(),
+  };
+  if all_day {
+    time.format("%Y-%m-%d").to_string()
+  } else {
+    time.format("%Y-%m-%d %H:%M").to_string()
+  }
+}
@@ -4,7 +4,7 @@ use tauri_plugin_shell::{ShellExt, process::CommandEvent};
 use crate::housekeeping::get_app_directory;
 use crate::text_extraction::txt;
 #[cfg(target_os = "windows")]
-use crate::utils::install_poppler_from_github;
+use crate::utils::local_poppler_path;
 
 pub async fn extract(file: &String, app: &tauri::AppHandle) -> Result<String, Box<dyn Error>> {
   // check if the file contains svg in its name
@@ -51,14 +51,8 @@ pub async fn extract(file: &String, app: &tauri::AppHandle) -> Result<String, Bo
     #[cfg(target_os = "windows")]
     {
       let output_path = format!("{}\\temp_output.txt", app_directory);
-      let poppler_path = format!("{}\\poppler-24.02.0\\Library\\bin", app_directory);
-      let poppler_executable = format!("{}\\pdftoppm.exe", &poppler_path);
-
-      let poppler_exists = std::path::Path::new(&poppler_executable).exists();
-      println!("poppler exists: {}", poppler_exists);
-      if !poppler_exists {
-        let _ = install_poppler_from_github().await?;
-      }
+      // images don't go through poppler, winocr only needs a folder for its argument
+      let poppler_path = local_poppler_path().unwrap_or_else(|| format!("{}\\poppler-24.02.0\\Library\\bin", app_directory));
 
       // run winocr on the file
       let sidecar_command = app.shell().sidecar("winocr").unwrap().args(["-i", file, "-o", output_path.as_str(), "--poppler-path", poppler_path.as_str()]);
@@ -0,0 +1,85 @@
+// Extract text from a Jupyter notebook (.ipynb), one section per cell
+// Markdown and code cells are the body. Cell outputs (printed text, results) go into the `outputs` field only,
+// so a long dataframe dump doesn't drown the notes and code around it.
+use std::error::Error;
+use std::fs::read_to_string;
+use serde_json::Value;
+use crate::text_extraction::{ContentSection, ExtractedContent};
+use crate::text_extraction::code::extract_from_source;
+
+pub fn extract(file: &String, _app: &tauri::AppHandle) -> Result<ExtractedContent, Box<dyn Error>> {
+  let notebook: Value = serde_json::from_str(&read_to_string(file)?)?;
+  let language = notebook_language(&notebook);
+
+  let mut sections: Vec<ContentSection> = vec![];
+  let cells = notebook["cells"].as_array().cloned().unwrap_or_default();
+  for (index, cell) in cells.iter().enumerate() {
+    let source = joined_text(&cell["source"]);
+    let mut section = match cell["cell_type"].as_str() {
+      Some("markdown") => ContentSection {
+        text: source,
+        ..Default::default()
+      },
+      Some("code") => {
+        let content = extract_from_source(&source, &language);
+        ContentSection {
+          text: content.text,
+          fields: content.sections.into_iter().flat_map(|section| section.fields).collect(),
+          ..Default::default()
+        }
+      }
+      _ => continue,
+    };
+    let outputs = cell_outputs(cell);
+    if !outputs.is_empty() {
+      section.fields.push(("outputs".to_string(), outputs));
+    }
+    if section.text.trim().is_empty() && section.fields.is_empty() {
+      continue;
+    }
+    section.label = format!("cell {}", index + 1);
+    section.number = index as i64 + 1;
+    sections.push(section);
+  }
+
+  Ok(ExtractedContent {
+    text: sections.iter().map(|section| section.text.as_str()).collect::<Vec<&str>>().join("\n"),
+    fields: vec![("code_language".to_string(), language)],
+    sections,
+    language: None,
+  })
+}
+
+// Language of the kernel, e.g. "python" or "rust" (evcxr)
+fn notebook_language(notebook: &Value) -> String {
+  notebook["metadata"]["language_info"]["name"]
+    .as_str()
+    .or(notebook["metadata"]["kernelspec"]["language"].as_str())
+    .unwrap_or("python")
+    .to_lowercase()
+}
+
+// Notebook text is stored either as a string or as a list of lines
+fn joined_text(value: &Value) -> String {
+  match value {
+    Value::String(text) => text.clone(),
+    Value::Array(lines) => lines.iter().filter_map(|line| line.as_str()).collect::<Vec<&str>>().join(""),
+    _ => "".to_string(),
+  }
+}
+
+// Text of stream outputs (stdout/stderr), results and errors. Images and HTML are skipped
+fn cell_outputs(cell: &Value) -> String {
+  let outputs = cell["outputs"].as_array().cloned().unwrap_or_default();
+  let mut texts: Vec<String> = vec![];
+  for output in outputs {
+    match output["output_type"].as_str() {
+      Some("stream") => texts.push(joined_text(&output["text"])),
+      Some("execute_result") | Some("display_data") => texts.push(joined_text(&output["data"]["text/plain"])),
+      Some("error") => texts.push(format!("{}: {}", output["ename"].as_str().unwrap_or(""), output["evalue"].as_str().unwrap_or(""))),
+      _ => {}
+    }
+  }
+  texts.retain(|text| !text.trim().is_empty());
+  texts.join("\n")
+}
@@ -0,0 +1,169 @@
+// Extract text from a .md file
+// YAML (---) or TOML (+++) front-matter is parsed into fields instead of being indexed as text:
+// title -> title, tags/keywords -> tags, aliases -> aliases, author -> author
+// Headings, [[wiki-links]] and inline #tags are also recorded as fields so they can be searched and boosted
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::read_to_string;
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::text_extraction::ExtractedContent;
+
+lazy_static! {
+  static ref HEADING: Regex = Regex::new(r"^#{1,6}\s+(.+?)\s*#*\s*$").unwrap();
+  static ref WIKI_LINK: Regex = Regex::new(r"!?\[\[([^\]|#]*)(#[^\]|]*)?(\|([^\]]*))?\]\]").unwrap();
+  // a tag needs at least one character that is not a digit, so #1 or #2024 are not tags
+  static ref INLINE_TAG: Regex = Regex::new(r"(?:^|\s)#([\p{L}\p{N}_/-]*[\p{L}_/-][\p{L}\p{N}_/-]*)").unwrap();
+}
+
+pub fn extract(filepath: &String, _app: &tauri::AppHandle) -> Result<ExtractedContent, Box<dyn Error>> {
+  let file_contents = read_to_string(filepath)?;
+  let (front_matter, markdown) = split_front_matter(&file_contents);
+
+  let mut fields: Vec<(String, String)> = vec![];
+  for (key, values) in front_matter {
+    let field_name = match key.to_lowercase().as_str() {
+      "title" => "title",
+      "tags" | "tag" | "keywords" => "tags",
+      "aliases" | "alias" => "aliases",
+      "author" | "authors" => "author",
+      _ => continue,
+    };
+    for value in values {
+      // tags may be written with a leading # in front-matter too
+      let value = if field_name == "tags" { value.trim_start_matches("#").to_string() } else { value };
+      fields.push((field_name.to_string(), value));
+    }
+  }
+
+  let mut lines: Vec<String> = vec![];
+  let mut inside_code_block = false;
+  for line in markdown.lines() {
+    if line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~") {
+      inside_code_block = !inside_code_block;
+      lines.push(line.to_string());
+      continue;
+    }
+    if inside_code_block {
+      lines.push(line.to_string());
+      continue;
+    }
+    if let Some(heading) = HEADING.captures(line) {
+      fields.push(("headings".to_string(), heading[1].to_string()));
+    } else {
+      for tag in INLINE_TAG.captures_iter(line) {
+        fields.push(("tags".to_string(), tag[1].to_string()));
+      }
+    }
+    for link in WIKI_LINK.captures_iter(line) {
+      let target = link[1].trim();
+      if !target.is_empty() {
+        fields.push(("links".to_string(), target.to_string()));
+      }
+    }
+    // [[target|shown text]] is indexed as the text a reader sees
+    let line = WIKI_LINK.replace_all(line, |link: &regex::Captures| {
+      link.get(4).map(|shown| shown.as_str()).unwrap_or(&link[1]).to_string()
+    });
+    lines.push(line.to_string());
+  }
+  let mut seen: HashSet<(String, String)> = HashSet::new();
+  fields.retain(|field| !field.1.trim().is_empty() && seen.insert(field.clone()));
+
+  Ok(ExtractedContent {
+    text: lines.join("\n\n"),
+    fields,
+    sections: vec![],
+    language: None,
+  })
+}
+
+// Returns the front-matter as (key, values) pairs and the markdown after it
+fn split_front_matter(contents: &str) -> (Vec<(String, Vec<String>)>, &str) {
+  let contents = contents.trim_start_matches('\u{feff}');
+  let delimiter = match contents.lines().next().map(|line| line.trim_end()) {
+    Some("---") => "---",
+    Some("+++") => "+++",
+    _ => return (vec![], contents),
+  };
+  let after_opening = &contents[contents.find('\n').map(|index| index + 1).unwrap_or(contents.len())..];
+
+  // find the closing delimiter line (YAML may also end with ...)
+  let mut offset = 0;
+  for line in after_opening.split_inclusive('\n') {
+    let trimmed = line.trim_end();
+    if trimmed == delimiter || (delimiter == "---" && trimmed == "...") {
+      let front_matter = &after_opening[..offset];
+      let markdown = &after_opening[offset + line.len()..];
+      let values = if delimiter == "---" {
+        parse_yaml_front_matter(front_matter)
+      } else {
+        parse_toml_front_matter(front_matter)
+      };
+      return (values, markdown);
+    }
+    offset += line.len();
+  }
+  // no closing delimiter, so this was not front-matter
+  (vec![], contents)
+}
+
+// Only the flat subset of YAML that notes use: `key: value`, `key: [a, b]` and block lists (`- a`)
+fn parse_yaml_front_matter(front_matter: &str) -> Vec<(String, Vec<String>)> {
+  let mut values: Vec<(String, Vec<String>)> = vec![];
+  for line in front_matter.lines() {
+    if line.trim().is_empty() || line.trim_start().starts_with("#") {
+      continue;
+    }
+    let is_indented = line.starts_with(' ') || line.starts_with('\t') || line.starts_with('-');
+    if is_indented {
+      if let (Some(item), Some((_, list))) = (line.trim().strip_prefix("-"), values.last_mut()) {
+        list.push(unquote(item));
+      }
+      continue;
+    }
+    if let Some((key, value)) = line.split_once(":") {
+      let value = value.trim();
+      let list = if value.is_empty() {
+        vec![]
+      } else if value.starts_with("[") && value.ends_with("]") {
+        value[1..value.len() - 1].split(",").map(unquote).collect()
+      } else if key.trim() == "tags" || key.trim() == "aliases" {
+        // Obsidian also accepts comma separated tags and aliases
+        value.split(",").map(unquote).collect()
+      } else {
+        vec![unquote(value)]
+      };
+      values.push((key.trim().to_string(), list));
+    }
+  }
+  values
+}
+
+fn parse_toml_front_matter(front_matter: &str) -> Vec<(String, Vec<String>)> {
+  let table = match front_matter.parse::<toml::Table>() {
+    Ok(table) => table,
+    Err(e) => {
+      println!("Error parsing TOML front-matter: {}", e);
+      return vec![];
+    }
+  };
+  table
+    .into_iter()
+    .map(|(key, value)| {
+      let list = match value {
+        toml::Value::String(value) => vec![value],
+        toml::Value::Array(items) => items
+          .into_iter()
+          .filter_map(|item| item.as_str().map(|item| item.to_string()))
+          .collect(),
+        other => vec![other.to_string()],
+      };
+      (key, list)
+    })
+    .collect()
+}
+
+fn unquote(value: &str) -> String {
+  value.trim().trim_matches('"').trim_matches('\'').trim().to_string()
+}
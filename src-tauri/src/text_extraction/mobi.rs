@@ -1,9 +1,47 @@
-// Extract text from a .epub file
+// Extract text from .mobi, .azw and .azw3 (KF8) files, one section per chapter
+// KF8 books use the same PalmDB container and MOBI header, so the same reader handles them
 use std::error::Error;
 use mobi::Mobi;
+use crate::text_extraction::{ContentSection, ExtractedContent};
+use crate::text_extraction::ebook::{html_to_text, normalize_isbn, split_html_chapters};
 
-pub fn extract(file: &String, _app: &tauri::AppHandle) -> Result<String, Box<dyn Error>> {
+pub fn extract(file: &String, _app: &tauri::AppHandle) -> Result<ExtractedContent, Box<dyn Error>> {
   let mobi_file = Mobi::from_path(file)?;
-  let text = mobi_file.content_as_string()?;
-  Ok(text)
-}
\ No newline at end of file
+  // KF8 text is not always valid UTF-8 at record boundaries
+  let html = mobi_file.content_as_string_lossy();
+
+  let sections: Vec<ContentSection> = split_html_chapters(&html)
+    .into_iter()
+    .enumerate()
+    .map(|(index, (name, chapter_html))| ContentSection {
+      label: name.clone(),
+      number: index as i64 + 1,
+      text: html_to_text(&chapter_html),
+      fields: vec![("section_title".to_string(), name)],
+    })
+    .collect();
+
+  let mut fields: Vec<(String, String)> = vec![("title".to_string(), mobi_file.title())];
+  // multiple authors are stored in one string, separated by & or ;
+  if let Some(authors) = mobi_file.author() {
+    for author in authors.split(|c| c == '&' || c == ';') {
+      fields.push(("author".to_string(), author.trim().to_string()));
+    }
+  }
+  if let Some(publisher) = mobi_file.publisher() {
+    fields.push(("publisher".to_string(), publisher));
+  }
+  if let Some(isbn) = mobi_file.isbn().and_then(|isbn| normalize_isbn(&isbn)) {
+    fields.push(("isbn".to_string(), isbn));
+  }
+  let text = sections.iter().map(|section| section.text.as_str()).collect::<Vec<&str>>().join("\n");
+  fields.retain(|(_, value)| !value.trim().is_empty());
+
+  Ok(ExtractedContent {
+    text,
+    fields,
+    sections,
+    // the language code in the MOBI header is often missing or wrong, so the language is detected from the text
+    language: None,
+  })
+}
@@ -1,15 +1,63 @@
 // use std::collections::HashMap;
 use std::error::Error;
 
+pub mod bib;
+pub mod code;
 pub mod csv;
 pub mod docx;
+pub mod ebook;
+pub mod encryption;
+pub mod ics;
 pub mod epub;
+pub mod highlights;
+pub mod md;
 pub mod mobi;
 pub mod pdf;
 pub mod pptx;
+pub mod subtitles;
+pub mod tex;
+pub mod transcription;
 pub mod txt;
+pub mod vcf;
+pub mod vobject;
 pub mod xlsx;
 pub mod image;
+pub mod ipynb;
+pub mod ooxml;
+
+// Text and searchable fields extracted from a file
+// `fields` are (field name, value) pairs that are indexed in the tantivy field of the same name, e.g. ("author", "Priya")
+// `sections` are set for files with natural boundaries (slides, chapters, pages). When present, each section is
+// chunked and indexed on its own so a hit can report where it is. `text` still holds the whole file
+// `language` is the ISO 639-1 code the file declares (e.g. the language of an EPUB), otherwise it is detected from `text`
+#[derive(Debug, Clone, Default)]
+pub struct ExtractedContent {
+  pub text: String,
+  pub fields: Vec<(String, String)>,
+  pub sections: Vec<ContentSection>,
+  pub language: Option<String>,
+}
+
+// A part of a file, e.g. label "slide 17" and number 17
+// `fields` only apply to the chunks of this section, e.g. ("section_title", "Q3 Budget") or ("notes", "...")
+#[derive(Debug, Clone, Default)]
+pub struct ContentSection {
+  pub label: String,
+  pub number: i64,
+  pub text: String,
+  pub fields: Vec<(String, String)>,
+}
+
+impl ExtractedContent {
+  pub fn from_text(text: String) -> Self {
+    Self {
+      text,
+      fields: vec![],
+      sections: vec![],
+      language: None,
+    }
+  }
+}
 
 pub struct Extractor;
 
@@ -27,19 +75,62 @@ impl Extractor {
     // println!("Extracting text from file: {}", file_path);
     match file_type.as_str() {
       "csv" => csv::extract(&file_path, app),
-      "docx" => docx::extract(&file_path, app),
-      "epub" => epub::extract(&file_path, app),
-      "mobi" => mobi::extract(&file_path, app),
-      "md" => txt::extract(&file_path, app),
-      "pdf" => pdf::extract(&file_path, app).await,
-      "pptx" => pptx::extract(&file_path, app),
+      "docx" => docx::extract(&file_path, app).map(|content| content.text),
+      "epub" => epub::extract(&file_path, app).map(|content| content.text),
+      "mobi" | "azw" | "azw3" => mobi::extract(&file_path, app).map(|content| content.text),
+      "md" => md::extract(&file_path, app).map(|content| content.text),
+      "pdf" => pdf::extract(&file_path, app).await.map(|content| content.text),
+      "pptx" => pptx::extract(&file_path, app).map(|content| content.text),
       "txt" => txt::extract(&file_path, app),
       "xlsx" => xlsx::extract(&file_path, app),
       "jpg" => image::extract(&file_path, app).await,
       "jpeg" => image::extract(&file_path, app).await,
       "png" => image::extract(&file_path, app).await,
       "svg" => image::extract(&file_path, app).await,
+      "rs" | "py" | "ts" => code::extract(&file_path, &file_type, app).map(|content| content.text),
+      "ipynb" => ipynb::extract(&file_path, app).map(|content| content.text),
+      "tex" => tex::extract(&file_path, app).map(|content| content.text),
+      "bib" => bib::extract(&file_path, app).map(|content| content.text),
+      "ics" => ics::extract(&file_path, app).map(|content| content.text),
+      "vcf" => vcf::extract(&file_path, app).map(|content| content.text),
+      "clippings" | "koreader" => highlights::extract(&file_path, &file_type, app).map(|content| content.text),
+      "mp4" | "mkv" | "avi" | "mov" | "wmv" | "mp3" | "wav" | "m4a" => extract_media(&file_path, &file_type, app).await.map(|content| content.text),
       _ => Err("File type not supported".into()),
     }
   }
-}
\ No newline at end of file
+
+  // Same as extract_text_from_file but keeps the searchable fields of file types that have them
+  pub async fn extract_content_from_file(
+    &self,
+    file_path: String,
+    file_type: String,
+    app: &tauri::AppHandle
+  ) -> Result<ExtractedContent, Box<dyn Error>> {
+    match file_type.as_str() {
+      "docx" => docx::extract(&file_path, app),
+      "pdf" => pdf::extract(&file_path, app).await,
+      "pptx" => pptx::extract(&file_path, app),
+      "epub" => epub::extract(&file_path, app),
+      "md" => md::extract(&file_path, app),
+      "mobi" | "azw" | "azw3" => mobi::extract(&file_path, app),
+      "rs" | "py" | "ts" => code::extract(&file_path, &file_type, app),
+      "ipynb" => ipynb::extract(&file_path, app),
+      "tex" => tex::extract(&file_path, app),
+      "bib" => bib::extract(&file_path, app),
+      "ics" => ics::extract(&file_path, app),
+      "vcf" => vcf::extract(&file_path, app),
+      "clippings" | "koreader" => highlights::extract(&file_path, &file_type, app),
+      "mp4" | "mkv" | "avi" | "mov" | "wmv" | "mp3" | "wav" | "m4a" => extract_media(&file_path, &file_type, app).await,
+      _ => self.extract_text_from_file(file_path, file_type, app).await.map(ExtractedContent::from_text),
+    }
+  }
+}
+
+// Videos are searchable through their sidecar subtitles. Transcripts of recordings are made by their own queue after
+// the other files are indexed (see parse_content_from_files), so audio has no text here
+async fn extract_media(file_path: &String, file_type: &String, app: &tauri::AppHandle) -> Result<ExtractedContent, Box<dyn Error>> {
+  if subtitles::VIDEO_FILETYPES.contains(&file_type.as_str()) {
+    return subtitles::extract(file_path, app);
+  }
+  Ok(ExtractedContent::default())
+}
@@ -0,0 +1,274 @@
+// Helpers for reading Office Open XML packages (docx, pptx, xlsx)
+// An OOXML file is a zip archive of XML "parts", e.g. word/document.xml or ppt/slides/slide1.xml
+use std::error::Error;
+use std::fs;
+use std::io::{Cursor, Read};
+use xml::name::OwnedName;
+use xml::reader::{EventReader, XmlEvent};
+use zip::ZipArchive;
+use crate::text_extraction::encryption::decrypt_office_file;
+
+// The package is read into memory so a password-protected file can be opened from its decrypted bytes
+pub type Package = ZipArchive<Cursor<Vec<u8>>>;
+
+pub fn open_package(file: &String, app: &tauri::AppHandle) -> Result<Package, Box<dyn Error>> {
+  let data = match decrypt_office_file(file, app)? {
+    Some(decrypted) => decrypted,
+    None => fs::read(file)?,
+  };
+  let archive = ZipArchive::new(Cursor::new(data))?;
+  Ok(archive)
+}
+
+// Names of all XML parts that start with `prefix`, sorted by the number in their name (slide2 before slide10)
+pub fn part_names(archive: &Package, prefix: &str) -> Vec<String> {
+  let mut names: Vec<String> = archive
+    .file_names()
+    .filter(|name| name.starts_with(prefix) && name.ends_with(".xml"))
+    .map(|name| name.to_string())
+    .collect();
+  names.sort_by_key(|name| (part_number(name), name.clone()));
+  names
+}
+
+// The number in a part name, e.g. 17 for ppt/slides/slide17.xml
+pub fn part_number(name: &str) -> i64 {
+  let file_name = name.rsplit("/").next().unwrap_or(name);
+  let digits: String = file_name.chars().filter(|c| c.is_ascii_digit()).collect();
+  digits.parse::<i64>().unwrap_or(0)
+}
+
+pub fn read_part(archive: &mut Package, name: &str) -> Option<String> {
+  let mut part = archive.by_name(name).ok()?;
+  let mut xml = String::new();
+  part.read_to_string(&mut xml).ok()?;
+  Some(xml)
+}
+
+const MARKUP_COMPATIBILITY_NAMESPACE: &str = "http://schemas.openxmlformats.org/markup-compatibility/2006";
+
+// An mc:AlternateContent holds the same content twice: as mc:Choice for newer readers and as mc:Fallback for older ones
+// (e.g. a text box as a DrawingML shape and again as a VML shape), so only the choice is read
+fn is_fallback(name: &OwnedName) -> bool {
+  name.local_name == "Fallback" && (name.namespace.as_deref() == Some(MARKUP_COMPATIBILITY_NAMESPACE) || name.prefix.as_deref() == Some("mc"))
+}
+
+// Extract the visible text from a WordprocessingML or DrawingML part
+// Text lives in `t` elements (w:t, a:t) so deleted text (w:delText) and field codes (w:instrText) are skipped
+// Paragraphs and line breaks become newlines
+pub fn part_text(xml: &str) -> String {
+  let parser = EventReader::new(xml.as_bytes());
+  let mut inside_text = false;
+  // how deep inside an mc:Fallback the parser is, 0 outside of one
+  let mut fallback_depth = 0;
+  let mut text = String::new();
+
+  for event in parser {
+    match event {
+      Ok(XmlEvent::StartElement { name, .. }) if fallback_depth > 0 || is_fallback(&name) => fallback_depth += 1,
+      Ok(XmlEvent::EndElement { .. }) if fallback_depth > 0 => fallback_depth -= 1,
+      Ok(XmlEvent::Characters(_)) | Ok(XmlEvent::Whitespace(_)) if fallback_depth > 0 => {}
+      Ok(XmlEvent::StartElement { name, .. }) => {
+        match name.local_name.as_str() {
+          "t" => inside_text = true,
+          "br" | "cr" => text.push('\n'),
+          "tab" => text.push('\t'),
+          _ => {}
+        }
+      }
+      Ok(XmlEvent::Characters(data)) | Ok(XmlEvent::Whitespace(data)) => {
+        if inside_text {
+          text.push_str(&data);
+        }
+      }
+      Ok(XmlEvent::EndElement { name }) => {
+        match name.local_name.as_str() {
+          "t" => inside_text = false,
+          "p" => text.push('\n'),
+          _ => {}
+        }
+      }
+      Err(e) => {
+        println!("Error reading XML part: {}", e);
+        break;
+      }
+      _ => {}
+    }
+  }
+
+  text
+}
+
+// Read docProps/core.xml into searchable fields
+// Returns (field name, value) pairs with the field names used in the tantivy index
+pub fn read_core_properties(archive: &mut Package) -> Vec<(String, String)> {
+  let xml = match read_part(archive, "docProps/core.xml") {
+    Some(xml) => xml,
+    None => return vec![],
+  };
+  let parser = EventReader::new(xml.as_bytes());
+  let mut current_field: Option<&str> = None;
+  let mut properties: Vec<(String, String)> = vec![];
+
+  for event in parser {
+    match event {
+      Ok(XmlEvent::StartElement { name, .. }) => {
+        current_field = match name.local_name.as_str() {
+          "creator" => Some("author"),
+          "title" => Some("title"),
+          "subject" => Some("subject"),
+          "keywords" => Some("keywords"),
+          "lastModifiedBy" => Some("last_modified_by"),
+          _ => None,
+        };
+      }
+      Ok(XmlEvent::Characters(data)) => {
+        if let Some(field) = current_field {
+          let value = data.trim().to_string();
+          if !value.is_empty() {
+            properties.push((field.to_string(), value));
+          }
+        }
+      }
+      Ok(XmlEvent::EndElement { .. }) => {
+        current_field = None;
+      }
+      Err(e) => {
+        println!("Error reading core properties: {}", e);
+        break;
+      }
+      _ => {}
+    }
+  }
+
+  properties
+}
+
+// Read a relationships part (e.g. ppt/slides/_rels/slide1.xml.rels) into (id, target part name) pairs
+// Targets are resolved against the folder of `part_name`, the part that owns the relationships
+pub fn read_relationships(archive: &mut Package, part_name: &str) -> Vec<(String, String)> {
+  let (folder, file_name) = match part_name.rsplit_once("/") {
+    Some((folder, file_name)) => (folder, file_name),
+    None => ("", part_name),
+  };
+  let rels_name = if folder.is_empty() {
+    format!("_rels/{}.rels", file_name)
+  } else {
+    format!("{}/_rels/{}.rels", folder, file_name)
+  };
+  let xml = match read_part(archive, &rels_name) {
+    Some(xml) => xml,
+    None => return vec![],
+  };
+  let parser = EventReader::new(xml.as_bytes());
+  let mut relationships: Vec<(String, String)> = vec![];
+
+  for event in parser {
+    match event {
+      Ok(XmlEvent::StartElement { name, attributes, .. }) if name.local_name == "Relationship" => {
+        let id = attributes.iter().find(|attribute| attribute.name.local_name == "Id");
+        let target = attributes.iter().find(|attribute| attribute.name.local_name == "Target");
+        if let (Some(id), Some(target)) = (id, target) {
+          relationships.push((id.value.clone(), resolve_target(folder, &target.value)));
+        }
+      }
+      Err(e) => {
+        println!("Error reading relationships: {}", e);
+        break;
+      }
+      _ => {}
+    }
+  }
+
+  relationships
+}
+
+// Resolve a relative relationship target such as ../notesSlides/notesSlide3.xml
+fn resolve_target(folder: &str, target: &str) -> String {
+  if let Some(absolute_target) = target.strip_prefix("/") {
+    return absolute_target.to_string();
+  }
+  let mut segments: Vec<&str> = folder.split("/").filter(|segment| !segment.is_empty()).collect();
+  for segment in target.split("/") {
+    match segment {
+      ".." => { segments.pop(); }
+      "." | "" => {}
+      _ => segments.push(segment),
+    }
+  }
+  segments.join("/")
+}
+
+// Text of every shape in a PresentationML part, with the placeholder type of the shape
+// e.g. ("title", "Q3 Budget") or ("body", "Speaker notes"). Shapes that are not placeholders have an empty type
+// and text outside of shapes (tables, charts) is returned last with an empty type
+pub fn shape_texts(xml: &str) -> Vec<(String, String)> {
+  let parser = EventReader::new(xml.as_bytes());
+  let mut shapes: Vec<(String, String)> = vec![];
+  let mut inside_shape = false;
+  let mut inside_text = false;
+  let mut placeholder_type = String::new();
+  let mut shape_text = String::new();
+  let mut outside_text = String::new();
+  let mut fallback_depth = 0;
+
+  for event in parser {
+    match event {
+      Ok(XmlEvent::StartElement { name, .. }) if fallback_depth > 0 || is_fallback(&name) => fallback_depth += 1,
+      Ok(XmlEvent::EndElement { .. }) if fallback_depth > 0 => fallback_depth -= 1,
+      Ok(XmlEvent::Characters(_)) | Ok(XmlEvent::Whitespace(_)) if fallback_depth > 0 => {}
+      Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+        match name.local_name.as_str() {
+          "sp" => {
+            inside_shape = true;
+            placeholder_type.clear();
+            shape_text.clear();
+          }
+          // a placeholder without a type is a body/content placeholder
+          "ph" if inside_shape => {
+            placeholder_type = attributes
+              .iter()
+              .find(|attribute| attribute.name.local_name == "type")
+              .map(|attribute| attribute.value.clone())
+              .unwrap_or("body".to_string());
+          }
+          "t" => inside_text = true,
+          "br" => {
+            if inside_shape { shape_text.push('\n'); } else { outside_text.push('\n'); }
+          }
+          _ => {}
+        }
+      }
+      Ok(XmlEvent::Characters(data)) | Ok(XmlEvent::Whitespace(data)) => {
+        if inside_text {
+          if inside_shape { shape_text.push_str(&data); } else { outside_text.push_str(&data); }
+        }
+      }
+      Ok(XmlEvent::EndElement { name }) => {
+        match name.local_name.as_str() {
+          "t" => inside_text = false,
+          "p" => {
+            if inside_shape { shape_text.push('\n'); } else { outside_text.push('\n'); }
+          }
+          "sp" => {
+            inside_shape = false;
+            if !shape_text.trim().is_empty() {
+              shapes.push((placeholder_type.clone(), shape_text.trim().to_string()));
+            }
+          }
+          _ => {}
+        }
+      }
+      Err(e) => {
+        println!("Error reading XML part: {}", e);
+        break;
+      }
+      _ => {}
+    }
+  }
+
+  if !outside_text.trim().is_empty() {
+    shapes.push(("".to_string(), outside_text.trim().to_string()));
+  }
+  shapes
+}
@@ -1,107 +1,573 @@
-use std::{error::Error, path::Path};
-// use futures::TryFutureExt;
+use std::error::Error;
+use std::fs;
+use std::panic::{catch_unwind, AssertUnwindSafe};
 use tauri_plugin_shell::{ShellExt, process::CommandEvent};
 use crate::housekeeping::get_app_directory;
-use crate::text_extraction::txt;
-use pdf_extract::extract_text;
+use crate::text_extraction::encryption::decrypt_pdf;
+use crate::text_extraction::{txt, ContentSection, ExtractedContent};
+use crate::utils::norm;
+use pdf_extract::{output_doc, MediaBox, OutputDev, OutputError, Transform};
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
 #[cfg(target_os = "windows")]
-use crate::utils::install_poppler_from_github;
+use crate::utils::{install_poppler_from_github, local_poppler_path};
 
-pub async fn extract(file: &String, app: &tauri::AppHandle) -> Result<String, Box<dyn Error>> {
+// The text of the PDF and its annotations
+// Annotated pages become sections (e.g. "page 4") whose `annotations` field holds the notes and highlights on that page,
+// so they can be searched on their own (`annotations:budget`) and a hit opens the right page
+// Encrypted PDFs are decrypted in memory with a saved password and only their text layer is read, scanned pages of an
+// encrypted PDF are not OCRed so the decrypted file never touches the disk
+// The file is parsed and laid out once, the text and the text under highlights both come from that layout
+pub async fn extract(file: &String, app: &tauri::AppHandle) -> Result<ExtractedContent, Box<dyn Error>> {
   println!("Extracting text from: {}", file);
-  // check if the file contains pdf in its name
-  let mut text_based_content = String::new();
-  if file.to_lowercase().contains(".pdf") {
-    text_based_content = match text_based_extraction(file) {
-        Ok(content) => content,
-        Err(_) => "false".to_string(),
-    };
+  let mut document = match catch_unwind(AssertUnwindSafe(|| Document::load(file))) {
+    Ok(Ok(document)) => document,
+    _ => {
+      println!("Could not parse {}, running OCR on the whole file", file);
+      return Ok(content_with_annotations(run_ocr(file, app).await?, vec![]));
+    }
+  };
+  if document.is_encrypted() {
+    decrypt_pdf(&mut document, file, app)?;
+    let (pages, positions) = lay_out(&document);
+    let annotations = guard_annotations(|| annotations_in_document(&document, &positions));
+    return Ok(content_with_annotations(join_pages(pages), annotations));
   }
+  let (pages, positions) = lay_out(&document);
+  let annotations = guard_annotations(|| annotations_in_document(&document, &positions));
+  let text = extract_body_text(file, &document, pages, app).await?;
+  Ok(content_with_annotations(text, annotations))
+}
 
-  if text_based_content != "false" && text_based_content.len() > 0 {
-    return Ok(text_based_content)
-  } else {
+// The text layer is read page by page, so one broken object only costs the pages it's on
+// OCR runs on the pages that have no text after that (scans, or pages neither parser could read) and works offline:
+// textra on macOS, winocr with a local poppler on Windows
+async fn extract_body_text(file: &String, document: &Document, mut pages: BTreeMap<u32, String>, app: &tauri::AppHandle) -> Result<String, Box<dyn Error>> {
+  let textless_pages: Vec<u32> = pages.iter().filter(|(_, text)| text.trim().is_empty()).map(|(page, _)| *page).collect();
+  if textless_pages.is_empty() {
+    return Ok(join_pages(pages));
+  }
+  if textless_pages.len() == pages.len() {
+    // a scan: the whole file goes to OCR in one go instead of a page at a time
     println!("Running OCR based text extraction");
-    // run textra on the file and save the output to a temporary file
-    let app_directory = get_app_directory();
-
-    #[cfg(target_os = "macos")]
-    {
-      let output_path = format!("{}/temp_output.txt", app_directory);
-
-      // run textra on the file
-      let sidecar_command = app.shell().sidecar("textra").unwrap().args([file, "-o", output_path.as_str()]);
-      let (mut rx, mut _child) = sidecar_command.spawn().unwrap();
-
-      // let mut text = String::new();
-      while let Some(event) = rx.recv().await {
-        // LOGIC:
-        // textra prints only the text to stdout, everything else goes to stderr
-        // but since we define an output file, there is no stdout
-        // so we just poll the stderr to keep the loop running till the extraction completes
-        if let CommandEvent::Stderr(line) = event {
-          let _output_line = String::from_utf8(line).unwrap();
-          // println!("textra stderr: {}", output_line);
-        }
+    return run_ocr(file, app).await;
+  }
+
+  println!("Running OCR on {} of {} pages", textless_pages.len(), pages.len());
+  let page_file = norm(&format!("{}/temp_page.pdf", get_app_directory()));
+  let page_ids = document.get_pages();
+  for page in textless_pages {
+    let Some(page_id) = page_ids.get(&page) else {
+      continue;
+    };
+    if let Err(e) = save_single_page(document, *page_id, &page_file) {
+      println!("Could not split page {} from {}: {}", page, file, e);
+      continue;
+    }
+    match run_ocr(&page_file, app).await {
+      Ok(text) => {
+        pages.insert(page, text);
       }
+      Err(e) => println!("Error running OCR on page {} of {}: {}", page, file, e),
+    }
+  }
+  let _ = fs::remove_file(&page_file);
+  Ok(join_pages(pages))
+}
 
-      // read the temporary file
-      let temp_file_path = format!("{}/temp_output.txt", app_directory);
-      let text = txt::extract(&temp_file_path, app)?;
+// The text of every page, by page number, and where its characters are drawn
+// pdf_extract lays the text out best but stops at the first object it can't handle, so the pages it didn't finish are
+// decoded again from their content streams by lopdf. Pages that neither parser reads are left empty
+fn lay_out(document: &Document) -> (BTreeMap<u32, String>, CharacterPositions) {
+  let mut output = PageLayout::default();
+  let result = catch_unwind(AssertUnwindSafe(|| output_doc(document, &mut output)));
+  if !matches!(result, Ok(Ok(()))) {
+    println!("pdf_extract stopped after {} pages, reading the rest with lopdf", output.text.pages.len());
+  }
 
-      // return the extracted text
-      return Ok(text)
+  let mut pages = output.text.pages;
+  for page in document.get_pages().keys() {
+    let has_text = pages.get(page).map(|text| !text.trim().is_empty()).unwrap_or(false);
+    if has_text {
+      continue;
     }
+    let text = match catch_unwind(AssertUnwindSafe(|| document.extract_text(&[*page]))) {
+      Ok(Ok(text)) => text,
+      _ => String::new(),
+    };
+    pages.insert(*page, text);
+  }
+  (pages, output.positions)
+}
 
-    #[cfg(target_os = "windows")]
-    {
-      let output_path = format!("{}\\temp_output.txt", app_directory);
-      let poppler_path = format!("{}\\poppler-24.02.0\\Library\\bin", app_directory);
-      let poppler_executable = format!("{}\\pdftoppm.exe", &poppler_path);
+fn join_pages(pages: BTreeMap<u32, String>) -> String {
+  pages.into_values().map(|text| text.trim().to_string()).filter(|text| !text.is_empty()).collect::<Vec<String>>().join("\n\n")
+}
 
-      let poppler_exists = std::path::Path::new(&poppler_executable).exists();
-      println!("poppler exists: {}", poppler_exists);
-      if !poppler_exists {
-        let _ = install_poppler_from_github().await?;
+// A PDF with only this page, for the OCR tools that take a whole file
+// Only the objects the page draws with are copied, not the whole document
+fn save_single_page(document: &Document, page_id: ObjectId, path: &str) -> Result<(), Box<dyn Error>> {
+  let mut page = document.get_dictionary(page_id)?.clone();
+  // the page tree may hand these down from a parent, which isn't copied
+  for key in [&b"Resources"[..], b"MediaBox", b"CropBox", b"Rotate"] {
+    if !page.has(key) {
+      if let Some(value) = inherited_attribute(document, page_id, key) {
+        page.set(key, value.clone());
       }
+    }
+  }
+  page.remove(b"Annots");
+
+  let mut single_page = Document::with_version(document.version.clone());
+  let mut pending = object_references(&Object::Dictionary(page.clone()));
+  while let Some(id) = pending.pop() {
+    if id == page_id || single_page.objects.contains_key(&id) {
+      continue;
+    }
+    if let Ok(object) = document.get_object(id) {
+      pending.extend(object_references(object));
+      single_page.objects.insert(id, object.clone());
+    }
+  }
+
+  // new ids start after the ones of the source, so they don't clash with the copied objects
+  single_page.max_id = document.max_id;
+  let pages_id = single_page.new_object_id();
+  page.set("Parent", pages_id);
+  single_page.objects.insert(page_id, Object::Dictionary(page));
+  let mut pages = Dictionary::new();
+  pages.set("Type", Object::Name(b"Pages".to_vec()));
+  pages.set("Kids", vec![Object::Reference(page_id)]);
+  pages.set("Count", 1);
+  single_page.objects.insert(pages_id, Object::Dictionary(pages));
+  let mut catalog = Dictionary::new();
+  catalog.set("Type", Object::Name(b"Catalog".to_vec()));
+  catalog.set("Pages", pages_id);
+  let catalog_id = single_page.add_object(catalog);
+  single_page.trailer.set("Root", catalog_id);
+  single_page.save(path)?;
+  Ok(())
+}
+
+fn inherited_attribute<'a>(document: &'a Document, page_id: ObjectId, key: &[u8]) -> Option<&'a Object> {
+  let mut node = document.get_dictionary(page_id).ok()?;
+  // a malformed tree can loop, no real one is this deep
+  for _ in 0..32 {
+    let parent_id = node.get(b"Parent").and_then(Object::as_reference).ok()?;
+    node = document.get_dictionary(parent_id).ok()?;
+    if let Ok(value) = node.get(key) {
+      return Some(value);
+    }
+  }
+  None
+}
+
+// The objects an object points to. /Parent links lead up to the page tree and the other pages, so they aren't followed
+fn object_references(object: &Object) -> Vec<ObjectId> {
+  match object {
+    Object::Reference(id) => vec![*id],
+    Object::Array(items) => items.iter().flat_map(object_references).collect(),
+    Object::Dictionary(dictionary) => dictionary_references(dictionary),
+    Object::Stream(stream) => dictionary_references(&stream.dict),
+    _ => vec![],
+  }
+}
+
+fn dictionary_references(dictionary: &Dictionary) -> Vec<ObjectId> {
+  dictionary.iter().filter(|(key, _)| key.as_slice() != b"Parent").flat_map(|(_, value)| object_references(value)).collect()
+}
 
-      // run winocr on the file
-      let sidecar_command = app.shell().sidecar("winocr").unwrap().args(["-i", file, "-o", output_path.as_str(), "--poppler-path", poppler_path.as_str()]);
-      let (mut rx, mut _child) = sidecar_command.spawn().unwrap();
+async fn run_ocr(file: &String, app: &tauri::AppHandle) -> Result<String, Box<dyn Error>> {
+  let output_path = norm(&format!("{}/temp_output.txt", get_app_directory()));
+  // a failed run must not pick up the text of the previous one
+  let _ = fs::remove_file(&output_path);
+
+  #[cfg(target_os = "macos")]
+  {
+    // run textra on the file
+    let sidecar_command = app.shell().sidecar("textra")?.args([file, "-o", output_path.as_str()]);
+    let (mut rx, mut _child) = sidecar_command.spawn()?;
+
+    // LOGIC:
+    // textra prints only the text to stdout, everything else goes to stderr
+    // but since we define an output file, there is no stdout
+    // so we just poll the events to keep the loop running till the extraction completes
+    while let Some(event) = rx.recv().await {
+      if let CommandEvent::Terminated(_) = event {
+        break;
+      }
+    }
+  }
 
-      while let Some(event) = rx.recv().await {
-        // LOGIC: so we just poll the stdout to keep the loop running till the extraction completes
-        if let CommandEvent::Stdout(line) = event {
-          let _output_line = String::from_utf8(line).unwrap();
-        }
+  #[cfg(target_os = "windows")]
+  {
+    // winocr renders PDF pages with poppler. Without a copy it is downloaded once, like before, and if that fails too
+    // the pages are left without text
+    if local_poppler_path().is_none() {
+      if let Err(e) = install_poppler_from_github().await {
+        println!("Error installing Poppler: {}", e);
+      }
+    }
+    let poppler_path = match local_poppler_path() {
+      Some(poppler_path) => poppler_path,
+      None => {
+        println!("Poppler was not found, skipping OCR for {}", file);
+        return Ok(String::new());
       }
+    };
 
-      // read the temporary file
-      let temp_file_path = format!("{}\\temp_output.txt", app_directory);
-      let text = txt::extract(&temp_file_path, app)?;
+    // run winocr on the file
+    let sidecar_command = app.shell().sidecar("winocr")?.args(["-i", file, "-o", output_path.as_str(), "--poppler-path", poppler_path.as_str()]);
+    let (mut rx, mut _child) = sidecar_command.spawn()?;
 
-      // return the extracted text
-      return Ok(text)
+    // LOGIC: so we just poll the events to keep the loop running till the extraction completes
+    while let Some(event) = rx.recv().await {
+      if let CommandEvent::Terminated(_) = event {
+        break;
+      }
     }
   }
+
+  if !std::path::Path::new(&output_path).exists() {
+    return Ok(String::new());
+  }
+  txt::extract(&output_path, app)
 }
 
-// pub fn text_based_extraction(file: &String) -> Result<String, Box<dyn Error>> {
-//   let content = match extract_text(Path::new(file)) {
-//     Ok(content) => content,
-//     Err(e) => return Ok("false".to_string()),
-//   };
-//   Ok(content)
-// }
+// One pass of pdf_extract fills both the page texts and the character positions
+#[derive(Default)]
+struct PageLayout {
+  text: PageTextOutput,
+  positions: CharacterPositions,
+}
 
-use std::panic::{catch_unwind, AssertUnwindSafe};
+impl OutputDev for PageLayout {
+  fn begin_page(&mut self, page_num: u32, media_box: &MediaBox, art_box: Option<(f64, f64, f64, f64)>) -> Result<(), OutputError> {
+    self.text.begin_page(page_num, media_box, art_box)?;
+    self.positions.begin_page(page_num, media_box, art_box)
+  }
+  fn end_page(&mut self) -> Result<(), OutputError> {
+    self.text.end_page()?;
+    self.positions.end_page()
+  }
+  fn output_character(&mut self, trm: &Transform, width: f64, spacing: f64, font_size: f64, char: &str) -> Result<(), OutputError> {
+    self.text.output_character(trm, width, spacing, font_size, char)?;
+    self.positions.output_character(trm, width, spacing, font_size, char)
+  }
+  fn begin_word(&mut self) -> Result<(), OutputError> {
+    self.text.begin_word()?;
+    self.positions.begin_word()
+  }
+  fn end_word(&mut self) -> Result<(), OutputError> {
+    self.text.end_word()?;
+    self.positions.end_word()
+  }
+  fn end_line(&mut self) -> Result<(), OutputError> {
+    self.text.end_line()?;
+    self.positions.end_line()
+  }
+}
 
-pub fn text_based_extraction(file: &String) -> Result<String, Box<dyn Error>> {
-  let result = catch_unwind(AssertUnwindSafe(|| extract_text(Path::new(file))));
+// The text of each page as pdf_extract lays it out, kept page by page so the pages finished before an error survive it
+// Spaces and line breaks come from where the characters are drawn, like pdf_extract's PlainTextOutput
+#[derive(Default)]
+struct PageTextOutput {
+  page: u32,
+  text: String,
+  pages: BTreeMap<u32, String>,
+  word_start: bool,
+  last_end: f64,
+  last_y: f64,
+}
 
-  let content = match result {
-    Ok(Ok(content)) => content,
-    Ok(Err(_)) | Err(_) => return Ok("false".to_string()),
-  };
-  Ok(content)
-}
\ No newline at end of file
+impl OutputDev for PageTextOutput {
+  fn begin_page(&mut self, page_num: u32, _media_box: &MediaBox, _art_box: Option<(f64, f64, f64, f64)>) -> Result<(), OutputError> {
+    self.page = page_num;
+    self.text = String::new();
+    self.word_start = false;
+    self.last_end = 0.;
+    self.last_y = 0.;
+    Ok(())
+  }
+  fn end_page(&mut self) -> Result<(), OutputError> {
+    self.pages.insert(self.page, std::mem::take(&mut self.text));
+    Ok(())
+  }
+  fn output_character(&mut self, trm: &Transform, width: f64, _spacing: f64, font_size: f64, char: &str) -> Result<(), OutputError> {
+    let (x, y) = (trm.m31, trm.m32);
+    let scaled_font_size = font_size * (trm.m11 * trm.m22).abs().sqrt();
+    if self.word_start && !self.text.is_empty() {
+      let new_line = (y - self.last_y).abs() > scaled_font_size * 1.5
+        || (x < self.last_end && (y - self.last_y).abs() > scaled_font_size * 0.5);
+      if new_line {
+        self.text.push('\n');
+      } else if x > self.last_end + scaled_font_size * 0.1 {
+        self.text.push(' ');
+      }
+    }
+    self.text.push_str(char);
+    self.word_start = false;
+    self.last_y = y;
+    self.last_end = x + width * scaled_font_size;
+    Ok(())
+  }
+  fn begin_word(&mut self) -> Result<(), OutputError> {
+    self.word_start = true;
+    Ok(())
+  }
+  fn end_word(&mut self) -> Result<(), OutputError> {
+    Ok(())
+  }
+  fn end_line(&mut self) -> Result<(), OutputError> {
+    Ok(())
+  }
+}
+
+lazy_static! {
+  // the rich text (/RC) of an annotation is XHTML
+  static ref XML_TAG: Regex = Regex::new(r"<[^>]+>").unwrap();
+}
+
+// Annotation types that carry a reader's note or mark text on the page
+// Links, form fields and the popups that display notes are left out
+const NOTE_ANNOTATIONS: [&str; 5] = ["Text", "FreeText", "Caret", "Square", "Circle"];
+const MARKUP_ANNOTATIONS: [&str; 4] = ["Highlight", "Underline", "StrikeOut", "Squiggly"];
+
+// A note or highlight on a page: the marked text (for highlights), the note and its author
+#[derive(Debug, Clone, Default)]
+pub struct PdfAnnotation {
+  pub page: u32,
+  pub marked_text: String,
+  pub note: String,
+  pub author: String,
+}
+
+impl PdfAnnotation {
+  // "Q3 revenue fell" check this number (Priya)
+  fn to_line(&self) -> String {
+    let mut parts: Vec<String> = vec![];
+    if !self.marked_text.is_empty() {
+      parts.push(format!("\"{}\"", self.marked_text));
+    }
+    if !self.note.is_empty() {
+      parts.push(self.note.clone());
+    }
+    if !self.author.is_empty() {
+      parts.push(format!("({})", self.author));
+    }
+    parts.join(" ")
+  }
+}
+
+fn content_with_annotations(text: String, annotations: Vec<PdfAnnotation>) -> ExtractedContent {
+  if annotations.is_empty() {
+    return ExtractedContent::from_text(text);
+  }
+  let mut pages: BTreeMap<u32, Vec<String>> = BTreeMap::new();
+  let mut authors: Vec<String> = vec![];
+  for annotation in &annotations {
+    pages.entry(annotation.page).or_default().push(annotation.to_line());
+    if !annotation.author.is_empty() && !authors.contains(&annotation.author) {
+      authors.push(annotation.author.clone());
+    }
+  }
+
+  // the text of the PDF has no page numbers, so it is kept as a section without a position (number 0)
+  let mut sections: Vec<ContentSection> = vec![];
+  if !text.trim().is_empty() {
+    sections.push(ContentSection {
+      label: "".to_string(),
+      number: 0,
+      text: text.clone(),
+      fields: vec![],
+    });
+  }
+  for (page, lines) in pages {
+    let page_annotations = lines.join("\n");
+    sections.push(ContentSection {
+      label: format!("page {}", page),
+      number: page as i64,
+      text: page_annotations.clone(),
+      fields: vec![("annotations".to_string(), page_annotations)],
+    });
+  }
+  ExtractedContent {
+    text: sections.iter().map(|section| section.text.as_str()).collect::<Vec<&str>>().join("\n\n"),
+    fields: authors.into_iter().map(|author| ("annotation_author".to_string(), author)).collect(),
+    sections,
+    language: None,
+  }
+}
+
+fn guard_annotations(read: impl FnOnce() -> Result<Vec<PdfAnnotation>, Box<dyn Error>>) -> Vec<PdfAnnotation> {
+  // lopdf panics on some malformed annotation arrays
+  let result = catch_unwind(AssertUnwindSafe(read));
+  match result {
+    Ok(Ok(annotations)) => annotations,
+    Ok(Err(e)) => {
+      println!("Error reading PDF annotations: {}", e);
+      vec![]
+    }
+    Err(_) => vec![],
+  }
+}
+
+// Notes, free text and markup annotations of every page
+// The note is the annotation's /Contents. The marked text of a highlight is the text under its /QuadPoints
+fn annotations_in_document(document: &Document, positions: &CharacterPositions) -> Result<Vec<PdfAnnotation>, Box<dyn Error>> {
+  let mut annotations: Vec<PdfAnnotation> = vec![];
+  // markup annotations with the boxes of the text they cover
+  let mut highlights: Vec<(usize, Vec<(f64, f64, f64, f64)>)> = vec![];
+  for (page, page_id) in document.get_pages() {
+    for dictionary in document.get_page_annotations(page_id) {
+      let subtype = dictionary.get(b"Subtype").and_then(Object::as_name_str).unwrap_or("");
+      let is_markup = MARKUP_ANNOTATIONS.contains(&subtype);
+      if !is_markup && !NOTE_ANNOTATIONS.contains(&subtype) {
+        continue;
+      }
+      let author = dictionary.get(b"T").and_then(Object::as_str).map(decode_pdf_string).unwrap_or_default();
+      if is_markup {
+        highlights.push((annotations.len(), quad_boxes(dictionary)));
+      }
+      annotations.push(PdfAnnotation {
+        page,
+        marked_text: String::new(),
+        note: annotation_text(dictionary),
+        author: author.trim().to_string(),
+      });
+    }
+  }
+
+  for (index, quads) in highlights {
+    let annotation = &mut annotations[index];
+    annotation.marked_text = positions.text_inside(annotation.page, &quads);
+    // some readers copy the marked text into /Contents instead of a comment
+    if annotation.note.split_whitespace().eq(annotation.marked_text.split_whitespace()) {
+      annotation.note = String::new();
+    }
+  }
+  annotations.retain(|annotation| !annotation.note.is_empty() || !annotation.marked_text.is_empty());
+  Ok(annotations)
+}
+
+// /Contents, or the rich text (/RC) without its markup
+fn annotation_text(dictionary: &Dictionary) -> String {
+  let contents = dictionary.get(b"Contents").and_then(Object::as_str).map(decode_pdf_string).unwrap_or_default();
+  if !contents.trim().is_empty() {
+    return contents.trim().to_string();
+  }
+  let rich_text = dictionary.get(b"RC").and_then(Object::as_str).map(decode_pdf_string).unwrap_or_default();
+  XML_TAG
+    .replace_all(&rich_text, " ")
+    .replace("&amp;", "&")
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .split_whitespace()
+    .collect::<Vec<&str>>()
+    .join(" ")
+}
+
+// The bounding boxes (min x, min y, max x, max y) of the quadrilaterals of a markup annotation, one per marked line
+fn quad_boxes(dictionary: &Dictionary) -> Vec<(f64, f64, f64, f64)> {
+  let points: Vec<f64> = dictionary
+    .get(b"QuadPoints")
+    .and_then(Object::as_array)
+    .map(|points| points.iter().filter_map(|point| point.as_float().ok()).map(|point| point as f64).collect())
+    .unwrap_or_default();
+  points
+    .chunks_exact(8)
+    .map(|quad| {
+      let xs = [quad[0], quad[2], quad[4], quad[6]];
+      let ys = [quad[1], quad[3], quad[5], quad[7]];
+      (
+        xs.iter().cloned().fold(f64::MAX, f64::min),
+        ys.iter().cloned().fold(f64::MAX, f64::min),
+        xs.iter().cloned().fold(f64::MIN, f64::max),
+        ys.iter().cloned().fold(f64::MIN, f64::max),
+      )
+    })
+    .collect()
+}
+
+// PDF text strings are UTF-16BE with a byte order mark, or PDFDocEncoding (close enough to Latin-1 for notes)
+fn decode_pdf_string(bytes: &[u8]) -> String {
+  if bytes.starts_with(&[0xFE, 0xFF]) {
+    let units: Vec<u16> = bytes[2..].chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect();
+    String::from_utf16_lossy(&units)
+  } else if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+    String::from_utf8_lossy(&bytes[3..]).to_string()
+  } else {
+    bytes.iter().map(|byte| *byte as char).collect()
+  }
+}
+
+// Where every character of the PDF is drawn, to find the text under highlights
+#[derive(Default)]
+struct CharacterPositions {
+  page: u32,
+  word_start: bool,
+  characters: HashMap<u32, Vec<PositionedCharacter>>,
+}
+
+struct PositionedCharacter {
+  x: f64,
+  y: f64,
+  text: String,
+  word_start: bool,
+}
+
+impl CharacterPositions {
+  // The characters of a page whose origin lies in one of the boxes, with spaces between words
+  fn text_inside(&self, page: u32, boxes: &[(f64, f64, f64, f64)]) -> String {
+    let mut text = String::new();
+    for character in self.characters.get(&page).into_iter().flatten() {
+      // the baseline sits inside the box, a point of slack covers rounding in the quads
+      let inside = boxes.iter().any(|(min_x, min_y, max_x, max_y)| {
+        character.x >= min_x - 1.0 && character.x <= *max_x && character.y >= min_y - 1.0 && character.y <= *max_y
+      });
+      if !inside {
+        continue;
+      }
+      if character.word_start && !text.is_empty() && !text.ends_with(' ') {
+        text.push(' ');
+      }
+      text.push_str(&character.text);
+    }
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+  }
+}
+
+impl OutputDev for CharacterPositions {
+  fn begin_page(&mut self, page_num: u32, _media_box: &MediaBox, _art_box: Option<(f64, f64, f64, f64)>) -> Result<(), OutputError> {
+    self.page = page_num;
+    Ok(())
+  }
+  fn end_page(&mut self) -> Result<(), OutputError> {
+    Ok(())
+  }
+  // trm maps text space to the page, so (m31, m32) is where the character is drawn in the same space as /QuadPoints
+  fn output_character(&mut self, trm: &Transform, _width: f64, _spacing: f64, _font_size: f64, char: &str) -> Result<(), OutputError> {
+    let word_start = self.word_start;
+    self.word_start = false;
+    self.characters.entry(self.page).or_default().push(PositionedCharacter {
+      x: trm.m31,
+      y: trm.m32,
+      text: char.to_string(),
+      word_start,
+    });
+    Ok(())
+  }
+  fn begin_word(&mut self) -> Result<(), OutputError> {
+    self.word_start = true;
+    Ok(())
+  }
+  fn end_word(&mut self) -> Result<(), OutputError> {
+    Ok(())
+  }
+  fn end_line(&mut self) -> Result<(), OutputError> {
+    self.word_start = true;
+    Ok(())
+  }
+}
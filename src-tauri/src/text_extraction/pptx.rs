@@ -1,10 +1,110 @@
-use dotext::*;
-use std::io::Read;
+// Extract text from a .pptx file, one section per slide
+// Slides are read in presentation order (ppt/presentation.xml) rather than by file name, because slide files keep
+// their original number when slides are reordered. Speaker notes are found through each slide's relationships.
 use std::error::Error;
+use crate::text_extraction::{ContentSection, ExtractedContent};
+use crate::text_extraction::ooxml::{open_package, part_names, read_core_properties, read_part, read_relationships, shape_texts, Package};
+use xml::reader::{EventReader, XmlEvent};
 
-pub fn extract(file: &String, _app: &tauri::AppHandle) -> Result<String, Box<dyn Error>> {
-  let mut file_buffer = Pptx::open(file)?;
-  let mut text = String::new();
-  file_buffer.read_to_string(&mut text)?;
-  Ok(text)
-}
\ No newline at end of file
+// Placeholders that repeat on every slide and would only add noise
+const IGNORED_PLACEHOLDERS: [&str; 5] = ["sldNum", "dt", "ftr", "hdr", "sldImg"];
+
+pub fn extract(file: &String, app: &tauri::AppHandle) -> Result<ExtractedContent, Box<dyn Error>> {
+  let mut archive = open_package(file, app)?;
+  let mut sections: Vec<ContentSection> = vec![];
+
+  for (index, slide_name) in slide_names_in_order(&mut archive).iter().enumerate() {
+    let slide_xml = match read_part(&mut archive, slide_name) {
+      Some(xml) => xml,
+      None => continue,
+    };
+    let mut title: Vec<String> = vec![];
+    let mut body: Vec<String> = vec![];
+    for (placeholder_type, text) in shape_texts(&slide_xml) {
+      match placeholder_type.as_str() {
+        "title" | "ctrTitle" => title.push(text),
+        t if IGNORED_PLACEHOLDERS.contains(&t) => {}
+        _ => body.push(text),
+      }
+    }
+    let notes = read_notes(&mut archive, slide_name);
+
+    let title = title.join(" ");
+    let mut fields: Vec<(String, String)> = vec![];
+    if !title.is_empty() {
+      fields.push(("section_title".to_string(), title.clone()));
+    }
+    if !notes.is_empty() {
+      fields.push(("notes".to_string(), notes.clone()));
+    }
+    let text = [title, body.join("\n"), notes]
+      .into_iter()
+      .filter(|text| !text.is_empty())
+      .collect::<Vec<String>>()
+      .join("\n");
+
+    let slide_number = index as i64 + 1;
+    sections.push(ContentSection {
+      label: format!("slide {}", slide_number),
+      number: slide_number,
+      text,
+      fields,
+    });
+  }
+
+  Ok(ExtractedContent {
+    text: sections.iter().map(|section| section.text.as_str()).collect::<Vec<&str>>().join("\n"),
+    fields: read_core_properties(&mut archive),
+    sections,
+    language: None,
+  })
+}
+
+// Slide part names in the order of <p:sldIdLst> in ppt/presentation.xml
+// Falls back to sorting ppt/slides/slideN.xml by number if the presentation part can't be read
+fn slide_names_in_order(archive: &mut Package) -> Vec<String> {
+  let relationships = read_relationships(archive, "ppt/presentation.xml");
+  let presentation_xml = read_part(archive, "ppt/presentation.xml").unwrap_or_default();
+  let parser = EventReader::new(presentation_xml.as_bytes());
+  let mut slide_names: Vec<String> = vec![];
+
+  for event in parser {
+    match event {
+      Ok(XmlEvent::StartElement { name, attributes, .. }) if name.local_name == "sldId" => {
+        // the relationship id is the r:id attribute, not the numeric id
+        let relationship_id = attributes
+          .iter()
+          .find(|attribute| attribute.name.local_name == "id" && attribute.name.prefix.is_some());
+        if let Some(relationship_id) = relationship_id {
+          if let Some((_, target)) = relationships.iter().find(|(id, _)| id == &relationship_id.value) {
+            slide_names.push(target.clone());
+          }
+        }
+      }
+      Err(_) => break,
+      _ => {}
+    }
+  }
+
+  if slide_names.is_empty() {
+    slide_names = part_names(archive, "ppt/slides/slide");
+  }
+  slide_names
+}
+
+fn read_notes(archive: &mut Package, slide_name: &str) -> String {
+  let notes_name = read_relationships(archive, slide_name)
+    .into_iter()
+    .map(|(_, target)| target)
+    .find(|target| target.starts_with("ppt/notesSlides/"));
+  let notes_xml = match notes_name.and_then(|notes_name| read_part(archive, &notes_name)) {
+    Some(xml) => xml,
+    None => return "".to_string(),
+  };
+  shape_texts(&notes_xml)
+    .into_iter()
+    .filter(|(placeholder_type, _)| placeholder_type == "body")
+    .map(|(_, text)| text)
+    .collect::<Vec<String>>()
+    .join("\n")
+}
@@ -0,0 +1,223 @@
+// Extract subtitles (.srt, .vtt, .ass/.ssa) as the text of the video they belong to
+// Subtitle files next to a video with the same name (movie.mp4 -> movie.srt, movie.en.vtt) are its sidecars.
+// Every cue is a section of its own, labelled with its start time (e.g. "1:02:03"). The section number is the start
+// time in whole seconds, so a hit opens the video at the cue that matched.
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::text_extraction::{ContentSection, ExtractedContent};
+
+pub const SUBTITLE_FILETYPES: [&str; 4] = ["srt", "vtt", "ass", "ssa"];
+pub const VIDEO_FILETYPES: [&str; 5] = ["mp4", "mkv", "avi", "mov", "wmv"];
+
+lazy_static! {
+  static ref CUE_TIMING: Regex = Regex::new(r"^\s*(\S+)\s+-->\s+(\S+)").unwrap();
+  // <i>, <font color=...>, <c.yellow>, <00:00:01.000> (karaoke timing in WebVTT)
+  static ref MARKUP_TAG: Regex = Regex::new(r"</?[^>]+>").unwrap();
+  // {\an8} in SRT, {\i1}, {\pos(10,20)} in ASS
+  static ref OVERRIDE_TAG: Regex = Regex::new(r"\{\\[^}]*\}").unwrap();
+}
+
+struct Cue {
+  start: f64,
+  text: String,
+}
+
+// The text of all sidecar subtitles of a video
+pub fn extract(file: &String, _app: &tauri::AppHandle) -> Result<ExtractedContent, Box<dyn Error>> {
+  let mut cues: Vec<Cue> = vec![];
+  for subtitle_file in sidecar_subtitle_files(Path::new(file)) {
+    let contents = match read_to_string(&subtitle_file) {
+      Ok(contents) => contents,
+      Err(e) => {
+        println!("Error reading subtitles {:?}: {}", subtitle_file, e);
+        continue;
+      }
+    };
+    let extension = subtitle_file.extension().and_then(|extension| extension.to_str()).unwrap_or("").to_lowercase();
+    match extension.as_str() {
+      "ass" | "ssa" => cues.extend(parse_ass(&contents)),
+      _ => cues.extend(parse_srt_or_vtt(&contents)),
+    }
+  }
+  // subtitles in several languages are merged into the same time windows
+  cues.sort_by(|a, b| a.start.partial_cmp(&b.start).unwrap_or(std::cmp::Ordering::Equal));
+
+  let sections = cue_sections(cues);
+  Ok(ExtractedContent {
+    text: sections.iter().map(|section| section.text.as_str()).collect::<Vec<&str>>().join("\n"),
+    fields: vec![],
+    sections,
+    language: None,
+  })
+}
+
+// Subtitle files in the folder of the video whose name is the video's name, optionally followed by a
+// language or other tag: movie.srt, movie.en.srt, movie.en.forced.vtt
+pub fn sidecar_subtitle_files(video: &Path) -> Vec<PathBuf> {
+  let folder_subtitle_files = match video.parent() {
+    Some(folder) => subtitle_files_in(folder),
+    None => return vec![],
+  };
+  let mut subtitle_files: Vec<PathBuf> = folder_subtitle_files.into_iter().filter(|subtitle_file| is_sidecar(subtitle_file, video)).collect();
+  subtitle_files.sort();
+  subtitle_files
+}
+
+fn subtitle_files_in(folder: &Path) -> Vec<PathBuf> {
+  let entries = match std::fs::read_dir(folder) {
+    Ok(entries) => entries,
+    Err(_) => return vec![],
+  };
+  entries
+    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+    .filter(|path| {
+      let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("").to_lowercase();
+      SUBTITLE_FILETYPES.contains(&extension.as_str())
+    })
+    .collect()
+}
+
+fn is_sidecar(subtitle_file: &Path, video: &Path) -> bool {
+  let video_stem = video.file_stem().and_then(|stem| stem.to_str()).unwrap_or("").to_lowercase();
+  let stem = subtitle_file.file_stem().and_then(|stem| stem.to_str()).unwrap_or("").to_lowercase();
+  !video_stem.is_empty() && (stem == video_stem || stem.starts_with(&format!("{}.", video_stem)))
+}
+
+// The subtitle files of the folders seen in one run of indexing, with their modification time (UNIX seconds)
+// Each folder is read once, however many videos it has
+#[derive(Default)]
+pub struct SidecarFolders {
+  folders: HashMap<PathBuf, Vec<(PathBuf, i64)>>,
+}
+
+impl SidecarFolders {
+  // The latest modification time of the sidecar subtitles of a video, 0 if it has none
+  // A video is parsed again when a subtitle file is added or edited, even if the video itself did not change
+  pub fn last_modified(&mut self, video: &Path) -> i64 {
+    let folder = match video.parent() {
+      Some(folder) => folder,
+      None => return 0,
+    };
+    let subtitle_files = self.folders.entry(folder.to_path_buf()).or_insert_with(|| {
+      subtitle_files_in(folder)
+        .into_iter()
+        .map(|subtitle_file| {
+          let modified = subtitle_file
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |modified| modified.as_secs() as i64);
+          (subtitle_file, modified)
+        })
+        .collect()
+    });
+    subtitle_files
+      .iter()
+      .filter(|(subtitle_file, _)| is_sidecar(subtitle_file, video))
+      .map(|(_, modified)| *modified)
+      .max()
+      .unwrap_or(0)
+  }
+}
+
+// Timestamped sections from SubRip or WebVTT text, e.g. a transcript written by whisper.cpp
+pub fn sections_from_srt(contents: &str) -> Vec<ContentSection> {
+  cue_sections(parse_srt_or_vtt(contents))
+}
+
+// SubRip and WebVTT cues: an optional identifier line, a timing line and the text up to the next blank line
+fn parse_srt_or_vtt(contents: &str) -> Vec<Cue> {
+  let mut cues: Vec<Cue> = vec![];
+  let contents = contents.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+  for block in contents.split("\n\n") {
+    let mut lines = block.lines().skip_while(|line| !CUE_TIMING.is_match(line));
+    let start = match lines.next().and_then(|timing| CUE_TIMING.captures(timing)).and_then(|timing| parse_timestamp(&timing[1])) {
+      Some(start) => start,
+      // WEBVTT header, NOTE and STYLE blocks have no timing line
+      None => continue,
+    };
+    let text = lines.map(clean_text).collect::<Vec<String>>().join(" ");
+    if !text.trim().is_empty() {
+      cues.push(Cue { start, text: text.trim().to_string() });
+    }
+  }
+  cues
+}
+
+// Advanced SubStation Alpha: `Dialogue:` lines in the [Events] section, laid out by its `Format:` line
+fn parse_ass(contents: &str) -> Vec<Cue> {
+  let mut cues: Vec<Cue> = vec![];
+  let mut columns: Vec<String> = vec![];
+  for line in contents.lines() {
+    if let Some(format) = line.strip_prefix("Format:") {
+      columns = format.split(',').map(|column| column.trim().to_lowercase()).collect();
+      continue;
+    }
+    let dialogue = match line.strip_prefix("Dialogue:") {
+      Some(dialogue) => dialogue,
+      None => continue,
+    };
+    let start_column = columns.iter().position(|column| column == "start").unwrap_or(1);
+    let text_column = columns.iter().position(|column| column == "text").unwrap_or(9);
+    // the text is the last column and may itself contain commas
+    let values: Vec<&str> = dialogue.splitn(text_column + 1, ',').collect();
+    if let (Some(start), Some(text)) = (values.get(start_column).and_then(|start| parse_timestamp(start.trim())), values.get(text_column)) {
+      let text = clean_text(&text.replace("\\N", " ").replace("\\n", " ").replace("\\h", " "));
+      if !text.trim().is_empty() {
+        cues.push(Cue { start, text: text.trim().to_string() });
+      }
+    }
+  }
+  cues
+}
+
+// 01:02:03,456 (SRT), 01:02:03.456 or 02:03.456 (WebVTT), 1:02:03.45 (ASS) -> seconds
+fn parse_timestamp(timestamp: &str) -> Option<f64> {
+  let mut seconds = 0.0;
+  for part in timestamp.replace(',', ".").split(':') {
+    seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+  }
+  Some(seconds)
+}
+
+fn clean_text(text: &str) -> String {
+  let text = OVERRIDE_TAG.replace_all(text, "");
+  MARKUP_TAG.replace_all(&text, "").trim().to_string()
+}
+
+// One section per cue, so a hit reports the time of the cue it is in
+// Roll-up captions repeat the previous line, so a cue that is the same as the one before is skipped
+fn cue_sections(cues: Vec<Cue>) -> Vec<ContentSection> {
+  let mut sections: Vec<ContentSection> = vec![];
+  let mut previous_text = String::new();
+  for cue in cues {
+    if cue.text == previous_text {
+      continue;
+    }
+    sections.push(ContentSection {
+      label: format_timestamp(cue.start),
+      // 0 means "no section", so a cue at the very beginning is number 1
+      number: (cue.start as i64).max(1),
+      text: cue.text.clone(),
+      fields: vec![],
+    });
+    previous_text = cue.text;
+  }
+  sections
+}
+
+// 75.4 -> "1:15", 3723.0 -> "1:02:03"
+fn format_timestamp(seconds: f64) -> String {
+  let seconds = seconds as i64;
+  let (hours, minutes, seconds) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+  if hours > 0 {
+    format!("{}:{:02}:{:02}", hours, minutes, seconds)
+  } else {
+    format!("{}:{:02}", minutes, seconds)
+  }
+}
@@ -0,0 +1,133 @@
+// Extract text from a LaTeX (.tex) file
+// Commands and environments are stripped and only their readable arguments are kept.
+// \title, \section (and its sub-levels) and the abstract are also recorded as fields: title, headings and abstract
+use std::error::Error;
+use std::fs::read_to_string;
+use lazy_static::lazy_static;
+use regex::Regex;
+use crate::text_extraction::ExtractedContent;
+
+lazy_static! {
+  // % starts a comment unless it is escaped as \%
+  static ref COMMENT: Regex = Regex::new(r"(?m)(^|[^\\])%.*$").unwrap();
+  static ref TITLE: Regex = Regex::new(r"\\title\s*(\[[^\]]*\])?\s*\{").unwrap();
+  static ref SECTION: Regex = Regex::new(r"\\(part|chapter|section|subsection|subsubsection|paragraph)\*?\s*(\[[^\]]*\])?\s*\{").unwrap();
+  static ref ABSTRACT: Regex = Regex::new(r"(?s)\\begin\{abstract\}(.*?)\\end\{abstract\}").unwrap();
+  // environments whose content is not prose
+  static ref IGNORED_ENVIRONMENT: Regex = Regex::new(r"(?s)\\begin\{(equation|align|figure|tikzpicture|verbatim|lstlisting|minted|table)\*?\}.*?\\end\{(equation|align|figure|tikzpicture|verbatim|lstlisting|minted|table)\*?\}").unwrap();
+  static ref DISPLAY_MATH: Regex = Regex::new(r"(?s)\$\$.*?\$\$|\\\[.*?\\\]").unwrap();
+  // commands whose arguments are references or layout, not text
+  static ref IGNORED_COMMAND: Regex = Regex::new(r"\\(label|ref|eqref|cite[a-z]*|usepackage|documentclass|bibliographystyle|bibliography|includegraphics|input|include|url|begin|end|vspace|hspace|setlength|newcommand|renewcommand)\*?(\[[^\]]*\])*(\{[^{}]*\})*").unwrap();
+  static ref COMMAND: Regex = Regex::new(r"\\[A-Za-z@]+\*?(\[[^\]]*\])?").unwrap();
+  static ref ESCAPED: Regex = Regex::new(r"\\([%&$#_{}])").unwrap();
+  static ref WHITESPACE: Regex = Regex::new(r"[ \t]+").unwrap();
+}
+
+pub fn extract(file: &String, _app: &tauri::AppHandle) -> Result<ExtractedContent, Box<dyn Error>> {
+  let source = read_to_string(file)?;
+  Ok(extract_from_source(&source))
+}
+
+fn extract_from_source(source: &str) -> ExtractedContent {
+  let source = COMMENT.replace_all(source, "$1").to_string();
+
+  let mut fields: Vec<(String, String)> = vec![];
+  for title in TITLE.find_iter(&source) {
+    if let Some(title) = braced_argument(&source, title.end()) {
+      fields.push(("title".to_string(), strip_commands(title)));
+    }
+  }
+  for section in SECTION.find_iter(&source) {
+    if let Some(heading) = braced_argument(&source, section.end()) {
+      fields.push(("headings".to_string(), strip_commands(heading)));
+    }
+  }
+  for abstract_text in ABSTRACT.captures_iter(&source) {
+    fields.push(("abstract".to_string(), strip_commands(&abstract_text[1])));
+  }
+  // \abstract{...} is used by some document classes instead of the environment
+  if let Some(abstract_command) = source.find("\\abstract{") {
+    if let Some(abstract_text) = braced_argument(&source, abstract_command + "\\abstract{".len()) {
+      fields.push(("abstract".to_string(), strip_commands(abstract_text)));
+    }
+  }
+  fields.retain(|(_, value)| !value.is_empty());
+
+  // only the document body is text, the preamble holds package settings and macros
+  let body = match (source.find("\\begin{document}"), source.find("\\end{document}")) {
+    (Some(start), Some(end)) if start < end => &source[start + "\\begin{document}".len()..end],
+    (Some(start), None) => &source[start + "\\begin{document}".len()..],
+    _ => source.as_str(),
+  };
+  let mut text = strip_commands(body);
+  // the title is usually set in the preamble, so it is added in front of the body
+  for (_, title) in fields.iter().filter(|(name, _)| name == "title") {
+    if !text.contains(title.as_str()) {
+      text = format!("{}\n\n{}", title, text);
+    }
+  }
+
+  ExtractedContent {
+    text,
+    fields,
+    sections: vec![],
+    language: None,
+  }
+}
+
+// The text inside the braces that open just before `start`, with nested braces kept
+fn braced_argument(source: &str, start: usize) -> Option<&str> {
+  let mut depth = 1;
+  for (offset, c) in source[start..].char_indices() {
+    match c {
+      '{' => depth += 1,
+      '}' => {
+        depth -= 1;
+        if depth == 0 {
+          return Some(&source[start..start + offset]);
+        }
+      }
+      _ => {}
+    }
+  }
+  None
+}
+
+// Remove commands but keep the text of their arguments, e.g. \emph{word} -> word
+fn strip_commands(latex: &str) -> String {
+  let text = IGNORED_ENVIRONMENT.replace_all(latex, " ");
+  let text = DISPLAY_MATH.replace_all(&text, " ");
+  let text = IGNORED_COMMAND.replace_all(&text, "");
+  // escaped braces and dollars are kept aside so they survive the removal of grouping braces and math
+  let text = ESCAPED.replace_all(&text, |escaped: &regex::Captures| {
+    match &escaped[1] {
+      "{" => "\u{E000}",
+      "}" => "\u{E001}",
+      "$" => "\u{E002}",
+      other => other,
+    }.to_string()
+  });
+  let text = COMMAND.replace_all(&text, " ");
+  let text = text
+    .replace("\\\\", "\n")
+    .replace(['{', '}', '$'], "")
+    .replace('~', " ")
+    .replace("``", "\"")
+    .replace("''", "\"")
+    .replace("---", "—")
+    .replace("--", "–")
+    .replace('\u{E000}', "{")
+    .replace('\u{E001}', "}")
+    .replace('\u{E002}', "$");
+  let text = WHITESPACE.replace_all(&text, " ");
+  text
+    .lines()
+    .map(|line| line.trim())
+    .collect::<Vec<&str>>()
+    .join("\n")
+    .split("\n\n")
+    .map(|paragraph| paragraph.trim())
+    .filter(|paragraph| !paragraph.is_empty())
+    .collect::<Vec<&str>>()
+    .join("\n\n")
+}
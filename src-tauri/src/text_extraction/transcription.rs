@@ -0,0 +1,118 @@
+// Transcribe audio and video with whisper.cpp, bundled as the whisper-cli sidecar
+// Nothing is downloaded: a ggml model (e.g. ggml-base.bin) is looked up in <app directory>/whisper. Files that whisper.cpp
+// cannot read are converted to 16 kHz mono WAV with ffmpeg first, from the same folder or PATH. The transcript is written
+// as SRT and grouped into timestamped sections like subtitles.
+// Recordings have their own queue that runs after the text of every other file is indexed (see indexing.rs)
+use std::error::Error;
+use std::path::Path;
+use tauri_plugin_shell::{ShellExt, process::CommandEvent};
+use crate::housekeeping::get_app_directory;
+use crate::text_extraction::{subtitles, ExtractedContent};
+
+pub const TRANSCRIPTION_FILETYPES: [&str; 4] = ["mp3", "wav", "m4a", "mp4"];
+
+pub async fn extract(file: &String, app: &tauri::AppHandle) -> Result<ExtractedContent, Box<dyn Error>> {
+  let transcript = transcribe(file, app).await?;
+  let sections = subtitles::sections_from_srt(&transcript);
+  Ok(ExtractedContent {
+    text: sections.iter().map(|section| section.text.as_str()).collect::<Vec<&str>>().join("\n"),
+    fields: vec![],
+    sections,
+    language: None,
+  })
+}
+
+// Transcription needs a model, the queue doesn't start without one
+pub fn is_available() -> bool {
+  find_model(&whisper_directory()).is_some()
+}
+
+fn whisper_directory() -> String {
+  format!("{}/whisper", get_app_directory())
+}
+
+// Returns the SRT transcript of the file
+async fn transcribe(file: &str, app: &tauri::AppHandle) -> Result<String, Box<dyn Error>> {
+  let whisper_directory = whisper_directory();
+  let model = find_model(&whisper_directory).ok_or("No whisper.cpp model (ggml-*.bin) found")?;
+
+  let app_directory = get_app_directory();
+  let wav_path = format!("{}/temp_transcription.wav", app_directory);
+  let output_base = format!("{}/temp_transcription", app_directory);
+  let srt_path = format!("{}.srt", output_base);
+
+  // whisper.cpp reads 16 kHz WAV, everything else goes through ffmpeg
+  let input = match find_ffmpeg(&whisper_directory) {
+    Some(ffmpeg) => {
+      let converted = tokio::process::Command::new(ffmpeg)
+        .args(["-y", "-loglevel", "error", "-i", file, "-ar", "16000", "-ac", "1", "-c:a", "pcm_s16le", wav_path.as_str()])
+        .output()
+        .await
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+      if !converted {
+        return Err(format!("ffmpeg could not convert {}", file).into());
+      }
+      wav_path.clone()
+    }
+    None if file.to_lowercase().ends_with(".wav") => file.to_string(),
+    None => return Err("ffmpeg is needed to transcribe files other than WAV".into()),
+  };
+
+  // half of the cores, so that the rest of the computer stays usable
+  let threads = std::thread::available_parallelism().map(|cores| (cores.get() / 2).max(1)).unwrap_or(1).to_string();
+  let sidecar_command = app
+    .shell()
+    .sidecar("whisper-cli")?
+    .args(["-m", model.as_str(), "-f", input.as_str(), "-osrt", "-of", output_base.as_str(), "-t", threads.as_str(), "-np"]);
+  let (mut rx, _child) = sidecar_command.spawn()?;
+  // and the lowest priority, whisper runs for about as long as the recording
+  #[cfg(unix)]
+  let _ = std::process::Command::new("renice").args(["-n", "19", "-p", &_child.pid().to_string()]).output();
+
+  let mut succeeded = false;
+  let mut stderr = String::new();
+  while let Some(event) = rx.recv().await {
+    match event {
+      CommandEvent::Stderr(line) => stderr.push_str(&String::from_utf8_lossy(&line)),
+      CommandEvent::Terminated(payload) => {
+        succeeded = payload.code == Some(0);
+        break;
+      }
+      _ => {}
+    }
+  }
+
+  let transcript: Result<String, Box<dyn Error>> = if succeeded {
+    std::fs::read_to_string(&srt_path).map_err(|e| e.into())
+  } else {
+    Err(format!("whisper.cpp could not transcribe {}: {}", file, stderr.trim()).into())
+  };
+  let _ = std::fs::remove_file(&wav_path);
+  let _ = std::fs::remove_file(&srt_path);
+  transcript
+}
+
+// ffmpeg in the whisper directory, or else on PATH
+fn find_ffmpeg(whisper_directory: &str) -> Option<String> {
+  let local = Path::new(whisper_directory).join(format!("ffmpeg{}", std::env::consts::EXE_SUFFIX));
+  if local.is_file() {
+    return Some(local.to_string_lossy().to_string());
+  }
+  std::process::Command::new("ffmpeg").arg("-version").output().ok().map(|_| "ffmpeg".to_string())
+}
+
+// The first ggml model in the whisper directory, e.g. ggml-base.en.bin
+fn find_model(whisper_directory: &str) -> Option<String> {
+  let mut models: Vec<String> = std::fs::read_dir(whisper_directory)
+    .ok()?
+    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+    .filter(|path| {
+      let name = path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+      name.starts_with("ggml-") && name.ends_with(".bin")
+    })
+    .map(|path| path.to_string_lossy().to_string())
+    .collect();
+  models.sort();
+  models.into_iter().next()
+}
@@ -0,0 +1,88 @@
+// Extract the contacts of a vCard (.vcf) file, one section per contact
+// Each section is labelled with the contact's name and carries their emails, phones and organization,
+// which are also used to store every contact as its own item in the `contact` table
+use std::error::Error;
+use std::fs::read_to_string;
+use crate::text_extraction::vobject::{components, split_structured, unescape_text, ContentLine};
+use crate::text_extraction::{ContentSection, ExtractedContent};
+
+pub fn extract(file: &String, _app: &tauri::AppHandle) -> Result<ExtractedContent, Box<dyn Error>> {
+  let source = read_to_string(file)?;
+  let sections: Vec<ContentSection> = components(&source, "VCARD")
+    .into_iter()
+    .enumerate()
+    .map(|(index, lines)| contact_section(&lines, index as i64 + 1))
+    .collect();
+
+  Ok(ExtractedContent {
+    text: sections.iter().map(|section| section.text.as_str()).collect::<Vec<&str>>().join("\n\n"),
+    fields: vec![],
+    sections,
+    language: None,
+  })
+}
+
+fn contact_section(lines: &Vec<ContentLine>, number: i64) -> ContentSection {
+  let values = |name: &str| -> Vec<String> {
+    lines
+      .iter()
+      .filter(|line| line.name == name)
+      .map(|line| unescape_text(&line.value))
+      .filter(|value| !value.is_empty())
+      .collect()
+  };
+  let emails = values("EMAIL");
+  let phones: Vec<String> = values("TEL").into_iter().map(|phone| phone.trim_start_matches("tel:").to_string()).collect();
+  let job_title = values("TITLE").join(", ");
+  let notes = values("NOTE").join("\n");
+  // ORG is "Company;Department"
+  let organization = lines
+    .iter()
+    .find(|line| line.name == "ORG")
+    .map(|line| split_structured(&line.value).into_iter().filter(|unit| !unit.is_empty()).collect::<Vec<String>>().join(", "))
+    .unwrap_or_default();
+  let name = contact_name(lines)
+    .or_else(|| emails.first().cloned())
+    .or_else(|| (!organization.is_empty()).then(|| organization.clone()))
+    .unwrap_or_else(|| format!("contact {}", number));
+
+  let mut fields: Vec<(String, String)> = vec![
+    ("section_title".to_string(), name.clone()),
+    ("organization".to_string(), organization.clone()),
+  ];
+  for email in &emails {
+    fields.push(("emails".to_string(), email.clone()));
+  }
+  for phone in &phones {
+    fields.push(("phones".to_string(), phone.clone()));
+  }
+  fields.retain(|(_, value)| !value.is_empty());
+
+  let text = [name.clone(), job_title, organization, emails.join(", "), phones.join(", "), notes]
+    .into_iter()
+    .filter(|line| !line.is_empty())
+    .collect::<Vec<String>>()
+    .join("\n");
+  ContentSection {
+    label: name,
+    number,
+    text,
+    fields,
+  }
+}
+
+// FN, or else N ("Last;First;Middle;Prefix;Suffix") put in reading order
+fn contact_name(lines: &Vec<ContentLine>) -> Option<String> {
+  let formatted_name = lines.iter().find(|line| line.name == "FN").map(|line| unescape_text(&line.value)).filter(|name| !name.is_empty());
+  if formatted_name.is_some() {
+    return formatted_name;
+  }
+  let parts = split_structured(&lines.iter().find(|line| line.name == "N")?.value);
+  let part = |index: usize| parts.get(index).cloned().unwrap_or_default();
+  let name = [part(3), part(1), part(2), part(0), part(4)]
+    .into_iter()
+    .filter(|part| !part.is_empty())
+    .collect::<Vec<String>>()
+    .join(" ");
+  (!name.is_empty()).then_some(name)
+}
@@ -0,0 +1,155 @@
+// Content lines of iCalendar (.ics) and vCard (.vcf) files
+// Both formats are lines of `NAME;PARAM=value:VALUE`, folded onto continuation lines that start with a space or a tab,
+// and grouped into BEGIN:X / END:X components
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default)]
+pub struct ContentLine {
+  // upper case and without a vCard group prefix (item1.EMAIL -> EMAIL)
+  pub name: String,
+  // parameter names are upper case, e.g. CN, TZID, TYPE, VALUE
+  pub params: HashMap<String, String>,
+  pub value: String,
+}
+
+impl ContentLine {
+  pub fn param(&self, name: &str) -> Option<&str> {
+    self.params.get(name).map(|value| value.as_str())
+  }
+}
+
+// The content lines of every `component` (e.g. VEVENT or VCARD), in file order
+// Nested components (e.g. the VALARM of an event) are skipped
+pub fn components(source: &str, component: &str) -> Vec<Vec<ContentLine>> {
+  let mut components: Vec<Vec<ContentLine>> = vec![];
+  let mut current: Option<Vec<ContentLine>> = None;
+  let mut nested_depth = 0;
+  for line in content_lines(source) {
+    let is_component = line.value.eq_ignore_ascii_case(component);
+    match line.name.as_str() {
+      "BEGIN" if is_component && current.is_none() => current = Some(vec![]),
+      "END" if is_component && nested_depth == 0 => {
+        if let Some(lines) = current.take() {
+          components.push(lines);
+        }
+      }
+      "BEGIN" if current.is_some() => nested_depth += 1,
+      "END" if current.is_some() => nested_depth -= 1,
+      _ => {
+        if let Some(lines) = current.as_mut() {
+          if nested_depth == 0 {
+            lines.push(line);
+          }
+        }
+      }
+    }
+  }
+  components
+}
+
+fn content_lines(source: &str) -> Vec<ContentLine> {
+  let mut unfolded: Vec<String> = vec![];
+  for line in source.trim_start_matches('\u{feff}').lines() {
+    let line = line.trim_end_matches('\r');
+    match unfolded.last_mut() {
+      Some(previous) if line.starts_with(' ') || line.starts_with('\t') => previous.push_str(&line[1..]),
+      // vCard 2.1 continues quoted-printable values with a soft line break (= at the end of the line)
+      Some(previous) if previous.ends_with('=') && previous.to_uppercase().contains("QUOTED-PRINTABLE") => {
+        previous.pop();
+        previous.push_str(line);
+      }
+      _ => unfolded.push(line.to_string()),
+    }
+  }
+  unfolded.iter().filter_map(|line| parse_content_line(line)).collect()
+}
+
+fn parse_content_line(line: &str) -> Option<ContentLine> {
+  // the value starts at the first colon outside of a quoted parameter value
+  let mut in_quotes = false;
+  let colon = line.char_indices().find(|(_, c)| {
+    if *c == '"' {
+      in_quotes = !in_quotes;
+    }
+    *c == ':' && !in_quotes
+  })?.0;
+  let (name_and_params, value) = (&line[..colon], &line[colon + 1..]);
+  let mut parts = name_and_params.split(';');
+  let name = parts.next()?.trim();
+  let name = name.rsplit('.').next().unwrap_or(name).to_uppercase();
+  let mut params: HashMap<String, String> = HashMap::new();
+  for param in parts {
+    match param.split_once('=') {
+      Some((param_name, param_value)) => {
+        params.insert(param_name.trim().to_uppercase(), param_value.trim_matches('"').to_string());
+      }
+      // vCard 2.1 writes bare types, e.g. TEL;CELL;QUOTED-PRINTABLE
+      None if param.eq_ignore_ascii_case("QUOTED-PRINTABLE") => {
+        params.insert("ENCODING".to_string(), "QUOTED-PRINTABLE".to_string());
+      }
+      None => {
+        params.insert("TYPE".to_string(), param.to_string());
+      }
+    }
+  }
+  let value = if params.get("ENCODING").map(|encoding| encoding.eq_ignore_ascii_case("QUOTED-PRINTABLE")).unwrap_or(false) {
+    decode_quoted_printable(value)
+  } else {
+    value.to_string()
+  };
+  Some(ContentLine { name, params, value })
+}
+
+// Text values escape newlines, commas, semicolons and backslashes: \n \, \; \\
+pub fn unescape_text(value: &str) -> String {
+  let mut text = String::new();
+  let mut chars = value.chars();
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      text.push(c);
+      continue;
+    }
+    match chars.next() {
+      Some('n') | Some('N') => text.push('\n'),
+      Some(escaped) => text.push(escaped),
+      None => {}
+    }
+  }
+  text.trim().to_string()
+}
+
+// Structured values (e.g. N and ORG) are separated by unescaped semicolons
+pub fn split_structured(value: &str) -> Vec<String> {
+  let mut parts: Vec<String> = vec![String::new()];
+  let mut escaped = false;
+  for c in value.chars() {
+    if c == ';' && !escaped {
+      parts.push(String::new());
+      continue;
+    }
+    escaped = c == '\\' && !escaped;
+    if let Some(part) = parts.last_mut() {
+      part.push(c);
+    }
+  }
+  parts.iter().map(|part| unescape_text(part)).collect()
+}
+
+fn decode_quoted_printable(value: &str) -> String {
+  let bytes = value.as_bytes();
+  let mut decoded: Vec<u8> = vec![];
+  let mut index = 0;
+  while index < bytes.len() {
+    if bytes[index] == b'=' && index + 2 < bytes.len() {
+      let hex = std::str::from_utf8(&bytes[index + 1..index + 3]).unwrap_or("");
+      if let Ok(byte) = u8::from_str_radix(hex, 16) {
+        decoded.push(byte);
+        index += 3;
+        continue;
+      }
+    }
+    decoded.push(bytes[index]);
+    index += 1;
+  }
+  String::from_utf8_lossy(&decoded).to_string()
+}
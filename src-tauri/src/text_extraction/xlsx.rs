@@ -1,11 +1,20 @@
 use dotext::*;
 use std::io::Read;
 use std::error::Error;
+use crate::text_extraction::encryption::decrypt_office_file;
+use crate::text_extraction::ooxml::{part_names, read_part, Package};
+use xml::reader::{EventReader, XmlEvent};
 
-pub fn extract(file: &String, _app: &tauri::AppHandle) -> Result<String, Box<dyn Error>> {
-  let mut file_buffer = Xlsx::open(file)?;
-  let mut text = String::new();
-  file_buffer.read_to_string(&mut text)?;
+pub fn extract(file: &String, app: &tauri::AppHandle) -> Result<String, Box<dyn Error>> {
+  let mut text = match decrypt_office_file(file, app)? {
+    Some(decrypted) => decrypted_workbook_text(decrypted)?,
+    None => {
+      let mut file_buffer = Xlsx::open(file)?;
+      let mut text = String::new();
+      file_buffer.read_to_string(&mut text)?;
+      text
+    }
+  };
   // remove all numbers from the text
   text = text.chars().filter(|c| !c.is_numeric()).collect();
   // keep only unique words
@@ -17,4 +26,42 @@ pub fn extract(file: &String, _app: &tauri::AppHandle) -> Result<String, Box<dyn
     }
   }
   Ok(text)
-}
\ No newline at end of file
+}
+
+// dotext only opens files, so a decrypted workbook is read from memory: the shared strings hold the text of the cells
+// and inline strings are in the sheets themselves
+fn decrypted_workbook_text(decrypted: Vec<u8>) -> Result<String, Box<dyn Error>> {
+  let mut archive = Package::new(std::io::Cursor::new(decrypted))?;
+  let mut parts = vec!["xl/sharedStrings.xml".to_string()];
+  parts.extend(part_names(&archive, "xl/worksheets/sheet"));
+  let text = parts
+    .iter()
+    .filter_map(|name| read_part(&mut archive, name))
+    .map(|xml| cell_strings(&xml))
+    .collect::<Vec<String>>()
+    .join("\n");
+  Ok(text)
+}
+
+// The `t` elements of a part, one per line (a shared string item or inline string can hold several runs)
+fn cell_strings(xml: &str) -> String {
+  let mut inside_text = false;
+  let mut text = String::new();
+  for event in EventReader::new(xml.as_bytes()) {
+    match event {
+      Ok(XmlEvent::StartElement { name, .. }) if name.local_name == "t" => inside_text = true,
+      Ok(XmlEvent::Characters(data)) | Ok(XmlEvent::Whitespace(data)) if inside_text => text.push_str(&data),
+      Ok(XmlEvent::EndElement { name }) => match name.local_name.as_str() {
+        "t" => inside_text = false,
+        "si" | "is" => text.push('\n'),
+        _ => {}
+      },
+      Err(e) => {
+        println!("Error reading XML part: {}", e);
+        break;
+      }
+      _ => {}
+    }
+  }
+  text
+}
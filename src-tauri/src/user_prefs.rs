@@ -1,10 +1,12 @@
 // Handles for User Preferences and App Data
 
 use diesel::{Connection, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection};
-use crate::custom_types::{UserPreferencesState, SyncRunningState};
+use crate::custom_types::{RankingWeights, UserPreferencesState, SyncRunningState};
 use crate::database::models::{AppData, UserPrefs, FileTypes};
-use crate::database::schema::{app_data, user_preferences, file_types};
+use crate::database::schema::{app_data, document, user_preferences, file_types};
 use crate::database::establish_connection;
+use crate::language::parse_languages_string;
+use crate::text_extraction::transcription::TRANSCRIPTION_FILETYPES;
 use crate::utils::string_to_modifiers;
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::str::FromStr;
@@ -29,6 +31,9 @@ pub fn set_default_user_prefs(conn: &mut SqliteConnection, reset_settings_flag:
         user_preferences::roadmap_survey_answered.eq(false),
         user_preferences::parse_pdfs.eq(false),
         user_preferences::manual_setup.eq(false),
+        user_preferences::search_languages.eq("en"),
+        user_preferences::transcribe_audio.eq(false),
+        user_preferences::ranking_weights.eq(""),
       ))
       .execute(conn)
       .unwrap();
@@ -54,6 +59,9 @@ pub fn set_default_user_prefs(conn: &mut SqliteConnection, reset_settings_flag:
       roadmap_survey_answered: false,
       parse_pdfs: false,
       manual_setup: false,
+      search_languages: "en".to_string(),
+      transcribe_audio: false,
+      ranking_weights: "".to_string(),
     };
     // insert new_user_prefs into the user_prefs table
     diesel::insert_into(user_preferences::table)
@@ -91,11 +99,15 @@ pub fn set_default_app_data(conn: &mut SqliteConnection) {
 }
 
 pub fn set_default_file_types(conn: &mut SqliteConnection) {
-  const DOCUMENT_FILETYPES: [&str; 11] = ["csv", "docx", "key", "md", "numbers", "pages", "pdf", "pptx", "txt", "xlsx", "xls"];
+  const DOCUMENT_FILETYPES: [&str; 15] = ["csv", "docx", "key", "md", "numbers", "pages", "pdf", "pptx", "txt", "xlsx", "xls", "tex", "bib", "ics", "vcf"];
   const IMAGE_FILETYPES: [&str; 4] = ["jpg", "jpeg", "png", "gif"];
-  const BOOK_FILETYPES: [&str; 4] = ["epub", "mobi", "azw3", "pdf"];
-  const AUDIO_FILETYPES: [&str; 5] = ["mp3", "wav", "aac", "flac", "ogg"];
+  const BOOK_FILETYPES: [&str; 6] = ["epub", "mobi", "azw3", "pdf", "clippings", "koreader"];
+  const AUDIO_FILETYPES: [&str; 6] = ["mp3", "wav", "aac", "flac", "ogg", "m4a"];
   const VIDEO_FILETYPES: [&str; 5] = ["mp4", "mkv", "avi", "mov", "wmv"];
+  const CODE_FILETYPES: [&str; 4] = ["rs", "py", "ts", "ipynb"];
+  // (file_type, file_type_category) pairs added after the first release
+  // these are also inserted for existing users if they are missing
+  const ADDED_FILETYPES: [(&str, &str); 11] = [("rs", "code"), ("py", "code"), ("ts", "code"), ("ipynb", "code"), ("tex", "document"), ("bib", "document"), ("m4a", "audio"), ("ics", "document"), ("vcf", "document"), ("clippings", "book"), ("koreader", "book")];
 
   // get the first row from file_types table
   let existing_file_types = file_types::table
@@ -151,6 +163,15 @@ pub fn set_default_file_types(conn: &mut SqliteConnection) {
       };
       new_file_types.push(new_file_type);
     }
+    for file_type in CODE_FILETYPES.iter() {
+      let new_file_type = FileTypes {
+        file_type: file_type.to_string(),
+        file_type_category: "code".to_string(),
+        file_type_allowed: true,
+        added_by_user: false,
+      };
+      new_file_types.push(new_file_type);
+    }
 
     // Add folder filetype
     let new_file_type = FileTypes {
@@ -169,6 +190,27 @@ pub fn set_default_file_types(conn: &mut SqliteConnection) {
               .execute(conn)
       })
       .unwrap();
+  } else {
+    let known_file_types = file_types::table
+      .select(file_types::file_type)
+      .load::<String>(conn)
+      .expect("Error loading file_types");
+    let missing_file_types: Vec<FileTypes> = ADDED_FILETYPES
+      .iter()
+      .filter(|(file_type, _)| !known_file_types.contains(&file_type.to_string()))
+      .map(|(file_type, file_type_category)| FileTypes {
+        file_type: file_type.to_string(),
+        file_type_category: file_type_category.to_string(),
+        file_type_allowed: true,
+        added_by_user: false,
+      })
+      .collect();
+    if missing_file_types.len() > 0 {
+      diesel::insert_into(file_types::table)
+        .values(missing_file_types)
+        .execute(conn)
+        .unwrap();
+    }
   }
 }
 
@@ -223,7 +265,10 @@ pub fn set_user_preferences_state_from_db_value(app: &tauri::AppHandle) {
       user_preferences::detailed_scan,
       user_preferences::roadmap_survey_answered,
       user_preferences::parse_pdfs,
-      user_preferences::manual_setup
+      user_preferences::manual_setup,
+      user_preferences::search_languages,
+      user_preferences::transcribe_audio,
+      user_preferences::ranking_weights
     ))
     .first::<UserPrefs>(&mut conn)
     .expect("Error loading user_prefs");
@@ -241,6 +286,9 @@ pub fn set_user_preferences_state_from_db_value(app: &tauri::AppHandle) {
   state.roadmap_survey_answered = user_preferences_from_db.roadmap_survey_answered;
   state.parse_pdfs = user_preferences_from_db.parse_pdfs;
   state.manual_setup = user_preferences_from_db.manual_setup;
+  state.search_languages = user_preferences_from_db.search_languages;
+  state.transcribe_audio = user_preferences_from_db.transcribe_audio;
+  state.ranking_weights = user_preferences_from_db.ranking_weights;
 }
 
 pub fn fix_global_shortcut_string(new_shortcut_string: String) -> String {
@@ -322,6 +370,22 @@ pub fn set_parse_pdfs_flag_in_db(flag: bool, app: &tauri::AppHandle) {
     .unwrap();
 }
 
+// Turning transcription on clears last_parsed of audio and video files, so that the ones parsed before
+// (with no text, or only their subtitles) are transcribed in the next parse
+pub fn set_transcribe_audio_flag_in_db(flag: bool, app: &tauri::AppHandle) {
+  let mut conn = establish_connection(&app);
+  let _ = diesel::update(user_preferences::table)
+    .set(user_preferences::transcribe_audio.eq(flag))
+    .execute(&mut conn)
+    .unwrap();
+  if flag {
+    let _ = diesel::update(document::table)
+      .filter(document::file_type.eq_any(TRANSCRIPTION_FILETYPES))
+      .set(document::last_parsed.eq(0))
+      .execute(&mut conn);
+  }
+}
+
 pub fn set_manual_setup_flag_in_db(flag: bool, app: &tauri::AppHandle) {
   let mut conn = establish_connection(&app);
   let _ = diesel::update(user_preferences::table)
@@ -346,6 +410,37 @@ pub fn set_automatic_background_sync_flag_in_db(flag: bool, app: &tauri::AppHand
     .unwrap();
 }
 
+pub fn set_search_languages_in_db(languages: String, app: &tauri::AppHandle) {
+  let mut conn = establish_connection(&app);
+  let _ = diesel::update(user_preferences::table)
+    .set(user_preferences::search_languages.eq(languages))
+    .execute(&mut conn)
+    .unwrap();
+}
+
+// Languages used to analyse search queries (ISO 639-1 codes)
+pub fn get_search_languages(app: &tauri::AppHandle) -> Vec<String> {
+  let state_mutex = app.state::<Mutex<UserPreferencesState>>();
+  let state = state_mutex.lock().unwrap();
+  parse_languages_string(&state.search_languages)
+}
+
+pub fn set_ranking_weights_in_db(ranking_weights: &RankingWeights, app: &tauri::AppHandle) {
+  let ranking_weights_string = serde_json::to_string(ranking_weights).unwrap_or_default();
+  let mut conn = establish_connection(&app);
+  let _ = diesel::update(user_preferences::table)
+    .set(user_preferences::ranking_weights.eq(ranking_weights_string))
+    .execute(&mut conn)
+    .unwrap();
+}
+
+// The weights of the ranking signals, the defaults until the user changes them
+pub fn get_ranking_weights(app: &tauri::AppHandle) -> RankingWeights {
+  let state_mutex = app.state::<Mutex<UserPreferencesState>>();
+  let state = state_mutex.lock().unwrap();
+  serde_json::from_str(&state.ranking_weights).unwrap_or_default()
+}
+
 pub fn is_global_shortcut_enabled(app: &tauri::AppHandle) -> bool {
   let state_mutex = app.state::<Mutex<UserPreferencesState>>();
   let state = state_mutex.lock().unwrap();
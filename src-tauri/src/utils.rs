@@ -128,50 +128,36 @@ pub fn _install_textra_from_github() -> Result<String, Error> {
   }
 }
 
+// The bin folder of a poppler install that winocr can render PDF pages with: the copy downloaded into the app directory,
+// or one on the PATH
 #[cfg(target_os = "windows")]
-pub async fn install_poppler_from_github() -> Result<String, Error> {
-  #[cfg(not(target_os = "windows"))]
-  {
-    Ok("WinOCR is only supported on Windows".to_string())
+pub fn local_poppler_path() -> Option<String> {
+  let app_directory_poppler = format!("{}\\poppler-24.02.0\\Library\\bin", get_app_directory());
+  let mut candidates = vec![std::path::PathBuf::from(app_directory_poppler)];
+  if let Some(path_variable) = std::env::var_os("PATH") {
+    candidates.extend(std::env::split_paths(&path_variable));
   }
-  #[cfg(target_os = "windows")]
-  {
-    let download_uri = "https://github.com/oschwartz10612/poppler-windows/releases/download/v24.02.0-0/Release-24.02.0-0.zip";
-    let app_directory = get_app_directory();
-    // check if poppler exe exists
-    let poppler_path = format!("{}\\poppler-24.02.0\\Library\\bin\\pdftoppm.exe", app_directory);
-    let poppler_exists = Path::new(&poppler_path).exists();
-    println!("Poppler exists: {}", poppler_exists);
-    if poppler_exists {
-      println!("Poppler is already installed");
-      return Ok("Poppler is already installed".to_string());
-    } else {
-      println!("Downloading and installing Poppler");
-      // download poppler using reqwest
-      let app_directory_clone = app_directory.clone();
-      let response = reqwest::get(download_uri).await.unwrap();
-      let mut file = std::fs::File::create(format!("{}\\poppler.zip", &app_directory_clone)).unwrap();
-      let mut content =  std::io::Cursor::new(response.bytes().await.unwrap());
-      std::io::copy(&mut content, &mut file).unwrap();
+  candidates
+    .into_iter()
+    .find(|folder| folder.join("pdftoppm.exe").exists())
+    .map(|folder| folder.to_string_lossy().to_string())
+}
 
-      // let zip_file_path = handle.path().resolve("poppler.zip", BaseDirectory::Resource)?;
-      // // copy zip_file to app_directory
-      // fs::copy(zip_file_path, format!("{}/poppler.zip", &app_directory)).unwrap();
+// Downloads poppler into the app directory, for when there is no copy yet
+#[cfg(target_os = "windows")]
+pub async fn install_poppler_from_github() -> Result<(), Box<dyn std::error::Error>> {
+  let download_uri = "https://github.com/oschwartz10612/poppler-windows/releases/download/v24.02.0-0/Release-24.02.0-0.zip";
+  let app_directory = get_app_directory();
+  let zip_path = format!("{}\\poppler.zip", app_directory);
+  println!("Downloading and installing Poppler");
+  let response = reqwest::get(download_uri).await?.error_for_status()?;
+  let mut content = io::Cursor::new(response.bytes().await?);
+  io::copy(&mut content, &mut fs::File::create(&zip_path)?)?;
 
-      // unzip poppler.zip using zip crate
-      let mut archive = zip::ZipArchive::new(fs::File::open(format!("{}/poppler.zip", &app_directory)).unwrap()).unwrap();
-      archive.extract(&app_directory).unwrap();
-      // remove poppler.zip
-      fs::remove_file(format!("{}\\poppler.zip", &app_directory)).unwrap();
-      // check if poppler exe exists
-      let poppler_path = format!("{}\\poppler-24.02.0\\Library\\bin\\pdftoppm.exe", &app_directory);
-      if Path::new(&poppler_path).exists() {
-        return Ok("Poppler is installed".to_string());
-      } else {
-        return Ok("Failed to install Poppler".to_string());
-      }
-    }
-  }
+  // the archive is closed before the zip is removed, Windows doesn't delete open files
+  zip::ZipArchive::new(fs::File::open(&zip_path)?)?.extract(&app_directory)?;
+  fs::remove_file(&zip_path)?;
+  Ok(())
 }
 
 pub async fn extract_text_from_pdf(file_path: String, conn: &mut SqliteConnection, app: &tauri::AppHandle) -> Result<Vec<String>, Error> {
@@ -0,0 +1,49 @@
+-- ./field-notes.sdr/metadata.pdf.lua
+return {
+    ["bookmarks"] = {
+        [1] = {
+            ["datetime"] = "2023-05-01 09:12:44",
+            ["highlighted"] = true,
+            ["notes"] = "Soil samples from the north field",
+            ["page"] = 15,
+            ["text"] = "Ask Dana about the pH readings",
+        },
+        [2] = {
+            ["datetime"] = "2023-05-02 17:40:03",
+            ["highlighted"] = true,
+            ["notes"] = "Rainfall was twice the average",
+            ["page"] = 3,
+            ["text"] = "Page 3 Rainfall was twice the average @ 2023-05-02 17:40:03",
+        },
+    },
+    ["highlight"] = {
+        [15] = {
+            [1] = {
+                ["chapter"] = "Results",
+                ["datetime"] = "2023-05-01 09:12:44",
+                ["drawer"] = "underscore",
+                ["pos0"] = { ["page"] = 15, ["x"] = 80.5, ["y"] = 312 },
+                ["pos1"] = { ["page"] = 15, ["x"] = 402, ["y"] = 330.25 },
+                ["text"] = "Soil samples from the north field",
+            },
+        },
+        [3] = {
+            [1] = {
+                ["chapter"] = "Methods",
+                ["datetime"] = "2023-05-02 17:40:03",
+                ["drawer"] = "lighten",
+                ["pos0"] = { ["page"] = 3, ["x"] = 72, ["y"] = -4.5e1 },
+                ["pos1"] = { ["page"] = 3, ["x"] = 500, ["y"] = 120 },
+                ["text"] = "Rainfall was twice the average",
+            },
+        },
+    },
+    ["stats"] = {
+        ["authors"] = "Dana Whitfield\
+Sam Okafor",
+        ["highlights"] = 2,
+        ["notes"] = 1,
+        ["pages"] = 48,
+        ["title"] = "",
+    },
+}
@@ -0,0 +1,47 @@
+-- we can read Lua syntax here!
+return {
+    ["annotations"] = {
+        [1] = {
+            ["chapter"] = "Book the First",
+            ["color"] = "yellow",
+            ["datetime"] = "2024-03-03 22:15:32",
+            ["drawer"] = "lighten",
+            ["page"] = "/body/DocFragment[3]/body/p[1]/text().0",
+            ["pageno"] = 12,
+            ["pos0"] = "/body/DocFragment[3]/body/p[1]/text().0",
+            ["pos1"] = "/body/DocFragment[3]/body/p[1]/text().51",
+            ["text"] = "It was the best of times,\
+it was the worst of times",
+        },
+        [2] = {
+            ["chapter"] = "Book the First",
+            ["datetime"] = "2024-03-04 08:01:00",
+            ["drawer"] = "lighten",
+            ["note"] = "Compare with the \"Carlyle\" passage \226\128\148 p. 40",
+            ["page"] = "/body/DocFragment[5]/body/p[7]/text().12",
+            ["pageno"] = 27,
+            ["pos0"] = "/body/DocFragment[5]/body/p[7]/text().12",
+            ["pos1"] = "/body/DocFragment[5]/body/p[7]/text().60",
+            ["text"] = "a wonderful fact to reflect upon",
+        },
+        [3] = {
+            ["datetime"] = "2024-03-05 19:30:12",
+            ["page"] = "/body/DocFragment[9]/body/p[2]/text().0",
+            ["pageno"] = 61,
+        },
+    },
+    ["doc_pages"] = 412,
+    ["doc_path"] = "/mnt/onboard/Books/tale.epub",
+    ["doc_props"] = {
+        ["authors"] = "Charles Dickens",
+        ["description"] = [[A story of
+London and Paris]],
+        ["language"] = "en",
+        ["title"] = "A Tale of Two Cities",
+    },
+    ["percent_finished"] = 0.148,
+    ["summary"] = {
+        ["modified"] = "2024-03-05",
+        ["status"] = "reading",
+    },
+}
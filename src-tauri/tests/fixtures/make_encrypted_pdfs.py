@@ -0,0 +1,119 @@
+# Builds small AES-encrypted PDFs (ISO 32000-2 standard security handler) with an object stream and an xref stream
+# Usage: python3 make_encrypted_pdfs.py <output folder>, needs the cryptography package
+import hashlib, os, sys, zlib
+from cryptography.hazmat.primitives.ciphers import Cipher, algorithms, modes
+from cryptography.hazmat.primitives import padding
+
+PAD = bytes.fromhex("28BF4E5E4E758A4164004E56FFFA01082E2E00B6D0683E802F0CA9FE6453697A")
+USER, OWNER = b"Secret 123", b"Owner 456"
+FILE_ID = bytes(range(0x30, 0x40))
+P = -1028
+
+def rc4(key, data):
+    s = list(range(256)); j = 0
+    for i in range(256):
+        j = (j + s[i] + key[i % len(key)]) % 256; s[i], s[j] = s[j], s[i]
+    i = j = 0; out = bytearray()
+    for byte in data:
+        i = (i + 1) % 256; j = (j + s[i]) % 256; s[i], s[j] = s[j], s[i]
+        out.append(byte ^ s[(s[i] + s[j]) % 256])
+    return bytes(out)
+
+def aes_cbc(key, iv, data, pad=True, decrypt=False):
+    if pad:
+        p = padding.PKCS7(128).padder(); data = p.update(data) + p.finalize()
+    c = Cipher(algorithms.AES(key), modes.CBC(iv))
+    op = c.decryptor() if decrypt else c.encryptor()
+    return op.update(data) + op.finalize()
+
+def padded(pw): return (pw + PAD)[:32]
+
+def r4_security():
+    okey = hashlib.md5(padded(OWNER)).digest()
+    for _ in range(50): okey = hashlib.md5(okey).digest()
+    o = rc4(okey, padded(USER))
+    for i in range(1, 20): o = rc4(bytes(b ^ i for b in okey), o)
+    k = hashlib.md5(padded(USER) + o + P.to_bytes(4, "little", signed=True) + FILE_ID).digest()
+    for _ in range(50): k = hashlib.md5(k).digest()
+    u = rc4(k, hashlib.md5(PAD + FILE_ID).digest())
+    for i in range(1, 20): u = rc4(bytes(b ^ i for b in k), u)
+    u = u + bytes(16)
+    enc = (b"<< /Filter /Standard /V 4 /R 4 /Length 128 /CF << /StdCF << /CFM /AESV2 /AuthEvent /DocOpen /Length 16 >> >> "
+           b"/StmF /StdCF /StrF /StdCF /O <" + o.hex().encode() + b"> /U <" + u.hex().encode() + b"> /P " + str(P).encode() + b" >>")
+    def object_key(num, gen):
+        return hashlib.md5(k + num.to_bytes(4, "little")[:3] + gen.to_bytes(2, "little") + b"sAlT").digest()
+    return enc, object_key
+
+def hash_2b(pw, salt, udata):
+    k = hashlib.sha256(pw + salt + udata).digest()
+    rnd = 0
+    while True:
+        k1 = (pw + k + udata) * 64
+        e = aes_cbc(k[:16], k[16:32], k1, pad=False)
+        h = [hashlib.sha256, hashlib.sha384, hashlib.sha512][int.from_bytes(e[:16], "big") % 3]
+        k = h(e).digest()
+        rnd += 1
+        if rnd >= 64 and e[-1] <= rnd - 32:
+            break
+    return k[:32]
+
+def r6_security():
+    key = bytes(range(0xA0, 0xC0))
+    uv, uk, ov, ok = b"uvsalt01", b"uksalt01", b"ovsalt01", b"oksalt01"
+    u = hash_2b(USER, uv, b"") + uv + uk
+    ue = aes_cbc(hash_2b(USER, uk, b""), bytes(16), key, pad=False)
+    o = hash_2b(OWNER, ov, u) + ov + ok
+    oe = aes_cbc(hash_2b(OWNER, ok, u), bytes(16), key, pad=False)
+    c = Cipher(algorithms.AES(key), modes.ECB()).encryptor()
+    perms = c.update(P.to_bytes(4, "little", signed=True) + b"\xff\xff\xff\xffTadb" + b"perm") + c.finalize()
+    enc = (b"<< /Filter /Standard /V 5 /R 6 /Length 256 /CF << /StdCF << /CFM /AESV3 /AuthEvent /DocOpen /Length 32 >> >> "
+           b"/StmF /StdCF /StrF /StdCF /O <" + o.hex().encode() + b"> /U <" + u.hex().encode() + b"> /OE <" + oe.hex().encode()
+           + b"> /UE <" + ue.hex().encode() + b"> /Perms <" + perms.hex().encode() + b"> /P " + str(P).encode() + b" >>")
+    return enc, lambda num, gen: key
+
+def build(security, path):
+    enc, object_key = security
+    iv = bytes(range(0x10, 0x20))
+    def encrypt(num, data): return iv + aes_cbc(object_key(num, 0), iv, data)
+    packed = {
+        1: b"<< /Type /Catalog /Pages 2 0 R >>",
+        2: b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>",
+        3: b"<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] /Contents 4 0 R /Resources << /Font << /F1 5 0 R >> >> /Annots [6 0 R 7 0 R] >>",
+        5: b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>",
+        6: b"<< /Type /Annot /Subtype /Text /Rect [72 600 92 620] /Contents (Packed note) >>",
+    }
+    header, body, offset = [], b"", 0
+    for num, obj in packed.items():
+        header.append(b"%d %d" % (num, offset)); body += obj + b"\n"; offset += len(obj) + 1
+    header = b" ".join(header) + b"\n"
+    objstm = zlib.compress(header + body)
+    content = zlib.compress(b"BT /F1 24 Tf 72 700 Td (Quarterly budget) Tj ET")
+    note = encrypt(7, b"Check the totals")
+    out = b"%PDF-1.7\n%\xe2\xe3\xcf\xd3\n"
+    offsets = {}
+    def add(num, data):
+        nonlocal out
+        offsets[num] = len(out)
+        out += b"%d 0 obj\n" % num + data + b"\nendobj\n"
+    c = encrypt(4, content)
+    add(4, b"<< /Length %d /Filter /FlateDecode >>\nstream\n" % len(c) + c + b"\nendstream")
+    add(7, b"<< /Type /Annot /Subtype /Text /Rect [72 560 92 580] /Contents <" + note.hex().encode() + b"> >>")
+    s = encrypt(8, objstm)
+    add(8, b"<< /Type /ObjStm /N %d /First %d /Length %d /Filter /FlateDecode >>\nstream\n" % (len(packed), len(header), len(s)) + s + b"\nendstream")
+    add(9, enc)
+    xref_offset = len(out)
+    rows = [bytes([0]) + (0).to_bytes(4, "big") + (65535).to_bytes(2, "big")]
+    index = {num: i for i, num in enumerate(packed)}
+    for num in range(1, 11):
+        if num in packed: rows.append(bytes([2]) + (8).to_bytes(4, "big") + index[num].to_bytes(2, "big"))
+        elif num == 10: rows.append(bytes([1]) + xref_offset.to_bytes(4, "big") + bytes(2))
+        else: rows.append(bytes([1]) + offsets[num].to_bytes(4, "big") + bytes(2))
+    xref = b"".join(rows)
+    ident = b"<" + FILE_ID.hex().encode() + b">"
+    out += (b"10 0 obj\n<< /Type /XRef /Size 11 /W [1 4 2] /Root 1 0 R /Encrypt 9 0 R /ID [" + ident + b" " + ident
+            + b"] /Length %d >>\nstream\n" % len(xref) + xref + b"\nendstream\nendobj\n")
+    out += b"startxref\n%d\n%%%%EOF\n" % xref_offset
+    open(path, "wb").write(out)
+
+build(r4_security(), sys.argv[1] + "/aes128.pdf")
+build(r6_security(), sys.argv[1] + "/aes256.pdf")
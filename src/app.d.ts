@@ -54,6 +54,32 @@ declare global {
     freceny_rank: number,
     frecency_last_accessed: number,
     comment: string | null,
+    is_encrypted?: boolean,
+    section?: string | null,
+    section_number?: number | null,
+    source_table?: string,
+    title?: string | null,
+    snippets?: SearchSnippetType[],
+  }
+
+  // a passage that matched the query, highlights are [start, end] offsets into text
+  interface SearchSnippetType {
+    field: string,
+    text: string,
+    highlights: [number, number][],
+  }
+
+  // the number of files that match a search under a facet, value is sent back as "facet:value" to drill down
+  interface FacetCountType {
+    facet: string,
+    value: string,
+    label: string,
+    count: number,
+  }
+
+  interface SearchResultsType {
+    hits: DocumentSearchResult[],
+    facets: FacetCountType[],
   }
 
   interface FileTypes {
@@ -69,6 +95,11 @@ declare global {
     is_folder: boolean,
   }
 
+  interface ProtectedPathType {
+    path: string,
+    is_folder: boolean,
+  }
+
   interface FileTypesDropdown {
     categories: string[],
     items: FileTypes[]
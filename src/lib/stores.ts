@@ -74,6 +74,7 @@ export const userPreferences = writable({
   "show_in_dock": true,
   "roadmap_survey_answered": false,
   "parse_pdfs": false,
+  "transcribe_audio": false,
   "manual_setup": false,
 })
 export const pagePath = writable("")
@@ -115,6 +116,10 @@ export const searchSuggestionsDialogOpen = writable(false);
 export const searchFiltersOpen = writable(false);
 export const ignoreDialogOpen = writable(false);
 export const dateLimitUNIX = writable(storedDateLimitUNIX || null)
+export const fuzzySearch = writable(false)
+// the facet counts of the current search and the facets picked to drill down, e.g. "file_type:/pdf"
+export const searchFacets = writable<FacetCountType[]>([])
+export const facetFilters = writable<string[]>([])
 
 // 3. Anytime the store changes, update the local storage value.
 if(typeof window !== "undefined") {
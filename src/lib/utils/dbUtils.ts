@@ -1,6 +1,6 @@
 import { invoke } from "@tauri-apps/api/core";
-import { extractDate, cleanSearchQuery } from "./queryParsing";
-import { searchQuery, locationShown, resultsPageShown, noMoreResults, searchInProgress, filetypeShown, resultsPerPage, documentsShown, allowedExtensions, base64Images, showIconGrid,  dateLimitUNIX } from "$lib/stores";
+import { extractDate } from "./queryParsing";
+import { searchQuery, locationShown, resultsPageShown, noMoreResults, searchInProgress, filetypeShown, resultsPerPage, documentsShown, allowedExtensions, base64Images, showIconGrid,  dateLimitUNIX, fuzzySearch, searchFacets, facetFilters } from "$lib/stores";
 import { trackEvent } from "@aptabase/web";
 import { setExtensionCategory } from "$lib/utils/miscUtils";
 import { getResultThumbnails } from '$lib/utils/fileTable';
@@ -44,18 +44,21 @@ export async function searchDocuments(query:string, page:number, limit:number, t
     if (dateLimit && dateLimit.text.length > 0) {
       query = dateLimit.text;
     }
-    let querySegments = cleanSearchQuery(query);
     
     if (type === "any") type = undefined;
 
     if (query.length === 0 && !(dateLimitUNIX && dateLimitUNIX.start !== "" && dateLimitUNIX.end !== "")) {
       results = await getDocumentsFromDB(page, limit);
     } else {
+      let response: SearchResultsType;
       if (dateLimit && dateLimit.start !== "" && dateLimit.end !== "") {
-        results = await invoke("run_search", { query: JSON.stringify(querySegments), page: page, limit: limit, fileType: type, dateLimit: dateLimit});
+        response = await invoke("run_search", { query: query, page: page, limit: limit, fileType: type, dateLimit: dateLimit, fuzzy: get(fuzzySearch), facetFilters: get(facetFilters)});
       } else {
-        results = await invoke("run_search", { query: JSON.stringify(querySegments), page: page, limit: limit, fileType: type});
+        response = await invoke("run_search", { query: query, page: page, limit: limit, fileType: type, fuzzy: get(fuzzySearch), facetFilters: get(facetFilters)});
       }
+      results = response.hits;
+      // the counts only come with the first page
+      if (page === 0) searchFacets.set(response.facets);
     }
   } else if (get(locationShown) === "browser history") {
     results = await invoke("run_browser_history_search", { userProfile: "Default", userQuery: query, limit: limit, page: page});
@@ -76,6 +79,7 @@ export async function triggerSearch() {
     filetypeToGet = setExtensionCategory(get(filetypeShown), get(allowedExtensions));
   }
   
+  searchFacets.set([]);
   let result = await searchDocuments(
     get(searchQuery),
     get(resultsPageShown),
@@ -2,10 +2,18 @@ import nlp from 'compromise';
 import datePlugin from 'compromise-dates';
 nlp.plugin(datePlugin);
 
+// operators like type:pdf or modified:>2024-03 are parsed by the backend, keep them away from the date parser
+const operatorRegex = /-?\b[a-z_]+:("[^"]*"?|\([^)]*\)?|\S+)/gi;
+
 export function extractDate(value: string) {
   console.log("extracting date from", value);
+  let operators = value.match(operatorRegex) ?? [];
+  value = value.replace(operatorRegex, ' ').trim().replace(/\s{2,}/g, ' ');
+  // relative dates like "last 2 weeks" are counted from today in the user's time zone
+  let timezone = Intl.DateTimeFormat().resolvedOptions().timeZone;
+  let today = new Date().toLocaleDateString('en-CA'); // YYYY-MM-DD in local time
   // @ts-ignore
-  let compromised = nlp(value).dates({"timezone": "Asia/Calcutta", "today": new Date().toISOString().slice(0,10)});
+  let compromised = nlp(value).dates({"timezone": timezone, "today": today});
   let parsedDates = compromised.get()[0];
   console.log("parsed dates:", parsedDates);
   
@@ -24,7 +32,7 @@ export function extractDate(value: string) {
       let dateLimitTrimmed: ParsedDatesUNIX = {
         "start": parsedDates.start.slice(0,10),
         "end": parsedDates.end.slice(0,10),
-        "text": text.trim().replace(/\s{2,}/g, ' ')
+        "text": [text.trim(), ...operators].join(' ').trim().replace(/\s{2,}/g, ' ')
       }
       // make sure DD/MM is correctly parsed
       dateLimitTrimmed = checkDateMonth(dateLimitTrimmed, value);
@@ -79,73 +87,3 @@ function getDefaultDateFormat() {
   const isMonthFirst = dateString.startsWith('2') || dateString.startsWith('02'); // check if if 10/2 or 2/10
   return isMonthFirst ? 'MM/DD' : 'DD/MM';
 }
-
-// Use '*' token to search even if word is not complete
-// Examples – `hello world` will search for `hello* world*`
-// `hello "world"` will search for `hello* "world"`
-// `dear "star wars" fan` will search for `dear* "star wars" fan*`
-export function cleanSearchQuery(value: string): {} {
-  // create a result object to store three types of segments: quoted, greedy and not
-  let result: { quotedSegments: string[], greedySegments: string[], notSegments: string[] } = {
-    quotedSegments: [],
-    greedySegments: [],
-    notSegments: []
-  };
-  
-  // if there is a single quote, double it up
-  value = value.replace(/'/g, "''");
-
-  // Split the input string into segments by space, but keep quoted strings and - together
-  const segments = value.match(/-?"[^"]+"|\S+/g) || [];
-
-  // Process each segment
-  segments.map(segment => {
-    // If the segment starts with quotes and ends with quotes, remove the quotes and push it to the quotedSegments array
-    if (segment.startsWith('"') && segment.endsWith('"')) {
-      let tempSeg = handle_punctuation(segment);
-      result.quotedSegments.push(tempSeg);
-      return;
-    }
-
-    // If the segment has a - in front of it, remove the - and add it to the notSegments array
-    if (segment.startsWith('-')) {
-      // remove the - and any quotes because they get added in the SQL query anyway
-      let tempSeg = handle_punctuation(segment.substring(1).trim().replace(/"/g, ''));
-      if (tempSeg.length > 0) result.notSegments.push(tempSeg);
-      return;
-    }
-
-    // If the segment matches <WORD/DIGIT><PUNCTUATION><WORD/DIGIT>, push it to the quotedSegments array
-    if (segment.match(/[\b\s]*[\w\d]*[!#$₹%&'()*+,./\\:;\-<=>?@[\]^_`{|}~][\w\d]*[\b\s]*/g)) {
-      let tempSeg = handle_punctuation(segment);
-      result.quotedSegments.push(tempSeg);
-      return;
-    }
-
-    // If the segment is a word, push it to the greedySegments array
-    if (segment.match(/^[a-zA-Z0-9]+$/)) {
-      result.greedySegments.push(segment.trim());
-      return;
-    }
-
-    // Otherwise, just push it to the greedySegments array
-    // BUGFIX: Sometimes doublequote becomes “ or ”
-    segment = segment.replace('“', '"').replace('”', '"')
-    result.greedySegments.push(segment.trim());
-    return;
-  });
-
-  console.log("Processed Segments:", result);
-
-  // Join the processed segments back together with spaces
-  return result;
-}
-
-function handle_punctuation(segment: string) {
-  let tempSeg = segment.replace(/"/g, '').trim();
-  // if the segment has a punctuation in it, prepend a ^^ so backend can double-quote it
-  if (tempSeg.match(/[\b\s]*[\w\d]*[!#$₹%&'()*+,./\\:;\-<=>?@[\]^_`{|}~][\w\d]*[\b\s]*/g)) {
-    tempSeg = '^^' + tempSeg;
-  }
-  return tempSeg.trim();
-}
\ No newline at end of file
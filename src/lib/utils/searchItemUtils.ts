@@ -18,6 +18,33 @@ export async function openFile(url: string) {
   invoke('open_file_or_folder', { filePath: url });
 }
 
+// Open a search result at the section where the query matched (e.g. slide 17), if there is one
+export async function openResult(result: DocumentSearchResult) {
+  trackEvent('click:openFile');
+  if (result.section_number) {
+    invoke('open_file_at_section', { filePath: result.path, fileType: result.file_type, sectionNumber: result.section_number });
+  } else {
+    invoke('open_file_or_folder', { filePath: result.path });
+  }
+}
+
+// The text of a snippet split into plain and highlighted parts, in order
+export function snippetParts(snippet: SearchSnippetType): { text: string, highlighted: boolean }[] {
+  let parts: { text: string, highlighted: boolean }[] = [];
+  let position = 0;
+  for (const [start, end] of snippet.highlights) {
+    if (start > position) {
+      parts.push({ text: snippet.text.slice(position, start), highlighted: false });
+    }
+    parts.push({ text: snippet.text.slice(start, end), highlighted: true });
+    position = end;
+  }
+  if (position < snippet.text.length) {
+    parts.push({ text: snippet.text.slice(position), highlighted: false });
+  }
+  return parts;
+}
+
 export function formatPath(url: string): string {
   const parts = url.split('/'); // Split the url into components
   const length = parts.length;